@@ -0,0 +1,35 @@
+/* C API of libunshelve, see src/ffi.rs. Build with:
+ * cargo rustc --release --lib --no-default-features --features ffi --crate-type cdylib
+ */
+#ifndef UNSHELVE_H
+#define UNSHELVE_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct UnshelveEngine UnshelveEngine;
+
+/* Load the config file (NULL - .env) with an optional profile (may be NULL) and connect. NULL on failure */
+UnshelveEngine *unshelve_init(const char *config, const char *profile);
+
+/* One check cycle: 1 - healthy, 0 - not healthy, -1 - failure */
+int unshelve_check(UnshelveEngine *engine);
+
+/* Last check result as JSON, free with unshelve_string_free. NULL on failure */
+char *unshelve_status(UnshelveEngine *engine);
+
+/* Unshelve if shelved: 1 - requested, 0 - not shelved, -1 - failure */
+int unshelve_trigger(UnshelveEngine *engine);
+
+/* Message of the last failure on this thread or NULL, valid until the next call */
+const char *unshelve_last_error(void);
+
+void unshelve_string_free(char *string);
+void unshelve_free(UnshelveEngine *engine);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,165 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+
+use crate::config;
+use crate::fleet::SharedState;
+
+/// Action submitted to OpenStack, recorded before submission
+#[derive(Clone)]
+pub struct ActionRecord {
+    pub server_id: String,
+    pub action: String,
+    /// Incident the action belongs to
+    pub incident: String,
+    pub submitted: DateTime<Local>,
+}
+
+/// In-flight actions stored in ACTION_STATE_FILE as `server_id action incident submitted_at` lines,
+/// so a restarted daemon waits for an action it already submitted instead of submitting it again.
+/// With shared state (STATE_URL) the records are locks visible to all instances.
+/// One store is shared by the monitors of a daemon, so their records don't overwrite each other
+pub struct ActionStore {
+    backend: Backend,
+    /// Records older than this are stale - the action finished or failed long ago
+    timeout: chrono::Duration,
+}
+
+enum Backend {
+    File { path: PathBuf, records: Mutex<Vec<ActionRecord>> },
+    Shared(Arc<dyn SharedState>),
+}
+
+impl ActionStore {
+    pub fn load(shared: Option<Arc<dyn SharedState>>) -> Result<Self> {
+        let timeout_minutes: i64 = config::var("ACTION_TIMEOUT_MINUTES")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse()
+            .context("ACTION_TIMEOUT_MINUTES must be a number")?;
+        let timeout = chrono::Duration::minutes(timeout_minutes);
+        if let Some(shared) = shared {
+            return Ok(ActionStore { backend: Backend::Shared(shared), timeout });
+        }
+
+        let path = path();
+        let records = read(&path)?;
+        Ok(ActionStore { backend: Backend::File { path, records: Mutex::new(records) }, timeout })
+    }
+
+    /// Action submitted for the server and not finished yet, stale records are ignored
+    pub fn in_flight(&self, server_id: &str, action: &str) -> Result<Option<ActionRecord>> {
+        match &self.backend {
+            Backend::File { records, .. } => Ok(lock(records)?
+                .iter()
+                .filter(|r| r.server_id == server_id && r.action == action)
+                .find(|r| Local::now() - r.submitted < self.timeout)
+                .cloned()),
+            Backend::Shared(shared) => shared.in_flight(server_id, action, Local::now() - self.timeout),
+        }
+    }
+
+    /// Persist the action before it's submitted. With shared state fails if another instance holds it
+    pub fn record(&self, server_id: &str, action: &str, incident: &str) -> Result<()> {
+        let record = ActionRecord {
+            server_id: server_id.to_string(),
+            action: action.to_string(),
+            incident: incident.to_string(),
+            submitted: Local::now(),
+        };
+        match &self.backend {
+            Backend::File { path, records } => {
+                let mut records = lock(records)?;
+                records.retain(|r| !(r.server_id == server_id && r.action == action));
+                records.push(record);
+                save(path, &records)
+            },
+            Backend::Shared(shared) => shared.record(&record, Local::now() - self.timeout),
+        }
+    }
+
+    /// Forget actions of the server - they finished or failed to submit
+    pub fn complete(&self, server_id: &str) -> Result<()> {
+        match &self.backend {
+            Backend::File { path, records } => {
+                let mut records = lock(records)?;
+                let before = records.len();
+                records.retain(|r| r.server_id != server_id);
+                if records.len() != before {
+                    save(path, &records)?;
+                }
+                Ok(())
+            },
+            Backend::Shared(shared) => shared.complete(server_id),
+        }
+    }
+}
+
+/// ACTION_STATE_FILE
+pub fn path() -> PathBuf {
+    PathBuf::from(config::var("ACTION_STATE_FILE").unwrap_or_else(|_| ".unshelve-actions".to_string()))
+}
+
+fn read(path: &Path) -> Result<Vec<ActionRecord>> {
+    let mut records: Vec<ActionRecord> = vec![];
+    if !path.exists() {
+        return Ok(records);
+    }
+    let content = fs::read_to_string(path)
+        .context(format!("Failed to read action state file: {}", path.display()))?;
+    for line in content.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [server_id, action, incident, submitted] = fields[..] else {
+            anyhow::bail!("Invalid line in {}: '{}'", path.display(), line);
+        };
+        let submitted = DateTime::parse_from_rfc3339(submitted)
+            .context(format!("Invalid time in {}: '{}'", path.display(), line))?
+            .with_timezone(&Local);
+        records.push(ActionRecord {
+            server_id: server_id.to_string(),
+            action: action.to_string(),
+            incident: incident.to_string(),
+            submitted,
+        });
+    }
+    Ok(records)
+}
+
+fn lock(records: &Mutex<Vec<ActionRecord>>) -> Result<std::sync::MutexGuard<'_, Vec<ActionRecord>>> {
+    records.lock().map_err(|_| anyhow::anyhow!("Action store lock poisoned"))
+}
+
+fn save(path: &Path, records: &[ActionRecord]) -> Result<()> {
+    let lines: Vec<String> = records
+        .iter()
+        .map(|r| format!("{} {} {} {}", r.server_id, r.action, r.incident, r.submitted.to_rfc3339()))
+        .collect();
+    let content = if lines.is_empty() { String::new() } else { lines.join("\n") + "\n" };
+    fs::write(path, content)
+        .context(format!("Failed to write action state file: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monitors_sharing_the_store_keep_each_others_records() {
+        let path = std::env::temp_dir().join(format!("unshelve-test-actions-{}", std::process::id()));
+        let store = ActionStore {
+            backend: Backend::File { path: path.clone(), records: Mutex::new(vec![]) },
+            timeout: chrono::Duration::minutes(10),
+        };
+        store.record("server-a", "unshelve", "inc-a").unwrap();
+        store.record("server-b", "unshelve", "inc-b").unwrap();
+        store.complete("server-a").unwrap();
+
+        let records = read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!((records[0].server_id.as_str(), records[0].incident.as_str()), ("server-b", "inc-b"));
+        assert!(store.in_flight("server-b", "unshelve").unwrap().is_some());
+        assert!(store.in_flight("server-a", "unshelve").unwrap().is_none());
+    }
+}
@@ -0,0 +1,52 @@
+use std::net::IpAddr;
+use anyhow::Result;
+use clap::ValueEnum;
+
+/// Address type as reported by OpenStack
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum AddressKind {
+    Floating,
+    Fixed,
+}
+
+/// Address of the server to connect to: of the given type and network if set,
+/// otherwise a floating address is preferred over a fixed one. Networks are tried in name order
+pub fn select(server: &openstack::compute::Server, kind: Option<AddressKind>, network: Option<&str>) -> Result<IpAddr> {
+    let mut networks: Vec<_> = server
+        .addresses()
+        .iter()
+        .filter(|(name, _)| network.is_none_or(|n| n == name.as_str()))
+        .collect();
+    if networks.is_empty() {
+        match network {
+            Some(network) => anyhow::bail!("Server '{}' has no addresses in network '{}'", server.name(), network),
+            None => anyhow::bail!("Server '{}' has no addresses", server.name()),
+        }
+    }
+    networks.sort_by(|a, b| a.0.cmp(b.0));
+
+    let kinds = match kind {
+        Some(kind) => vec![kind],
+        None => vec![AddressKind::Floating, AddressKind::Fixed],
+    };
+    for kind in &kinds {
+        let name = format!("{:?}", kind).to_lowercase();
+        let found = networks
+            .iter()
+            .flat_map(|(_, ips)| ips.iter())
+            .find(|ip| ip.addr_type.as_ref().is_some_and(|t| t.to_string().eq_ignore_ascii_case(&name)));
+        if let Some(ip) = found {
+            return Ok(ip.addr);
+        }
+    }
+    // Clouds that don't report the type - any address will do when the type wasn't asked for
+    if kind.is_none() {
+        if let Some(ip) = networks.iter().flat_map(|(_, ips)| ips.iter()).next() {
+            return Ok(ip.addr);
+        }
+    }
+    anyhow::bail!("Server '{}' has no {} address{}",
+                  server.name(),
+                  kinds.iter().map(|k| format!("{:?}", k).to_lowercase()).collect::<Vec<_>>().join(" or "),
+                  network.map(|n| format!(" in network '{}'", n)).unwrap_or_default())
+}
@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+use crate::config;
+use crate::labels;
+use crate::notify::{Event, Severity};
+
+/// Alerts are sent with endsAt this far ahead and refreshed while the incident lasts,
+/// so they resolve by themselves if the daemon stops
+const ALERT_TTL: chrono::Duration = chrono::Duration::hours(1);
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Stateful alert and its severity label for the event kind, resolved when the server recovers.
+/// Labels identify an alert in Alertmanager, so they don't change while it fires
+fn stateful_alert(kind: &str) -> Option<(&'static str, &'static str)> {
+    match kind {
+        "unshelve_sent" | "actions_disabled" | "maintenance_suppressed" => Some(("UnshelveServerDown", "warning")),
+        "unshelve_failed" | "precondition_failed" | "pipeline_failed" | "root_volume_failed" => Some(("UnshelveFailed", "critical")),
+        "server_gone" => Some(("UnshelveServerGone", "critical")),
+        _ => None,
+    }
+}
+
+struct Firing {
+    severity: &'static str,
+    starts_at: DateTime<Local>,
+    /// Last event of the alert
+    kind: &'static str,
+    message: String,
+    /// SERVER_LABELS, static for the server
+    labels: BTreeMap<String, String>,
+}
+
+/// Alerts in Prometheus Alertmanager v2 format (ALERTMANAGER_URL), routing and silencing
+/// is left to Alertmanager. Down and failure events fire alerts resolved on recovery,
+/// other warning and critical events are sent as UnshelveEvent alerts expiring after an hour.
+/// ALERTMANAGER_LABELS adds static labels, e.g. 'receiver=ops', SERVER_LABELS of the server override them
+pub struct Alertmanager {
+    url: String,
+    labels: BTreeMap<String, String>,
+    /// (alertname, server) -> alert
+    firing: Mutex<BTreeMap<(&'static str, String), Firing>>,
+    refreshed: Mutex<Option<Instant>>,
+}
+
+impl Alertmanager {
+    /// None if ALERTMANAGER_URL is not set
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(url) = config::var("ALERTMANAGER_URL").ok().filter(|u| !u.trim().is_empty()) else {
+            return Ok(None);
+        };
+        let labels = labels::parse("ALERTMANAGER_LABELS", &config::var("ALERTMANAGER_LABELS").unwrap_or_default())?;
+        Ok(Some(Alertmanager {
+            url: format!("{}/api/v2/alerts", url.trim_end_matches('/')),
+            labels,
+            firing: Mutex::new(BTreeMap::new()),
+            refreshed: Mutex::new(None),
+        }))
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Fire or resolve alerts for the event, firing alerts are resent every few minutes
+    pub async fn handle(&self, client: &reqwest::Client, event: &Event) -> Result<()> {
+        let now = Local::now();
+        let mut firing = self.firing.lock().await;
+        let mut refreshed = self.refreshed.lock().await;
+        let mut alerts: Vec<Value> = vec![];
+
+        if event.kind == "recovered" {
+            let resolved: Vec<(&'static str, String)> = firing.keys().filter(|(_, server)| *server == event.server).cloned().collect();
+            for key in resolved {
+                if let Some(alert) = firing.remove(&key) {
+                    alerts.push(self.alert(key.0, &key.1, &alert, now));
+                }
+            }
+        } else if let Some((name, severity)) = stateful_alert(event.kind) {
+            let alert = firing.entry((name, event.server.clone())).or_insert_with(|| Firing {
+                severity,
+                starts_at: event.time,
+                kind: event.kind,
+                message: String::new(),
+                labels: event.labels.clone(),
+            });
+            alert.kind = event.kind;
+            alert.message = event.message.clone();
+        } else if event.severity >= Severity::Warning {
+            let alert = Firing {
+                severity: if event.severity == Severity::Critical { "critical" } else { "warning" },
+                starts_at: event.time,
+                kind: event.kind,
+                message: event.message.clone(),
+                labels: event.labels.clone(),
+            };
+            alerts.push(self.alert("UnshelveEvent", &event.server, &alert, now + ALERT_TTL));
+        }
+
+        // All firing alerts go out with a new or updated alert and on refresh
+        let changed = stateful_alert(event.kind).is_some();
+        if changed || (!firing.is_empty() && refreshed.is_none_or(|t| t.elapsed() >= REFRESH_INTERVAL)) {
+            for ((name, server), alert) in firing.iter() {
+                alerts.push(self.alert(name, server, alert, now + ALERT_TTL));
+            }
+        }
+        drop(firing);
+
+        if alerts.is_empty() {
+            return Ok(());
+        }
+        client
+            .post(&self.url)
+            .json(&alerts)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| anyhow::anyhow!("{}", e.without_url()))
+            .context("Failed to post alerts to Alertmanager")?;
+        *refreshed = Some(Instant::now());
+        Ok(())
+    }
+
+    fn alert(&self, name: &str, server: &str, alert: &Firing, ends_at: DateTime<Local>) -> Value {
+        let mut labels = self.labels.clone();
+        labels.extend(alert.labels.clone());
+        labels.insert("alertname".to_string(), name.to_string());
+        labels.insert("server".to_string(), server.to_string());
+        labels.insert("severity".to_string(), alert.severity.to_string());
+        if name == "UnshelveEvent" {
+            labels.insert("kind".to_string(), alert.kind.to_string());
+        }
+        json!({
+            "labels": labels,
+            "annotations": {
+                "summary": alert.message.lines().next().unwrap_or_default(),
+                "description": alert.message,
+                "kind": alert.kind,
+            },
+            "startsAt": alert.starts_at.to_rfc3339(),
+            "endsAt": ends_at.to_rfc3339(),
+        })
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use anyhow::Result;
+
+use crate::config::Vars;
+
+/// Parse SERVER_ALIASES, e.g. SERVER_ALIASES='db=0123-abcd,web=web-frontend-01'
+pub fn load() -> Result<HashMap<String, String>> {
+    load_from(&Vars::default())
+}
+
+fn load_from(vars: &Vars) -> Result<HashMap<String, String>> {
+    let mut aliases: HashMap<String, String> = HashMap::new();
+    let raw = vars.get("SERVER_ALIASES").unwrap_or_default();
+
+    for pair in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (alias, target) = pair
+            .split_once('=')
+            .map(|(a, t)| (a.trim(), t.trim()))
+            .filter(|(a, t)| !a.is_empty() && !t.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Invalid SERVER_ALIASES entry: '{}'. Expected alias=NAME_OR_UUID", pair))?;
+
+        if aliases.insert(alias.to_string(), target.to_string()).is_some() {
+            anyhow::bail!("Duplicate alias '{}' in SERVER_ALIASES", alias);
+        }
+    }
+    Ok(aliases)
+}
+
+/// Replace alias with its server name or UUID, other identifiers are returned as is
+pub fn resolve(identifier: &str) -> Result<String> {
+    resolve_in(identifier, &Vars::default())
+}
+
+/// `resolve` with SERVER_ALIASES from config values read into memory
+pub fn resolve_in(identifier: &str, vars: &Vars) -> Result<String> {
+    match load_from(vars)?.get(identifier) {
+        Some(target) => {
+            // stderr - stdout of ip, --json and --format goes to scripts
+            eprintln!("Alias '{}' -> {}", identifier, target);
+            Ok(target.clone())
+        },
+        None => Ok(identifier.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vars {
+        Vars::new(pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect())
+    }
+
+    #[test]
+    fn resolves_aliases_from_config_values() {
+        let vars = vars(&[("SERVER_ALIASES", "db=0123-abcd, web = web-frontend-01")]);
+        assert_eq!(resolve_in("web", &vars).unwrap(), "web-frontend-01");
+        assert_eq!(resolve_in("other", &vars).unwrap(), "other");
+    }
+
+    #[test]
+    fn invalid_aliases() {
+        assert!(load_from(&vars(&[("SERVER_ALIASES", "db")]))).is_err());
+        assert!(load_from(&vars(&[("SERVER_ALIASES", "db=a,db=b")]))).is_err());
+    }
+}
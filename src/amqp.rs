@@ -0,0 +1,87 @@
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use lapin::options::{BasicConsumeOptions, QueueBindOptions, QueueDeclareOptions};
+use lapin::types::FieldTable;
+use lapin::{Connection, ConnectionProperties};
+use serde_json::Value;
+use tokio::sync::Notify;
+
+use crate::config;
+use crate::redact;
+use crate::tasks::TaskGroup;
+use crate::webhook::down_notification;
+
+/// Listen to Nova notifications on the cloud message bus (AMQP_URL).
+/// A private queue is bound to the exchange, so other consumers of
+/// notifications.info (e.g. ceilometer) don't lose messages.
+/// Returns false if the listener is not configured
+pub async fn start(server_name: &str, signal: Arc<Notify>, tasks: &mut TaskGroup) -> Result<bool> {
+    let Some(url) = config::var("AMQP_URL").ok().filter(|u| !u.trim().is_empty()) else {
+        return Ok(false);
+    };
+    let exchange = config::var("AMQP_EXCHANGE").unwrap_or_else(|_| "nova".to_string());
+    let routing_key = config::var("AMQP_ROUTING_KEY").unwrap_or_else(|_| "notifications.info".to_string());
+
+    let connection = Connection::connect(&url, ConnectionProperties::default())
+        .await
+        .context("Failed to connect to AMQP_URL")?;
+    let channel = connection.create_channel().await.context("Failed to open AMQP channel")?;
+
+    let queue = channel
+        .queue_declare("", QueueDeclareOptions { exclusive: true, auto_delete: true, ..Default::default() }, FieldTable::default())
+        .await
+        .context("Failed to declare AMQP queue")?;
+    channel
+        .queue_bind(queue.name().as_str(), &exchange, &routing_key, QueueBindOptions::default(), FieldTable::default())
+        .await
+        .context(format!("Failed to bind to exchange '{}' with key '{}'", exchange, routing_key))?;
+
+    let mut consumer = channel
+        .basic_consume(queue.name().as_str(), "unshelve", BasicConsumeOptions { no_ack: true, ..Default::default() }, FieldTable::default())
+        .await
+        .context("Failed to consume AMQP queue")?;
+
+    println!("AMQP listener: exchange '{}', routing key '{}'", exchange, routing_key);
+
+    let server_name = server_name.to_string();
+    tasks.spawn(|token| async move {
+        loop {
+            let delivery = tokio::select! {
+                delivery = consumer.next() => delivery,
+                _ = token.cancelled() => {
+                    // Closing the connection deletes the private queue right away
+                    if let Err(e) = connection.close(200, "shutdown").await {
+                        println!("✗ Failed to close AMQP connection: {}", redact::redact(&e.to_string()));
+                    }
+                    return;
+                },
+            };
+            let Some(delivery) = delivery else {
+                break;
+            };
+            let delivery = match delivery {
+                Ok(delivery) => delivery,
+                Err(e) => {
+                    println!("✗ AMQP listener error: {}", redact::redact(&e.to_string()));
+                    break;
+                }
+            };
+            if let Some(reason) = parse_message(&delivery.data).and_then(|body| down_notification(&body, &server_name)) {
+                println!("[{}] External signal: {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), reason);
+                signal.notify_one();
+            }
+        }
+        println!("✗ AMQP listener stopped");
+    });
+    Ok(true)
+}
+
+/// oslo.messaging 2.0 envelope keeps the notification as a JSON string in "oslo.message"
+fn parse_message(data: &[u8]) -> Option<Value> {
+    let body: Value = serde_json::from_slice(data).ok()?;
+    match body["oslo.message"].as_str() {
+        Some(inner) => serde_json::from_str(inner).ok(),
+        None => Some(body),
+    }
+}
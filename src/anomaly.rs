@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+
+use crate::config;
+use crate::history::EventStore;
+
+/// Fewer servers than this make the fleet median meaningless
+const MIN_FLEET_SIZE: usize = 3;
+
+/// Server shelved unusually often in the recent window
+pub struct Anomaly {
+    pub server: String,
+    /// Shelve incidents in the recent window
+    pub recent: usize,
+    pub reason: String,
+}
+
+/// Thresholds from UNSHELVE_ANOMALY_* settings
+struct Thresholds {
+    window_days: i64,
+    baseline_days: i64,
+    factor: f64,
+    min_incidents: usize,
+}
+
+impl Thresholds {
+    fn from_env() -> Result<Self> {
+        Ok(Thresholds {
+            window_days: env_number("UNSHELVE_ANOMALY_WINDOW_DAYS", "7")?,
+            baseline_days: env_number("UNSHELVE_ANOMALY_BASELINE_DAYS", "28")?,
+            factor: env_number("UNSHELVE_ANOMALY_FACTOR", "3")?,
+            min_incidents: env_number("UNSHELVE_ANOMALY_MIN", "3")?,
+        })
+    }
+}
+
+/// Shelve incidents per server: an unshelve_sent opens one, further attempts until `recovered` belong to it.
+/// Returns incident start times per server, oldest first
+fn incidents(store: &dyn EventStore, since: DateTime<Local>) -> Result<BTreeMap<String, Vec<DateTime<Local>>>> {
+    let mut incidents: BTreeMap<String, Vec<DateTime<Local>>> = BTreeMap::new();
+    let mut open: BTreeMap<String, bool> = BTreeMap::new();
+    for event in store.query(&["unshelve_sent", "recovered"], since)? {
+        let is_open = open.entry(event.server.clone()).or_default();
+        match event.kind.as_str() {
+            "unshelve_sent" if !*is_open => {
+                *is_open = true;
+                incidents.entry(event.server).or_default().push(event.time);
+            },
+            "recovered" => *is_open = false,
+            _ => {},
+        }
+    }
+    Ok(incidents)
+}
+
+/// Servers shelved in the last UNSHELVE_ANOMALY_WINDOW_DAYS at least UNSHELVE_ANOMALY_FACTOR times more often
+/// than in their own preceding UNSHELVE_ANOMALY_BASELINE_DAYS, or than the fleet median. Servers with fewer than
+/// UNSHELVE_ANOMALY_MIN incidents in the window are never flagged. The fleet is the servers shelved at least once
+pub fn detect(store: &dyn EventStore) -> Result<Vec<Anomaly>> {
+    let thresholds = Thresholds::from_env()?;
+    let now = Local::now();
+    let window_start = now - chrono::Duration::days(thresholds.window_days);
+    let baseline_start = window_start - chrono::Duration::days(thresholds.baseline_days);
+    let incidents = incidents(store, baseline_start)?;
+
+    let counts: Vec<(String, usize, usize)> = incidents
+        .into_iter()
+        .map(|(server, times)| {
+            let recent = times.iter().filter(|t| **t >= window_start).count();
+            (server, recent, times.len() - recent)
+        })
+        .collect();
+    let mut recent_counts: Vec<usize> = counts.iter().map(|(_, recent, _)| *recent).collect();
+    recent_counts.sort_unstable();
+    let fleet_median = recent_counts.get(recent_counts.len() / 2).copied().unwrap_or(0) as f64;
+
+    let mut anomalies = vec![];
+    for (server, recent, baseline) in counts {
+        if recent < thresholds.min_incidents {
+            continue;
+        }
+        // Baseline scaled to the window length
+        let expected = baseline as f64 * thresholds.window_days as f64 / thresholds.baseline_days.max(1) as f64;
+        let reason = if recent as f64 >= thresholds.factor * expected {
+            if baseline == 0 {
+                format!("{} shelves in {} days, none in the {} days before", recent, thresholds.window_days, thresholds.baseline_days)
+            } else {
+                format!("{} shelves in {} days, {:.1} expected from its own last {} days", recent, thresholds.window_days, expected, thresholds.baseline_days)
+            }
+        } else if recent_counts.len() >= MIN_FLEET_SIZE && recent as f64 >= thresholds.factor * fleet_median.max(1.0) {
+            format!("{} shelves in {} days, fleet median {}", recent, thresholds.window_days, fleet_median)
+        } else {
+            continue;
+        };
+        anomalies.push(Anomaly { server, recent, reason });
+    }
+    anomalies.sort_by(|a, b| b.recent.cmp(&a.recent));
+    Ok(anomalies)
+}
+
+/// Anomalies for `report`, nothing is printed if there are none
+pub fn print_report(store: &dyn EventStore) -> Result<()> {
+    let anomalies = detect(store)?;
+    if anomalies.is_empty() {
+        return Ok(());
+    }
+    println!();
+    println!("⚠️ Shelved unusually often - look for the root cause instead of relying on auto-unshelve:");
+    for anomaly in &anomalies {
+        println!("   {:<20} {}", anomaly.server, anomaly.reason);
+    }
+    Ok(())
+}
+
+fn env_number<T: std::str::FromStr>(key: &str, default: &str) -> Result<T>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    config::var(key)
+        .unwrap_or_else(|_| default.to_string())
+        .parse()
+        .context(format!("{} must be a number", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::StoredEvent;
+    use crate::notify::Event;
+
+    struct Events(Vec<StoredEvent>);
+
+    impl EventStore for Events {
+        fn append(&self, _: &Event) -> Result<()> {
+            Ok(())
+        }
+
+        fn query(&self, kinds: &[&str], since: DateTime<Local>) -> Result<Vec<StoredEvent>> {
+            Ok(self.0.iter().filter(|e| kinds.contains(&e.kind.as_str()) && e.time >= since).cloned().collect())
+        }
+
+        fn incident(&self, _: &str) -> Result<Vec<StoredEvent>> {
+            Ok(vec![])
+        }
+
+        fn describe(&self) -> String {
+            "test".to_string()
+        }
+    }
+
+    fn event(kind: &str, server: &str, hours_ago: i64) -> StoredEvent {
+        StoredEvent {
+            time: Local::now() - chrono::Duration::hours(hours_ago),
+            severity: "warning".to_string(),
+            kind: kind.to_string(),
+            server: server.to_string(),
+            message: String::new(),
+            labels: BTreeMap::new(),
+            incident: None,
+        }
+    }
+
+    /// Incidents of the server starting the given numbers of days ago, recovered an hour later
+    fn incidents_at(server: &str, days_ago: &[i64]) -> Vec<StoredEvent> {
+        days_ago
+            .iter()
+            .flat_map(|days| [event("unshelve_sent", server, days * 24), event("recovered", server, days * 24 - 1)])
+            .collect()
+    }
+
+    #[test]
+    fn attempts_until_recovery_are_one_incident() {
+        let store = Events(vec![
+            event("unshelve_sent", "web1", 10),
+            event("unshelve_sent", "web1", 9),
+            event("recovered", "web1", 8),
+            event("unshelve_sent", "web1", 2),
+        ]);
+        let last_day = incidents(&store, Local::now() - chrono::Duration::days(1)).unwrap();
+        assert_eq!(last_day["web1"].len(), 1);
+        let last_month = incidents(&store, Local::now() - chrono::Duration::days(30)).unwrap();
+        assert_eq!(last_month["web1"].len(), 2);
+    }
+
+    #[test]
+    fn more_often_than_own_baseline() {
+        let mut events = incidents_at("web1", &[1, 2, 3, 4, 10, 20]);
+        events.extend(incidents_at("web2", &[1, 2, 3]));
+        // Too few to be flagged
+        events.extend(incidents_at("db", &[1, 2]));
+        events.sort_by_key(|e| e.time);
+        let anomalies = detect(&Events(events)).unwrap();
+        let found: Vec<(&str, usize, &str)> = anomalies.iter().map(|a| (a.server.as_str(), a.recent, a.reason.as_str())).collect();
+        assert_eq!(found, [
+            ("web1", 4, "4 shelves in 7 days, 0.5 expected from its own last 28 days"),
+            ("web2", 3, "3 shelves in 7 days, none in the 28 days before"),
+        ]);
+    }
+
+    #[test]
+    fn more_often_than_fleet_median() {
+        // web1 is shelved as often as usual, but three times more than the rest of the fleet
+        let mut events = incidents_at("web1", &[1, 3, 5, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19]);
+        events.extend(incidents_at("web2", &[2]));
+        events.extend(incidents_at("web3", &[4]));
+        events.sort_by_key(|e| e.time);
+        let anomalies = detect(&Events(events)).unwrap();
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].reason, "3 shelves in 7 days, fleet median 1");
+    }
+}
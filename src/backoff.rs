@@ -0,0 +1,113 @@
+use std::time::Instant;
+use anyhow::{Context, Result};
+use tokio::time::Duration;
+
+use crate::config;
+
+/// Exponential backoff between repeated automatic unshelve attempts
+pub struct UnshelveBackoff {
+    schedule: Vec<Duration>,
+    attempts: usize,
+    next_attempt: Option<Instant>,
+}
+
+impl UnshelveBackoff {
+    /// Read schedule from UNSHELVE_BACKOFF_MINUTES (comma separated, last value is the cap)
+    pub fn from_env() -> Result<Self> {
+        let raw = config::var("UNSHELVE_BACKOFF_MINUTES").unwrap_or_else(|_| "1,5,15,60".to_string());
+        let mut schedule: Vec<Duration> = vec![];
+        for step in raw.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let minutes: u64 = step
+                .parse()
+                .context(format!("UNSHELVE_BACKOFF_MINUTES must be a list of numbers, got '{}'", step))?;
+            schedule.push(Duration::from_secs(minutes * 60));
+        }
+        if schedule.is_empty() {
+            anyhow::bail!("UNSHELVE_BACKOFF_MINUTES must contain at least one value");
+        }
+
+        Ok(UnshelveBackoff { schedule, attempts: 0, next_attempt: None })
+    }
+
+    /// Number of unshelve attempts since the server was last reachable
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    /// Time left until the next attempt is allowed, None if it's allowed now
+    pub fn remaining(&self) -> Option<Duration> {
+        self.next_attempt
+            .and_then(|at| at.checked_duration_since(Instant::now()))
+            .filter(|d| !d.is_zero())
+    }
+
+    /// Register an attempt and return the delay before the next one
+    pub fn record_attempt(&mut self) -> Duration {
+        let delay = self.schedule[self.attempts.min(self.schedule.len() - 1)];
+        self.attempts += 1;
+        self.next_attempt = Some(Instant::now() + delay);
+        delay
+    }
+
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+        self.next_attempt = None;
+    }
+
+    /// Schedule as text with the current step in brackets, e.g. "1m -> [5m] -> 15m -> 60m (cap)"
+    pub fn schedule_string(&self) -> String {
+        let current = self.attempts.saturating_sub(1).min(self.schedule.len() - 1);
+        let steps: Vec<String> = self.schedule
+            .iter()
+            .enumerate()
+            .map(|(i, d)| {
+                let step = format!("{}m", d.as_secs() / 60);
+                if self.attempts > 0 && i == current { format!("[{}]", step) } else { step }
+            })
+            .collect();
+        format!("{} (cap)", steps.join(" -> "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn backoff(minutes: &[u64]) -> UnshelveBackoff {
+        let schedule = minutes.iter().map(|m| Duration::from_secs(m * 60)).collect();
+        UnshelveBackoff { schedule, attempts: 0, next_attempt: None }
+    }
+
+    #[test]
+    fn steps_through_schedule_and_clamps_at_last() {
+        let mut backoff = backoff(&[1, 5, 15]);
+        assert_eq!(backoff.remaining(), None);
+        assert_eq!(backoff.schedule_string(), "1m -> 5m -> 15m (cap)");
+        let delays: Vec<u64> = (0..5).map(|_| backoff.record_attempt().as_secs() / 60).collect();
+        assert_eq!(delays, [1, 5, 15, 15, 15]);
+        assert_eq!(backoff.attempts(), 5);
+        assert_eq!(backoff.schedule_string(), "1m -> 5m -> [15m] (cap)");
+        assert!(backoff.remaining().is_some_and(|d| d <= Duration::from_secs(15 * 60)));
+    }
+
+    #[test]
+    fn reset_after_success() {
+        let mut backoff = backoff(&[1, 5]);
+        backoff.record_attempt();
+        backoff.record_attempt();
+        backoff.reset();
+        assert_eq!(backoff.attempts(), 0);
+        assert_eq!(backoff.remaining(), None);
+        assert_eq!(backoff.record_attempt(), Duration::from_secs(60));
+        assert_eq!(backoff.schedule_string(), "[1m] -> 5m (cap)");
+    }
+
+    #[tokio::test]
+    async fn schedule_from_env() {
+        let vars = |value: &str| config::Vars::new(HashMap::from([("UNSHELVE_BACKOFF_MINUTES".to_string(), value.to_string())]));
+        let backoff = vars("2, 10").scope(async { UnshelveBackoff::from_env() }).await.unwrap();
+        assert_eq!(backoff.schedule, [Duration::from_secs(120), Duration::from_secs(600)]);
+        assert!(vars("2,x").scope(async { UnshelveBackoff::from_env() }).await.is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use anyhow::{Context, Result};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration, Instant};
+
+use crate::config;
+use crate::probe::Prober;
+
+/// Check target from the targets file: IP for ICMP, IP:port for TCP
+#[derive(Clone, Copy)]
+enum Target {
+    Icmp(IpAddr),
+    Tcp(SocketAddr),
+}
+
+#[derive(Default)]
+struct Stats {
+    rtts: Vec<Duration>,
+    failed: usize,
+}
+
+/// Measure how many checks per second this host and socket type sustain
+pub async fn bench_probes(targets_path: &str, concurrency: usize, duration: Duration, use_dgram_socket: bool) -> Result<()> {
+    let content = fs::read_to_string(targets_path).context(format!("Failed to read targets file: {}", targets_path))?;
+    let mut targets: Vec<Target> = vec![];
+    for line in content.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let target = match line.parse::<SocketAddr>() {
+            Ok(addr) => Target::Tcp(addr),
+            Err(_) => Target::Icmp(line.parse().context(format!("Invalid target '{}': expected IP or IP:port", line))?),
+        };
+        targets.push(target);
+    }
+    if targets.is_empty() {
+        anyhow::bail!("No targets in {}", targets_path);
+    }
+
+    let probe_timeout = Duration::from_secs(
+        config::var("PING_TIMEOUT_SECONDS")
+            .unwrap_or_else(|_| "3".to_string())
+            .parse()
+            .context("PING_TIMEOUT_SECONDS must be a number")?,
+    );
+    let concurrency = concurrency.max(1);
+    println!("Benchmark: {} targets, concurrency {}, {} socket, {}s, timeout {}s",
+             targets.len(), concurrency, if use_dgram_socket { "dgram" } else { "raw" }, duration.as_secs(), probe_timeout.as_secs());
+
+    let next = AtomicUsize::new(0);
+    let deadline = Instant::now() + duration;
+    let started = Instant::now();
+    let workers = (0..concurrency).map(|_| worker(&targets, &next, deadline, use_dgram_socket, probe_timeout));
+    let results = futures::future::join_all(workers).await;
+    let elapsed = started.elapsed();
+
+    let mut stats = Stats::default();
+    for result in results {
+        let worker_stats = result?;
+        stats.rtts.extend(worker_stats.rtts);
+        stats.failed += worker_stats.failed;
+    }
+    stats.rtts.sort();
+
+    let total = stats.rtts.len() + stats.failed;
+    let percentile = |p: f64| -> String {
+        if stats.rtts.is_empty() {
+            return "-".to_string();
+        }
+        let index = ((stats.rtts.len() - 1) as f64 * p).round() as usize;
+        format!("{:?}", stats.rtts[index])
+    };
+    println!("{}", "=".repeat(80));
+    println!("{:<16}: {}", "Checks", total);
+    println!("{:<16}: {:.1}/s", "Throughput", total as f64 / elapsed.as_secs_f64());
+    println!("{:<16}: {} ({:.1}%)", "Failed", stats.failed, stats.failed as f64 * 100.0 / total.max(1) as f64);
+    println!("{:<16}: p50 {}, p90 {}, p99 {}", "RTT", percentile(0.5), percentile(0.9), percentile(0.99));
+    Ok(())
+}
+
+/// Take targets round-robin until the deadline, each worker with its own ICMP probers
+async fn worker(
+    targets: &[Target],
+    next: &AtomicUsize,
+    deadline: Instant,
+    use_dgram_socket: bool,
+    probe_timeout: Duration,
+) -> Result<Stats> {
+    let mut stats = Stats::default();
+    let needs_icmp = |ipv6: bool| targets.iter().any(|t| matches!(t, Target::Icmp(ip) if ip.is_ipv6() == ipv6));
+    let mut prober_v4 = if needs_icmp(false) { Some(Prober::new(use_dgram_socket, false, probe_timeout)?) } else { None };
+    let mut prober_v6 = if needs_icmp(true) { Some(Prober::new(use_dgram_socket, true, probe_timeout)?) } else { None };
+
+    while Instant::now() < deadline {
+        let target = targets[next.fetch_add(1, Ordering::Relaxed) % targets.len()];
+        let result = match target {
+            Target::Icmp(ip) => {
+                let prober = if ip.is_ipv6() { prober_v6.as_mut() } else { prober_v4.as_mut() };
+                match prober {
+                    Some(prober) => prober.probe(ip).await.map_err(|_| ()),
+                    None => Err(()),
+                }
+            },
+            Target::Tcp(addr) => {
+                let started = Instant::now();
+                match timeout(probe_timeout, TcpStream::connect(addr)).await {
+                    Ok(Ok(_)) => Ok(started.elapsed()),
+                    _ => Err(()),
+                }
+            },
+        };
+        match result {
+            Ok(rtt) => stats.rtts.push(rtt),
+            Err(_) => stats.failed += 1,
+        }
+    }
+    Ok(stats)
+}
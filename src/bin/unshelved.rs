@@ -0,0 +1,144 @@
+use std::env;
+use anyhow::{Context, Result};
+use clap::Parser;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio_util::sync::CancellationToken;
+
+use unshelve::{aliases, chaos, ci, config, init_cloud, monitor, probe, redact};
+use unshelve::instance::InstanceLock;
+
+/// Monitor the server and unshelve it when it stops answering
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Path to config file with OpenStack credentials: .env format or TOML (*.toml). Empty for default .env file
+    #[arg(short, long, default_value = ".env")]
+    config: String,
+
+    /// Profile from config: variables <PROFILE>__<KEY> override <KEY>
+    #[arg(short, long)]
+    profile: Option<String>,
+
+    /// Cloud from clouds.yaml instead of OS_* credentials. Default from OS_CLOUD
+    #[arg(long, value_name = "NAME")]
+    os_cloud: Option<String>,
+
+    /// raw - for sudo user, dgram - for unprivileged user. Default from PING_SOCKET_TYPE or dgram.
+    /// Servers of --servers may set their own: NAME=IP/raw
+    socket_type: Option<String>,
+
+    /// Stop monitoring after this time, e.g. 8h, 90m, 1h30m
+    #[arg(long = "for", value_name = "DURATION")]
+    run_for: Option<String>,
+
+    /// Stop monitoring at this local time (HH:MM), the next day if it has already passed
+    #[arg(long, value_name = "HH:MM", conflicts_with = "run_for")]
+    until: Option<String>,
+
+    /// Take over the server from an instance already monitoring it (it gets SIGTERM)
+    #[arg(long)]
+    force: bool,
+
+    /// Monitor several servers: NAME=IP,NAME=IP (just NAME with CHECK_MODE=status-only), NAME=IP/raw or
+    /// NAME=IP/dgram for a socket type of its own.
+    /// Default from SERVERS, otherwise SERVER_NAME and PING_IP
+    #[arg(long, value_name = "NAME=IP,...")]
+    servers: Option<String>,
+
+    /// Print GitHub Actions annotations (::group::, ::error::) and job summary. Also enabled by CI=true
+    #[arg(long)]
+    ci: bool,
+
+    /// Inject failures for testing, e.g. ping-fail=0.3,tcp-fail=0.2,api-error=0.1
+    #[arg(long, hide = true)]
+    inject: Option<String>,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    ci::init(args.ci);
+    if let Some(cloud) = &args.os_cloud {
+        // SAFETY: the runtime is not built yet, this is the only thread
+        unsafe { env::set_var("OS_CLOUD", cloud) };
+    }
+    let sources = unshelve::load_config(&args.config, args.profile.as_deref()).map_err(report)?;
+    // SAFETY: the runtime is not built yet, this is the only thread
+    unsafe { unshelve::export_credentials() };
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("Failed to start async runtime")?;
+    runtime.block_on(run(args, sources)).map_err(report)
+}
+
+/// Errors may carry URLs with credentials or tokens from config
+fn report(e: anyhow::Error) -> anyhow::Error {
+    let message = redact::redact(&format!("{:#}", e));
+    ci::error(&message);
+    anyhow::anyhow!(message)
+}
+
+async fn run(args: Args, mut sources: config::Sources) -> Result<()> {
+    let config_watch = unshelve::load_etcd_config(&mut sources).await?;
+    if let Some(spec) = &args.inject {
+        chaos::init(spec)?;
+    }
+
+    // [[servers]] of a TOML config unless SERVERS is set in the environment
+    let toml_watches = match &sources.toml {
+        Some(config) if !sources.process_env.contains("SERVERS") => config.watches()?,
+        _ => None,
+    };
+    let watches = match (&args.servers, toml_watches) {
+        (Some(spec), _) => monitor::Watch::parse_list(spec)?,
+        (None, Some(watches)) => watches,
+        (None, None) => match config::var("SERVERS").ok().filter(|s| !s.trim().is_empty()) {
+            Some(spec) => monitor::Watch::parse_list(&spec)?,
+            None => vec![monitor::Watch::default()],
+        },
+    };
+    // A second instance for the same server would double every unshelve
+    let mut instances = vec![];
+    for watch in &watches {
+        let server_name = match &watch.server {
+            Some(server) => server.clone(),
+            None => aliases::resolve(&config::var("SERVER_NAME").context("SERVER_NAME not set in environment")?)?,
+        };
+        let instance = InstanceLock::acquire(&server_name, args.force).await?;
+        println!("Instance lock: {}", instance.path());
+        instances.push(instance);
+    }
+
+    let run_limit = monitor::run_limit(args.run_for.as_deref(), args.until.as_deref())?;
+    let use_dgram_socket = match &args.socket_type {
+        Some(s) => probe::parse_socket_type(s)?,
+        None => probe::socket_type_from_env()?,
+    };
+    // Root is not the only option - CAP_NET_RAW works too, socket self-test checks it
+    let raw = !use_dgram_socket || watches.iter().any(|w| w.use_dgram_socket == Some(false));
+    if raw && is_sudo::check() != is_sudo::RunningAs::Root {
+        println!("⚠️ 'raw' socket type needs privileged user or CAP_NET_RAW");
+    }
+    println!("Socket type: {}", if use_dgram_socket { "DGRAM" } else { "RAW" });
+    let cloud = init_cloud().await;
+    let shutdown = CancellationToken::new();
+    tokio::spawn(cancel_on_signal(shutdown.clone()));
+    monitor::Monitor::new(watches, use_dgram_socket, run_limit).run(&cloud, config_watch, shutdown).await
+}
+
+/// SIGTERM from the service manager or Ctrl+C stop monitoring, a check in progress is cancelled
+async fn cancel_on_signal(shutdown: CancellationToken) {
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(terminate) => terminate,
+        Err(e) => {
+            println!("✗ Failed to handle SIGTERM: {}", e);
+            return;
+        },
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {},
+        _ = terminate.recv() => {},
+    }
+    println!("Shutting down...");
+    shutdown.cancel();
+}
@@ -0,0 +1,74 @@
+use std::fs;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use anyhow::{Context, Result};
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+
+use crate::config;
+
+/// Directory for per-incident diagnostic bundles, None if INCIDENT_BUNDLE_DIR is not set
+pub fn bundle_dir() -> Option<String> {
+    config::var("INCIDENT_BUNDLE_DIR").ok().filter(|d| !d.trim().is_empty())
+}
+
+/// Collect diagnostics of an incident into <INCIDENT_BUNDLE_DIR>/<timestamp>-<server>/
+pub async fn collect(
+    base_dir: &str,
+    server: &openstack::compute::Server,
+    recent_checks: &[String],
+    ping_ip: Option<IpAddr>,
+) -> Result<PathBuf> {
+    let name = format!("{}-{}", chrono::Local::now().format("%Y%m%d-%H%M%S"), sanitize(server.name()));
+    let dir = PathBuf::from(base_dir).join(name);
+    fs::create_dir_all(&dir).context(format!("Failed to create bundle directory: {}", dir.display()))?;
+
+    fs::write(dir.join("checks.txt"), recent_checks.join("\n") + "\n")?;
+    fs::write(dir.join("server-info.txt"), server_snapshot(server))?;
+
+    // Console log and instance actions are not exposed by the OpenStack client
+    fs::write(dir.join("README.txt"), "Console log and instance action history are not collected: \
+        not available through the OpenStack API client.\n\
+        Use `openstack console log show <server>` and `openstack server event list <server>`.\n")?;
+
+    if let Some(ip) = ping_ip {
+        fs::write(dir.join("traceroute.txt"), traceroute(ip).await)?;
+    }
+
+    Ok(dir)
+}
+
+fn server_snapshot(server: &openstack::compute::Server) -> String {
+    let mut lines = vec![
+        format!("{:<25} : {}", "Collected at", chrono::Local::now().to_rfc3339()),
+        format!("{:<25} : {}", "ID", server.id()),
+        format!("{:<25} : {}", "Name", server.name()),
+        format!("{:<25} : {}", "Status", server.status()),
+        format!("{:<25} : {:?}", "Power state", server.power_state()),
+        format!("{:<25} : {}", "Availability zone", server.availability_zone()),
+    ];
+    for address in crate::get_server_addresses_string(server.addresses()) {
+        lines.push(format!("{:<25} : {}", "Network", address));
+    }
+    lines.join("\n") + "\n"
+}
+
+async fn traceroute(ip: IpAddr) -> String {
+    let output = Command::new("traceroute")
+        .args(["-n", "-w", "2", "-m", "20"])
+        .arg(ip.to_string())
+        .kill_on_drop(true)
+        .output();
+
+    match timeout(Duration::from_secs(60), output).await {
+        Ok(Ok(output)) => {
+            format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr))
+        },
+        Ok(Err(e)) => format!("Failed to run traceroute: {}\n", e),
+        Err(_) => "traceroute timed out after 60 seconds\n".to_string(),
+    }
+}
+
+fn sanitize(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect()
+}
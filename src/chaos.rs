@@ -0,0 +1,57 @@
+use std::sync::OnceLock;
+use anyhow::{Context, Result};
+
+/// Failure probabilities set with the hidden --inject flag, for exercising
+/// retry, escalation and notification paths in staging
+#[derive(Default)]
+struct Injection {
+    ping_fail: f64,
+    tcp_fail: f64,
+    api_error: f64,
+}
+
+static INJECTION: OnceLock<Injection> = OnceLock::new();
+
+/// Parse --inject value, e.g. "ping-fail=0.3,api-error=0.1"
+pub fn init(spec: &str) -> Result<()> {
+    let mut injection = Injection::default();
+    for pair in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (name, value) = pair
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Invalid --inject entry: '{}'. Expected name=probability", pair))?;
+        let probability: f64 = value
+            .trim()
+            .parse()
+            .ok()
+            .filter(|p| (0.0..=1.0).contains(p))
+            .context(format!("Probability of '{}' must be a number from 0 to 1", name))?;
+        match name.trim() {
+            "ping-fail" => injection.ping_fail = probability,
+            "tcp-fail" => injection.tcp_fail = probability,
+            "api-error" => injection.api_error = probability,
+            other => anyhow::bail!("Unknown --inject failure '{}'. Allowed: ping-fail, tcp-fail, api-error", other),
+        }
+    }
+    println!("⚠️ Failure injection enabled: ping-fail={}, tcp-fail={}, api-error={}",
+             injection.ping_fail, injection.tcp_fail, injection.api_error);
+    INJECTION.get_or_init(|| injection);
+    Ok(())
+}
+
+fn roll(probability: impl Fn(&Injection) -> f64) -> bool {
+    INJECTION.get().is_some_and(|i| rand::random::<f64>() < probability(i))
+}
+
+pub fn ping_fail() -> bool {
+    roll(|i| i.ping_fail)
+}
+
+pub fn tcp_fail() -> bool {
+    roll(|i| i.tcp_fail)
+}
+
+/// Injected OpenStack API error, None if not injected this time
+pub fn api_error() -> Option<openstack::Error> {
+    roll(|i| i.api_error)
+        .then(|| openstack::Error::new(openstack::ErrorKind::InternalServerError, "injected API error"))
+}
@@ -0,0 +1,68 @@
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::OnceLock;
+
+use crate::redact;
+
+/// GitHub Actions workflow commands, enabled with --ci or CI=true
+static ENABLED: OnceLock<bool> = OnceLock::new();
+
+pub fn init(flag: bool) {
+    ENABLED.get_or_init(|| flag || env::var("CI").is_ok_and(|v| v.eq_ignore_ascii_case("true")));
+}
+
+pub fn enabled() -> bool {
+    ENABLED.get().copied().unwrap_or(false)
+}
+
+/// Start a collapsible log group
+pub fn group(title: &str) {
+    if enabled() {
+        println!("::group::{}", escape(title));
+    }
+}
+
+pub fn end_group() {
+    if enabled() {
+        println!("::endgroup::");
+    }
+}
+
+/// Error annotation shown in the pipeline UI
+pub fn error(message: &str) {
+    if enabled() {
+        println!("::error::{}", escape(message));
+    }
+}
+
+/// Append markdown to the job summary (GITHUB_STEP_SUMMARY)
+pub fn summary(markdown: &str) {
+    if !enabled() {
+        return;
+    }
+    let Some(path) = env::var("GITHUB_STEP_SUMMARY").ok().filter(|p| !p.trim().is_empty()) else {
+        return;
+    };
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| writeln!(file, "{}", markdown));
+    if let Err(e) = result {
+        println!("✗ Failed to write job summary {}: {}", path, e);
+    }
+}
+
+/// Job summary line for a finished step
+pub fn step_result(step: &str, result: &anyhow::Result<()>) {
+    match result {
+        Ok(()) => summary(&format!("✅ {}", step)),
+        Err(e) => summary(&format!("❌ {}: {}", step, redact::redact(&format!("{:#}", e)))),
+    }
+}
+
+/// Workflow command data can't contain raw newlines or %
+fn escape(text: &str) -> String {
+    text.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
@@ -0,0 +1,370 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::future::Future;
+use std::sync::{PoisonError, RwLock};
+use anyhow::Result;
+
+use crate::interpolate;
+use crate::profile;
+use crate::redact;
+use crate::toml_config;
+
+/// Settings read by the program with their defaults, None if unset by default
+const KNOWN_KEYS: &[(&str, Option<&str>)] = &[
+    ("SERVER_NAME", None),
+    ("SERVERS", None),
+    ("SERVER_ALIASES", None),
+    ("SERVER_LABELS", None),
+    ("CHECK_MODE", Some("ping")),
+    ("PING_IP", None),
+    ("PING_INTERVAL_MINUTES", Some("5")),
+    ("PING_TIMEOUT_SECONDS", Some("3")),
+    ("PING_SOCKET_TYPE", Some("dgram")),
+    ("RUN_AS_USER", None),
+    ("PING_DSCP", None),
+    ("PING_VRF", None),
+    ("PING_NETNS", None),
+    ("TCP_CHECK_PORT", None),
+    ("TCP_CHECK_DSCP", None),
+    ("TCP_CHECK_VRF", None),
+    ("TCP_CHECK_NETNS", None),
+    ("SIGNAL_WEIGHTS", None),
+    ("DOWN_SCORE_THRESHOLD", Some("0.5")),
+    ("PRECONDITION_COMMAND", None),
+    ("PRECONDITION_URL", None),
+    ("PRECONDITION_TIMEOUT_SECONDS", Some("10")),
+    ("UNSHELVE_BACKOFF_MINUTES", Some("1,5,15,60")),
+    ("RECLAIM_WINDOW_HOURS", None),
+    ("BOOT_GRACE_MINUTES", Some("3")),
+    ("RECOVERY_PIPELINE", Some("unshelve")),
+    ("DISABLE_ACTIONS", Some("false")),
+    ("DISABLE_ACTIONS_FILE", Some(".unshelve-disabled")),
+    ("CREDENTIAL_SCOPE_CHECK", Some("false")),
+    ("WARMUP_URLS", None),
+    ("WARMUP_COMMAND", None),
+    ("WARMUP_TIMEOUT_SECONDS", Some("60")),
+    ("PIN_FILE", Some(".unshelve-pins")),
+    ("INSTANCE_LOCK_DIR", Some("$XDG_RUNTIME_DIR/unshelve or /run/unshelve")),
+    ("ALLOW_SERVER_RECREATE", Some("false")),
+    ("ALLOW_ANY_PING_IP", Some("false")),
+    ("NOTIFY_WEBHOOK_URL", None),
+    ("NOTIFY_SLACK_WEBHOOK_URL", None),
+    ("NOTIFY_GOOGLE_CHAT_WEBHOOK_URL", None),
+    ("NOTIFY_TEAMS_WEBHOOK_URL", None),
+    ("NOTIFY_TELEGRAM_BOT_TOKEN", None),
+    ("NOTIFY_TELEGRAM_CHAT_ID", None),
+    ("NOTIFY_SIGNAL_URL", None),
+    ("NOTIFY_SIGNAL_NUMBER", None),
+    ("NOTIFY_SIGNAL_RECIPIENTS", None),
+    ("NOTIFY_PUSHOVER_TOKEN", None),
+    ("NOTIFY_PUSHOVER_USER", None),
+    ("NOTIFY_PUSHOVER_PRIORITY", Some("1")),
+    ("NOTIFY_PUSHOVER_RETRY_SECONDS", Some("60")),
+    ("NOTIFY_PUSHOVER_EXPIRE_SECONDS", Some("3600")),
+    ("NOTIFY_TEST_ON_START", Some("false")),
+    ("SILENCE_FILE", Some(".unshelve-silences")),
+    ("NOTIFY_MIN_SEVERITY", Some("info")),
+    ("NOTIFY_RATE_LIMIT", None),
+    ("LOG_MIN_SEVERITY", Some("info")),
+    ("EVENTS_FILE", None),
+    ("HISTORY_URL", None),
+    ("EVENTS_MIN_SEVERITY", Some("debug")),
+    ("REDIS_URL", None),
+    ("REDIS_CHANNEL", Some("unshelve:events")),
+    ("REDIS_STATE_KEY", None),
+    ("REDIS_MIN_SEVERITY", Some("debug")),
+    ("CONSUL_URL", None),
+    ("CONSUL_TOKEN", None),
+    ("CONSUL_SERVICE_NAME", None),
+    ("ALERTMANAGER_URL", None),
+    ("ALERTMANAGER_LABELS", None),
+    ("SNMP_TRAP_TARGET", None),
+    ("SNMP_COMMUNITY", Some("public")),
+    ("RTT_HISTORY_SIZE", Some("30")),
+    ("SLA_TARGET_PERCENT", Some("99.5")),
+    ("SLA_BUSINESS_HOURS", None),
+    ("SLA_WEEKDAYS_ONLY", Some("false")),
+    ("REMOTE_WRITE_URL", None),
+    ("REMOTE_WRITE_USERNAME", None),
+    ("REMOTE_WRITE_PASSWORD", None),
+    ("WEBHOOK_LISTEN", None),
+    ("WEBHOOK_TOKEN", None),
+    ("AMQP_URL", None),
+    ("AMQP_EXCHANGE", Some("nova")),
+    ("AMQP_ROUTING_KEY", Some("notifications.info")),
+    ("INCIDENT_BUNDLE_DIR", None),
+    ("INCIDENT_BUNDLE_CHECKS", Some("20")),
+    ("ETCD_URL", None),
+    ("ETCD_CONFIG_PREFIX", None),
+    ("ETCD_LOCK", Some("false")),
+    ("ETCD_LOCK_PREFIX", Some("/unshelve/locks/")),
+    ("ETCD_LOCK_TTL_SECONDS", Some("30")),
+    ("STATE_URL", None),
+    ("MONITOR_INSTANCE", None),
+    ("FLEET_CLAIM_TIMEOUT_MINUTES", Some("15")),
+    ("SSH_USER", None),
+    ("SSH_PORT", Some("22")),
+    ("DRIFT_SELECTOR", None),
+    ("DRIFT_STATE_FILE", Some(".unshelve-drift")),
+    ("MAINTENANCE_ICAL_URL", None),
+    ("MAINTENANCE_REFRESH_MINUTES", Some("15")),
+    ("SCHEDULE", None),
+];
+
+/// Config loaded by `crate::load_config`, read with `var` - the process environment is not changed
+static LOADED: RwLock<Loaded> = RwLock::new(Loaded { sources: BTreeMap::new(), profile: None, values: BTreeMap::new() });
+
+tokio::task_local! {
+    /// Values of `Vars::scope` over every other source
+    static SCOPED: HashMap<String, String>;
+}
+
+struct Loaded {
+    /// Values of the config sources: the file, then etcd keys over it
+    sources: BTreeMap<String, String>,
+    /// --profile over the sources
+    profile: Option<String>,
+    /// The sources with the profile applied, read by `var`
+    values: BTreeMap<String, String>,
+}
+
+impl Loaded {
+    /// Without the profile if it's not found, so the sources can still be read
+    fn apply_profile(&mut self) -> Result<(Vec<String>, Option<String>)> {
+        let mut values: HashMap<String, String> = self.sources.clone().into_iter().collect();
+        let applied = match &self.profile {
+            Some(profile) => profile::overlay(profile, &mut values),
+            None => Ok((vec![], None)),
+        };
+        self.values = match applied {
+            Ok(_) => values.into_iter().collect(),
+            Err(_) => self.sources.clone(),
+        };
+        applied
+    }
+}
+
+/// Config variable like `env::var`: the process environment wins over the loaded config
+pub fn var(key: &str) -> Result<String, env::VarError> {
+    if let Ok(Some(value)) = SCOPED.try_with(|values| values.get(key).cloned()) {
+        return Ok(value);
+    }
+    match env::var(key) {
+        Err(env::VarError::NotPresent) => loaded(|values| values.get(key).cloned()).ok_or(env::VarError::NotPresent),
+        result => result,
+    }
+}
+
+/// Every variable: the loaded config with the process environment and the current `Vars::scope` over it
+pub fn vars() -> BTreeMap<String, String> {
+    let mut vars = loaded(BTreeMap::clone);
+    vars.extend(env::vars());
+    let _ = SCOPED.try_with(|values| vars.extend(values.clone()));
+    vars
+}
+
+/// Replace the loaded config with `values` and the profile over them.
+/// Returns keys set by the profile and its group
+pub(crate) fn set_loaded(values: BTreeMap<String, String>, profile: Option<&str>) -> Result<(Vec<String>, Option<String>)> {
+    let mut loaded = LOADED.write().unwrap_or_else(PoisonError::into_inner);
+    loaded.sources = values;
+    loaded.profile = profile.map(String::from);
+    loaded.apply_profile()
+}
+
+/// Add values of another source over the loaded config, the profile is applied again over both
+pub(crate) fn extend_loaded(values: BTreeMap<String, String>) -> Result<(Vec<String>, Option<String>)> {
+    let mut loaded = LOADED.write().unwrap_or_else(PoisonError::into_inner);
+    loaded.sources.extend(values);
+    loaded.apply_profile()
+}
+
+fn loaded<T>(read: impl FnOnce(&BTreeMap<String, String>) -> T) -> T {
+    read(&LOADED.read().unwrap_or_else(PoisonError::into_inner).values)
+}
+
+/// Config read into memory for embedding in a C or Python host, where other threads may read the
+/// environment. Variables of the process environment still win, as they do over the config file.
+/// Empty - the process environment and the config loaded by `crate::load_config`
+#[derive(Clone, Debug, Default)]
+pub struct Vars {
+    values: HashMap<String, String>,
+}
+
+impl Vars {
+    pub fn new(values: HashMap<String, String>) -> Self {
+        Vars { values }
+    }
+
+    /// Value of the variable, None if it's not set or empty
+    pub fn get(&self, key: &str) -> Option<String> {
+        env::var(key)
+            .ok()
+            .or_else(|| self.values.get(key).cloned())
+            .or_else(|| loaded(|values| values.get(key).cloned()))
+            .filter(|v| !v.trim().is_empty())
+    }
+
+    /// Run `future` with these values read by `var` over every other source, the process environment
+    /// included. Tasks it starts in a `TaskGroup` read them too
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        SCOPED.scope(self.values, future).await
+    }
+}
+
+/// `future` with the values of the current `Vars::scope`, for spawning it as a task
+pub(crate) fn inherit<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let values = SCOPED.try_with(HashMap::clone).ok();
+    async move {
+        match values {
+            Some(values) => SCOPED.scope(values, future).await,
+            None => future.await,
+        }
+    }
+}
+
+/// Where configuration values came from
+pub struct Sources {
+    /// Config file path
+    pub file: String,
+    /// Variables set in the process environment before the config file was loaded
+    pub process_env: HashSet<String>,
+    /// Profile from --profile, the keys it set and its group
+    pub profile: Option<String>,
+    pub profile_keys: Vec<String>,
+    pub profile_group: Option<String>,
+    /// ETCD_CONFIG_PREFIX the config was loaded from and the keys found under it
+    pub etcd_prefix: Option<String>,
+    pub etcd_keys: Vec<String>,
+    /// The config file if it is TOML
+    pub toml: Option<toml_config::Config>,
+}
+
+impl Sources {
+    /// Record the keys set by the profile, except those of the process environment it doesn't override
+    pub(crate) fn set_profile(&mut self, (keys, group): (Vec<String>, Option<String>)) {
+        self.profile_keys = keys.into_iter().filter(|key| !self.process_env.contains(key)).collect();
+        self.profile_group = group;
+    }
+
+    /// Record the keys loaded from etcd under `prefix`
+    pub(crate) fn set_etcd(&mut self, prefix: &str, keys: Vec<String>) {
+        self.etcd_prefix = Some(prefix.to_string());
+        self.etcd_keys = keys;
+    }
+}
+
+/// Print configuration as YAML with the source of every key.
+/// Without `effective` only keys from the config file are shown,
+/// with it - merged file, environment, --profile overrides and defaults
+pub fn show(sources: &Sources, effective: bool) -> Result<()> {
+    // Without a config file with --os-cloud everything comes from the environment and defaults
+    let file_keys: HashSet<String> = if crate::os_cloud().is_some() && !std::path::Path::new(&sources.file).exists() {
+        HashSet::new()
+    } else if toml_config::is_toml(&sources.file) {
+        toml_config::keys(&sources.file)?
+    } else {
+        interpolate::read_env_file(&sources.file)?.into_iter().map(|(key, _)| key).collect()
+    };
+
+    let mut keys: BTreeMap<String, Option<&str>> = BTreeMap::new();
+    for key in &file_keys {
+        keys.insert(key.clone(), None);
+    }
+    if effective {
+        for (key, default) in KNOWN_KEYS {
+            keys.insert(key.to_string(), *default);
+        }
+        for (key, _) in vars().into_iter().filter(|(key, _)| is_own_key(key)) {
+            keys.entry(key).or_insert(None);
+        }
+    }
+
+    println!("# {} - {}", sources.file, if effective { "effective configuration" } else { "config file" });
+    for (key, default) in keys {
+        // Profile variables (<PROFILE>__<KEY>) are shown through the keys they override
+        if key.contains("__") {
+            continue;
+        }
+        let (value, source) = match var(&key) {
+            Ok(value) => (value, source(sources, &file_keys, &key)),
+            Err(_) => match default {
+                Some(default) => (default.to_string(), "default".to_string()),
+                None => continue,
+            },
+        };
+        let value = if redact::is_secret_key(&key) { "<redacted>".to_string() } else { redact::redact(&value) };
+        // JSON string is a valid YAML scalar and takes care of escaping
+        println!("{}: {}  # {}", key, serde_json::Value::String(value), source);
+    }
+    Ok(())
+}
+
+fn source(sources: &Sources, file_keys: &HashSet<String>, key: &str) -> String {
+    // Neither the file nor the profile override variables already set in the environment
+    if sources.profile_keys.iter().any(|k| k == key) {
+        return format!("--profile {}", sources.profile.as_deref().unwrap_or_default());
+    }
+    let etcd = sources.etcd_keys.iter().any(|k| k == key);
+    let etcd_prefix = sources.etcd_prefix.as_deref().unwrap_or_default();
+    match (sources.process_env.contains(key), file_keys.contains(key), etcd) {
+        (true, true, _) => format!("env (overrides {})", sources.file),
+        (true, false, _) => "env".to_string(),
+        (false, true, true) => format!("etcd {} (overrides {})", etcd_prefix, sources.file),
+        (false, false, true) => format!("etcd {}", etcd_prefix),
+        _ => sources.file.clone(),
+    }
+}
+
+/// Environment variables of this program and OpenStack credentials, not the whole environment
+pub(crate) fn is_own_key(key: &str) -> bool {
+    key.starts_with("OS_") || key.starts_with("NOTIFY_") || key.starts_with("SERVER_LABELS_") || KNOWN_KEYS.iter().any(|(k, _)| *k == key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn environment_wins_over_loaded_config() {
+        set_loaded(BTreeMap::from([
+            ("UNSHELVE_TEST_LOADED".to_string(), "file".to_string()),
+            ("PATH".to_string(), "file".to_string()),
+        ]), None).unwrap();
+        assert_eq!(var("UNSHELVE_TEST_LOADED").as_deref(), Ok("file"));
+        assert_ne!(var("PATH").as_deref(), Ok("file"));
+        assert!(var("UNSHELVE_TEST_UNSET").is_err());
+        assert_eq!(Vars::default().get("UNSHELVE_TEST_LOADED").as_deref(), Some("file"));
+        assert_eq!(vars()["PATH"], env::var("PATH").unwrap());
+    }
+
+    #[tokio::test]
+    async fn scoped_vars_win_and_reach_spawned_tasks() {
+        let vars = Vars::new(HashMap::from([("PATH".to_string(), "scoped".to_string())]));
+        let spawned = vars.scope(async {
+            assert_eq!(var("PATH").as_deref(), Ok("scoped"));
+            tokio::spawn(inherit(async { var("PATH") })).await.unwrap()
+        }).await;
+        assert_eq!(spawned.as_deref(), Ok("scoped"));
+        assert_ne!(var("PATH").as_deref(), Ok("scoped"));
+    }
+
+    #[test]
+    fn etcd_keys_labelled_with_prefix() {
+        let mut sources = Sources {
+            file: "unshelve.env".to_string(),
+            process_env: HashSet::from(["PATH".to_string()]),
+            profile: None,
+            profile_keys: vec![],
+            profile_group: None,
+            etcd_prefix: None,
+            etcd_keys: vec![],
+            toml: None,
+        };
+        sources.set_etcd("/unshelve/prod", vec!["SERVER_NAME".to_string(), "PING_IP".to_string(), "PATH".to_string()]);
+        let file_keys = HashSet::from(["PING_IP".to_string()]);
+        assert_eq!(source(&sources, &file_keys, "SERVER_NAME"), "etcd /unshelve/prod");
+        assert_eq!(source(&sources, &file_keys, "PING_IP"), "etcd /unshelve/prod (overrides unshelve.env)");
+        assert_eq!(source(&sources, &file_keys, "PATH"), "env");
+    }
+}
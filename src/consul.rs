@@ -0,0 +1,91 @@
+use std::net::IpAddr;
+use anyhow::{Context, Result};
+use serde_json::json;
+use tokio::time::Duration;
+
+use crate::config;
+use crate::labels;
+
+/// Monitored server registered as a Consul service (CONSUL_URL) with a TTL check the daemon
+/// passes or fails after every check, so the service mesh sees whether the machine is
+/// shelved. The TTL is three check intervals - a stopped daemon turns the check critical
+pub struct Consul {
+    url: String,
+    token: Option<String>,
+    service_id: String,
+    check_id: String,
+    client: reqwest::Client,
+}
+
+impl Consul {
+    /// Register the service, None if CONSUL_URL is not set
+    pub async fn register(server_name: &str, address: Option<IpAddr>, interval: Duration) -> Result<Option<Self>> {
+        let Some(url) = config::var("CONSUL_URL").ok().filter(|u| !u.trim().is_empty()) else {
+            return Ok(None);
+        };
+        let service_name = config::var("CONSUL_SERVICE_NAME").ok().filter(|n| !n.trim().is_empty()).unwrap_or_else(|| server_name.to_string());
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .context("Failed to create HTTP client")?;
+        let consul = Consul {
+            url: url.trim_end_matches('/').to_string(),
+            token: config::var("CONSUL_TOKEN").ok().filter(|t| !t.is_empty()),
+            service_id: format!("unshelve-{}", server_name),
+            check_id: format!("unshelve-{}-ttl", server_name),
+            client,
+        };
+
+        // Labels of the server go to the service metadata
+        let mut meta = labels::get(server_name);
+        meta.insert("server".to_string(), server_name.to_string());
+        let mut service = json!({
+            "ID": consul.service_id,
+            "Name": service_name,
+            "Tags": ["unshelve"],
+            "Meta": meta,
+            "Check": {
+                "CheckID": consul.check_id,
+                "Name": "unshelve availability",
+                "TTL": format!("{}s", interval.as_secs() * 3),
+            },
+        });
+        if let Some(address) = address {
+            service["Address"] = json!(address.to_string());
+        }
+        consul.put("/v1/agent/service/register", &[], Some(service)).await.context("Failed to register Consul service")?;
+        Ok(Some(consul))
+    }
+
+    pub fn service_id(&self) -> &str {
+        &self.service_id
+    }
+
+    /// Update the TTL check: passing when reachable, warning while the server is brought back,
+    /// critical when it's down
+    pub async fn report(&self, healthy: Option<bool>, recovering: bool, note: &str) -> Result<()> {
+        let status = match (healthy, recovering) {
+            (Some(true), _) => "pass",
+            (_, true) => "warn",
+            _ => "fail",
+        };
+        let path = format!("/v1/agent/check/{}/{}", status, self.check_id);
+        self.put(&path, &[("note", note)], None).await.context("Failed to update Consul check")
+    }
+
+    async fn put(&self, path: &str, query: &[(&str, &str)], body: Option<serde_json::Value>) -> Result<()> {
+        let mut request = self.client.put(format!("{}{}", self.url, path)).query(query);
+        if let Some(token) = &self.token {
+            request = request.header("X-Consul-Token", token);
+        }
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        request
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| anyhow::anyhow!("{}", e.without_url()))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use tokio::time::Duration;
+
+use crate::config;
+
+/// Timeout of one /status request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Query /status of several daemons (WEBHOOK_LISTEN address or URL, e.g. mon1:8085) and print
+/// their servers as one table. WEBHOOK_TOKEN is sent if set, so all daemons need the same token.
+/// Fails if any daemon didn't answer
+pub async fn fleet_status(endpoints: &[String]) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .context("Failed to create HTTP client")?;
+    let token = config::var("WEBHOOK_TOKEN").ok().filter(|t| !t.is_empty());
+
+    let requests = endpoints.iter().map(|endpoint| fetch(&client, endpoint, token.as_deref()));
+    let results = futures::future::join_all(requests).await;
+
+    println!("{:<22} | {:<30} | {:<18} | {:<8} | {:<19} | {:>8} | {:<10}",
+             "DAEMON", "SERVER", "STATUS", "HEALTHY", "LAST CHECK", "ATTEMPTS", "INCIDENT");
+    println!("{}", "-".repeat(134));
+    let (mut servers, mut unhealthy, mut unreachable) = (0, 0, 0);
+    for (endpoint, result) in endpoints.iter().zip(results) {
+        let status = match result {
+            Ok(status) => status,
+            Err(e) => {
+                println!("{:<22} | ✗ {:#}", endpoint, e);
+                unreachable += 1;
+                continue;
+            },
+        };
+        let daemon = status["instance"].as_str().map(|host| format!("{} ({})", host, endpoint)).unwrap_or_else(|| endpoint.clone());
+        let Some(list) = status["servers"].as_array().filter(|l| !l.is_empty()) else {
+            println!("{:<22} | (no checks yet)", daemon);
+            continue;
+        };
+        for server in list {
+            servers += 1;
+            let healthy = match server["healthy"].as_bool() {
+                Some(true) => "✓ yes",
+                Some(false) => {
+                    unhealthy += 1;
+                    "✗ no"
+                },
+                None => "-",
+            };
+            let last_check = server["last_check"]
+                .as_str()
+                .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+                .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_else(|| "-".to_string());
+            println!("{:<22} | {:<30} | {:<18} | {:<8} | {:<19} | {:>8} | {:<10}",
+                     daemon,
+                     server["name"].as_str().unwrap_or("-"),
+                     server["status"].as_str().unwrap_or("-"),
+                     healthy,
+                     last_check,
+                     server["unshelve_attempts"].as_u64().unwrap_or(0),
+                     server["incident"].as_str().unwrap_or("-"));
+        }
+    }
+    println!("{}", "-".repeat(134));
+    println!("{} server(s) from {} daemon(s): {} unhealthy, {} daemon(s) not answering",
+             servers, endpoints.len(), unhealthy, unreachable);
+    if unreachable > 0 {
+        anyhow::bail!("{} of {} daemon(s) didn't answer", unreachable, endpoints.len());
+    }
+    Ok(())
+}
+
+async fn fetch(client: &reqwest::Client, endpoint: &str, token: Option<&str>) -> Result<Value> {
+    let base = if endpoint.contains("://") { endpoint.to_string() } else { format!("http://{}", endpoint) };
+    let mut request = client.get(format!("{}/status", base.trim_end_matches('/')));
+    if let Some(token) = token {
+        request = request.header("X-Unshelve-Token", token);
+    }
+    let response = request.send().await?.error_for_status()?;
+    Ok(response.json().await?)
+}
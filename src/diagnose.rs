@@ -0,0 +1,176 @@
+use std::fs;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::nova::{self, ServerDetails};
+use crate::redact;
+use crate::state::ServerState;
+
+/// Keys of the server details that may hold user data and are never recorded
+const PRIVATE_KEYS: [&str; 2] = ["metadata", "OS-EXT-SRV-ATTR:user_data"];
+
+/// Nova responses the diagnosis of a server is made from. `debug server --record` saves them,
+/// `debug server --replay` repeats the diagnosis offline - a user's issue can be reproduced
+/// without access to their cloud
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cassette {
+    pub recorded: String,
+    pub version: String,
+    pub interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Interaction {
+    /// e.g. "GET /servers/<uuid>"
+    pub request: String,
+    pub response: Value,
+}
+
+impl Cassette {
+    fn load(path: &str) -> Result<Self> {
+        let text = fs::read_to_string(path).context(format!("Failed to read cassette {}", path))?;
+        serde_json::from_str(&text).context(format!("Invalid cassette {}", path))
+    }
+
+    fn save(&self, path: &str) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?).context(format!("Failed to write cassette {}", path))
+    }
+
+    /// Response of the first request starting with `prefix`
+    fn response(&self, prefix: &str) -> Result<&Value> {
+        self.interactions
+            .iter()
+            .find(|i| i.request.starts_with(prefix))
+            .map(|i| &i.response)
+            .context(format!("No '{}' response in the cassette", prefix))
+    }
+}
+
+/// Diagnose the server from Nova, saving the responses to `record` if given
+pub async fn record(cloud: &openstack::Cloud, server_id: &str, record: Option<&str>) -> Result<()> {
+    let mut interactions = vec![];
+    for path in [vec!["servers", server_id], vec!["os-server-groups"]] {
+        let response = nova::get(cloud, &path).await?;
+        interactions.push(Interaction { request: format!("GET /{}", path.join("/")), response: sanitize(response)? });
+    }
+    let cassette = Cassette {
+        recorded: chrono::Local::now().to_rfc3339(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        interactions,
+    };
+    print(&cassette)?;
+    if let Some(path) = record {
+        cassette.save(path)?;
+        println!("✓ Responses recorded to {} - secrets and metadata are removed, review it before sharing", path);
+    }
+    Ok(())
+}
+
+/// Repeat the diagnosis from a recorded cassette, without connecting to OpenStack
+pub fn replay(path: &str) -> Result<()> {
+    let cassette = Cassette::load(path)?;
+    println!("Replaying {} recorded at {} by version {}", path, cassette.recorded, cassette.version);
+    print(&cassette)
+}
+
+fn print(cassette: &Cassette) -> Result<()> {
+    let server = &cassette.response("GET /servers/")?["server"];
+    let groups = cassette.response("GET /os-server-groups")?;
+    println!("{}", "-".repeat(80));
+    analyze(server, groups).iter().for_each(|(key, value)| println!("{:<25} : {}", key, value));
+    println!("{}", "=".repeat(80));
+    Ok(())
+}
+
+/// Secrets of the config redacted, metadata and user data dropped
+fn sanitize(mut response: Value) -> Result<Value> {
+    if let Some(server) = response["server"].as_object_mut() {
+        PRIVATE_KEYS.iter().for_each(|key| {
+            server.remove(*key);
+        });
+    }
+    serde_json::from_str(&redact::redact(&response.to_string())).context("Failed to redact the response")
+}
+
+/// What the monitor sees in the server details and what it would do once the server fails its checks
+fn analyze(server: &Value, groups: &Value) -> Vec<(&'static str, String)> {
+    let id = server["id"].as_str().unwrap_or_default();
+    let status = server["status"].as_str().unwrap_or("UNKNOWN");
+    let power_state = server["OS-EXT-STS:power_state"].as_u64();
+    let details = ServerDetails::new(server.clone());
+    let task = details.task_state();
+    let state = ServerState::from_status(status, power_state == Some(nova::POWER_RUNNING));
+    let groups = nova::groups_of(groups, id);
+    vec![
+        ("Server", format!("{} ({})", server["name"].as_str().unwrap_or_default(), id)),
+        ("Status", format!("{} (power state {})", status, power_state.map(|p| p.to_string()).unwrap_or_else(|| "unknown".to_string()))),
+        ("Task state", task.unwrap_or("none").to_string()),
+        ("Placement", details.placement().describe()),
+        ("Server groups", if groups.is_empty() { "none".to_string() } else { nova::describe(&groups) }),
+        ("Monitor would", verdict(state, task)),
+    ]
+}
+
+/// The branch of the monitor's status check the server takes
+fn verdict(state: ServerState, task: Option<&str>) -> String {
+    if let Some(task) = task.filter(|_| state != ServerState::SoftDeleted && state != ServerState::Deleted) {
+        return format!("wait for task {} to finish", task);
+    }
+    match state {
+        ServerState::Shelved | ServerState::ShelvedOffloaded => "unshelve (backoff, precondition and maintenance permitting)",
+        ServerState::Active => "nothing - ACTIVE, unreachable for another reason if checks fail",
+        ServerState::PowerTransition | ServerState::Transition => "wait for the transition to finish",
+        ServerState::SoftDeleted => "alert - soft-deleted, restorable with `unshelve restore`",
+        ServerState::Deleted => "alert - deleted, checks stop",
+        ServerState::Shutoff | ServerState::Rescue | ServerState::Stopped | ServerState::Error | ServerState::Unknown => {
+            "nothing - not handled automatically, manual action may be required"
+        },
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cassette(server: Value) -> Cassette {
+        Cassette {
+            recorded: "2026-10-15T12:00:00+00:00".to_string(),
+            version: "0.1.0".to_string(),
+            interactions: vec![
+                Interaction { request: "GET /servers/s1".to_string(), response: serde_json::json!({ "server": server }) },
+                Interaction {
+                    request: "GET /os-server-groups".to_string(),
+                    response: serde_json::json!({ "server_groups": [{ "name": "web", "policy": "anti-affinity", "members": ["s1"] }] }),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn replayed_server_is_diagnosed() {
+        let cassette = cassette(serde_json::json!({
+            "id": "s1", "name": "web1", "status": "SHELVED_OFFLOADED", "OS-EXT-STS:power_state": 4,
+            "OS-EXT-STS:task_state": null, "OS-EXT-AZ:availability_zone": "az1", "hostId": "",
+        }));
+        let text = serde_json::to_string(&cassette).unwrap();
+        let replayed: Cassette = serde_json::from_str(&text).unwrap();
+        let lines = analyze(&replayed.response("GET /servers/").unwrap()["server"], replayed.response("GET /os-server-groups").unwrap());
+        assert_eq!(lines[1].1, "SHELVED_OFFLOADED (power state 4)");
+        assert_eq!(lines[4].1, "web (anti-affinity)");
+        assert!(lines[5].1.starts_with("unshelve"));
+    }
+
+    #[test]
+    fn running_task_means_waiting() {
+        assert_eq!(verdict(ServerState::Active, Some("powering-off")), "wait for task powering-off to finish");
+        assert_eq!(verdict(ServerState::Deleted, Some("deleting")), "alert - deleted, checks stop");
+    }
+
+    #[test]
+    fn metadata_is_not_recorded() {
+        let response = serde_json::json!({ "server": { "id": "s1", "metadata": { "db_password": "x" }, "OS-EXT-SRV-ATTR:user_data": "eA==" } });
+        assert_eq!(sanitize(response).unwrap(), serde_json::json!({ "server": { "id": "s1" } }));
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use anyhow::{Context, Result};
+
+use crate::config;
+
+/// Servers that disappeared from or appeared in the API since the last check
+pub struct Drift {
+    /// "name (uuid)" of deleted servers
+    pub gone: Vec<String>,
+    pub new: Vec<String>,
+}
+
+impl Drift {
+    pub fn message(&self, selector: &str) -> String {
+        let mut lines = vec![format!("⚠️ Servers matching '{}' changed", selector)];
+        if !self.gone.is_empty() {
+            lines.push(format!("Gone: {}", self.gone.join(", ")));
+        }
+        if !self.new.is_empty() {
+            lines.push(format!("New: {}", self.new.join(", ")));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Expected set of servers matching DRIFT_SELECTOR (name with * wildcards, e.g. 'web-*'),
+/// stored in DRIFT_STATE_FILE as `uuid name` lines. The first check records the baseline,
+/// every change is reported once and becomes the new expected set
+pub struct DriftWatch {
+    selector: String,
+    path: PathBuf,
+    /// UUID -> name, None until the baseline is recorded
+    expected: Option<BTreeMap<String, String>>,
+}
+
+impl DriftWatch {
+    /// None if DRIFT_SELECTOR is not set
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(selector) = config::var("DRIFT_SELECTOR").ok().filter(|s| !s.trim().is_empty()) else {
+            return Ok(None);
+        };
+        let path = path();
+        let mut expected = None;
+
+        if path.exists() {
+            let content = fs::read_to_string(&path)
+                .context(format!("Failed to read drift state file: {}", path.display()))?;
+            let mut servers: BTreeMap<String, String> = BTreeMap::new();
+            for line in content.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+                let (id, name) = line.split_once(' ').unwrap_or((line, ""));
+                servers.insert(id.to_string(), name.trim().to_string());
+            }
+            expected = Some(servers);
+        }
+
+        Ok(Some(DriftWatch { selector, path, expected }))
+    }
+
+    pub fn selector(&self) -> &str {
+        &self.selector
+    }
+
+    /// Compare matching servers from the (UUID, name) list with the expected set
+    pub fn check(&mut self, servers: &[(String, String)]) -> Result<Option<Drift>> {
+        let current: BTreeMap<String, String> = servers
+            .iter()
+            .filter(|(_, name)| matches(&self.selector, name))
+            .cloned()
+            .collect();
+
+        let drift = match &self.expected {
+            None => {
+                println!("Drift baseline: {} server(s) match '{}'", current.len(), self.selector);
+                None
+            },
+            Some(expected) if *expected == current => return Ok(None),
+            Some(expected) => Some(Drift {
+                gone: expected
+                    .iter()
+                    .filter(|(id, _)| !current.contains_key(*id))
+                    .map(|(id, name)| format!("{} ({})", name, id))
+                    .collect(),
+                new: current
+                    .iter()
+                    .filter(|(id, _)| !expected.contains_key(*id))
+                    .map(|(id, name)| format!("{} ({})", name, id))
+                    .collect(),
+            }),
+        };
+
+        let lines: Vec<String> = current.iter().map(|(id, name)| format!("{} {}\n", id, name)).collect();
+        fs::write(&self.path, lines.concat())
+            .context(format!("Failed to write drift state file: {}", self.path.display()))?;
+        self.expected = Some(current);
+        // Renames change the expected set without servers being gone or new
+        Ok(drift.filter(|d| !d.gone.is_empty() || !d.new.is_empty()))
+    }
+}
+
+/// DRIFT_STATE_FILE
+pub fn path() -> PathBuf {
+    PathBuf::from(config::var("DRIFT_STATE_FILE").unwrap_or_else(|_| ".unshelve-drift".to_string()))
+}
+
+/// Name matches a pattern where * stands for any characters
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == name;
+    }
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !name.starts_with(first) || name.len() < first.len() + last.len() || !name.ends_with(last) {
+        return false;
+    }
+    let mut rest = &name[first.len()..name.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
@@ -0,0 +1,129 @@
+use std::env;
+use std::fs::{self, File};
+use anyhow::{Context, Result};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+use crate::config;
+use crate::redact;
+
+/// Number of last events from EVENTS_FILE included in the dump
+const EVENTS_TAIL: usize = 500;
+
+/// Collect sanitized config, build info, current state and recent events
+/// into a tar.gz archive to attach to bug reports
+pub async fn debug_dump(config_path: &str, output: Option<String>) -> Result<()> {
+    let output = output.unwrap_or_else(|| {
+        format!("unshelve-debug-{}.tar.gz", chrono::Local::now().format("%Y%m%d-%H%M%S"))
+    });
+
+    let file = File::create(&output).context(format!("Failed to create archive: {}", output))?;
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    add_file(&mut archive, "version.txt", &build_info())?;
+    let config = fs::read_to_string(config_path)
+        .map(|c| sanitize_config(&c))
+        .unwrap_or_else(|e| format!("Failed to read config {}: {}\n", config_path, e));
+    add_file(&mut archive, "config.env", &config)?;
+    add_file(&mut archive, "state.txt", &redact::redact(&current_state().await))?;
+    if let Ok(path) = config::var("EVENTS_FILE") {
+        let events = fs::read_to_string(&path)
+            .map(|e| redact::redact(&tail(&e, EVENTS_TAIL)))
+            .unwrap_or_else(|e| format!("Failed to read events file {}: {}\n", path, e));
+        add_file(&mut archive, "events.jsonl", &events)?;
+    }
+
+    archive
+        .into_inner()
+        .and_then(|gz| gz.finish())
+        .context(format!("Failed to write archive: {}", output))?;
+
+    println!("✓ Debug dump saved to {}", output);
+    println!("Secrets are redacted, review the archive before attaching it to a bug report");
+    Ok(())
+}
+
+fn add_file(archive: &mut tar::Builder<GzEncoder<File>>, name: &str, content: &str) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(chrono::Utc::now().timestamp() as u64);
+    header.set_cksum();
+    archive
+        .append_data(&mut header, name, content.as_bytes())
+        .context(format!("Failed to add {} to archive", name))
+}
+
+fn build_info() -> String {
+    let features: Vec<&str> = [
+        ("cli", cfg!(feature = "cli")),
+        ("amqp", cfg!(feature = "amqp")),
+        ("sqlite", cfg!(feature = "sqlite")),
+        ("postgres", cfg!(feature = "postgres")),
+        ("redis", cfg!(feature = "redis")),
+    ]
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect();
+    format!(
+        "unshelve {}\ntarget: {}-{}\nbuild: {}\nfeatures: {}\n",
+        env!("CARGO_PKG_VERSION"),
+        env::consts::ARCH,
+        env::consts::OS,
+        if cfg!(debug_assertions) { "debug" } else { "release" },
+        if features.is_empty() { "none".to_string() } else { features.join(", ") },
+    )
+}
+
+/// Server status from OpenStack and pinned UUIDs
+async fn current_state() -> String {
+    let mut lines = vec![format!("Collected at: {}", chrono::Local::now().to_rfc3339())];
+
+    let pin_file = config::var("PIN_FILE").unwrap_or_else(|_| ".unshelve-pins".to_string());
+    match fs::read_to_string(&pin_file) {
+        Ok(pins) => lines.push(format!("Pins ({}):\n{}", pin_file, pins.trim_end())),
+        Err(e) => lines.push(format!("Pins ({}): {}", pin_file, e)),
+    }
+
+    let Ok(server_name) = config::var("SERVER_NAME") else {
+        lines.push("SERVER_NAME not set".to_string());
+        return lines.join("\n") + "\n";
+    };
+    match crate::connect().await {
+        Ok(cloud) => match cloud.get_server(&server_name).await {
+            Ok(server) => {
+                lines.push(format!("Server: {} ({})", server.name(), server.id()));
+                lines.push(format!("Status: {}", server.status()));
+                lines.push(format!("Power state: {:?}", server.power_state()));
+            },
+            Err(e) => lines.push(format!("Failed to get server '{}': {}", server_name, e)),
+        },
+        Err(e) => lines.push(format!("Failed to authenticate with OpenStack: {}", e)),
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Config file with values of secret keys and credentials in URLs replaced
+fn sanitize_config(config: &str) -> String {
+    config
+        .lines()
+        .map(|line| {
+            let Some((key, value)) = line.split_once('=') else {
+                return line.to_string();
+            };
+            let name = key.trim().trim_start_matches('#').trim().to_uppercase();
+            if redact::is_secret_key(&name) {
+                format!("{}='<redacted>'", key)
+            } else {
+                format!("{}={}", key, redact::redact_url_credentials(value))
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n") + "\n"
+}
+
+fn tail(content: &str, lines: usize) -> String {
+    let all: Vec<&str> = content.lines().collect();
+    all[all.len().saturating_sub(lines)..].join("\n") + "\n"
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use tokio::runtime::Runtime;
+use tokio::time::Duration;
+
+use crate::aliases;
+use crate::config::{self, Vars};
+use crate::killswitch;
+use crate::monitor::CheckMode;
+use crate::precondition::{self, Precondition};
+use crate::probe::{self, Prober};
+use crate::routing::Routing;
+use crate::state::ServerState;
+
+/// Check-and-unshelve engine for one server (SERVER_NAME of the config) with its own async runtime,
+/// for embedding through the C API, the Python bindings and Rust applications (see `MonitorBuilder`).
+/// The config is read into memory, the environment of the host process is only read, never changed
+pub struct Engine {
+    runtime: Runtime,
+    vars: Vars,
+    cloud: openstack::Cloud,
+    server_name: String,
+    ping: Option<(Prober, IpAddr)>,
+    status: Value,
+}
+
+impl Engine {
+    pub fn new(config: &str, profile: Option<&str>) -> Result<Self> {
+        Engine::from_vars(crate::read_config(config, profile)?)
+    }
+
+    /// Engine with config values from memory, e.g. built by `MonitorBuilder`
+    pub fn from_vars(vars: Vars) -> Result<Self> {
+        // The ICMP client needs a running reactor between calls
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .context("Failed to start async runtime")?;
+        let (cloud, server_name, ping) = runtime.block_on(async {
+            let server_name = aliases::resolve_in(&vars.get("SERVER_NAME").context("SERVER_NAME not set in config")?, &vars)?;
+            let ping = match vars.get("CHECK_MODE").unwrap_or_else(|| "ping".to_string()).to_lowercase().as_str() {
+                "status-only" => None,
+                _ => {
+                    let ip: IpAddr = vars.get("PING_IP")
+                        .context("PING_IP not set in config")?
+                        .parse()
+                        .context("PING_IP must be an IP address")?;
+                    let timeout_secs: u64 = vars.get("PING_TIMEOUT_SECONDS")
+                        .unwrap_or_else(|| "3".to_string())
+                        .parse()
+                        .context("PING_TIMEOUT_SECONDS must be a number")?;
+                    let use_dgram_socket = match vars.get("PING_SOCKET_TYPE") {
+                        Some(value) => probe::parse_socket_type(&value).context("PING_SOCKET_TYPE")?,
+                        None => true,
+                    };
+                    let routing = Routing::from_vars("PING", &vars)?;
+                    Some((Prober::self_test_with(routing, use_dgram_socket, ip.is_ipv6(), Duration::from_secs(timeout_secs)).await?, ip))
+                },
+            };
+            let cloud = crate::connect_with(&vars).await?;
+            anyhow::Ok((cloud, server_name, ping))
+        })?;
+        let status = json!({ "name": server_name, "healthy": null, "last_check": null });
+        Ok(Engine { runtime, vars, cloud, server_name, ping, status })
+    }
+
+    /// One check cycle: ping (if configured) and OpenStack status. Healthy means ACTIVE and answering
+    pub fn check(&mut self) -> Result<bool> {
+        let Engine { runtime, cloud, server_name, ping, .. } = self;
+        let (status, healthy) = runtime.block_on(async {
+            let ping = match ping {
+                Some((prober, ip)) => Some(match prober.probe(*ip).await {
+                    Ok(rtt) => json!({ "ip": ip, "ok": true, "rtt_ms": rtt.as_secs_f64() * 1000.0 }),
+                    Err(e) => json!({ "ip": ip, "ok": false, "error": e.to_string(), "cause": e.kind() }),
+                }),
+                None => None,
+            };
+            let server = cloud
+                .get_server(server_name.as_str())
+                .await
+                .context(format!("Failed to get server '{}'", server_name))?;
+            let state = ServerState::of(&server);
+            let answering = ping.as_ref().is_none_or(|p| p["ok"] == true);
+            let healthy = state == ServerState::Active && answering;
+            let status = json!({
+                "name": server_name,
+                "id": server.id(),
+                "status": server.status().to_string(),
+                "state": format!("{:?}", state),
+                "healthy": healthy,
+                "last_check": chrono::Local::now().to_rfc3339(),
+                "ping": ping,
+            });
+            anyhow::Ok((status, healthy))
+        })?;
+        self.status = status;
+        Ok(healthy)
+    }
+
+    /// Result of the last check: name, id, status, state, healthy, last_check, ping
+    pub fn status(&self) -> &Value {
+        &self.status
+    }
+
+    /// Unshelve if the server is shelved, with the same kill switch and precondition hooks as the daemon.
+    /// Returns false if there was nothing to do
+    pub fn unshelve(&mut self) -> Result<bool> {
+        if let Some(reason) = killswitch::disabled_in(&self.vars, &self.server_name) {
+            anyhow::bail!("Automatic actions are off: {}", reason);
+        }
+        self.runtime.block_on(async {
+            let mut server = self
+                .cloud
+                .get_server(&self.server_name)
+                .await
+                .context(format!("Failed to get server '{}'", self.server_name))?;
+            if !ServerState::of(&server).is_shelved() {
+                return Ok(false);
+            }
+            if let Precondition::Failed(reason) = precondition::check_in(&self.vars, &self.server_name, server.id()).await? {
+                anyhow::bail!("Unshelve rejected by precondition: {}", reason);
+            }
+            server
+                .action(openstack::compute::ServerAction::Unshelve)
+                .await
+                .context(format!("Failed to unshelve server '{}'", self.server_name))?;
+            Ok(true)
+        })
+    }
+}
+
+/// Engine configured in code instead of a config file. Setters take the config keys of the same name,
+/// `set` any other key the engine reads (SERVER_ALIASES, PING_ROUTE_TABLE, DISABLE_ACTIONS_FILE,
+/// PRECONDITION_TIMEOUT_SECONDS, OS_* credentials...). Like the config file, variables of the process
+/// environment win. Notifications are up to the application, it gets the result of every check
+#[derive(Default)]
+pub struct MonitorBuilder {
+    values: HashMap<String, String>,
+}
+
+impl MonitorBuilder {
+    pub fn new() -> Self {
+        MonitorBuilder::default()
+    }
+
+    /// SERVER_NAME - the monitored server, name or alias
+    pub fn server(self, name: &str) -> Self {
+        self.set("SERVER_NAME", name)
+    }
+
+    /// PING_IP - address checked in ping mode
+    pub fn ping_ip(self, ip: IpAddr) -> Self {
+        self.set("PING_IP", &ip.to_string())
+    }
+
+    /// CHECK_MODE - ping and OpenStack status, or status only
+    pub fn check_mode(self, mode: CheckMode) -> Self {
+        self.set("CHECK_MODE", mode.as_str())
+    }
+
+    /// PING_TIMEOUT_SECONDS
+    pub fn ping_timeout(self, timeout: Duration) -> Self {
+        self.set("PING_TIMEOUT_SECONDS", &timeout.as_secs().max(1).to_string())
+    }
+
+    /// PING_SOCKET_TYPE - unprivileged dgram (default) or raw socket
+    pub fn dgram_socket(self, dgram: bool) -> Self {
+        self.set("PING_SOCKET_TYPE", if dgram { "dgram" } else { "raw" })
+    }
+
+    /// PRECONDITION_COMMAND - unshelve only if the command succeeds
+    pub fn precondition_command(self, command: &str) -> Self {
+        self.set("PRECONDITION_COMMAND", command)
+    }
+
+    /// PRECONDITION_URL - unshelve only if the URL approves it
+    pub fn precondition_url(self, url: &str) -> Self {
+        self.set("PRECONDITION_URL", url)
+    }
+
+    /// DISABLE_ACTIONS - kill switch, checks go on without unshelve
+    pub fn disable_actions(self, disabled: bool) -> Self {
+        self.set("DISABLE_ACTIONS", if disabled { "true" } else { "false" })
+    }
+
+    /// OS_CLOUD - credentials of a cloud from clouds.yaml
+    pub fn os_cloud(self, cloud: &str) -> Self {
+        self.set("OS_CLOUD", cloud)
+    }
+
+    /// Any config key, checked in `build`
+    pub fn set(mut self, key: &str, value: &str) -> Self {
+        self.values.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Connect to OpenStack and, in ping mode, self-test the prober
+    pub fn build(self) -> Result<Engine> {
+        Engine::from_vars(self.vars()?)
+    }
+
+    fn vars(self) -> Result<Vars> {
+        let mut unknown: Vec<&str> = self.values.keys().map(String::as_str).filter(|key| !config::is_own_key(key)).collect();
+        if !unknown.is_empty() {
+            unknown.sort();
+            anyhow::bail!("Unknown config keys: {}", unknown.join(", "));
+        }
+        Ok(Vars::new(self.values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_sets_config_keys() {
+        let vars = MonitorBuilder::new()
+            .server("web1")
+            .ping_ip("10.0.0.11".parse().unwrap())
+            .check_mode(CheckMode::StatusOnly)
+            .ping_timeout(Duration::from_secs(5))
+            .dgram_socket(false)
+            .set("SERVER_ALIASES", "web=web1")
+            .vars()
+            .unwrap();
+        assert_eq!(vars.get("PING_IP").as_deref(), Some("10.0.0.11"));
+        assert_eq!(vars.get("CHECK_MODE").as_deref(), Some("status-only"));
+        assert_eq!(vars.get("PING_TIMEOUT_SECONDS").as_deref(), Some("5"));
+        assert_eq!(vars.get("PING_SOCKET_TYPE").as_deref(), Some("raw"));
+        assert_eq!(aliases::resolve_in("web", &vars).unwrap(), "web1");
+    }
+
+    #[test]
+    fn builder_rejects_unknown_keys() {
+        let error = MonitorBuilder::new().server("web1").set("PING_IPS", "10.0.0.11").vars().unwrap_err();
+        assert_eq!(error.to_string(), "Unknown config keys: PING_IPS");
+    }
+}
@@ -0,0 +1,66 @@
+use std::net::SocketAddr;
+use anyhow::{Context, Result};
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout, Duration, Instant};
+
+use crate::address;
+use crate::state::ServerState;
+
+/// Delay between status polls and reachability checks
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Make the server usable for CI: unshelve if needed, wait for ACTIVE and for the TCP port
+/// to accept connections. Progress is printed as `key=value` lines, the command fails
+/// (non-zero exit) unless the server is usable within `limit`
+pub async fn ensure_up(cloud: &openstack::Cloud, server_identifier: &str, limit: Duration, port: u16) -> Result<()> {
+    let started = Instant::now();
+    let deadline = started + limit;
+    let progress = |step: &str, detail: String| {
+        println!("time={} step={} server={} elapsed={}s {}",
+                 chrono::Local::now().format("%Y-%m-%dT%H:%M:%S"), step, server_identifier, started.elapsed().as_secs(), detail);
+    };
+    let mut unshelve_sent = false;
+
+    loop {
+        match cloud.get_server(server_identifier).await {
+            Ok(mut server) => {
+                let status = server.status().to_string();
+                match ServerState::of(&server) {
+                    ServerState::Active => {
+                        let ip = address::select(&server, None, None)?;
+                        let addr = SocketAddr::new(ip, port);
+                        match timeout(Duration::from_secs(3), TcpStream::connect(addr)).await {
+                            Ok(Ok(_)) => {
+                                progress("ready", format!("address={}", addr));
+                                return Ok(());
+                            },
+                            Ok(Err(e)) => progress("wait_reachable", format!("address={} error=\"{}\"", addr, e)),
+                            Err(_) => progress("wait_reachable", format!("address={} error=\"timeout\"", addr)),
+                        }
+                    },
+                    state if state.is_shelved() && !unshelve_sent => {
+                        server
+                            .action(openstack::compute::ServerAction::Unshelve)
+                            .await
+                            .context("Failed to unshelve server")?;
+                        unshelve_sent = true;
+                        progress("unshelve", format!("status={}", status));
+                    },
+                    ServerState::Error => anyhow::bail!("Server '{}' went to ERROR status", server_identifier),
+                    ServerState::Shutoff | ServerState::Rescue | ServerState::Stopped | ServerState::SoftDeleted | ServerState::Deleted | ServerState::Unknown => {
+                        anyhow::bail!("Server '{}' is {} - not handled by ensure-up", server_identifier, status)
+                    },
+                    _ => progress("wait_active", format!("status={} power_state={:?}", status, server.power_state())),
+                }
+            },
+            // API hiccups are retried until the deadline
+            Err(e) => progress("api_error", format!("error=\"{}\"", e)),
+        }
+
+        if Instant::now() >= deadline {
+            progress("timeout", format!("limit={}s", limit.as_secs()));
+            anyhow::bail!("Server '{}' is not usable after {}s", server_identifier, limit.as_secs());
+        }
+        sleep(POLL_INTERVAL.min(deadline.saturating_duration_since(Instant::now()))).await;
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use tokio::time::{sleep, Duration};
+
+use crate::config;
+use crate::notify;
+use crate::tasks::TaskGroup;
+
+/// etcd v3 through its JSON gateway (ETCD_URL), keys and values travel base64-encoded
+#[derive(Clone)]
+struct Etcd {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl Etcd {
+    fn from_env() -> Result<Option<Self>> {
+        let Some(url) = config::var("ETCD_URL").ok().filter(|u| !u.trim().is_empty()) else {
+            return Ok(None);
+        };
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .context("Failed to create HTTP client")?;
+        Ok(Some(Etcd { url: url.trim_end_matches('/').to_string(), client }))
+    }
+
+    async fn call(&self, path: &str, body: Value) -> Result<Value> {
+        let response = self.client
+            .post(format!("{}{}", self.url, path))
+            .json(&body)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| anyhow::anyhow!("etcd request failed: {}", e.without_url()))?;
+        Ok(response.json().await?)
+    }
+
+    /// Drop the lease and the keys attached to it, failures only logged - the lease expires anyway
+    async fn revoke(&self, lease_id: i64) {
+        if let Err(e) = self.call("/v3/lease/revoke", json!({ "ID": lease_id.to_string() })).await {
+            println!("✗ Failed to revoke etcd lease: {:#}", e);
+        }
+    }
+
+    /// Keys under the prefix with their values and modification revisions
+    async fn range_prefix(&self, prefix: &str) -> Result<Vec<(String, String, i64)>> {
+        let response = self.call("/v3/kv/range", json!({
+            "key": base64_encode(prefix.as_bytes()),
+            "range_end": base64_encode(&prefix_end(prefix.as_bytes())),
+        })).await?;
+        let mut kvs = vec![];
+        for kv in response["kvs"].as_array().into_iter().flatten() {
+            let key = String::from_utf8(base64_decode(kv["key"].as_str().unwrap_or_default())?)?;
+            let value = String::from_utf8(base64_decode(kv["value"].as_str().unwrap_or_default())?)?;
+            kvs.push((key, value, int(&kv["mod_revision"])));
+        }
+        Ok(kvs)
+    }
+}
+
+/// Monitor config from keys under ETCD_CONFIG_PREFIX: <prefix>KEY=value is used like KEY in the
+/// config file. Profiles work the same way (<prefix>WEB1__SERVER_NAME). OpenStack credentials are
+/// not accepted, the OpenStack client only reads them before the config is loaded from etcd.
+/// Returns the values and the watch for changes
+pub async fn load_config() -> Result<Option<(BTreeMap<String, String>, ConfigWatch)>> {
+    let Some(prefix) = config::var("ETCD_CONFIG_PREFIX").ok().filter(|p| !p.trim().is_empty()) else {
+        return Ok(None);
+    };
+    let etcd = Etcd::from_env()?.context("ETCD_URL must be set with ETCD_CONFIG_PREFIX")?;
+    let kvs = etcd.range_prefix(&prefix).await.context("Failed to load config from etcd")?;
+    let mut values = BTreeMap::new();
+    let mut revisions = BTreeMap::new();
+    for (key, value, revision) in kvs {
+        let name = key[prefix.len()..].trim_start_matches('/').to_string();
+        if name.is_empty() || name.contains('/') {
+            continue;
+        }
+        if is_credential(&name) {
+            anyhow::bail!("{} can't come from etcd ({}) - keep OpenStack credentials in the config file or clouds.yaml", name, key);
+        }
+        values.insert(name, value);
+        revisions.insert(key, revision);
+    }
+    println!("Config: {} key(s) from etcd {}", revisions.len(), prefix);
+    Ok(Some((values, ConfigWatch { etcd, prefix, revisions })))
+}
+
+/// OS_* variable, also of a profile (<PROFILE>__OS_*)
+fn is_credential(name: &str) -> bool {
+    name.starts_with("OS_") || name.contains("__OS_")
+}
+
+/// Detects changes of the etcd config prefix - keys added, changed or deleted
+pub struct ConfigWatch {
+    etcd: Etcd,
+    prefix: String,
+    revisions: BTreeMap<String, i64>,
+}
+
+impl ConfigWatch {
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Changed keys since the config was loaded, empty if nothing changed or etcd is unreachable
+    pub async fn changed(&self) -> Vec<String> {
+        let kvs = match self.etcd.range_prefix(&self.prefix).await {
+            Ok(kvs) => kvs,
+            Err(e) => {
+                println!("✗ Failed to check etcd config: {:#}", e);
+                return vec![];
+            },
+        };
+        let current: BTreeMap<String, i64> = kvs.into_iter().map(|(key, _, revision)| (key, revision)).collect();
+        let mut changed: Vec<String> = current
+            .iter()
+            .filter(|(key, revision)| self.revisions.get(*key) != Some(revision))
+            .map(|(key, _)| key.clone())
+            .collect();
+        changed.extend(self.revisions.keys().filter(|key| !current.contains_key(*key)).cloned());
+        changed
+    }
+}
+
+/// Lock on the server in etcd (ETCD_LOCK=true) for HA pairs: the key holds the instance name
+/// and lives as long as the lease the holder keeps alive. The standby waits until the lock is free
+pub struct Lock {
+    key: String,
+    held: Arc<AtomicBool>,
+}
+
+impl Lock {
+    /// Wait for the lock on the server, None if locking is not enabled or shutdown came first.
+    /// The lease is kept alive in `tasks` and revoked on shutdown, so the standby takes over at once
+    pub async fn acquire(server_name: &str, tasks: &mut TaskGroup) -> Result<Option<Self>> {
+        if !config::var("ETCD_LOCK").is_ok_and(|v| v.eq_ignore_ascii_case("true")) {
+            return Ok(None);
+        }
+        let etcd = Etcd::from_env()?.context("ETCD_URL must be set with ETCD_LOCK")?;
+        let ttl: i64 = config::var("ETCD_LOCK_TTL_SECONDS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .context("ETCD_LOCK_TTL_SECONDS must be a number")?;
+        let instance = config::var("MONITOR_INSTANCE").ok().filter(|i| !i.trim().is_empty()).unwrap_or_else(notify::hostname);
+        let prefix = config::var("ETCD_LOCK_PREFIX").unwrap_or_else(|_| "/unshelve/locks/".to_string());
+        let key = format!("{}/{}", prefix.trim_end_matches('/'), server_name);
+
+        let lease = etcd.call("/v3/lease/grant", json!({ "TTL": ttl })).await.context("Failed to grant etcd lease")?;
+        let lease_id = int(&lease["ID"]);
+        let mut announced = false;
+        loop {
+            // Create the key only if it doesn't exist
+            let response = etcd.call("/v3/kv/txn", json!({
+                "compare": [{ "key": base64_encode(key.as_bytes()), "target": "CREATE", "result": "EQUAL", "create_revision": "0" }],
+                "success": [{ "request_put": { "key": base64_encode(key.as_bytes()), "value": base64_encode(instance.as_bytes()), "lease": lease_id.to_string() } }],
+                "failure": [{ "request_range": { "key": base64_encode(key.as_bytes()) } }],
+            })).await.context("Failed to take etcd lock")?;
+            if response["succeeded"].as_bool() == Some(true) {
+                break;
+            }
+            let holder = response["responses"][0]["response_range"]["kvs"][0]["value"]
+                .as_str()
+                .and_then(|v| base64_decode(v).ok())
+                .map(|v| String::from_utf8_lossy(&v).to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+            if !announced {
+                println!("Standby: server '{}' is monitored by instance '{}', waiting for the etcd lock {}", server_name, holder, key);
+                announced = true;
+            }
+            // The lease of this instance must outlive the wait
+            etcd.call("/v3/lease/keepalive", json!({ "ID": lease_id.to_string() })).await?;
+            let shutdown = tasks.token();
+            tokio::select! {
+                _ = sleep(Duration::from_secs((ttl as u64 / 3).max(1))) => {},
+                _ = shutdown.cancelled() => {
+                    etcd.revoke(lease_id).await;
+                    return Ok(None);
+                },
+            }
+        }
+        println!("✓ Took etcd lock {} as instance '{}'", key, instance);
+
+        let held = Arc::new(AtomicBool::new(true));
+        let keepalive = held.clone();
+        tasks.spawn(|token| async move {
+            loop {
+                tokio::select! {
+                    _ = sleep(Duration::from_secs((ttl as u64 / 3).max(1))) => {},
+                    _ = token.cancelled() => {
+                        etcd.revoke(lease_id).await;
+                        keepalive.store(false, Ordering::Relaxed);
+                        return;
+                    },
+                }
+                let alive = match etcd.call("/v3/lease/keepalive", json!({ "ID": lease_id.to_string() })).await {
+                    Ok(response) => int(&response["result"]["TTL"]) > 0,
+                    Err(e) => {
+                        println!("✗ etcd lease keepalive failed: {:#}", e);
+                        continue;
+                    },
+                };
+                if !alive {
+                    keepalive.store(false, Ordering::Relaxed);
+                    break;
+                }
+            }
+        });
+        Ok(Some(Lock { key, held }))
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// False once the lease expired - another instance may hold the lock now
+    pub fn is_held(&self) -> bool {
+        self.held.load(Ordering::Relaxed)
+    }
+}
+
+/// int64 fields come as strings from the JSON gateway
+fn int(value: &Value) -> i64 {
+    value.as_str().and_then(|v| v.parse().ok()).or_else(|| value.as_i64()).unwrap_or_default()
+}
+
+/// End of the key range for a prefix: the prefix with its last byte incremented
+fn prefix_end(prefix: &[u8]) -> Vec<u8> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < 0xFF {
+            end.push(last + 1);
+            return end;
+        }
+    }
+    // All keys
+    vec![0]
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | ((*b as u32) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[((n >> (18 - 6 * i)) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn base64_decode(text: &str) -> Result<Vec<u8>> {
+    let mut out = vec![];
+    let (mut n, mut bits) = (0u32, 0);
+    for c in text.bytes().filter(|c| *c != b'=') {
+        let value = BASE64.iter().position(|b| *b == c).context("Invalid base64 in etcd response")?;
+        n = (n << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credentials_not_from_etcd() {
+        assert!(is_credential("OS_PASSWORD"));
+        assert!(is_credential("STAGING__OS_PROJECT_NAME"));
+        assert!(!is_credential("SERVER_NAME"));
+        assert!(!is_credential("WEB1__SERVER_NAME"));
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::OnceLock;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use openstack::auth::{IdOrName, Token};
+use serde_json::Value;
+use tokio::time::Duration;
+
+use crate::config;
+
+/// Keystone federated auth types (names as in keystoneauth/openstackclient), exchanged for a token before connecting
+const OIDC_AUTH_TYPES: [&str; 3] = ["v3oidcclientcredentials", "v3oidcpassword", "v3oidcaccesstoken"];
+
+/// Expiry of the federated Keystone token, None with other auth types
+static EXPIRES: OnceLock<DateTime<Local>> = OnceLock::new();
+
+/// For OS_AUTH_TYPE v3oidc*: get an OIDC access token from the identity provider, exchange it at Keystone
+/// (OS-FEDERATION, OS_IDENTITY_PROVIDER and OS_PROTOCOL) for an unscoped token and connect with it,
+/// scoped to OS_PROJECT_ID or OS_PROJECT_NAME. None for other auth types
+pub async fn connect() -> Result<Option<openstack::Cloud>> {
+    let Some(token) = token().await? else {
+        return Ok(None);
+    };
+    let project = match (config::var("OS_PROJECT_ID"), config::var("OS_PROJECT_NAME")) {
+        (Ok(id), _) => IdOrName::Id(id),
+        (Err(_), Ok(name)) => IdOrName::Name(name),
+        _ => anyhow::bail!("OS_PROJECT_ID or OS_PROJECT_NAME must be set for OS_AUTH_TYPE v3oidc*"),
+    };
+    let domain = match (config::var("OS_PROJECT_DOMAIN_ID"), config::var("OS_PROJECT_DOMAIN_NAME")) {
+        (Ok(id), _) => IdOrName::Id(id),
+        (Err(_), Ok(name)) => IdOrName::Name(name),
+        _ => IdOrName::Name("Default".to_string()),
+    };
+    let auth = Token::new(&required("OS_AUTH_URL")?, token)
+        .context("Invalid OS_AUTH_URL")?
+        .with_project_scope(project, domain);
+    let mut cloud = openstack::Cloud::new(auth)
+        .await
+        .context("Failed to scope the federated token to the project")?;
+    if let Some(region) = config::var("OS_REGION_NAME").ok().filter(|r| !r.trim().is_empty()) {
+        cloud.endpoint_filters_mut().region = Some(region);
+    }
+    Ok(Some(cloud))
+}
+
+/// Unscoped Keystone token for the OIDC login, None for other auth types
+async fn token() -> Result<Option<String>> {
+    let auth_type = config::var("OS_AUTH_TYPE").unwrap_or_default().to_lowercase();
+    if !OIDC_AUTH_TYPES.contains(&auth_type.as_str()) {
+        return Ok(None);
+    }
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .context("Failed to create HTTP client")?;
+
+    let access_token = match auth_type.as_str() {
+        "v3oidcaccesstoken" => required("OS_ACCESS_TOKEN")?,
+        _ => oidc_token(&client, &auth_type).await?,
+    };
+
+    let auth_url = required("OS_AUTH_URL")?;
+    let provider = required("OS_IDENTITY_PROVIDER")?;
+    let protocol = config::var("OS_PROTOCOL").unwrap_or_else(|_| "openid".to_string());
+    let url = format!("{}/OS-FEDERATION/identity_providers/{}/protocols/{}/auth",
+                      auth_url.trim_end_matches('/'), provider, protocol);
+    let response = client
+        .post(&url)
+        .bearer_auth(&access_token)
+        .send()
+        .await
+        .context("Keystone federated auth request failed")?
+        .error_for_status()
+        .context(format!("Keystone rejected the OIDC token of identity provider '{}'", provider))?;
+    let token = response
+        .headers()
+        .get("X-Subject-Token")
+        .and_then(|t| t.to_str().ok())
+        .map(String::from)
+        .context("Keystone response has no X-Subject-Token")?;
+    let body: Value = response.json().await.context("Invalid Keystone token response")?;
+    let expires = body["token"]["expires_at"]
+        .as_str()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.with_timezone(&Local));
+
+    if let Some(expires) = expires {
+        EXPIRES.get_or_init(|| expires);
+    }
+    Ok(Some(token))
+}
+
+/// Expiry of the federated token. A daemon can't renew it without the identity provider,
+/// so it exits shortly before and is restarted with a fresh one
+pub fn expires() -> Option<DateTime<Local>> {
+    EXPIRES.get().copied()
+}
+
+/// Access token from the identity provider's token endpoint: OS_ACCESS_TOKEN_ENDPOINT, or from
+/// OS_DISCOVERY_ENDPOINT (.well-known/openid-configuration)
+async fn oidc_token(client: &reqwest::Client, auth_type: &str) -> Result<String> {
+    let endpoint = match config::var("OS_ACCESS_TOKEN_ENDPOINT").ok().filter(|e| !e.trim().is_empty()) {
+        Some(endpoint) => endpoint,
+        None => {
+            let discovery = required("OS_DISCOVERY_ENDPOINT")
+                .context("Set OS_ACCESS_TOKEN_ENDPOINT or OS_DISCOVERY_ENDPOINT")?;
+            let config: Value = client
+                .get(&discovery)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await
+                .context(format!("Invalid OIDC discovery document: {}", discovery))?;
+            config["token_endpoint"]
+                .as_str()
+                .map(String::from)
+                .context(format!("No token_endpoint in {}", discovery))?
+        },
+    };
+    let scope = config::var("OS_OPENID_SCOPE").unwrap_or_else(|_| "openid profile".to_string());
+    let mut form = vec![
+        ("client_id", required("OS_CLIENT_ID")?),
+        ("client_secret", config::var("OS_CLIENT_SECRET").unwrap_or_default()),
+        ("scope", scope),
+    ];
+    if auth_type == "v3oidcpassword" {
+        form.push(("grant_type", "password".to_string()));
+        form.push(("username", required("OS_USERNAME")?));
+        form.push(("password", required("OS_PASSWORD")?));
+    } else {
+        form.push(("grant_type", "client_credentials".to_string()));
+    }
+    let response: Value = client
+        .post(&endpoint)
+        .form(&form)
+        .send()
+        .await
+        .context("OIDC token request failed")?
+        .error_for_status()
+        .context("Identity provider refused the OIDC token request")?
+        .json()
+        .await
+        .context("Invalid OIDC token response")?;
+    response["access_token"]
+        .as_str()
+        .map(String::from)
+        .context("OIDC token response has no access_token")
+}
+
+fn required(key: &str) -> Result<String> {
+    config::var(key)
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .context(format!("{} must be set for OS_AUTH_TYPE v3oidc*", key))
+}
@@ -0,0 +1,104 @@
+//! C API for embedding the engine, e.g. from Python (ctypes/cffi) or Go (cgo). Built as a shared library with
+//! `cargo rustc --release --lib --no-default-features --features ffi --crate-type cdylib`, declarations are in
+//! include/unshelve.h. Functions never unwind into the caller: failures return -1 or NULL and
+//! unshelve_last_error() tells why. One engine must not be used from several threads at once
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use anyhow::{Context, Result};
+
+use crate::engine::Engine;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Opaque to C
+pub type UnshelveEngine = Engine;
+
+/// Load the config file (.env format or TOML, as for unshelved) with an optional profile, connect to OpenStack
+/// and create ICMP sockets. `profile` may be NULL. Returns NULL on failure
+#[unsafe(no_mangle)]
+pub extern "C" fn unshelve_init(config: *const c_char, profile: *const c_char) -> *mut UnshelveEngine {
+    call(ptr::null_mut(), || {
+        let config = string_arg(config, "config")?.unwrap_or(".env");
+        let profile = string_arg(profile, "profile")?;
+        Ok(Box::into_raw(Box::new(Engine::new(config, profile)?)))
+    })
+}
+
+/// Run one check cycle. Returns 1 if the server is healthy, 0 if not, -1 on failure
+#[unsafe(no_mangle)]
+pub extern "C" fn unshelve_check(engine: *mut UnshelveEngine) -> c_int {
+    call(-1, || Ok(engine_arg(engine)?.check()? as c_int))
+}
+
+/// Result of the last check as JSON (name, id, status, state, healthy, last_check, ping).
+/// Free it with unshelve_string_free. Returns NULL on failure
+#[unsafe(no_mangle)]
+pub extern "C" fn unshelve_status(engine: *mut UnshelveEngine) -> *mut c_char {
+    call(ptr::null_mut(), || {
+        let status = engine_arg(engine)?.status().to_string();
+        Ok(CString::new(status)?.into_raw())
+    })
+}
+
+/// Unshelve the server if it is shelved. Returns 1 if unshelve was requested, 0 if the server
+/// is not shelved, -1 on failure (including kill switch and rejecting precondition hooks)
+#[unsafe(no_mangle)]
+pub extern "C" fn unshelve_trigger(engine: *mut UnshelveEngine) -> c_int {
+    call(-1, || Ok(engine_arg(engine)?.unshelve()? as c_int))
+}
+
+/// Message of the last failure on this thread, NULL if none. Valid until the next call on the thread
+#[unsafe(no_mangle)]
+pub extern "C" fn unshelve_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map(|e| e.as_ptr()).unwrap_or(ptr::null()))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn unshelve_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(unsafe { CString::from_raw(string) });
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn unshelve_free(engine: *mut UnshelveEngine) {
+    if !engine.is_null() {
+        drop(unsafe { Box::from_raw(engine) });
+    }
+}
+
+/// Run `f`, turning errors and panics into `failed` and the last error
+fn call<T>(failed: T, f: impl FnOnce() -> Result<T>) -> T {
+    LAST_ERROR.with(|e| *e.borrow_mut() = None);
+    let error = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => return value,
+        Ok(Err(e)) => format!("{:#}", e),
+        Err(panic) => match panic.downcast_ref::<&str>() {
+            Some(message) => format!("panic: {}", message),
+            None => match panic.downcast_ref::<String>() {
+                Some(message) => format!("panic: {}", message),
+                None => "panic".to_string(),
+            },
+        },
+    };
+    let error = CString::new(error.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(error));
+    failed
+}
+
+fn string_arg<'a>(value: *const c_char, name: &str) -> Result<Option<&'a str>> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    let value = unsafe { CStr::from_ptr(value) };
+    Ok(Some(value.to_str().context(format!("{} must be UTF-8", name))?))
+}
+
+fn engine_arg<'a>(engine: *mut UnshelveEngine) -> Result<&'a mut UnshelveEngine> {
+    unsafe { engine.as_mut() }.context("engine is NULL")
+}
@@ -0,0 +1,204 @@
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+
+use crate::actions::ActionRecord;
+use crate::config;
+
+/// State shared by monitor instances watching different servers (STATE_URL).
+/// Each instance watches its SERVER_NAME - that is its shard. The shared state keeps
+/// which instance watches which server, in-flight actions as locks and the last snapshot of each instance
+pub trait SharedState: Send + Sync {
+    /// Name of this instance (MONITOR_INSTANCE)
+    fn instance(&self) -> &str;
+    /// Take the server for this instance, error if another live instance watches it
+    fn claim(&self, server_id: &str, server_name: &str) -> Result<()>;
+    /// Refresh the claim with the current snapshot, error if another instance took the server over
+    fn heartbeat(&self, server_id: &str, state: &serde_json::Value) -> Result<()>;
+    /// Action for the server submitted at or after `since` and not finished yet
+    fn in_flight(&self, server_id: &str, action: &str, since: DateTime<Local>) -> Result<Option<ActionRecord>>;
+    /// Store the action as a lock, error if another instance submitted it after `stale_before`
+    fn record(&self, record: &ActionRecord, stale_before: DateTime<Local>) -> Result<()>;
+    /// Forget actions of the server
+    fn complete(&self, server_id: &str) -> Result<()>;
+    /// Where the state is stored, for logs
+    fn describe(&self) -> String;
+}
+
+/// Shared state from STATE_URL (postgres://...), None if not set
+pub fn from_env() -> Result<Option<Arc<dyn SharedState>>> {
+    let Some(url) = config::var("STATE_URL").ok().filter(|u| !u.trim().is_empty()) else {
+        return Ok(None);
+    };
+    if !(url.starts_with("postgres://") || url.starts_with("postgresql://")) {
+        anyhow::bail!("Unsupported STATE_URL: expected postgres://...");
+    }
+    let instance = config::var("MONITOR_INSTANCE")
+        .ok()
+        .filter(|i| !i.trim().is_empty())
+        .context("MONITOR_INSTANCE must be set with STATE_URL - it names this instance in the shared state")?;
+    let claim_timeout_minutes: i64 = config::var("FLEET_CLAIM_TIMEOUT_MINUTES")
+        .unwrap_or_else(|_| "15".to_string())
+        .parse()
+        .context("FLEET_CLAIM_TIMEOUT_MINUTES must be a number")?;
+    connect(&url, instance, chrono::Duration::minutes(claim_timeout_minutes)).map(Some)
+}
+
+#[cfg(feature = "postgres")]
+fn connect(url: &str, instance: String, claim_timeout: chrono::Duration) -> Result<Arc<dyn SharedState>> {
+    Ok(Arc::new(postgres::PostgresState::connect(url, instance, claim_timeout)?))
+}
+
+#[cfg(not(feature = "postgres"))]
+fn connect(_url: &str, _instance: String, _claim_timeout: chrono::Duration) -> Result<Arc<dyn SharedState>> {
+    anyhow::bail!("Shared state needs a build with --features postgres")
+}
+
+#[cfg(feature = "postgres")]
+mod postgres {
+    use std::sync::Mutex;
+    use anyhow::{Context, Result};
+    use chrono::{DateTime, Local, TimeZone};
+
+    use super::SharedState;
+    use crate::actions::ActionRecord;
+
+    const CREATE_TABLES: &str = "
+        CREATE TABLE IF NOT EXISTS unshelve_monitors (
+            server_id TEXT PRIMARY KEY,
+            server_name TEXT NOT NULL,
+            instance TEXT NOT NULL,
+            heartbeat_ms BIGINT NOT NULL,
+            state TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS unshelve_actions (
+            server_id TEXT NOT NULL,
+            action TEXT NOT NULL,
+            incident TEXT NOT NULL,
+            instance TEXT NOT NULL,
+            submitted_ms BIGINT NOT NULL,
+            PRIMARY KEY (server_id, action)
+        );";
+
+    /// Postgres database shared by all instances (STATE_URL=postgres://...).
+    /// The synchronous client runs its own runtime, so calls leave the tokio one with block_in_place
+    pub struct PostgresState {
+        url: String,
+        instance: String,
+        /// Claim of an instance without heartbeat for this long can be taken over
+        claim_timeout: chrono::Duration,
+        client: Mutex<::postgres::Client>,
+    }
+
+    impl PostgresState {
+        pub fn connect(url: &str, instance: String, claim_timeout: chrono::Duration) -> Result<Self> {
+            let client = tokio::task::block_in_place(|| -> Result<::postgres::Client> {
+                let mut client = ::postgres::Client::connect(url, ::postgres::NoTls)
+                    .context("Failed to connect to Postgres shared state")?;
+                client.batch_execute(CREATE_TABLES)?;
+                Ok(client)
+            })?;
+            Ok(PostgresState { url: url.to_string(), instance, claim_timeout, client: Mutex::new(client) })
+        }
+
+        fn client(&self) -> Result<std::sync::MutexGuard<'_, ::postgres::Client>> {
+            self.client.lock().map_err(|_| anyhow::anyhow!("Postgres shared state lock poisoned"))
+        }
+    }
+
+    impl SharedState for PostgresState {
+        fn instance(&self) -> &str {
+            &self.instance
+        }
+
+        fn claim(&self, server_id: &str, server_name: &str) -> Result<()> {
+            let mut client = self.client()?;
+            let now = Local::now();
+            let stale_before = (now - self.claim_timeout).timestamp_millis();
+            tokio::task::block_in_place(|| -> Result<()> {
+                let claimed = client.execute(
+                    "INSERT INTO unshelve_monitors (server_id, server_name, instance, heartbeat_ms, state)
+                     VALUES ($1, $2, $3, $4, '{}')
+                     ON CONFLICT (server_id) DO UPDATE
+                     SET server_name = EXCLUDED.server_name, instance = EXCLUDED.instance, heartbeat_ms = EXCLUDED.heartbeat_ms
+                     WHERE unshelve_monitors.instance = EXCLUDED.instance OR unshelve_monitors.heartbeat_ms < $5",
+                    &[&server_id, &server_name, &self.instance, &now.timestamp_millis(), &stale_before],
+                )?;
+                if claimed == 0 {
+                    let owner: String = client
+                        .query_one("SELECT instance FROM unshelve_monitors WHERE server_id = $1", &[&server_id])?
+                        .get(0);
+                    anyhow::bail!("Server '{}' is already monitored by instance '{}'", server_name, owner);
+                }
+                Ok(())
+            })
+        }
+
+        fn heartbeat(&self, server_id: &str, state: &serde_json::Value) -> Result<()> {
+            let mut client = self.client()?;
+            let updated = tokio::task::block_in_place(|| {
+                client.execute(
+                    "UPDATE unshelve_monitors SET heartbeat_ms = $1, state = $2 WHERE server_id = $3 AND instance = $4",
+                    &[&Local::now().timestamp_millis(), &state.to_string(), &server_id, &self.instance],
+                )
+            })?;
+            if updated == 0 {
+                anyhow::bail!("Server {} was taken over by another instance", server_id);
+            }
+            Ok(())
+        }
+
+        fn in_flight(&self, server_id: &str, action: &str, since: DateTime<Local>) -> Result<Option<ActionRecord>> {
+            let mut client = self.client()?;
+            let row = tokio::task::block_in_place(|| {
+                client.query_opt(
+                    "SELECT incident, submitted_ms FROM unshelve_actions WHERE server_id = $1 AND action = $2 AND submitted_ms >= $3",
+                    &[&server_id, &action, &since.timestamp_millis()],
+                )
+            })?;
+            Ok(row.map(|row| ActionRecord {
+                server_id: server_id.to_string(),
+                action: action.to_string(),
+                incident: row.get(0),
+                submitted: Local.timestamp_millis_opt(row.get(1)).single().unwrap_or_else(Local::now),
+            }))
+        }
+
+        fn record(&self, record: &ActionRecord, stale_before: DateTime<Local>) -> Result<()> {
+            let mut client = self.client()?;
+            let recorded = tokio::task::block_in_place(|| {
+                client.execute(
+                    "INSERT INTO unshelve_actions (server_id, action, incident, instance, submitted_ms)
+                     VALUES ($1, $2, $3, $4, $5)
+                     ON CONFLICT (server_id, action) DO UPDATE
+                     SET incident = EXCLUDED.incident, instance = EXCLUDED.instance, submitted_ms = EXCLUDED.submitted_ms
+                     WHERE unshelve_actions.instance = EXCLUDED.instance OR unshelve_actions.submitted_ms < $6",
+                    &[
+                        &record.server_id,
+                        &record.action,
+                        &record.incident,
+                        &self.instance,
+                        &record.submitted.timestamp_millis(),
+                        &stale_before.timestamp_millis(),
+                    ],
+                )
+            })?;
+            if recorded == 0 {
+                anyhow::bail!("{} of {} is already submitted by another instance", record.action, record.server_id);
+            }
+            Ok(())
+        }
+
+        fn complete(&self, server_id: &str) -> Result<()> {
+            let mut client = self.client()?;
+            tokio::task::block_in_place(|| {
+                client.execute("DELETE FROM unshelve_actions WHERE server_id = $1", &[&server_id])
+            })?;
+            Ok(())
+        }
+
+        fn describe(&self) -> String {
+            format!("{} (instance {})", crate::redact::redact_url_credentials(&self.url), self.instance)
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::net::IpAddr;
+use anyhow::{Context, Result};
+use tokio::time::{sleep, Duration, Instant};
+
+use crate::config;
+use crate::probe::{self, Prober};
+use crate::state::ServerState;
+
+/// Delay between status polls and soak checks
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Consecutive failed pings during soak treated as a regression
+const MAX_FAILURES: u32 = 3;
+
+/// Unshelve, wait for ACTIVE and watch the server for GUARD_SOAK_MINUTES.
+/// On regression the whole cycle is retried once, then the command fails
+pub async fn guard(cloud: &openstack::Cloud, server_identifier: &str) -> Result<()> {
+    let active_timeout = Duration::from_secs(env_minutes("GUARD_ACTIVE_TIMEOUT_MINUTES", 15)? * 60);
+    let soak = Duration::from_secs(env_minutes("GUARD_SOAK_MINUTES", 10)? * 60);
+
+    let ping_ip: Option<IpAddr> = match config::var("PING_IP") {
+        Ok(ip) => Some(ip.parse().context("PING_IP must be an IP address")?),
+        Err(_) => None,
+    };
+    let mut prober = match ping_ip {
+        Some(ip) => {
+            let timeout_secs: u64 = config::var("PING_TIMEOUT_SECONDS")
+                .unwrap_or_else(|_| "3".to_string())
+                .parse()
+                .context("PING_TIMEOUT_SECONDS must be a number")?;
+            let use_dgram_socket = probe::socket_type_from_env()?;
+            Some(Prober::self_test(use_dgram_socket, ip.is_ipv6(), Duration::from_secs(timeout_secs)).await?)
+        },
+        None => {
+            println!("PING_IP not set - soak checks OpenStack status only");
+            None
+        },
+    };
+
+    for attempt in 1..=2 {
+        println!("{}", "=".repeat(80));
+        println!("Guard attempt #{}", attempt);
+
+        let mut server = cloud.get_server(server_identifier).await.context("Failed to get server info")?;
+        println!("Server status: {}", server.status());
+        if ServerState::of(&server).is_shelved() {
+            server
+                .action(openstack::compute::ServerAction::Unshelve)
+                .await
+                .context("Failed to unshelve server")?;
+            println!("✓ Unshelve command sent successfully");
+        }
+
+        let result = match wait_active(cloud, server_identifier, active_timeout).await {
+            Ok(()) => soak_check(cloud, server_identifier, prober.as_mut().zip(ping_ip), soak).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => {
+                println!("✓ Server '{}' is stable after {} min soak", server_identifier, soak.as_secs() / 60);
+                return Ok(());
+            },
+            Err(e) => println!("✗ {}", e),
+        }
+    }
+    anyhow::bail!("Server '{}' did not stay up after unshelve and one retry", server_identifier)
+}
+
+/// Poll status until ACTIVE, error on ERROR status or timeout
+pub async fn wait_active(cloud: &openstack::Cloud, server_identifier: &str, limit: Duration) -> Result<()> {
+    println!("Waiting for server to become ACTIVE (up to {} min)...", limit.as_secs() / 60);
+    let deadline = Instant::now() + limit;
+    loop {
+        let server = cloud.get_server(server_identifier).await.context("Failed to get server info")?;
+        let status = server.status();
+        match ServerState::of(&server) {
+            ServerState::Active => {
+                println!("✓ Server is ACTIVE");
+                return Ok(());
+            },
+            ServerState::Error => anyhow::bail!("Server went to ERROR status"),
+            // ACTIVE is reported before the guest is powered on
+            _ => println!("[{}] Status: {} ({:?})", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), status, server.power_state()),
+        }
+        if Instant::now() >= deadline {
+            anyhow::bail!("Server is not ACTIVE after {} min (status {})", limit.as_secs() / 60, status);
+        }
+        sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Ping (or poll status without PING_IP) for the soak period, error on regression
+async fn soak_check(
+    cloud: &openstack::Cloud,
+    server_identifier: &str,
+    mut ping: Option<(&mut Prober, IpAddr)>,
+    soak: Duration,
+) -> Result<()> {
+    println!("Soak check for {} min...", soak.as_secs() / 60);
+    let deadline = Instant::now() + soak;
+    let mut failures = 0;
+    while Instant::now() < deadline {
+        let time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+        match &mut ping {
+            Some((prober, ip)) => match prober.probe(*ip).await {
+                Ok(rtt) => {
+                    println!("[{}] {} Ping successful {:?}", time, ip, rtt);
+                    failures = 0;
+                },
+                Err(e) => {
+                    println!("[{}] {} Ping failed: {}", time, ip, e);
+                    failures += 1;
+                    if failures >= MAX_FAILURES {
+                        anyhow::bail!("Regression: {} pings in a row failed during soak", failures);
+                    }
+                },
+            },
+            None => {
+                let server = cloud.get_server(server_identifier).await.context("Failed to get server info")?;
+                println!("[{}] Status: {}", time, server.status());
+                if ServerState::of(&server) != ServerState::Active {
+                    anyhow::bail!("Regression: server status changed to {} ({:?}) during soak", server.status(), server.power_state());
+                }
+            },
+        }
+        sleep(POLL_INTERVAL.min(deadline.saturating_duration_since(Instant::now()))).await;
+    }
+    Ok(())
+}
+
+fn env_minutes(key: &str, default: u64) -> Result<u64> {
+    config::var(key)
+        .unwrap_or_else(|_| default.to_string())
+        .parse()
+        .context(format!("{} must be a number", key))
+}
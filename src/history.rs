@@ -0,0 +1,350 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde_json::json;
+
+use crate::config;
+use crate::notify::Event;
+
+/// Event read back from the history
+#[derive(Clone)]
+pub struct StoredEvent {
+    pub time: DateTime<Local>,
+    pub severity: String,
+    pub kind: String,
+    pub server: String,
+    pub message: String,
+    pub labels: BTreeMap<String, String>,
+    pub incident: Option<String>,
+}
+
+/// Storage of monitoring events (check results, unshelve attempts, ...),
+/// read back by `report` and `export-history`
+pub trait EventStore: Send + Sync {
+    fn append(&self, event: &Event) -> Result<()>;
+    /// Events of the given kinds recorded at or after `since`, oldest first
+    fn query(&self, kinds: &[&str], since: DateTime<Local>) -> Result<Vec<StoredEvent>>;
+    /// Events stamped with the incident id, oldest first
+    fn incident(&self, incident: &str) -> Result<Vec<StoredEvent>>;
+    /// Where events are stored, for logs
+    fn describe(&self) -> String;
+}
+
+/// Event store from config: HISTORY_URL (sqlite:<path> or postgres://...) or EVENTS_FILE (JSON lines).
+/// None if neither is set. The daemon opens it once, its monitors share the connection
+pub fn from_env() -> Result<Option<Arc<dyn EventStore>>> {
+    if let Some(url) = config::var("HISTORY_URL").ok().filter(|u| !u.trim().is_empty()) {
+        if let Some(path) = url.strip_prefix("sqlite:") {
+            return open_sqlite(path).map(|store| Some(Arc::from(store)));
+        }
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            return open_postgres(&url).map(|store| Some(Arc::from(store)));
+        }
+        anyhow::bail!("Unsupported HISTORY_URL: expected sqlite:<path> or postgres://...");
+    }
+    Ok(config::var("EVENTS_FILE")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .map(|path| Arc::new(JsonlStore { path }) as Arc<dyn EventStore>))
+}
+
+/// Event store from config, error if history is not recorded
+pub fn require() -> Result<Arc<dyn EventStore>> {
+    from_env()?.context("Neither HISTORY_URL nor EVENTS_FILE set - check results are not recorded")
+}
+
+/// JSON lines file (EVENTS_FILE), one event per line
+struct JsonlStore {
+    path: String,
+}
+
+impl JsonlStore {
+    /// All events in the file, lines that don't parse are skipped
+    fn read(&self) -> Result<Vec<StoredEvent>> {
+        let content = fs::read_to_string(&self.path).context(format!("Failed to read events file: {}", self.path))?;
+        let mut events = vec![];
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            let Ok(event) = serde_json::from_str::<serde_json::Value>(line) else {
+                continue;
+            };
+            let Some(kind) = event["kind"].as_str() else {
+                continue;
+            };
+            let Some(time) = event["time"].as_str().and_then(|t| DateTime::parse_from_rfc3339(t).ok()) else {
+                continue;
+            };
+            events.push(StoredEvent {
+                time: time.with_timezone(&Local),
+                severity: event["severity"].as_str().unwrap_or("info").to_string(),
+                kind: kind.to_string(),
+                server: event["server"].as_str().unwrap_or("unknown").to_string(),
+                message: event["message"].as_str().unwrap_or("").to_string(),
+                labels: serde_json::from_value(event["labels"].clone()).unwrap_or_default(),
+                incident: event["incident"].as_str().map(String::from),
+            });
+        }
+        Ok(events)
+    }
+}
+
+impl EventStore for JsonlStore {
+    fn append(&self, event: &Event) -> Result<()> {
+        let line = json!({
+            "time": event.time.to_rfc3339(),
+            "severity": event.severity.to_string().to_lowercase(),
+            "kind": event.kind,
+            "server": event.server,
+            "message": event.message,
+            "labels": event.labels,
+            "incident": event.incident,
+        });
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", line)?;
+        Ok(())
+    }
+
+    fn query(&self, kinds: &[&str], since: DateTime<Local>) -> Result<Vec<StoredEvent>> {
+        Ok(self.read()?.into_iter().filter(|e| kinds.contains(&e.kind.as_str()) && e.time >= since).collect())
+    }
+
+    fn incident(&self, incident: &str) -> Result<Vec<StoredEvent>> {
+        Ok(self.read()?.into_iter().filter(|e| e.incident.as_deref() == Some(incident)).collect())
+    }
+
+    fn describe(&self) -> String {
+        self.path.clone()
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+fn from_millis(millis: i64) -> DateTime<Local> {
+    use chrono::TimeZone;
+    Local.timestamp_millis_opt(millis).single().unwrap_or_else(Local::now)
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+const CREATE_TABLE: &str = "CREATE TABLE IF NOT EXISTS unshelve_events (
+    time_ms BIGINT NOT NULL,
+    severity TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    server TEXT NOT NULL,
+    message TEXT NOT NULL,
+    labels TEXT NOT NULL DEFAULT '',
+    incident TEXT
+)";
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+const SELECT: &str = "SELECT time_ms, severity, kind, server, message, labels, incident FROM unshelve_events";
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+const CREATE_INDEX: &str = "CREATE INDEX IF NOT EXISTS unshelve_events_time ON unshelve_events (time_ms)";
+
+#[cfg(feature = "sqlite")]
+fn open_sqlite(path: &str) -> Result<Box<dyn EventStore>> {
+    Ok(Box::new(sqlite::SqliteStore::open(path)?))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn open_sqlite(_path: &str) -> Result<Box<dyn EventStore>> {
+    anyhow::bail!("SQLite history needs a build with --features sqlite")
+}
+
+#[cfg(feature = "postgres")]
+fn open_postgres(url: &str) -> Result<Box<dyn EventStore>> {
+    Ok(Box::new(postgres::PostgresStore::open(url)?))
+}
+
+#[cfg(not(feature = "postgres"))]
+fn open_postgres(_url: &str) -> Result<Box<dyn EventStore>> {
+    anyhow::bail!("Postgres history needs a build with --features postgres")
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use std::sync::Mutex;
+    use anyhow::{Context, Result};
+    use chrono::{DateTime, Local};
+
+    use super::{from_millis, EventStore, StoredEvent, CREATE_INDEX, CREATE_TABLE, SELECT};
+    use crate::labels;
+    use crate::notify::Event;
+
+    fn stored_event(row: &rusqlite::Row) -> rusqlite::Result<StoredEvent> {
+        Ok(StoredEvent {
+            time: from_millis(row.get(0)?),
+            severity: row.get(1)?,
+            kind: row.get(2)?,
+            server: row.get(3)?,
+            message: row.get(4)?,
+            labels: labels::parse("labels", &row.get::<_, String>(5)?).unwrap_or_default(),
+            incident: row.get(6)?,
+        })
+    }
+
+    /// Local SQLite database (HISTORY_URL=sqlite:<path>)
+    pub struct SqliteStore {
+        path: String,
+        connection: Mutex<rusqlite::Connection>,
+    }
+
+    impl SqliteStore {
+        pub fn open(path: &str) -> Result<Self> {
+            let connection = rusqlite::Connection::open(path).context(format!("Failed to open SQLite history: {}", path))?;
+            connection.execute(CREATE_TABLE, [])?;
+            // Tables created before SERVER_LABELS lack the column, SQLite has no ADD COLUMN IF NOT EXISTS
+            let has_labels = connection
+                .prepare("SELECT labels FROM unshelve_events LIMIT 0")
+                .is_ok();
+            if !has_labels {
+                connection.execute("ALTER TABLE unshelve_events ADD COLUMN labels TEXT NOT NULL DEFAULT ''", [])?;
+            }
+            let has_incident = connection
+                .prepare("SELECT incident FROM unshelve_events LIMIT 0")
+                .is_ok();
+            if !has_incident {
+                connection.execute("ALTER TABLE unshelve_events ADD COLUMN incident TEXT", [])?;
+            }
+            connection.execute(CREATE_INDEX, [])?;
+            Ok(SqliteStore { path: path.to_string(), connection: Mutex::new(connection) })
+        }
+    }
+
+    impl EventStore for SqliteStore {
+        fn append(&self, event: &Event) -> Result<()> {
+            let connection = self.connection.lock().map_err(|_| anyhow::anyhow!("SQLite history lock poisoned"))?;
+            connection.execute(
+                "INSERT INTO unshelve_events (time_ms, severity, kind, server, message, labels, incident) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                rusqlite::params![
+                    event.time.timestamp_millis(),
+                    event.severity.to_string().to_lowercase(),
+                    event.kind,
+                    event.server,
+                    event.message,
+                    labels::to_stored(&event.labels),
+                    event.incident,
+                ],
+            )?;
+            Ok(())
+        }
+
+        fn query(&self, kinds: &[&str], since: DateTime<Local>) -> Result<Vec<StoredEvent>> {
+            let connection = self.connection.lock().map_err(|_| anyhow::anyhow!("SQLite history lock poisoned"))?;
+            let mut statement = connection.prepare(&format!("{} WHERE time_ms >= ?1 ORDER BY time_ms", SELECT))?;
+            let rows = statement.query_map([since.timestamp_millis()], stored_event)?;
+            let mut events = vec![];
+            for row in rows {
+                let event = row?;
+                if kinds.contains(&event.kind.as_str()) {
+                    events.push(event);
+                }
+            }
+            Ok(events)
+        }
+
+        fn incident(&self, incident: &str) -> Result<Vec<StoredEvent>> {
+            let connection = self.connection.lock().map_err(|_| anyhow::anyhow!("SQLite history lock poisoned"))?;
+            let mut statement = connection.prepare(&format!("{} WHERE incident = ?1 ORDER BY time_ms", SELECT))?;
+            let rows = statement.query_map([incident], stored_event)?;
+            Ok(rows.collect::<Result<Vec<_>, _>>()?)
+        }
+
+        fn describe(&self) -> String {
+            format!("sqlite:{}", self.path)
+        }
+    }
+}
+
+#[cfg(feature = "postgres")]
+mod postgres {
+    use std::sync::Mutex;
+    use anyhow::{Context, Result};
+    use chrono::{DateTime, Local};
+
+    use super::{from_millis, EventStore, StoredEvent, CREATE_INDEX, CREATE_TABLE, SELECT};
+    use crate::labels;
+    use crate::notify::Event;
+
+    fn stored_event(row: &::postgres::Row) -> StoredEvent {
+        StoredEvent {
+            time: from_millis(row.get(0)),
+            severity: row.get(1),
+            kind: row.get(2),
+            server: row.get(3),
+            message: row.get(4),
+            labels: labels::parse("labels", row.get(5)).unwrap_or_default(),
+            incident: row.get(6),
+        }
+    }
+
+    /// Shared Postgres database (HISTORY_URL=postgres://...) for several monitor instances.
+    /// The synchronous client runs its own runtime, so calls leave the tokio one with block_in_place
+    pub struct PostgresStore {
+        url: String,
+        client: Mutex<::postgres::Client>,
+    }
+
+    impl PostgresStore {
+        pub fn open(url: &str) -> Result<Self> {
+            let client = tokio::task::block_in_place(|| -> Result<::postgres::Client> {
+                let mut client = ::postgres::Client::connect(url, ::postgres::NoTls)
+                    .context("Failed to connect to Postgres history")?;
+                // ADD COLUMN for tables created before SERVER_LABELS and incident ids
+                client.batch_execute(&format!(
+                    "{};\nALTER TABLE unshelve_events ADD COLUMN IF NOT EXISTS labels TEXT NOT NULL DEFAULT '';\n\
+                     ALTER TABLE unshelve_events ADD COLUMN IF NOT EXISTS incident TEXT;\n{}",
+                    CREATE_TABLE, CREATE_INDEX
+                ))?;
+                Ok(client)
+            })?;
+            Ok(PostgresStore { url: url.to_string(), client: Mutex::new(client) })
+        }
+    }
+
+    impl EventStore for PostgresStore {
+        fn append(&self, event: &Event) -> Result<()> {
+            let mut client = self.client.lock().map_err(|_| anyhow::anyhow!("Postgres history lock poisoned"))?;
+            tokio::task::block_in_place(|| {
+                client.execute(
+                    "INSERT INTO unshelve_events (time_ms, severity, kind, server, message, labels, incident) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                    &[
+                        &event.time.timestamp_millis(),
+                        &event.severity.to_string().to_lowercase(),
+                        &event.kind,
+                        &event.server,
+                        &event.message,
+                        &labels::to_stored(&event.labels),
+                        &event.incident,
+                    ],
+                )
+            })?;
+            Ok(())
+        }
+
+        fn query(&self, kinds: &[&str], since: DateTime<Local>) -> Result<Vec<StoredEvent>> {
+            let mut client = self.client.lock().map_err(|_| anyhow::anyhow!("Postgres history lock poisoned"))?;
+            let kinds: Vec<String> = kinds.iter().map(|k| k.to_string()).collect();
+            let rows = tokio::task::block_in_place(|| {
+                client.query(
+                    &format!("{} WHERE time_ms >= $1 AND kind = ANY($2) ORDER BY time_ms", SELECT),
+                    &[&since.timestamp_millis(), &kinds],
+                )
+            })?;
+            Ok(rows.iter().map(stored_event).collect())
+        }
+
+        fn incident(&self, incident: &str) -> Result<Vec<StoredEvent>> {
+            let mut client = self.client.lock().map_err(|_| anyhow::anyhow!("Postgres history lock poisoned"))?;
+            let rows = tokio::task::block_in_place(|| {
+                client.query(&format!("{} WHERE incident = $1 ORDER BY time_ms", SELECT), &[&incident])
+            })?;
+            Ok(rows.iter().map(stored_event).collect())
+        }
+
+        fn describe(&self) -> String {
+            crate::redact::redact_url_credentials(&self.url)
+        }
+    }
+}
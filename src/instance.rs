@@ -0,0 +1,106 @@
+use std::env;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use tokio::time::{sleep, Duration, Instant};
+
+use crate::config;
+
+/// How long --force waits for the running instance to stop
+const TAKEOVER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Lock file of the monitored server in INSTANCE_LOCK_DIR, held with flock for the daemon's lifetime.
+/// The kernel releases it when the process dies, so a file left by a crash doesn't block the next start
+pub struct InstanceLock {
+    path: PathBuf,
+    // Dropping the file releases the lock
+    _file: File,
+}
+
+impl InstanceLock {
+    /// Lock the server or refuse to start if another instance monitors it.
+    /// With `force` the other instance gets SIGTERM and the lock is taken once it has stopped
+    pub async fn acquire(server_name: &str, force: bool) -> Result<Self> {
+        let dir = match config::var("INSTANCE_LOCK_DIR").ok().filter(|d| !d.trim().is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => default_dir(env::var("XDG_RUNTIME_DIR").ok()),
+        };
+        fs::create_dir_all(&dir)
+            .context(format!("Failed to create instance lock directory {} - set INSTANCE_LOCK_DIR to a writable directory", dir.display()))?;
+        let name: String = server_name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let path = dir.join(format!(".unshelve-{}.lock", name));
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .context(format!("Failed to open instance lock file: {}", path.display()))?;
+
+        if !try_lock(&file, &path)? {
+            let holder = fs::read_to_string(&path).unwrap_or_default().trim().parse::<i32>().ok();
+            let holder_text = holder.map(|pid| format!("PID {}", pid)).unwrap_or_else(|| "unknown PID".to_string());
+            if !force {
+                anyhow::bail!("Server '{}' is already monitored by another unshelved ({}, lock {}). \
+                               Stop it or start with --force to take over", server_name, holder_text, path.display());
+            }
+            let Some(pid) = holder else {
+                anyhow::bail!("Server '{}' is locked by an instance with unknown PID ({}), can't take over", server_name, path.display());
+            };
+            println!("⚠️ Taking over server '{}' from PID {} - sending SIGTERM", server_name, pid);
+            kill(Pid::from_raw(pid), Signal::SIGTERM).context(format!("Failed to stop PID {}", pid))?;
+            let deadline = Instant::now() + TAKEOVER_TIMEOUT;
+            while !try_lock(&file, &path)? {
+                if Instant::now() >= deadline {
+                    anyhow::bail!("PID {} didn't stop in {}s - server '{}' still locked", pid, TAKEOVER_TIMEOUT.as_secs(), server_name);
+                }
+                sleep(Duration::from_millis(500)).await;
+            }
+        }
+
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+        file.flush()?;
+        Ok(InstanceLock { path, _file: file })
+    }
+
+    pub fn path(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+/// $XDG_RUNTIME_DIR/unshelve or /run/unshelve: the same directory for every daemon of the user,
+/// whatever directory it's started from
+fn default_dir(runtime_dir: Option<String>) -> PathBuf {
+    match runtime_dir.filter(|d| !d.trim().is_empty()) {
+        Some(runtime_dir) => Path::new(&runtime_dir).join("unshelve"),
+        None => PathBuf::from("/run/unshelve"),
+    }
+}
+
+/// false if another process holds the lock
+fn try_lock(file: &File, path: &Path) -> Result<bool> {
+    match file.try_lock() {
+        Ok(()) => Ok(true),
+        Err(TryLockError::WouldBlock) => Ok(false),
+        Err(TryLockError::Error(e)) => Err(e).context(format!("Failed to lock {}", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_dir_doesnt_depend_on_working_directory() {
+        assert_eq!(default_dir(Some("/run/user/1000".to_string())), PathBuf::from("/run/user/1000/unshelve"));
+        assert_eq!(default_dir(Some(" ".to_string())), PathBuf::from("/run/unshelve"));
+        assert_eq!(default_dir(None), PathBuf::from("/run/unshelve"));
+    }
+}
@@ -0,0 +1,151 @@
+use std::env;
+use std::fs;
+use std::collections::HashMap;
+use anyhow::{Context, Result};
+
+/// Variables of a config file in .env format, in file order. ${VAR} in values is replaced with the
+/// process environment or a variable defined earlier in the file. Values in single quotes are
+/// taken as is. A variable defined nowhere is an error with its line number, not an empty string
+pub fn read_env_file(path: &str) -> Result<Vec<(String, String)>> {
+    let content = fs::read_to_string(path)
+        .context(format!("Failed to read config file: {}", path))?;
+    parse(&content).context(format!("Invalid config file {}", path))
+}
+
+/// Replace every ${VAR} with `lookup`. Err lists the variables `lookup` doesn't know
+pub fn substitute(value: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String, Vec<String>> {
+    let mut result = String::new();
+    let mut missing: Vec<String> = vec![];
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            break;
+        };
+        result.push_str(&rest[..start]);
+        let name = &after[..end];
+        match lookup(name) {
+            Some(value) => result.push_str(&value),
+            None => missing.push(name.to_string()),
+        }
+        rest = &after[end + 1..];
+    }
+    result.push_str(rest);
+    if missing.is_empty() { Ok(result) } else { Err(missing) }
+}
+
+/// ${VAR} from the process environment, error naming `what` the value belongs to
+pub fn substitute_env(value: &str, what: &str) -> Result<String> {
+    substitute(value, |name| env::var(name).ok()).map_err(|missing| {
+        let names: Vec<String> = missing.iter().map(|name| format!("${{{}}}", name)).collect();
+        anyhow::anyhow!("{} references undefined variables: {}", what, names.join(", "))
+    })
+}
+
+fn parse(content: &str) -> Result<Vec<(String, String)>> {
+    let mut vars: Vec<(String, String)> = vec![];
+    let mut defined: HashMap<String, String> = HashMap::new();
+    let mut missing: Vec<String> = vec![];
+
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            anyhow::bail!("line {}: expected KEY=VALUE", number + 1);
+        };
+        let key = key.trim();
+        let value = value.trim();
+
+        let value = match value.chars().next() {
+            // No substitution inside single quotes
+            Some('\'') => quoted(value, '\'').context(format!("line {}: unterminated ' in {}", number + 1, key))?,
+            Some('"') => {
+                let value = quoted(value, '"').context(format!("line {}: unterminated \" in {}", number + 1, key))?;
+                expand(&value, key, number, &defined, &mut missing)
+            },
+            _ => {
+                // Unquoted value ends at a comment
+                let value = value.split(" #").next().unwrap_or_default().trim_end();
+                expand(value, key, number, &defined, &mut missing)
+            },
+        };
+        defined.insert(key.to_string(), value.clone());
+        vars.push((key.to_string(), value));
+    }
+
+    if !missing.is_empty() {
+        anyhow::bail!("Config references undefined variables:\n  {}", missing.join("\n  "));
+    }
+    Ok(vars)
+}
+
+/// Value between quotes, with \" \\ \n escapes in double quotes
+fn quoted(value: &str, quote: char) -> Option<String> {
+    let mut result = String::new();
+    let mut chars = value.chars().skip(1);
+    while let Some(c) = chars.next() {
+        match c {
+            c if c == quote => return Some(result),
+            '\\' if quote == '"' => match chars.next()? {
+                'n' => result.push('\n'),
+                c => result.push(c),
+            },
+            c => result.push(c),
+        }
+    }
+    None
+}
+
+/// The environment wins over earlier lines, as it does for the variables themselves
+fn expand(value: &str, key: &str, number: usize, defined: &HashMap<String, String>, missing: &mut Vec<String>) -> String {
+    let lookup = |name: &str| env::var(name).ok().or_else(|| defined.get(name).cloned());
+    match substitute(value, lookup) {
+        Ok(value) => value,
+        Err(names) => {
+            missing.extend(names.iter().map(|name| format!("line {}: ${{{}}} in {}", number + 1, name, key)));
+            String::new()
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substitutes_earlier_variables() {
+        let vars = parse("BASE=web\nexport NAME=\"${BASE}-1\"\nLITERAL='${BASE}'\nPLAIN=${BASE}2 # comment\n").unwrap();
+        assert_eq!(vars, vec![
+            ("BASE".to_string(), "web".to_string()),
+            ("NAME".to_string(), "web-1".to_string()),
+            ("LITERAL".to_string(), "${BASE}".to_string()),
+            ("PLAIN".to_string(), "web2".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn undefined_variable_is_an_error() {
+        let error = parse("A=1\nB=\"${UNSHELVE_TEST_UNDEFINED}\"\n").unwrap_err();
+        assert!(error.to_string().contains("line 2: ${UNSHELVE_TEST_UNDEFINED} in B"), "{}", error);
+    }
+
+    #[test]
+    fn quotes_and_escapes() {
+        let vars = parse("A=\"say \\\"hi\\\"\\n\"\nB='a\\n # b'\nC=\n").unwrap();
+        assert_eq!(vars[0].1, "say \"hi\"\n");
+        assert_eq!(vars[1].1, "a\\n # b");
+        assert_eq!(vars[2].1, "");
+        assert!(parse("A=\"open\n").is_err());
+    }
+
+    #[test]
+    fn substitute_reports_every_missing_name() {
+        let lookup = |name: &str| (name == "X").then(|| "1".to_string());
+        assert_eq!(substitute("${X}-${X}", lookup), Ok("1-1".to_string()));
+        assert_eq!(substitute("${Y}${X}${Z}", lookup), Err(vec!["Y".to_string(), "Z".to_string()]));
+        assert_eq!(substitute("no ${ end", lookup), Ok("no ${ end".to_string()));
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::config::Vars;
+
+/// Set through the control API (POST /actions/disable, /actions/enable)
+static API_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Set at startup for servers the credentials turn out to be read-only for, for the daemon's lifetime
+static READ_ONLY: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+/// Kill switch file, checked before every automatic action
+fn file(vars: &Vars) -> String {
+    vars.get("DISABLE_ACTIONS_FILE").unwrap_or_else(|| ".unshelve-disabled".to_string())
+}
+
+/// Why automatic actions on the server are off, None if they are allowed. When humans take over
+/// during an incident the daemon only observes and alerts: DISABLE_ACTIONS=true, the kill switch
+/// file exists (`touch .unshelve-disabled`) or it was switched off through the control API.
+/// Credentials that are read-only for the server switch its actions off too
+pub fn disabled(server: &str) -> Option<String> {
+    disabled_in(&Vars::default(), server)
+}
+
+/// `disabled` with DISABLE_ACTIONS and DISABLE_ACTIONS_FILE from config values read into memory
+pub fn disabled_in(vars: &Vars, server: &str) -> Option<String> {
+    if let Some(reason) = read_only(server) {
+        return Some(reason);
+    }
+    if vars.get("DISABLE_ACTIONS").is_some_and(|v| v.eq_ignore_ascii_case("true")) {
+        return Some("DISABLE_ACTIONS=true".to_string());
+    }
+    let file = file(vars);
+    if Path::new(&file).exists() {
+        return Some(format!("kill switch file {} exists", file));
+    }
+    if API_DISABLED.load(Ordering::Relaxed) {
+        return Some("disabled through the control API".to_string());
+    }
+    None
+}
+
+pub fn set_api_disabled(disabled: bool) {
+    API_DISABLED.store(disabled, Ordering::Relaxed);
+}
+
+pub fn set_read_only(server: &str, reason: &str) {
+    if let Ok(mut read_only) = READ_ONLY.lock() {
+        read_only.entry(server.to_string()).or_insert_with(|| reason.to_string());
+    }
+}
+
+pub fn read_only(server: &str) -> Option<String> {
+    READ_ONLY.lock().ok().and_then(|read_only| read_only.get(server).cloned())
+}
+
+pub fn describe(server: &str) -> String {
+    match disabled(server) {
+        Some(reason) => format!("DISABLED ({})", reason),
+        None => format!("enabled (touch {} to disable)", file(&Vars::default())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disabled_by_config_values() {
+        let vars = |pairs: &[(&str, &str)]| Vars::new(pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect());
+        let missing_file = ("DISABLE_ACTIONS_FILE", "/nonexistent/unshelve-test-disabled");
+        assert_eq!(disabled_in(&vars(&[missing_file]), "unshelve-test-web"), None);
+        assert_eq!(disabled_in(&vars(&[missing_file, ("DISABLE_ACTIONS", "TRUE")]), "unshelve-test-web").as_deref(), Some("DISABLE_ACTIONS=true"));
+    }
+
+    #[test]
+    fn read_only_is_per_server() {
+        let vars = Vars::new([("DISABLE_ACTIONS_FILE".to_string(), "/nonexistent/unshelve-test-disabled".to_string())].into());
+        set_read_only("unshelve-test-db", "credentials can't perform server actions");
+        assert_eq!(disabled_in(&vars, "unshelve-test-db").as_deref(), Some("credentials can't perform server actions"));
+        assert_eq!(disabled_in(&vars, "unshelve-test-cache"), None);
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use anyhow::Result;
+
+use crate::config;
+
+/// Labels every event, metric and notification carries besides server, kind and severity
+const RESERVED: [&str; 4] = ["server", "kind", "severity", "alertname"];
+
+/// Labels of every monitored server from SERVER_LABELS, e.g. 'team=infra,env=prod,cost_center=web'
+static LABELS: OnceLock<BTreeMap<String, String>> = OnceLock::new();
+
+/// Labels of single servers of the daemon on top of SERVER_LABELS, by server
+static SERVER_LABELS: Mutex<BTreeMap<String, BTreeMap<String, String>>> = Mutex::new(BTreeMap::new());
+
+/// Read SERVER_LABELS, after the profile is applied so WEB1__SERVER_LABELS works
+pub fn init() -> Result<()> {
+    let labels = parse("SERVER_LABELS", &config::var("SERVER_LABELS").unwrap_or_default())?;
+    check_reserved("SERVER_LABELS", &labels)?;
+    LABELS.get_or_init(|| labels);
+    Ok(())
+}
+
+/// Labels of one server of SERVERS (SERVER_LABELS_<NAME> or labels of its [[servers]] entry),
+/// they override SERVER_LABELS for this server
+pub fn set(server: &str, labels: &BTreeMap<String, String>) -> Result<()> {
+    let what = format!("labels of '{}'", server);
+    for name in labels.keys() {
+        check_name(&what, name)?;
+    }
+    check_reserved(&what, labels)?;
+    let mut merged = LABELS.get().cloned().unwrap_or_default();
+    merged.extend(labels.clone());
+    SERVER_LABELS
+        .lock()
+        .map_err(|_| anyhow::anyhow!("Labels lock poisoned"))?
+        .insert(server.to_string(), merged);
+    Ok(())
+}
+
+/// Labels of the server, empty if not configured
+pub fn get(server: &str) -> BTreeMap<String, String> {
+    let own = SERVER_LABELS.lock().ok().and_then(|labels| labels.get(server).cloned());
+    own.unwrap_or_else(|| LABELS.get().cloned().unwrap_or_default())
+}
+
+/// Variable with the labels of a server in SERVERS: SERVER_LABELS_ and the name in upper case,
+/// characters other than letters and digits replaced with _, e.g. SERVER_LABELS_WEB_1 for web-1
+pub fn key(server: &str) -> String {
+    let name: String = server.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' }).collect();
+    format!("SERVER_LABELS_{}", name)
+}
+
+fn check_name(what: &str, name: &str) -> Result<()> {
+    let valid = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.starts_with("__");
+    if !valid {
+        anyhow::bail!("Invalid label name '{}' in {}: letters, digits and _ only, not starting with a digit or __", name, what);
+    }
+    Ok(())
+}
+
+fn check_reserved(what: &str, labels: &BTreeMap<String, String>) -> Result<()> {
+    for name in labels.keys() {
+        if RESERVED.contains(&name.as_str()) {
+            anyhow::bail!("{} can't set '{}', it's set for every event. Reserved: {}", what, name, RESERVED.join(", "));
+        }
+    }
+    Ok(())
+}
+
+/// NAME=VALUE pairs separated by commas. Names follow Prometheus rules, so labels can be used in metrics as is
+pub fn parse(key: &str, raw: &str) -> Result<BTreeMap<String, String>> {
+    let mut labels = BTreeMap::new();
+    for pair in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let Some((name, value)) = pair.split_once('=') else {
+            anyhow::bail!("Invalid {} entry: '{}'. Expected NAME=VALUE", key, pair);
+        };
+        let name = name.trim();
+        check_name(key, name)?;
+        labels.insert(name.to_string(), value.trim().to_string());
+    }
+    Ok(labels)
+}
+
+/// Labels as 'env=prod, team=infra' for messages and logs
+pub fn describe(labels: &BTreeMap<String, String>) -> String {
+    labels.iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>().join(", ")
+}
+
+/// Labels in the stored form 'env=prod,team=infra', read back with `parse`
+pub fn to_stored(labels: &BTreeMap<String, String>) -> String {
+    labels.iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>().join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn server_labels_override_defaults() {
+        let labels = parse("SERVER_LABELS_TEST", "team=web, tier=frontend").unwrap();
+        set("unshelve-test-web-1", &labels).unwrap();
+        let merged = get("unshelve-test-web-1");
+        assert_eq!(merged["team"], "web");
+        assert_eq!(merged["tier"], "frontend");
+        assert!(!get("unshelve-test-other").contains_key("tier"));
+        assert!(set("unshelve-test-web-2", &parse("X", "server=other").unwrap()).is_err());
+    }
+
+    #[test]
+    fn variable_of_a_server() {
+        assert_eq!(key("web-1"), "SERVER_LABELS_WEB_1");
+        assert_eq!(key("db.prod"), "SERVER_LABELS_DB_PROD");
+    }
+
+    #[test]
+    fn invalid_labels() {
+        assert!(parse("SERVER_LABELS", "team").is_err());
+        assert!(parse("SERVER_LABELS", "1team=x").is_err());
+        assert!(parse("SERVER_LABELS", "__name=x").is_err());
+        assert_eq!(parse("SERVER_LABELS", " env = prod ,").unwrap()["env"], "prod");
+    }
+}
@@ -0,0 +1,264 @@
+//! Shared code of the `unshelve` CLI and the `unshelved` monitoring daemon
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use anyhow::{Context, Result};
+use openstack::auth::{IdOrName, Password};
+use openstack::compute::ServerAddress;
+
+pub mod actions;
+pub mod address;
+pub mod alertmanager;
+pub mod aliases;
+#[cfg(feature = "amqp")]
+pub mod amqp;
+pub mod anomaly;
+pub mod backoff;
+pub mod bench;
+pub mod bundle;
+pub mod chaos;
+pub mod ci;
+pub mod config;
+pub mod consul;
+pub mod control;
+pub mod diagnose;
+pub mod drift;
+pub mod dump;
+pub mod engine;
+pub mod ensure;
+pub mod etcd;
+pub mod federation;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fleet;
+pub mod guard;
+pub mod history;
+pub mod instance;
+pub mod interpolate;
+pub mod killswitch;
+pub mod labels;
+pub mod maintenance;
+pub mod monitor;
+pub mod notify;
+pub mod nova;
+pub mod pins;
+pub mod pipeline;
+pub mod precondition;
+pub mod privileges;
+pub mod probe;
+pub mod profile;
+pub mod pubsub;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod ratelimit;
+pub mod recovery;
+pub mod redact;
+pub mod remote_write;
+pub mod report;
+pub mod rescue;
+pub mod restore;
+pub mod routing;
+pub mod rtt;
+pub mod schedule;
+pub mod scope;
+pub mod selftest;
+pub mod shard;
+pub mod signals;
+pub mod silence;
+pub mod snapshot;
+pub mod snmp;
+pub mod ssh;
+pub mod state;
+pub mod target;
+pub mod tasks;
+pub mod template;
+pub mod terraform;
+pub mod timeline;
+pub mod toml_config;
+pub mod volume;
+pub mod wait;
+pub mod warmup;
+pub mod webhook;
+
+/// Load config for both binaries into memory (see `config::var`): the config file, then the profile.
+/// Variables set in the process environment win over them. Call from main before the async runtime
+/// is built, keys from etcd come later with `load_etcd_config`
+pub fn load_config(file: &str, profile: Option<&str>) -> Result<config::Sources> {
+    // Variables set before loading the config win over it
+    let process_env: HashSet<String> = env::vars().map(|(key, _)| key).collect();
+
+    let os_cloud = os_cloud().filter(|_| !std::path::Path::new(file).exists());
+    let mut toml = None;
+    let vars = if let Some(cloud) = os_cloud {
+        // Credentials come from clouds.yaml, the other settings may all have defaults
+        println!("Config file {} not found - using OpenStack cloud '{}' from clouds.yaml", file, cloud);
+        vec![]
+    } else if toml_config::is_toml(file) {
+        let (config, vars) = toml_config::load(file)?;
+        toml = Some(config);
+        vars
+    } else {
+        interpolate::read_env_file(file)?
+    };
+    let mut sources = config::Sources {
+        file: file.to_string(),
+        process_env,
+        profile: profile.map(String::from),
+        profile_keys: vec![],
+        profile_group: None,
+        etcd_prefix: None,
+        etcd_keys: vec![],
+        toml,
+    };
+    match config::set_loaded(first_wins(vars), profile) {
+        Ok(applied) => sources.set_profile(applied),
+        // The profile may be defined in etcd, it's applied again with the etcd keys
+        Err(_) if config::var("ETCD_CONFIG_PREFIX").is_ok_and(|p| !p.trim().is_empty()) => {},
+        Err(e) => return Err(e),
+    }
+    Ok(sources)
+}
+
+/// Keys under ETCD_CONFIG_PREFIX override the config file and the profile is applied over them,
+/// the process environment still wins. Labels are checked once the config is complete.
+/// Returns the watch for changes of the etcd keys
+pub async fn load_etcd_config(sources: &mut config::Sources) -> Result<Option<etcd::ConfigWatch>> {
+    let config_watch = match etcd::load_config().await? {
+        Some((values, config_watch)) => {
+            sources.set_etcd(config_watch.prefix(), values.keys().cloned().collect());
+            let applied = config::extend_loaded(values)?;
+            sources.set_profile(applied);
+            Some(config_watch)
+        },
+        None => None,
+    };
+    match (&sources.profile, &sources.profile_group) {
+        (Some(profile), Some(group)) => println!("Profile: {} (group: {})", profile, group),
+        (Some(profile), None) => println!("Profile: {}", profile),
+        (None, _) => {},
+    }
+    labels::init()?;
+    Ok(config_watch)
+}
+
+/// Export OpenStack credentials of the loaded config (OS_*) to the process environment, the only
+/// place the OpenStack client reads them. Variables already set there are kept
+///
+/// # Safety
+/// Changes the process environment: call from main before the async runtime or any other thread is started
+pub unsafe fn export_credentials() {
+    for (key, value) in config::vars() {
+        if key.starts_with("OS_") && env::var_os(&key).is_none() {
+            // SAFETY: guaranteed by the caller
+            unsafe { env::set_var(&key, value) };
+        }
+    }
+}
+
+/// Config file and profile read into memory, the process environment is not changed - for embedding
+/// the engine in a C or Python host. etcd config is not loaded
+pub fn read_config(file: &str, profile: Option<&str>) -> Result<config::Vars> {
+    let vars = if os_cloud().is_some() && !std::path::Path::new(file).exists() {
+        vec![]
+    } else if toml_config::is_toml(file) {
+        toml_config::read(file)?
+    } else {
+        interpolate::read_env_file(file)?
+    };
+    let mut values: HashMap<String, String> = first_wins(vars).into_iter().collect();
+    if let Some(profile) = profile {
+        profile::overlay(profile, &mut values)?;
+    }
+    Ok(config::Vars::new(values))
+}
+
+/// The first value of every key of a config source, like the variables of the process environment
+/// are not overridden by the config
+fn first_wins(vars: Vec<(String, String)>) -> BTreeMap<String, String> {
+    let mut values = BTreeMap::new();
+    for (key, value) in vars {
+        values.entry(key).or_insert(value);
+    }
+    values
+}
+
+/// Cloud from clouds.yaml selected with OS_CLOUD or --os-cloud
+pub fn os_cloud() -> Option<String> {
+    config::var("OS_CLOUD").ok().filter(|c| !c.trim().is_empty())
+}
+
+/// Authenticate with OpenStack: a cloud from clouds.yaml (OS_CLOUD, searched in the current directory,
+/// ~/.config/openstack and /etc/openstack, with secure.yaml) or OS_* variables,
+/// federated logins are exchanged for a token first
+pub async fn connect() -> Result<openstack::Cloud> {
+    if let Some(name) = os_cloud() {
+        return openstack::Cloud::from_config(&name)
+            .await
+            .context(format!("Failed to authenticate with OpenStack cloud '{}' from clouds.yaml", name));
+    }
+    if let Some(cloud) = federation::connect().await.context("Federated authentication failed")? {
+        return Ok(cloud);
+    }
+    openstack::Cloud::from_env()
+        .await
+        .context("Failed to authenticate with OpenStack")
+}
+
+/// Authenticate with settings read by `read_config`: a cloud from clouds.yaml (OS_CLOUD) or password
+/// credentials. Other auth types and OS_REGION_NAME are only supported through clouds.yaml here,
+/// `connect` reads them from the process environment
+pub async fn connect_with(vars: &config::Vars) -> Result<openstack::Cloud> {
+    if let Some(name) = vars.get("OS_CLOUD") {
+        return openstack::Cloud::from_config(&name)
+            .await
+            .context(format!("Failed to authenticate with OpenStack cloud '{}' from clouds.yaml", name));
+    }
+    let auth_type = vars.get("OS_AUTH_TYPE").unwrap_or_else(|| "password".to_string()).to_lowercase();
+    if auth_type != "password" && auth_type != "v3password" {
+        anyhow::bail!("OS_AUTH_TYPE={} is not supported when embedded - use a cloud from clouds.yaml (OS_CLOUD)", auth_type);
+    }
+    if vars.get("OS_REGION_NAME").is_some() {
+        anyhow::bail!("OS_REGION_NAME is not supported when embedded - use a cloud from clouds.yaml (OS_CLOUD) with region_name");
+    }
+    let required = |key: &str| vars.get(key).context(format!("{} not set in config", key));
+    let user_domain = vars.get("OS_USER_DOMAIN_NAME").unwrap_or_else(|| "Default".to_string());
+    let project = match (vars.get("OS_PROJECT_ID"), vars.get("OS_PROJECT_NAME")) {
+        (Some(id), _) => IdOrName::Id(id),
+        (None, Some(name)) => IdOrName::Name(name),
+        (None, None) => anyhow::bail!("OS_PROJECT_ID or OS_PROJECT_NAME not set in config"),
+    };
+    let project_domain = vars.get("OS_PROJECT_DOMAIN_NAME").unwrap_or_else(|| user_domain.clone());
+    let auth = Password::new(&required("OS_AUTH_URL")?, required("OS_USERNAME")?, required("OS_PASSWORD")?, user_domain)
+        .context("Invalid OS_AUTH_URL")?
+        .with_project_scope(project, IdOrName::Name(project_domain));
+    openstack::Cloud::new(auth)
+        .await
+        .context("Failed to authenticate with OpenStack")
+}
+
+pub async fn init_cloud() -> openstack::Cloud {
+    let cloud = connect().await.unwrap();
+
+    println!("Connected to OpenStack successfully!");
+    if let Some(expires) = federation::expires() {
+        println!("Federated token expires {}", expires.format("%Y-%m-%d %H:%M:%S"));
+    }
+    cloud
+}
+
+pub fn get_server_addresses_string(addresses: &HashMap<String, Vec<ServerAddress>>) -> Vec<String> {
+    let mut address_strings: Vec<String> = vec![];
+    for net in addresses {
+        let (net_name, ips) = net;
+        let mut ip_attrib: Vec<String> = vec![];
+        for ip in ips {
+            let ip_type = match ip.addr_type {
+                Some(ip_type) => ip_type.to_string(),
+                None => "None".to_string(),
+            };
+            ip_attrib.push(format!("{} - {} ", ip.addr.to_string(), ip_type));
+        }
+        address_strings.push(format!("[{}] {}", net_name, ip_attrib.join(", ")));
+    }
+    address_strings
+}
@@ -1,19 +1,46 @@
 use std::env;
-use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::collections::BTreeMap;
 use anyhow::{Context, Result};
-use tokio::time::{sleep, Duration};
-use clap::{Parser, Subcommand};
-use openstack::compute::ServerAddress;
+use clap::{Parser, Subcommand, ValueEnum};
+use tokio::time::Duration;
 // use openstack::waiter::Waiter;
 // use clap::builder::TypedValueParser;
 
+use unshelve::{
+    address, aliases, bench, chaos, ci, config, control, diagnose, dump, ensure, guard, monitor, notify, nova, probe,
+    recovery, redact, remote_write, report, rescue, restore, schedule, selftest, shard, silence, ssh, state, template,
+    terraform, timeline, wait,
+};
+use unshelve::{get_server_addresses_string, init_cloud};
+use notify::{Notifier, Severity};
+use silence::SilenceStore;
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Path to config file with OpenStack credentials. Empty for default .env file
+    /// Path to config file with OpenStack credentials: .env format or TOML (*.toml). Empty for default .env file
     #[arg(short, long, default_value = ".env")]
     config: String,
 
+    /// Profile from config: variables <PROFILE>__<KEY> override <KEY>
+    #[arg(short, long)]
+    profile: Option<String>,
+
+    /// Cloud from clouds.yaml instead of OS_* credentials. Default from OS_CLOUD
+    #[arg(long, value_name = "NAME")]
+    os_cloud: Option<String>,
+
+    /// Print GitHub Actions annotations (::group::, ::error::) and job summary. Also enabled by CI=true
+    #[arg(long)]
+    ci: bool,
+
+    /// Inject failures for testing, e.g. ping-fail=0.3,tcp-fail=0.2,api-error=0.1
+    #[arg(long, hide = true)]
+    inject: Option<String>,
+
     /// Command to execute
     #[command(subcommand)]
     command: Command,
@@ -22,98 +49,733 @@ struct Args {
 #[derive(Subcommand, Debug)]
 enum Command {
     /// Show list of all servers
-    ServerList,
+    ServerList {
+        /// Cluster servers by status, availability zone or flavor, with a count per group
+        #[arg(long, value_enum)]
+        group_by: Option<GroupBy>,
+        /// Only counts per status, availability zone and flavor, without server rows
+        #[arg(long, conflicts_with = "group_by")]
+        summary: bool,
+        /// One line per server from a template, e.g. '{{ .name }} {{ .status }} {{ .addresses.private.0 }}'.
+        /// Fields: id, name, status, power_state, availability_zone, flavor, addresses.<network>.<n>
+        #[arg(long, conflicts_with_all = ["group_by", "summary"])]
+        format: Option<String>,
+    },
     /// Display detailed server information.
-    /// Add <SERVER_NAME_OR_UUID> e.g. ./bin_file server-info ServerName or set SERVER_NAME var in .env or config
+    /// Add <SERVER_NAME_OR_UUID> e.g. ./bin_file server-info ServerName or set SERVER_NAME var in .env or config.
+    /// Without both, an interactive server picker is shown
     ServerInfo {
-        /// Server name or UUID
+        /// Server names, UUIDs or aliases from SERVER_ALIASES. Several servers are fetched concurrently
+        #[arg(value_name = "SERVER_NAME")]
+        server_identifiers: Vec<String>,
+        /// Print JSON array instead of text
+        #[arg(long)]
+        json: bool,
+        /// One line per server from a template, e.g. '{{ .name }} {{ .status }} {{ .addresses.private.0 }}'.
+        /// Fields are the same as in server-list --format
+        #[arg(long, conflicts_with = "json")]
+        format: Option<String>,
+    },
+    /// Print the server address, e.g. ssh $(unshelve ip web1)
+    Ip {
+        /// Server name, UUID or alias from SERVER_ALIASES. Default from SERVER_NAME
         #[arg(value_name = "SERVER_NAME")]
         server_identifier: Option<String>,
+        /// Address type, floating is preferred by default
+        #[arg(long = "type", value_enum)]
+        kind: Option<address::AddressKind>,
+        /// Only addresses in this network
+        #[arg(long)]
+        network: Option<String>,
+    },
+    /// Unshelve the server if needed, wait until it is ACTIVE and SSH is reachable, then run ssh.
+    /// User and port from SSH_USER and SSH_PORT
+    Ssh {
+        /// Server name, UUID or alias from SERVER_ALIASES. Default from SERVER_NAME
+        #[arg(value_name = "SERVER_NAME")]
+        server_identifier: Option<String>,
+        /// Address type, floating is preferred by default
+        #[arg(long = "type", value_enum)]
+        kind: Option<address::AddressKind>,
+        /// Only addresses in this network
+        #[arg(long)]
+        network: Option<String>,
+        /// Remote command and ssh arguments after --
+        #[arg(last = true)]
+        command: Vec<String>,
+    },
+    /// Keep SSH port forwards to the server up, e.g. tunnel devbox -L 8080:localhost:80.
+    /// The server is unshelved if needed and the tunnel re-established after disconnects
+    Tunnel {
+        /// Server name, UUID or alias from SERVER_ALIASES. Default from SERVER_NAME
+        #[arg(value_name = "SERVER_NAME")]
+        server_identifier: Option<String>,
+        /// Local forward as in ssh -L, can be repeated
+        #[arg(short = 'L', value_name = "[BIND:]PORT:HOST:HOSTPORT", required = true)]
+        forwards: Vec<String>,
+        /// Address type, floating is preferred by default
+        #[arg(long = "type", value_enum)]
+        kind: Option<address::AddressKind>,
+        /// Only addresses in this network
+        #[arg(long)]
+        network: Option<String>,
+    },
+    /// Ansible dynamic inventory JSON of the project's servers, grouped by status, availability zone and flavor.
+    /// ansible_host is the preferred address (floating, then fixed)
+    Inventory {
+        /// Whole inventory (default, for ansible -i)
+        #[arg(long)]
+        list: bool,
+        /// Variables of one host - empty, they are in _meta of --list
+        #[arg(long, conflicts_with = "list")]
+        host: Option<String>,
+    },
+    /// For CI pipelines: unshelve if needed, wait for ACTIVE and an open TCP port, print progress
+    /// as key=value lines. Exit code is 0 only when the server is usable
+    EnsureUp {
+        /// Server name, UUID or alias from SERVER_ALIASES. Default from SERVER_NAME
+        #[arg(value_name = "SERVER_NAME")]
+        server_identifier: Option<String>,
+        /// Give up after this time, e.g. 15m, 1h
+        #[arg(long, default_value = "15m")]
+        timeout: String,
+        /// TCP port that must accept connections. Default from SSH_PORT or 22
+        #[arg(long)]
+        port: Option<u16>,
     },
     /// Manual unshelve server.
-    /// Add <SERVER_NAME_OR_UUID> e.g. ./bin_file unshelve ServerName  or set SERVER_NAME var in .env or config
+    /// Add <SERVER_NAME_OR_UUID> e.g. ./bin_file unshelve ServerName  or set SERVER_NAME var in .env or config.
+    /// Without both, an interactive server picker is shown.
+    /// Several servers are unshelved with UNSHELVE_STAGGER_SECONDS delay and UNSHELVE_MAX_IN_FLIGHT limit
     Unshelve {
-        /// Server name or UUID
+        /// Server names, UUIDs or aliases from SERVER_ALIASES
+        #[arg(value_name = "SERVER_NAME")]
+        server_identifiers: Vec<String>,
+        /// Unshelve every SHELVED_OFFLOADED or SHELVED server in the project
+        #[arg(long, conflicts_with_all = ["server_identifiers", "guard"])]
+        all_shelved: bool,
+        /// With --all-shelved handle only this part of the servers, e.g. 2/5, so several
+        /// instances split a large fleet without overlapping. Servers are assigned by UUID hash
+        #[arg(long, value_name = "K/N", requires = "all_shelved")]
+        shard: Option<String>,
+        /// Wait for ACTIVE, watch pings for GUARD_SOAK_MINUTES and retry once on regression
+        #[arg(long)]
+        guard: bool,
+        /// Block until the server accepts connections on SSH_PORT, then print a banner
+        #[arg(long, conflicts_with_all = ["all_shelved", "guard"])]
+        wait: bool,
+        /// With --wait ring the terminal bell when the server is up (or the wait fails)
+        #[arg(long, requires = "wait")]
+        bell: bool,
+        /// With --wait give up after this time, e.g. 30m
+        #[arg(long, default_value = "30m", requires = "wait")]
+        timeout: String,
+    },
+    /// Block until the server is ACTIVE and accepts connections, then print a banner.
+    /// Doesn't unshelve - for a server being unshelved elsewhere, e.g. by the monitor
+    Wait {
+        /// Server name, UUID or alias from SERVER_ALIASES. Default from SERVER_NAME
         #[arg(value_name = "SERVER_NAME")]
         server_identifier: Option<String>,
+        /// Give up after this time, e.g. 30m
+        #[arg(long, default_value = "30m")]
+        timeout: String,
+        /// TCP port that must accept connections. Default from SSH_PORT or 22
+        #[arg(long)]
+        port: Option<u16>,
+        /// Ring the terminal bell when the server is up (or the wait fails)
+        #[arg(long)]
+        bell: bool,
     },
-    /// Monitor server with auto-unshelve
+    /// Boot the server from a rescue image with its disk attached, e.g. when it came back broken
+    /// after unshelve. Waits for RESCUE status
+    Rescue {
+        /// Server name, UUID or alias from SERVER_ALIASES. Default from SERVER_NAME
+        #[arg(value_name = "SERVER_NAME")]
+        server_identifier: Option<String>,
+        /// Rescue image name or UUID. Default is the cloud's rescue image
+        #[arg(long)]
+        image: Option<String>,
+        /// Give up waiting after this time, e.g. 10m
+        #[arg(long, default_value = "10m")]
+        timeout: String,
+    },
+    /// Boot a rescued server from its own disk again. Waits for ACTIVE status
+    Unrescue {
+        /// Server name, UUID or alias from SERVER_ALIASES. Default from SERVER_NAME
+        #[arg(value_name = "SERVER_NAME")]
+        server_identifier: Option<String>,
+        /// Give up waiting after this time, e.g. 10m
+        #[arg(long, default_value = "10m")]
+        timeout: String,
+    },
+    /// Restore a SOFT_DELETED server while the cloud's reclaim window (RECLAIM_WINDOW_HOURS) allows
+    Restore {
+        /// Server name, UUID or alias from SERVER_ALIASES. Default from SERVER_NAME
+        #[arg(value_name = "SERVER_NAME")]
+        server_identifier: Option<String>,
+    },
+    /// Boot a disposable server, shelve it and check that monitoring unshelves it: proves credentials,
+    /// sockets and notifications of this setup end to end. The server is deleted afterwards
+    Selftest {
+        /// Flavor of the test server, the smallest one will do
+        #[arg(long)]
+        flavor: String,
+        /// Image name or UUID of the test server, it must answer pings
+        #[arg(long)]
+        image: String,
+        /// Network of the test server, reachable from this host
+        #[arg(long)]
+        network: String,
+        /// Time limit of each step (boot, shelve, recovery), e.g. 20m
+        #[arg(long, default_value = "20m")]
+        timeout: String,
+        /// Don't delete the test server, e.g. to inspect a failure
+        #[arg(long)]
+        keep: bool,
+    },
+    /// Monitor server with auto-unshelve: runs the unshelved daemon with the same config, profile and cloud
     Start {
-        /// raw - for sudo user, dgram - for unprivileged user
-        #[arg(default_value = "dgram")]
+        /// Arguments of unshelved, e.g. raw --for 8h
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Measure ICMP/TCP checks per second this host sustains, to choose intervals for large fleets
+    BenchProbes {
+        /// File with one target per line: IP for ICMP, IP:port for TCP
+        #[arg(long)]
+        targets: String,
+        /// Number of checks in flight at the same time
+        #[arg(long, default_value_t = 10)]
+        concurrency: usize,
+        /// Benchmark time, e.g. 30s, 2m
+        #[arg(long, default_value = "10s")]
+        duration: String,
+        /// raw or dgram. Default from PING_SOCKET_TYPE or dgram
+        #[arg(long)]
         socket_type: Option<String>,
     },
+    /// Availability and error budget used this month, from check results in the event history.
+    /// report inventory - server inventory as a document
+    #[command(args_conflicts_with_subcommands = true)]
+    Report {
+        #[command(subcommand)]
+        command: Option<ReportCommand>,
+        /// Aggregate servers by a SERVER_LABELS label (team, env, ...) instead of listing them
+        #[arg(long, value_name = "LABEL")]
+        by: Option<String>,
+    },
+    /// Push ping results recorded in the event history to REMOTE_WRITE_URL
+    ExportHistory,
+    /// Recorded events: history show <INCIDENT_ID> [--timeline] for post-incident reviews
+    History {
+        #[command(subcommand)]
+        command: HistoryCommand,
+    },
+    /// Notification channels
+    Notify {
+        #[command(subcommand)]
+        command: NotifyCommand,
+    },
+    /// Show configuration with the source of every value
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Troubleshooting tools
+    Debug {
+        #[command(subcommand)]
+        command: DebugCommand,
+    },
+    /// Generate monitor profiles from infrastructure-as-code state
+    Import {
+        #[command(subcommand)]
+        command: ImportCommand,
+    },
+    /// Mute notifications of matching events for a time range, events are still recorded
+    Silence {
+        #[command(subcommand)]
+        command: SilenceCommand,
+    },
+    /// Daily shelve and unshelve of the servers selected by OpenStack metadata (SCHEDULE)
+    Schedule {
+        #[command(subcommand)]
+        command: ScheduleCommand,
+    },
+    /// Several daemons monitoring servers from different hosts
+    Fleet {
+        #[command(subcommand)]
+        command: FleetCommand,
+    },
+}
+
+/// server-list grouping
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum GroupBy {
+    Status,
+    Az,
+    Flavor,
+}
+
+impl GroupBy {
+    fn key(self, server: &openstack::compute::Server) -> String {
+        let key = match self {
+            GroupBy::Status => server.status().to_string(),
+            GroupBy::Az => server.availability_zone().clone(),
+            GroupBy::Flavor => server.flavor().original_name.clone(),
+        };
+        if key.is_empty() { "-".to_string() } else { key }
+    }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+#[derive(Subcommand, Debug)]
+enum NotifyCommand {
+    /// Send test message to all configured channels or only to the given one
+    Test {
+        /// webhook, slack, google_chat, teams, telegram, signal or pushover
+        channel: Option<String>,
+    },
+    /// Send message to configured channels, e.g. from shell hooks and scripts
+    Send {
+        /// Message severity
+        #[arg(short, long, value_enum, default_value_t = Severity::Info)]
+        severity: Severity,
+        /// Message text
+        #[arg(short, long)]
+        message: String,
+        /// Send only to this channel: webhook, slack, google_chat, teams, telegram, signal or pushover
+        #[arg(long)]
+        channel: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Print config file keys as YAML, with --effective - merged config file, environment, profile and defaults
+    Show {
+        /// Show effective configuration instead of the config file only
+        #[arg(long)]
+        effective: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ImportCommand {
+    /// Profile <NAME>__SERVER_NAME / <NAME>__PING_IP per openstack_compute_instance_v2 in Terraform/OpenTofu state
+    Terraform {
+        /// terraform.tfstate path, http(s) URL of the state or - for stdin (terraform state pull | unshelve import terraform -)
+        state: String,
+        /// Config file to write the profiles to, the previously imported block is replaced. Default stdout
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ScheduleCommand {
+    /// Show the jobs with their next run and the servers they select now
+    List,
+    /// Run the jobs at their time until Ctrl+C
+    Run,
+}
+
+#[derive(Subcommand, Debug)]
+enum SilenceCommand {
+    /// Add a silence, e.g. silence add -m server=web1 -m kind=ping_* --for 2h -c "disk replacement"
+    Add {
+        /// LABEL=PATTERN, labels: server, kind, severity. * stands for any characters. Repeat to match all
+        #[arg(short, long = "match", required = true)]
+        matchers: Vec<String>,
+        /// Start as local time YYYY-MM-DD HH:MM. Default now
+        #[arg(long)]
+        start: Option<String>,
+        /// Length, e.g. 2h, 90m or 1d
+        #[arg(long = "for", default_value = "2h")]
+        duration: String,
+        /// Why notifications are muted
+        #[arg(short, long)]
+        comment: Option<String>,
+    },
+    /// Show active and pending silences
+    List {
+        /// Also show expired silences
+        #[arg(long)]
+        all: bool,
+    },
+    /// Remove a silence by id
+    Remove {
+        id: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ReportCommand {
+    /// Per-server table with status, addresses, flavor, zone and the last incident, for wikis and ops notes
+    Inventory {
+        /// Document format
+        #[arg(long, value_enum, default_value_t = report::OutputFormat::Md)]
+        output: report::OutputFormat,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum HistoryCommand {
+    /// Events of an incident, the id is in notifications, SNAPSHOT_FILE and `debug dump`
+    Show {
+        incident_id: String,
+        /// Aligned timeline with time since the start and phases: detection, API calls, retries, recovery
+        #[arg(long)]
+        timeline: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum FleetCommand {
+    /// Servers of several daemons in one table, from their control API (WEBHOOK_LISTEN) /status
+    Status {
+        /// Daemon addresses or URLs, e.g. mon1:8085,mon2:8085
+        #[arg(long, value_delimiter = ',', required = true)]
+        endpoints: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DebugCommand {
+    /// Save sanitized config, version, current state and recent events to an archive for bug reports
+    Dump {
+        /// Archive path. Default unshelve-debug-<timestamp>.tar.gz
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// What the monitor sees in the server's Nova details (status, task, placement, server groups) and what it
+    /// would do. --record saves the responses, --replay repeats the diagnosis from them offline
+    Server {
+        /// Server name, UUID or alias from SERVER_ALIASES. Default from SERVER_NAME
+        #[arg(value_name = "SERVER_NAME")]
+        server_identifier: Option<String>,
+        /// Save the Nova responses (secrets and metadata removed) to this file
+        #[arg(long, value_name = "FILE")]
+        record: Option<String>,
+        /// Diagnose from a recorded file, without connecting to OpenStack
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["server_identifier", "record"])]
+        replay: Option<String>,
+    },
+}
+
+fn main() -> Result<()> {
     let args = Args::parse();
+    if let Command::Start { args: daemon_args } = &args.command {
+        return start_daemon(&args, daemon_args);
+    }
+    ci::init(args.ci);
+    if let Some(cloud) = &args.os_cloud {
+        // SAFETY: the runtime is not built yet, this is the only thread
+        unsafe { env::set_var("OS_CLOUD", cloud) };
+    }
+    let sources = unshelve::load_config(&args.config, args.profile.as_deref()).map_err(report)?;
+    // SAFETY: the runtime is not built yet, this is the only thread
+    unsafe { unshelve::export_credentials() };
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("Failed to start async runtime")?;
+    runtime.block_on(run(args, sources)).map_err(report)
+}
 
-    // Load environment variables from file
-    dotenv::from_filename(&args.config).context(format!(
-        "Failed to load environment from file: {}",
-        args.config
-    ))?;
+/// Errors may carry URLs with credentials or tokens from config
+fn report(e: anyhow::Error) -> anyhow::Error {
+    let message = redact::redact(&format!("{:#}", e));
+    ci::error(&message);
+    anyhow::anyhow!(message)
+}
+
+/// Replace this process with unshelved installed next to this binary (or found in PATH),
+/// the global options are passed on. The daemon loads the config itself
+fn start_daemon(args: &Args, daemon_args: &[String]) -> Result<()> {
+    let program = env::current_exe()
+        .ok()
+        .map(|exe| exe.with_file_name("unshelved"))
+        .filter(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from("unshelved"));
+    let mut command = std::process::Command::new(&program);
+    command.arg("--config").arg(&args.config);
+    if let Some(profile) = &args.profile {
+        command.arg("--profile").arg(profile);
+    }
+    if let Some(cloud) = &args.os_cloud {
+        command.arg("--os-cloud").arg(cloud);
+    }
+    if args.ci {
+        command.arg("--ci");
+    }
+    if let Some(spec) = &args.inject {
+        command.arg("--inject").arg(spec);
+    }
+    // exec returns only on failure
+    let e = command.args(daemon_args).exec();
+    Err(e).context(format!("Failed to run {} - monitoring is done by the unshelved binary", program.display()))
+}
+
+async fn run(args: Args, mut sources: config::Sources) -> Result<()> {
+    // Changes of the etcd config are only watched by the daemon
+    unshelve::load_etcd_config(&mut sources).await?;
+    if let Some(spec) = &args.inject {
+        chaos::init(spec)?;
+    }
 
     match args.command {
-        Command::ServerList => {
+        Command::ServerList { group_by, summary, format } => {
+            if let Some(format) = format {
+                let template = template::Template::parse(&format)?;
+                // No connection banner - output goes to scripts
+                let cloud = unshelve::connect().await?;
+                return list_servers_formatted(&cloud, &template).await;
+            }
             let cloud = init_cloud().await;
-            list_servers(&cloud).await
+            if summary {
+                return servers_summary(&cloud).await;
+            }
+            list_servers(&cloud, group_by).await
         },
-        Command::ServerInfo { server_identifier } => {
-            let identifier = match server_identifier {
-                Some(id) => id,
-                None => {
-                    env::var("SERVER_NAME")
-                        .context("No server identifier provided and SERVER_NAME env var not set")?
+        Command::ServerInfo { server_identifiers, json, format } => {
+            let template = format.as_deref().map(template::Template::parse).transpose()?;
+            if server_identifiers.len() > 1 || json || template.is_some() {
+                // No connection banner - JSON and template output must stay parseable
+                let cloud = unshelve::connect().await?;
+                let mut identifiers = server_identifiers
+                    .iter()
+                    .map(|id| aliases::resolve(id))
+                    .collect::<Result<Vec<String>>>()?;
+                if identifiers.is_empty() {
+                    identifiers.push(aliases::resolve(&config::var("SERVER_NAME")
+                        .context("No server identifier provided and SERVER_NAME env var not set")?)?);
                 }
-            };
+                return servers_info(&cloud, &identifiers, json, template.as_ref()).await;
+            }
             let cloud = init_cloud().await;
+            let identifier = get_server_identifier(&cloud, server_identifiers.into_iter().next()).await?;
             server_info(&cloud, &identifier).await
         },
-        Command::Unshelve { server_identifier } => {
-            let identifier = match server_identifier {
-                Some(id) => id,
-                None => {
-                    env::var("SERVER_NAME")
-                        .context("No server identifier provided and SERVER_NAME env var not set")?
-                }
+        Command::Ip { server_identifier, kind, network } => {
+            let identifier = server_or_default(server_identifier)?;
+            // No connection banner - the address is used in command substitution
+            let cloud = unshelve::connect().await?;
+            let server = find_server(&cloud, &identifier, false).await?;
+            println!("{}", address::select(&server, kind, network.as_deref())?);
+            Ok(())
+        },
+        Command::Inventory { list, host } => {
+            // --host only gets {}: variables of every host are in _meta of the inventory
+            let list = list || host.is_none();
+            if !list {
+                println!("{{}}");
+                return Ok(());
+            }
+            // No connection banner - output is parsed by Ansible
+            let cloud = unshelve::connect().await?;
+            ansible_inventory(&cloud).await
+        },
+        Command::EnsureUp { server_identifier, timeout, port } => {
+            let limit = monitor::parse_duration(&timeout)?;
+            let port = match port {
+                Some(port) => port,
+                None => ssh::ssh_port()?,
             };
+            let identifier = server_or_default(server_identifier)?;
+            let cloud = unshelve::connect().await?;
+            let server = find_server(&cloud, &identifier, false).await?;
+            ci::group(&format!("ensure-up {}", identifier));
+            let result = ensure::ensure_up(&cloud, server.id(), limit, port).await;
+            ci::end_group();
+            ci::step_result(&format!("ensure-up `{}`", identifier), &result);
+            result
+        },
+        Command::Ssh { server_identifier, kind, network, command } => {
+            let identifier = server_or_default(server_identifier)?;
             let cloud = init_cloud().await;
-            unshelve_manual(&cloud, &identifier).await
+            let server = find_server(&cloud, &identifier, true).await?;
+            ssh::ssh(&cloud, server.id(), kind, network.as_deref(), &command).await
         },
-        Command::Start { socket_type } => {
-            let s = socket_type.unwrap().to_lowercase();
-            let lower = s.to_lowercase();
-            let use_dgram_socket: bool = if lower == "raw" {
-                if is_sudo::check() != is_sudo::RunningAs::Root {
-                    anyhow::bail!("For 'raw' socket type need privileged user");
-                }
-                false
-            } else if lower == "dgram" {
-                true
+        Command::Tunnel { server_identifier, forwards, kind, network } => {
+            let identifier = server_or_default(server_identifier)?;
+            let cloud = init_cloud().await;
+            let server = find_server(&cloud, &identifier, true).await?;
+            ssh::tunnel(&cloud, server.id(), kind, network.as_deref(), &forwards).await
+        },
+        Command::Unshelve { server_identifiers, all_shelved, shard, guard, wait, bell, timeout } => {
+            let wait = if wait { Some((monitor::parse_duration(&timeout)?, bell)) } else { None };
+            let step = if all_shelved {
+                "unshelve --all-shelved".to_string()
             } else {
-                anyhow::bail!("Invalid socket type: '{}'. Allowed values: 'raw', 'dgram' (Case insensitive)", s);
+                format!("unshelve {}", server_identifiers.join(" ")).trim().to_string()
+            };
+            ci::group(&step);
+            let result = unshelve_command(server_identifiers, all_shelved, shard, guard, wait).await;
+            ci::end_group();
+            ci::step_result(&step, &result);
+            result
+        },
+        Command::Wait { server_identifier, timeout, port, bell } => {
+            let limit = monitor::parse_duration(&timeout)?;
+            let port = match port {
+                Some(port) => port,
+                None => ssh::ssh_port()?,
             };
-            println!("Socket type: {}", lower.to_uppercase());
+            let identifier = server_or_default(server_identifier)?;
+            let cloud = init_cloud().await;
+            let server = find_server(&cloud, &identifier, false).await?;
+            wait::wait_up(&cloud, server.id(), limit, port, bell).await
+        },
+        Command::Rescue { server_identifier, image, timeout } => {
+            let limit = monitor::parse_duration(&timeout)?;
+            let identifier = server_or_default(server_identifier)?;
+            let cloud = init_cloud().await;
+            let mut server = find_server(&cloud, &identifier, true).await?;
+            rescue::rescue(&cloud, &mut server, image.as_deref(), limit).await
+        },
+        Command::Unrescue { server_identifier, timeout } => {
+            let limit = monitor::parse_duration(&timeout)?;
+            let identifier = server_or_default(server_identifier)?;
             let cloud = init_cloud().await;
-            start_monitoring(&cloud, use_dgram_socket).await
+            let mut server = find_server(&cloud, &identifier, true).await?;
+            rescue::unrescue(&cloud, &mut server, limit).await
+        },
+        Command::Restore { server_identifier } => {
+            let identifier = server_or_default(server_identifier)?;
+            let cloud = init_cloud().await;
+            let mut server = find_server(&cloud, &identifier, true).await?;
+            restore::restore(&mut server).await
+        },
+        Command::Selftest { flavor, image, network, timeout, keep } => {
+            let limit = monitor::parse_duration(&timeout)?;
+            let cloud = init_cloud().await;
+            selftest::selftest(&cloud, &flavor, &image, &network, limit, keep).await
+        },
+        Command::Start { .. } => unreachable!("start replaces the process before the config is loaded"),
+        Command::BenchProbes { targets, concurrency, duration, socket_type } => {
+            let duration = monitor::parse_duration(&duration)?;
+            let use_dgram_socket = match &socket_type {
+                Some(socket_type) => probe::parse_socket_type(socket_type)?,
+                None => probe::socket_type_from_env()?,
+            };
+            bench::bench_probes(&targets, concurrency, duration, use_dgram_socket).await
+        },
+        Command::Report { command, by } => match command {
+            Some(ReportCommand::Inventory { output }) => {
+                // No connection banner - the document goes to a file or a wiki
+                let cloud = unshelve::connect().await?;
+                report::inventory(&cloud, output).await
+            },
+            None => report::sla_report(by.as_deref()),
         },
+        Command::ExportHistory => remote_write::export_history().await,
+        Command::History { command } => match command {
+            HistoryCommand::Show { incident_id, timeline } => timeline::show(&incident_id, timeline),
+        },
+        Command::Notify { command } => match command {
+            NotifyCommand::Test { channel } => {
+                Notifier::from_env()?.test(channel.as_deref()).await
+            },
+            NotifyCommand::Send { severity, message, channel } => {
+                let text = format!("[{}] {}", severity, message);
+                Notifier::from_env()?.deliver(channel.as_deref(), severity, &text).await
+            },
+        },
+        Command::Config { command } => match command {
+            ConfigCommand::Show { effective } => config::show(&sources, effective),
+        },
+        Command::Debug { command } => match command {
+            DebugCommand::Dump { output } => dump::debug_dump(&args.config, output).await,
+            DebugCommand::Server { replay: Some(path), .. } => diagnose::replay(&path),
+            DebugCommand::Server { server_identifier, record, replay: None } => {
+                let identifier = server_or_default(server_identifier)?;
+                let cloud = unshelve::connect().await?;
+                let server = find_server(&cloud, &identifier, false).await?;
+                diagnose::record(&cloud, server.id(), record.as_deref()).await
+            },
+        },
+        Command::Import { command } => match command {
+            ImportCommand::Terraform { state, output } => terraform::import(&state, output.as_deref()).await,
+        },
+        Command::Silence { command } => match command {
+            SilenceCommand::Add { matchers, start, duration, comment } => {
+                SilenceStore::load()?.add(&matchers, start.as_deref(), &duration, comment.as_deref())
+            },
+            SilenceCommand::List { all } => {
+                SilenceStore::load()?.list(all);
+                Ok(())
+            },
+            SilenceCommand::Remove { id } => SilenceStore::load()?.remove(&id),
+        },
+        Command::Schedule { command } => {
+            let jobs = schedule::from_env()?;
+            let cloud = init_cloud().await;
+            match command {
+                ScheduleCommand::List => schedule::list(&cloud, &jobs).await,
+                ScheduleCommand::Run => schedule::run(&cloud, &jobs).await,
+            }
+        },
+        Command::Fleet { command } => match command {
+            FleetCommand::Status { endpoints } => control::fleet_status(&endpoints).await,
+        },
+    }
+}
+
+/// Server identifier from arguments or SERVER_NAME env var, aliases resolved
+fn server_or_default(server_identifier: Option<String>) -> Result<String> {
+    match server_identifier {
+        Some(id) => aliases::resolve(&id),
+        None => aliases::resolve(&config::var("SERVER_NAME")
+            .context("No server identifier provided and SERVER_NAME env var not set")?),
     }
 }
 
-async fn init_cloud() -> openstack::Cloud {
-    let cloud = openstack::Cloud::from_env()
+/// Server identifier from arguments, SERVER_NAME env var or interactive picker
+async fn get_server_identifier(cloud: &openstack::Cloud, server_identifier: Option<String>) -> Result<String> {
+    if let Some(id) = server_identifier {
+        return aliases::resolve(&id);
+    }
+    if let Ok(id) = config::var("SERVER_NAME") {
+        return aliases::resolve(&id);
+    }
+    // Picker only makes sense for a human at the terminal, scripts get an error
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        anyhow::bail!("No server identifier provided and SERVER_NAME env var not set");
+    }
+    pick_server(cloud).await
+}
+
+/// Fuzzy-searchable picker over the live server list
+async fn pick_server(cloud: &openstack::Cloud) -> Result<String> {
+    let servers = cloud
+        .list_servers()
         .await
-        .context("Failed to authenticate with OpenStack")
-        .unwrap();
+        .context("Failed to fetch server list")?;
+
+    if servers.is_empty() {
+        anyhow::bail!("No servers found in the project");
+    }
+
+    let items: Vec<String> = servers
+        .iter()
+        .map(|s| format!("{} ({})", s.name(), s.id()))
+        .collect();
 
-    println!("Connected to OpenStack successfully!");
-    cloud
+    let selection = dialoguer::FuzzySelect::new()
+        .with_prompt("Select server (type to search)")
+        .items(&items)
+        .default(0)
+        .interact_opt()
+        .context("Failed to show server picker")?;
+
+    match selection {
+        Some(index) => Ok(servers[index].id().clone()),
+        None => anyhow::bail!("No server selected"),
+    }
 }
 
 /// List all servers in the project
-async fn list_servers(cloud: &openstack::Cloud) -> Result<()> {
+async fn list_servers(cloud: &openstack::Cloud, group_by: Option<GroupBy>) -> Result<()> {
     println!("Fetching list of servers...");
     println!("{}", "-".repeat(90));
 
@@ -122,47 +784,153 @@ async fn list_servers(cloud: &openstack::Cloud) -> Result<()> {
         .await
         .context("Failed to fetch server list")?;
 
+    let mut details: Vec<openstack::compute::Server> = vec![];
+    for server in &servers {
+        details.push(server.details().await?);
+    }
+
     println!("{:<10} | {:<40} | {:<15} | {:<20}",
              "NAME", "ID", "STATUS", "POWER");
     println!("{}", "=".repeat(90));
 
-    for server in servers.clone() {
+    match group_by {
+        None => details.iter().for_each(print_server_row),
+        Some(group_by) => {
+            let mut groups: BTreeMap<String, Vec<&openstack::compute::Server>> = BTreeMap::new();
+            for server in &details {
+                groups.entry(group_by.key(server)).or_default().push(server);
+            }
+            for (key, members) in groups {
+                println!("{} ({})", key, members.len());
+                println!("{}", "=".repeat(90));
+                members.into_iter().for_each(print_server_row);
+            }
+        },
+    }
 
-        let details = server.details().await?;
+    println!("Total servers: {}", servers.len());
+    Ok(())
+}
 
-        println!("{:<10} | {:<40} | {:<15} | {:<20?}",
-                 server.name(),
-                 server.id(),
-                 details.status().to_string(),
-                 details.power_state()
-        );
-        println!("{}", "-".repeat(90));
+/// One template line per server, for scripts
+async fn list_servers_formatted(cloud: &openstack::Cloud, template: &template::Template) -> Result<()> {
+    let servers = cloud
+        .find_servers()
+        .detailed()
+        .all()
+        .await
+        .context("Failed to fetch server list")?;
+    for server in &servers {
+        println!("{}", template.render(&server_value(server)));
+    }
+    Ok(())
+}
 
-        let addresses = details.addresses();
-        let address_strings = get_server_addresses_string(&addresses);
-        address_strings.iter().for_each(|s| println!("{:<12} {}", " ", s));
-        println!("{}", "-".repeat(90));
+/// Ansible dynamic inventory: groups status_*, az_*, flavor_* and host variables in _meta
+async fn ansible_inventory(cloud: &openstack::Cloud) -> Result<()> {
+    let servers = cloud
+        .find_servers()
+        .detailed()
+        .all()
+        .await
+        .context("Failed to fetch server list")?;
+
+    let group_name = |prefix: &str, key: String| {
+        let key: String = key
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        format!("{}_{}", prefix, key)
+    };
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut hostvars = serde_json::Map::new();
+    for server in &servers {
+        for (prefix, group_by) in [("status", GroupBy::Status), ("az", GroupBy::Az), ("flavor", GroupBy::Flavor)] {
+            groups.entry(group_name(prefix, group_by.key(server))).or_default().push(server.name().clone());
+        }
+        let mut vars = serde_json::json!({
+            "openstack_id": server.id(),
+            "openstack_status": server.status().to_string(),
+        });
+        if let Ok(ip) = address::select(server, None, None) {
+            vars["ansible_host"] = serde_json::json!(ip.to_string());
+        }
+        hostvars.insert(server.name().clone(), vars);
     }
 
-    println!("Total servers: {}", servers.len());
+    let mut inventory = serde_json::json!({
+        "_meta": { "hostvars": hostvars },
+        "all": { "children": groups.keys().collect::<Vec<_>>() },
+    });
+    for (group, hosts) in groups {
+        inventory[group] = serde_json::json!({ "hosts": hosts });
+    }
+    println!("{}", serde_json::to_string_pretty(&inventory)?);
     Ok(())
 }
 
-fn get_server_addresses_string(addresses: &HashMap<String, Vec<ServerAddress>>) -> Vec<String> {
-    let mut address_strings: Vec<String> = vec![];
-    for net in addresses {
-        let (net_name, ips) = net;
-        let mut ip_attrib: Vec<String> = vec![];
-        for ip in ips {
-            let ip_type = match ip.addr_type {
-                Some(ip_type) => ip_type.to_string(),
-                None => "None".to_string(),
-            };
-            ip_attrib.push(format!("{} - {} ", ip.addr.to_string(), ip_type));
+/// Server fields available in --format templates
+fn server_value(server: &openstack::compute::Server) -> serde_json::Value {
+    let addresses: serde_json::Map<String, serde_json::Value> = server
+        .addresses()
+        .iter()
+        .map(|(network, ips)| (network.clone(), ips.iter().map(|ip| ip.addr.to_string()).collect()))
+        .collect();
+    serde_json::json!({
+        "id": server.id(),
+        "name": server.name(),
+        "status": server.status().to_string(),
+        "power_state": format!("{:?}", server.power_state()),
+        "availability_zone": server.availability_zone(),
+        "flavor": &server.flavor().original_name,
+        "addresses": addresses,
+    })
+}
+
+/// Fleet overview: server counts per status, availability zone and flavor
+async fn servers_summary(cloud: &openstack::Cloud) -> Result<()> {
+    // One detailed listing instead of a details request per server
+    let servers = cloud
+        .find_servers()
+        .detailed()
+        .all()
+        .await
+        .context("Failed to fetch server list")?;
+
+    for group_by in [GroupBy::Status, GroupBy::Az, GroupBy::Flavor] {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for server in &servers {
+            *counts.entry(group_by.key(server)).or_default() += 1;
+        }
+        println!("{:<30} | {}", format!("{:?}", group_by).to_uppercase(), "COUNT");
+        println!("{}", "=".repeat(40));
+        for (key, count) in counts {
+            println!("{:<30} | {}", key, count);
         }
-        address_strings.push(format!("[{}] {}", net_name, ip_attrib.join(", ")));
+        println!();
+    }
+
+    println!("Total servers: {}", servers.len());
+    Ok(())
+}
+
+fn print_server_row(server: &openstack::compute::Server) {
+    println!("{:<10} | {:<40} | {:<15} | {:<20?}",
+             server.name(),
+             server.id(),
+             server.status().to_string(),
+             server.power_state()
+    );
+    if state::ServerState::of(server) == state::ServerState::SoftDeleted {
+        println!("{:<12} 🗑️ {} - unshelve restore {}", " ", restore::describe(server), server.id());
     }
-    address_strings
+    println!("{}", "-".repeat(90));
+
+    let addresses = server.addresses();
+    let address_strings = get_server_addresses_string(&addresses);
+    address_strings.iter().for_each(|s| println!("{:<12} {}", " ", s));
+    println!("{}", "-".repeat(90));
 }
 
 /// Display detailed information about a specific server
@@ -170,12 +938,21 @@ async fn server_info(cloud: &openstack::Cloud, server_identifier: &str) -> Resul
     println!("Getting information for server: {}", server_identifier);
     println!("{}", "-".repeat(80));
 
-    // Try to find server by name or ID
-    let server: openstack::compute::Server = match cloud.get_server(server_identifier).await {
-        Ok(server) => server,
+    let server = find_server(cloud, server_identifier, true).await?;
+    let groups = nova::server_groups(cloud, server.id()).await;
+    print_server_info(&server, &groups)?;
+    Ok(())
+}
+
+/// Find server by name or ID, falling back to a partial name match in the server list
+async fn find_server(cloud: &openstack::Cloud, server_identifier: &str, verbose: bool) -> Result<openstack::compute::Server> {
+    match cloud.get_server(server_identifier).await {
+        Ok(server) => Ok(server),
         Err(_) => {
             // If not found by exact match, search in the list
-            println!("Failed to get server: {}, try get identifier from server list...", server_identifier);
+            if verbose {
+                println!("Failed to get server: {}, try get identifier from server list...", server_identifier);
+            }
             let servers = cloud
                 .list_servers()
                 .await
@@ -186,29 +963,79 @@ async fn server_info(cloud: &openstack::Cloud, server_identifier: &str) -> Resul
                 .find(|s| s.name().contains(server_identifier));
 
             match found {
-                Some(server) => server.details().await?,
+                Some(server) => Ok(server.details().await?),
                 None => anyhow::bail!("Server '{}' not found", server_identifier),
             }
         }
-    };
+    }
+}
 
-    print_server_info(&server)?;
+/// Information about several servers fetched concurrently, as a table or JSON array.
+/// Fails after printing if any server was not found
+async fn servers_info(cloud: &openstack::Cloud, identifiers: &[String], json: bool, template: Option<&template::Template>) -> Result<()> {
+    let results = futures::future::join_all(identifiers.iter().map(|id| find_server(cloud, id, false))).await;
+
+    if let Some(template) = template {
+        for (identifier, result) in identifiers.iter().zip(&results) {
+            match result {
+                Ok(server) => println!("{}", template.render(&server_value(server))),
+                // Errors go to stderr to keep the output parseable
+                Err(e) => eprintln!("✗ {}: {:#}", identifier, e),
+            }
+        }
+    } else if json {
+        let items: Vec<serde_json::Value> = identifiers
+            .iter()
+            .zip(&results)
+            .map(|(identifier, result)| match result {
+                Ok(server) => serde_json::json!({
+                    "identifier": identifier,
+                    "id": server.id(),
+                    "name": server.name(),
+                    "status": server.status().to_string(),
+                    "power_state": format!("{:?}", server.power_state()),
+                    "addresses": get_server_addresses_string(server.addresses()),
+                }),
+                Err(e) => serde_json::json!({ "identifier": identifier, "error": format!("{:#}", e) }),
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&items)?);
+    } else {
+        println!("{:<20} | {:<40} | {:<18} | {:<10} | {}", "NAME", "ID", "STATUS", "POWER", "ADDRESSES");
+        println!("{}", "=".repeat(120));
+        for (identifier, result) in identifiers.iter().zip(&results) {
+            match result {
+                Ok(server) => println!("{:<20} | {:<40} | {:<18} | {:<10} | {}",
+                                       server.name(),
+                                       server.id(),
+                                       server.status().to_string(),
+                                       format!("{:?}", server.power_state()),
+                                       get_server_addresses_string(server.addresses()).join("; ")),
+                Err(e) => println!("{:<20} | ✗ {:#}", identifier, e),
+            }
+        }
+    }
+
+    let failed = results.iter().filter(|r| r.is_err()).count();
+    if failed > 0 {
+        anyhow::bail!("{} of {} server(s) not found", failed, identifiers.len());
+    }
     Ok(())
 }
 
 /// Print detailed server information
-fn print_server_info(server: &openstack::compute::Server) -> Result<()> {
+fn print_server_info(server: &openstack::compute::Server, groups: &Result<Vec<nova::ServerGroup>>) -> Result<()> {
 
     println!("{:<25} : {}", "ID", server.id());
     println!("{:<25} : {}", "Name", server.name());
     // println!("{:<25} : {}", "Status", server.status());
 
-    if server.status().to_string() == "ACTIVE" {
-        println!("{:<25} : ✅ {}", "Status", server.status());
-    } else if server.status().to_string() == "SHELVED_OFFLOADED" {
-        println!("{:<25} : ❄️ {}", "Status", server.status());
-    } else {
-        println!("{:<25} : ⚠️ {}", "Status", server.status());
+    match state::ServerState::of(server) {
+        state::ServerState::Active => println!("{:<25} : ✅ {}", "Status", server.status()),
+        state::ServerState::Shelved | state::ServerState::ShelvedOffloaded => println!("{:<25} : ❄️ {}", "Status", server.status()),
+        state::ServerState::Rescue => println!("{:<25} : 🛟 {} (unshelve unrescue to boot from its own disk)", "Status", server.status()),
+        state::ServerState::SoftDeleted => println!("{:<25} : 🗑️ {} ({})", "Status", server.status(), restore::describe(server)),
+        _ => println!("{:<25} : ⚠️ {}", "Status", server.status()),
     }
 
     println!("{:<25} : {:?}", "Power state", server.power_state());
@@ -217,12 +1044,52 @@ fn print_server_info(server: &openstack::compute::Server) -> Result<()> {
     let address_strings: Vec<String> = get_server_addresses_string(&addresses);
     address_strings.iter().for_each(|s| println!("{:<25} {} {}", "Network", ":", s));
 
+    match groups {
+        Ok(groups) if groups.is_empty() => println!("{:<25} : none", "Server groups"),
+        Ok(groups) => println!("{:<25} : {}", "Server groups", nova::describe(groups)),
+        Err(e) => println!("{:<25} : unknown ({:#})", "Server groups", e),
+    }
+
     println!("{}", "=".repeat(80));
     // println!("SERVER STATUS: {}", server.status());
 
     Ok(())
 }
 
+async fn unshelve_command(server_identifiers: Vec<String>, all_shelved: bool, shard: Option<String>, guard: bool,
+                          wait: Option<(Duration, bool)>) -> Result<()> {
+    let shard = shard.as_deref().map(shard::Shard::parse).transpose()?;
+    let cloud = init_cloud().await;
+    if all_shelved {
+        let mut identifiers = recovery::shelved_servers(&cloud).await?;
+        if let Some(shard) = shard {
+            let total = identifiers.len();
+            identifiers.retain(|id| shard.contains(id));
+            println!("Shard {}: {} of {} shelved server(s)", shard, identifiers.len(), total);
+        }
+        return recovery::unshelve_many(&cloud, &identifiers).await;
+    }
+    if server_identifiers.len() > 1 {
+        if guard || wait.is_some() {
+            anyhow::bail!("--guard and --wait work with a single server");
+        }
+        let identifiers = server_identifiers
+            .iter()
+            .map(|id| aliases::resolve(id))
+            .collect::<Result<Vec<String>>>()?;
+        return recovery::unshelve_many(&cloud, &identifiers).await;
+    }
+    let identifier = get_server_identifier(&cloud, server_identifiers.into_iter().next()).await?;
+    if guard {
+        guard::guard(&cloud, &identifier).await
+    } else if let Some((limit, bell)) = wait {
+        unshelve_manual(&cloud, &identifier).await?;
+        wait::wait_up(&cloud, &identifier, limit, ssh::ssh_port()?, bell).await
+    } else {
+        unshelve_manual(&cloud, &identifier).await
+    }
+}
+
 async fn unshelve_manual(cloud: &openstack::Cloud, server_identifier: &str) -> Result<()> {
 
     match cloud.get_server(&server_identifier).await {
@@ -236,109 +1103,15 @@ async fn unshelve_manual(cloud: &openstack::Cloud, server_identifier: &str) -> R
                 }
                 Err(e) => {
                     println!("✗ Failed to unshelve server: {}", e);
+                    ci::error(&format!("Failed to unshelve server: {}", e));
                 }
             }
         }
         Err(e) => {
             println!("✗ Failed to get server info: {}", e);
+            ci::error(&format!("Failed to get server info: {}", e));
         }
     }
 
     Ok(())
 }
-
-fn ping_server(ip: &str, timeout_secs: u64, use_dgram_socket: bool) -> bool {
-    let timeout = Duration::from_secs(timeout_secs);
-    let socket_type = if use_dgram_socket { ping::DGRAM } else { ping::RAW };
-
-    match ping::new(ip.parse().unwrap())
-        .socket_type(socket_type)
-        .timeout(timeout)
-        // .ttl(128)
-        // .seq_cnt(3)
-        .send()
-    {
-        Ok(r) => {
-            println!("[{}] {} Ping successful {:?}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), r.target, r.rtt);
-            true
-        },
-        Err(_e) => {
-            println!("[{}] {} Ping failed", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), ip);
-            false
-        },
-    }
-}
-
-// need sudo sysctl -w net.ipv4.ping_group_range="0 1000" for Ubuntu (check sysctl net.ipv4.ping_group_range | default "1 0")
-async fn start_monitoring(cloud: &openstack::Cloud, use_dgram_socket: bool) -> Result<()> {
-    // Get configuration from environment
-    let server_name = env::var("SERVER_NAME")
-        .context("SERVER_NAME not set in environment")?;
-
-    let ping_ip = env::var("PING_IP")
-        .context("PING_IP not set in environment")?;
-
-    let ping_interval_minutes: u64 = env::var("PING_INTERVAL_MINUTES")
-        .unwrap_or_else(|_| "5".to_string())
-        .parse()
-        .context("PING_INTERVAL_MINUTES must be a number")?;
-
-    let ping_timeout_secs: u64 = env::var("PING_TIMEOUT_SECONDS")
-        .unwrap_or_else(|_| "3".to_string())
-        .parse()
-        .context("PING_TIMEOUT_SECONDS must be a number")?;
-
-    println!("Starting monitoring for server '{}'", server_name);
-    println!("Ping target: {}", ping_ip);
-    println!("Check interval: {} minutes", ping_interval_minutes);
-    println!("Ping timeout: {} seconds", ping_timeout_secs);
-    println!("{}", "=".repeat(80));
-
-    // let mut interval = Duration::from_secs(ping_interval_minutes * 60);
-
-    loop {
-        let mut interval = Duration::from_secs(ping_interval_minutes * 60);
-
-        let is_ping_successful = ping_server(&ping_ip, ping_timeout_secs, use_dgram_socket);
-
-        if is_ping_successful {
-        } else {
-            println!("checking OpenStack status...");
-
-            // 2. Get server status from OpenStack
-            match cloud.get_server(&server_name).await {
-                Ok(mut server) => {
-                    let status = server.status();
-                    println!("Server status in OpenStack: {}", status);
-
-                    // 3. Check if server is shelved_offloaded
-                    if status.to_string() == "SHELVED_OFFLOADED" {
-                        println!("Server is shelved_offloaded - attempting to unshelve...");
-
-                        match server.action(openstack::compute::ServerAction::Unshelve).await {
-                            Ok(_) => {
-                                println!("✓ Unshelve command sent successfully");
-
-                                // Wait for server to become active
-                                println!("Waiting for server to become ACTIVE...");
-                                interval = Duration::from_secs(1 * 60);
-
-                            }
-                            Err(e) => {
-                                println!("✗ Failed to unshelve server: {}", e);
-                            }
-                        }
-                    } else {
-                        println!("Server status is '{}' - no action required", status);
-                    }
-                }
-                Err(e) => {
-                    println!("✗ Failed to get server info: {}", e);
-                }
-            }
-        }
-
-        // println!("Next check in {} minutes...", ping_interval_minutes);
-        sleep(interval).await
-    }
-}
\ No newline at end of file
@@ -0,0 +1,171 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use tokio::time::{Duration, Instant};
+
+use crate::config;
+
+/// Planned work from the maintenance calendar
+#[derive(Clone)]
+pub struct Window {
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    pub summary: String,
+}
+
+/// Maintenance windows from an iCal feed (MAINTENANCE_ICAL_URL), refetched every
+/// MAINTENANCE_REFRESH_MINUTES. Auto-unshelve is suppressed while a window is active.
+/// Recurring events (RRULE) are not expanded, TZID times are read as local time
+pub struct MaintenanceCalendar {
+    url: String,
+    refresh: Duration,
+    client: reqwest::Client,
+    windows: Vec<Window>,
+    fetched: Option<Instant>,
+}
+
+impl MaintenanceCalendar {
+    /// None if MAINTENANCE_ICAL_URL is not set
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(url) = config::var("MAINTENANCE_ICAL_URL").ok().filter(|u| !u.trim().is_empty()) else {
+            return Ok(None);
+        };
+        let refresh_minutes: u64 = config::var("MAINTENANCE_REFRESH_MINUTES")
+            .unwrap_or_else(|_| "15".to_string())
+            .parse()
+            .context("MAINTENANCE_REFRESH_MINUTES must be a number")?;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .context("Failed to create HTTP client")?;
+        Ok(Some(MaintenanceCalendar { url, refresh: Duration::from_secs(refresh_minutes * 60), client, windows: vec![], fetched: None }))
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Window active now, the calendar is refetched first if it's due.
+    /// A failed fetch keeps the previously loaded windows
+    pub async fn active(&mut self) -> Option<Window> {
+        if self.fetched.is_none_or(|t| t.elapsed() >= self.refresh) {
+            match self.fetch().await {
+                Ok(windows) => self.windows = windows,
+                Err(e) => println!("✗ Failed to fetch maintenance calendar: {:#}", e),
+            }
+            self.fetched = Some(Instant::now());
+        }
+        active_at(&self.windows, Local::now()).cloned()
+    }
+
+    async fn fetch(&self) -> Result<Vec<Window>> {
+        let response = self.client.get(&self.url).send().await?.error_for_status()?;
+        Ok(parse(&response.text().await?))
+    }
+}
+
+/// Window containing `now`, the end is exclusive
+fn active_at(windows: &[Window], now: DateTime<Local>) -> Option<&Window> {
+    windows.iter().find(|w| w.start <= now && now < w.end)
+}
+
+/// VEVENTs with DTSTART and DTEND (all-day events without DTEND last one day)
+fn parse(calendar: &str) -> Vec<Window> {
+    // Long lines are folded: continuation lines start with a space or tab
+    let mut lines: Vec<String> = vec![];
+    for line in calendar.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut windows = vec![];
+    let (mut start, mut end, mut all_day, mut summary) = (None, None, false, String::new());
+    for line in &lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        match name.split(';').next().unwrap_or_default() {
+            "BEGIN" if value == "VEVENT" => (start, end, all_day, summary) = (None, None, false, String::new()),
+            "DTSTART" => {
+                all_day = value.len() == 8;
+                start = parse_time(value);
+            },
+            "DTEND" => end = parse_time(value),
+            "SUMMARY" => summary = value.replace("\\,", ",").replace("\\;", ";"),
+            "END" if value == "VEVENT" => {
+                let end = end.or_else(|| start.filter(|_| all_day).map(|s| s + chrono::Duration::days(1)));
+                if let (Some(start), Some(end)) = (start, end) {
+                    windows.push(Window { start, end, summary: summary.clone() });
+                }
+            },
+            _ => {},
+        }
+    }
+    windows
+}
+
+/// 20261020T220000Z (UTC), 20261020T220000 (local) or 20261020 (local midnight)
+fn parse_time(value: &str) -> Option<DateTime<Local>> {
+    if let Some(utc) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(Utc.from_utc_datetime(&time).with_timezone(&Local));
+    }
+    let time = match NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        Ok(time) => time,
+        Err(_) => NaiveDate::parse_from_str(value, "%Y%m%d").ok()?.and_hms_opt(0, 0, 0)?,
+    };
+    time.and_local_timezone(Local).earliest()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(value: &str) -> DateTime<Local> {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap().and_local_timezone(Local).earliest().unwrap()
+    }
+
+    #[test]
+    fn events_with_folded_lines_and_all_day() {
+        let calendar = "BEGIN:VCALENDAR\r\n\
+                        BEGIN:VEVENT\r\n\
+                        DTSTART:20261020T080000\r\n\
+                        DTEND:20261020T090000\r\n\
+                        SUMMARY:Kernel update\\, hosts cmp-1\r\n  to cmp-4\r\n\
+                        END:VEVENT\r\n\
+                        BEGIN:VEVENT\r\n\
+                        DTSTART;VALUE=DATE:20261110\r\n\
+                        SUMMARY:Datacenter move\r\n\
+                        END:VEVENT\r\n\
+                        BEGIN:VEVENT\r\n\
+                        SUMMARY:No start\r\n\
+                        END:VEVENT\r\n\
+                        END:VCALENDAR\r\n";
+        let windows = parse(calendar);
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0].summary, "Kernel update, hosts cmp-1 to cmp-4");
+        assert_eq!((windows[0].start, windows[0].end), (local("2026-10-20 08:00"), local("2026-10-20 09:00")));
+        assert_eq!((windows[1].start, windows[1].end), (local("2026-11-10 00:00"), local("2026-11-11 00:00")));
+    }
+
+    #[test]
+    fn window_spanning_midnight() {
+        let windows = parse("BEGIN:VEVENT\nDTSTART:20261020T220000\nDTEND:20261021T020000\nSUMMARY:Storage\nEND:VEVENT\n");
+        assert!(active_at(&windows, local("2026-10-20 21:59")).is_none());
+        assert!(active_at(&windows, local("2026-10-20 22:00")).is_some());
+        assert!(active_at(&windows, local("2026-10-20 23:30")).is_some());
+        assert!(active_at(&windows, local("2026-10-21 01:59")).is_some());
+        assert!(active_at(&windows, local("2026-10-21 02:00")).is_none(), "end is exclusive");
+    }
+
+    #[test]
+    fn utc_and_tzid_times() {
+        let windows = parse("BEGIN:VEVENT\nDTSTART:20261020T220000Z\nDTEND;TZID=Europe/Berlin:20261021T020000\nEND:VEVENT\n");
+        // UTC converted to local time, TZID read as local time
+        assert_eq!(windows[0].start, Utc.with_ymd_and_hms(2026, 10, 20, 22, 0, 0).unwrap().with_timezone(&Local));
+        assert_eq!(windows[0].end, local("2026-10-21 02:00"));
+        assert_eq!(parse_time("20261020T220000Z").map(|t| t.with_timezone(&Utc).to_rfc3339()).as_deref(), Some("2026-10-20T22:00:00+00:00"));
+        assert!(parse_time("2026-10-20").is_none());
+    }
+}
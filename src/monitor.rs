@@ -0,0 +1,1680 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use anyhow::{Context, Result};
+use tokio::sync::{broadcast, Notify};
+use tokio::time::{sleep, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::actions::ActionStore;
+use crate::aliases;
+use crate::anomaly;
+use crate::backoff::UnshelveBackoff;
+use crate::bundle;
+use crate::chaos;
+use crate::config;
+use crate::consul::Consul;
+use crate::drift::DriftWatch;
+use crate::etcd::{self, ConfigWatch};
+use crate::federation;
+use crate::history::{self, EventStore};
+use crate::labels;
+use crate::killswitch;
+use crate::fleet::{self, SharedState};
+use crate::maintenance::MaintenanceCalendar;
+use crate::notify::{self, Event, Notifier, Severity};
+use crate::nova;
+use crate::pins::PinStore;
+use crate::pipeline::{Pipeline, Step, Target};
+use crate::precondition::{self, Precondition};
+use crate::privileges;
+use crate::redact;
+use crate::restore;
+use crate::scope;
+use crate::probe::{self, ProbeError, Prober};
+use crate::ratelimit::{self, RateLimit};
+use crate::remote_write::{RemoteWriter, TimeSeries};
+use crate::rtt::RttHistory;
+use crate::signals::{Scoring, Signal, TcpCheck};
+use crate::snapshot;
+use crate::state::ServerState;
+use crate::target;
+use crate::tasks::TaskGroup;
+use crate::webhook;
+use crate::volume;
+use crate::warmup;
+#[cfg(feature = "amqp")]
+use crate::amqp;
+
+/// Background tasks get this long to finish after shutdown
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+/// A federated token is given up this long before it expires
+const TOKEN_RENEW_MARGIN: Duration = Duration::from_secs(10 * 60);
+
+/// Shelve frequency anomaly is announced at most this often
+const ANOMALY_NOTIFY_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Settings that drop privileges another monitor still needs to create its sockets
+const SINGLE_SERVER_KEYS: [&str; 1] = ["RUN_AS_USER"];
+
+/// Events kept for a slow subscriber of `Monitor`
+const EVENT_BUFFER: usize = 256;
+
+/// Monitors of one daemon start one at a time, so startup summaries and prompts don't interleave
+static STARTUP: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Stop of the whole daemon to be restarted by the service manager (federated token expiry,
+/// config change in etcd), as opposed to a failure of one server's monitor
+#[derive(Debug)]
+struct Restart(String);
+
+impl std::fmt::Display for Restart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for Restart {}
+
+/// Server watched by one monitor. None fields come from SERVER_NAME, PING_IP and the socket type of the daemon
+#[derive(Clone, Debug, Default)]
+pub struct Watch {
+    pub server: Option<String>,
+    pub ping_ip: Option<IpAddr>,
+    pub use_dgram_socket: Option<bool>,
+    /// Labels of the server on top of SERVER_LABELS
+    pub labels: BTreeMap<String, String>,
+}
+
+impl Watch {
+    /// Servers to watch from SERVERS or `unshelved --servers`: comma separated NAME=IP, or just NAME
+    /// with CHECK_MODE=status-only. NAME=IP/raw or NAME=IP/dgram sets the socket type of the server.
+    /// Names may be UUIDs or aliases. Labels of a server come from SERVER_LABELS_<NAME> (see `labels::key`)
+    pub fn parse_list(spec: &str) -> Result<Vec<Watch>> {
+        let mut watches: Vec<Watch> = vec![];
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (server, ip, use_dgram_socket) = match entry.split_once('=') {
+                Some((server, target)) => {
+                    let (ip, socket_type) = match target.split_once('/') {
+                        Some((ip, socket_type)) => (ip, Some(socket_type)),
+                        None => (target, None),
+                    };
+                    let ip: IpAddr = ip.trim().parse().context(format!("Invalid IP address in SERVERS entry '{}'", entry))?;
+                    let use_dgram_socket = socket_type
+                        .map(probe::parse_socket_type)
+                        .transpose()
+                        .context(format!("Invalid SERVERS entry '{}'", entry))?;
+                    (server.trim(), Some(ip), use_dgram_socket)
+                },
+                None => (entry, None, None),
+            };
+            let key = labels::key(server);
+            let labels = labels::parse(&key, &config::var(&key).unwrap_or_default())?;
+            let server = aliases::resolve(server)?;
+            if watches.iter().any(|w| w.server.as_deref() == Some(server.as_str())) {
+                anyhow::bail!("Server '{}' is listed twice in SERVERS", server);
+            }
+            watches.push(Watch { server: Some(server), ping_ip: ip, use_dgram_socket, labels });
+        }
+        if watches.is_empty() {
+            anyhow::bail!("SERVERS is empty");
+        }
+        Ok(watches)
+    }
+
+    /// Server of the watch, SERVER_NAME (alias resolved) if the watch doesn't name one
+    fn server_name(&self) -> Result<String> {
+        match &self.server {
+            Some(server) => Ok(server.clone()),
+            None => aliases::resolve(&config::var("SERVER_NAME").context("SERVER_NAME not set in environment")?),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match (&self.server, self.ping_ip) {
+            (Some(server), Some(ip)) => match self.use_dgram_socket {
+                Some(dgram) => format!("{} ({}, {})", server, ip, if dgram { "dgram" } else { "raw" }),
+                None => format!("{} ({})", server, ip),
+            },
+            (Some(server), None) => server.clone(),
+            (None, _) => "SERVER_NAME".to_string(),
+        }
+    }
+}
+
+/// How the monitor detects that the server is down
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CheckMode {
+    /// ICMP ping, OpenStack status is checked only when ping fails
+    Ping,
+    /// No network path to the server - poll OpenStack status only
+    StatusOnly,
+}
+
+impl CheckMode {
+    fn from_env() -> Result<Self> {
+        match config::var("CHECK_MODE").unwrap_or_else(|_| "ping".to_string()).to_lowercase().as_str() {
+            "ping" => Ok(CheckMode::Ping),
+            "status-only" => Ok(CheckMode::StatusOnly),
+            other => anyhow::bail!("Invalid CHECK_MODE: '{}'. Allowed values: 'ping', 'status-only'", other),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CheckMode::Ping => "ping",
+            CheckMode::StatusOnly => "status-only",
+        }
+    }
+}
+
+/// State shared by the monitors of one daemon: every server's action records and pins go to one
+/// store, so monitors don't overwrite each other's files, and the event history is opened once.
+/// One webhook receiver and one snapshot cover all servers
+struct Daemon {
+    actions: ActionStore,
+    pins: PinStore,
+    /// STATE_URL - claim of the server and action locks shared with other instances
+    shared: Option<Arc<dyn SharedState>>,
+    history: Option<Arc<dyn EventStore>>,
+    /// Socket type of servers without their own
+    use_dgram_socket: bool,
+    /// Signals of the monitors by server, notified by the webhook receiver and AMQP consumers
+    signals: HashMap<String, Arc<Notify>>,
+    /// SNAPSHOT_FILE - current state as JSON for external scripts and dashboards
+    snapshot_file: Option<String>,
+    /// Snapshot served on the control API /status, None without WEBHOOK_LISTEN
+    control_status: Option<Arc<Mutex<serde_json::Value>>>,
+    /// Last state of every server in the snapshot, by name
+    snapshot_servers: Mutex<BTreeMap<String, serde_json::Value>>,
+    /// One set of channels for all monitors, so rate limits cover the whole daemon
+    notifier: Arc<Notifier>,
+}
+
+impl Daemon {
+    /// Open the stores and start the webhook receiver for the servers in `tasks`
+    async fn start(servers: &[String], use_dgram_socket: bool, events: broadcast::Sender<Event>, tasks: &mut TaskGroup) -> Result<Self> {
+        let shared = fleet::from_env()?;
+        let signals: HashMap<String, Arc<Notify>> = servers.iter().map(|name| (name.clone(), Arc::new(Notify::new()))).collect();
+        let control_status = Arc::new(Mutex::new(serde_json::json!({ "instance": notify::hostname(), "servers": [] })));
+        let receivers = servers.iter().map(|name| (name.clone(), signals[name].clone())).collect();
+        let webhook_enabled = webhook::start(receivers, control_status.clone(), tasks).await?;
+        let history = history::from_env()?;
+        let notifier = Notifier::with_history(history.clone())?.with_events(events);
+        if config::var("NOTIFY_TEST_ON_START").map(|v| v.eq_ignore_ascii_case("true")).unwrap_or(false) {
+            notifier.test(None).await.context("Notification channels verification failed")?;
+        }
+        Ok(Daemon {
+            actions: ActionStore::load(shared.clone())?,
+            pins: PinStore::load()?,
+            shared,
+            history,
+            use_dgram_socket,
+            signals,
+            snapshot_file: config::var("SNAPSHOT_FILE").ok().filter(|p| !p.trim().is_empty()),
+            control_status: webhook_enabled.then_some(control_status),
+            snapshot_servers: Mutex::new(BTreeMap::new()),
+            notifier: Arc::new(notifier),
+        })
+    }
+
+    fn signal(&self, server: &str) -> Arc<Notify> {
+        self.signals.get(server).cloned().unwrap_or_default()
+    }
+
+    fn keeps_snapshot(&self) -> bool {
+        self.snapshot_file.is_some() || self.control_status.is_some()
+    }
+
+    /// Replace the state of the server in SNAPSHOT_FILE and on the control API, next to the other servers
+    fn update_snapshot(&self, server: &str, state: &serde_json::Value) {
+        if !self.keeps_snapshot() {
+            return;
+        }
+        let Ok(mut servers) = self.snapshot_servers.lock() else {
+            return;
+        };
+        servers.insert(server.to_string(), state.clone());
+        let snapshot = serde_json::json!({
+            "updated": chrono::Local::now().to_rfc3339(),
+            "servers": servers.values().collect::<Vec<_>>(),
+        });
+        if let Some(path) = &self.snapshot_file {
+            if let Err(e) = snapshot::write_atomic(path, &snapshot) {
+                println!("✗ {:#}", e);
+            }
+        }
+        if let Some(status) = &self.control_status {
+            if let Ok(mut status) = status.lock() {
+                let mut snapshot = snapshot;
+                snapshot["instance"] = notify::hostname().into();
+                *status = snapshot;
+            }
+        }
+    }
+}
+
+struct ServerMonitor<'a> {
+    cloud: &'a openstack::Cloud,
+    daemon: &'a Daemon,
+    server_name: String,
+    /// UUID of the monitored server, None until resolved
+    server_id: Option<String>,
+    /// Prober and target, None in status-only mode
+    ping: Option<(Prober, IpAddr)>,
+    interval: Duration,
+    notifier: Arc<Notifier>,
+    backoff: UnshelveBackoff,
+    rtt_history: RttHistory,
+    remote_writer: Option<RemoteWriter>,
+    external_signal: Option<Arc<Notify>>,
+    external_signal_received: bool,
+    recreated_notified: bool,
+    /// Error of the last check that failed before a verdict, announced once
+    check_error: Option<String>,
+    tcp_check: Option<TcpCheck>,
+    scoring: Option<Scoring>,
+    /// Signal breakdown of the last "down" verdict, added to notifications
+    last_verdict: Option<String>,
+    /// Last check results for the incident bundle
+    recent_checks: VecDeque<String>,
+    recent_checks_size: usize,
+    bundle_dir: Option<String>,
+    /// Diagnostic bundle of the current incident, added to notifications
+    incident_bundle: Option<PathBuf>,
+    /// Stop monitoring at this moment (unshelved --for / --until)
+    deadline: Option<Instant>,
+    /// STATE_URL - claim of the server and action locks shared with other instances
+    shared: Option<Arc<dyn SharedState>>,
+    /// Current incident, set on the first unshelve attempt
+    incident_id: Option<String>,
+    rate_limit: RateLimit,
+    /// Result of the last check: reachable, OpenStack status
+    healthy: Option<bool>,
+    last_status: Option<String>,
+    last_check: Option<chrono::DateTime<chrono::Local>>,
+    /// DRIFT_SELECTOR - alert when matching servers are deleted or created
+    drift: Option<DriftWatch>,
+    /// MAINTENANCE_ICAL_URL - no auto-unshelve during planned work
+    maintenance: Option<MaintenanceCalendar>,
+    /// Start of the maintenance window already announced
+    maintenance_notified: Option<chrono::DateTime<chrono::Local>>,
+    /// Last known placement of the server, from startup or the last recovery
+    placement: Option<nova::Placement>,
+    /// Placement when unshelve was sent, compared after recovery
+    placement_before_unshelve: Option<nova::Placement>,
+    /// After unshelve the server is ACTIVE before its services are up -
+    /// failed checks don't count for BOOT_GRACE_MINUTES
+    boot_grace: Duration,
+    grace_until: Option<Instant>,
+    /// Server was deleted or renamed - checks stop until restart with updated config
+    gone: bool,
+    /// RECOVERY_PIPELINE - steps after unshelve
+    pipeline: Pipeline,
+    /// Kill switch already announced
+    actions_disabled_notified: bool,
+    /// Reason of the last precondition failure, announced once per distinct reason
+    precondition_failure: Option<String>,
+    /// SOFT_DELETED already announced, reset once the server has another status
+    soft_deleted_notified: bool,
+    /// Root volume UUID of a boot-from-volume server, verified after unshelve
+    root_volume: Option<String>,
+    /// Root volume failure already announced in this incident
+    root_volume_alerted: bool,
+    /// Last shelve frequency anomaly notification
+    anomaly_notified: Option<Instant>,
+    /// CONSUL_URL - service with a TTL check updated after every check
+    consul: Option<Consul>,
+    /// ETCD_CONFIG_PREFIX - config changes stop the daemon to be restarted with them
+    config_watch: Option<ConfigWatch>,
+    /// ETCD_LOCK - this instance of an HA pair holds the server
+    lock: Option<etcd::Lock>,
+    /// Cancelled on SIGTERM/SIGINT - checks and waits stop
+    shutdown: CancellationToken,
+}
+
+/// Monitoring time limit from unshelved --for <DURATION> or --until <HH:MM>
+pub fn run_limit(run_for: Option<&str>, until: Option<&str>) -> Result<Option<Duration>> {
+    if let Some(value) = run_for {
+        return parse_duration(value).map(Some);
+    }
+    let Some(value) = until else {
+        return Ok(None);
+    };
+    let time = chrono::NaiveTime::parse_from_str(value.trim(), "%H:%M")
+        .context(format!("--until must be a local time HH:MM, got '{}'", value))?;
+    let now = chrono::Local::now();
+    let mut stop = now.date_naive().and_time(time);
+    if stop <= now.naive_local() {
+        stop += chrono::Duration::days(1);
+    }
+    let remaining = (stop - now.naive_local()).to_std().context("Invalid --until time")?;
+    Ok(Some(remaining))
+}
+
+/// Duration like 8h, 90m, 45s, 1d or combined 1h30m
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let mut total = 0u64;
+    let mut number = String::new();
+    for c in value.trim().chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            _ => anyhow::bail!("Invalid duration '{}': unknown unit '{}'. Use s, m, h or d, e.g. 8h or 1h30m", value, c),
+        };
+        let n: u64 = number.parse().context(format!("Invalid duration '{}': number expected before '{}'", value, c))?;
+        total += n * unit;
+        number.clear();
+    }
+    if !number.is_empty() || total == 0 {
+        anyhow::bail!("Invalid duration '{}': use s, m, h or d, e.g. 8h or 1h30m", value);
+    }
+    Ok(Duration::from_secs(total))
+}
+
+// need sudo sysctl -w net.ipv4.ping_group_range="0 1000" for Ubuntu (check sysctl net.ipv4.ping_group_range | default "1 0")
+/// Monitor until `shutdown` is cancelled, the run time limit or an error.
+/// Background tasks are stopped before returning in every case
+pub async fn start_monitoring(cloud: &openstack::Cloud, watch: &Watch, use_dgram_socket: bool, run_limit: Option<Duration>,
+                              config_watch: Option<ConfigWatch>, shutdown: CancellationToken) -> Result<()> {
+    Monitor::new(vec![watch.clone()], use_dgram_socket, run_limit).run(cloud, config_watch, shutdown).await
+}
+
+/// Monitor of one server of the daemon with its own background tasks
+async fn start_one(cloud: &openstack::Cloud, daemon: &Daemon, watch: &Watch, server_name: &str, run_limit: Option<Duration>,
+                   config_watch: Option<ConfigWatch>, shutdown: CancellationToken) -> Result<()> {
+    let mut tasks = TaskGroup::new(shutdown);
+    let result = run_monitor(cloud, daemon, watch, server_name, run_limit, config_watch, &mut tasks).await;
+    tasks.shutdown(SHUTDOWN_GRACE).await;
+    result
+}
+
+/// Monitoring of several servers in this process, each with its own checks, backoff and incidents. Other settings,
+/// the stores, the notification channels, the webhook receiver and the snapshot of the `Daemon` are shared.
+/// A monitor that fails is announced and the others keep running. A config change in etcd or an expiring
+/// federated token stops all of them, the service manager restarts the daemon.
+/// Applications embedding the library subscribe to its events before running it
+pub struct Monitor {
+    watches: Vec<Watch>,
+    use_dgram_socket: bool,
+    run_limit: Option<Duration>,
+    events: broadcast::Sender<Event>,
+}
+
+impl Monitor {
+    pub fn new(watches: Vec<Watch>, use_dgram_socket: bool, run_limit: Option<Duration>) -> Self {
+        Monitor { watches, use_dgram_socket, run_limit, events: broadcast::channel(EVENT_BUFFER).0 }
+    }
+
+    /// Events of all servers: redacted, with labels and incident. Independent of the notification channels,
+    /// their minimum severity, silences and rate limits don't apply (see `Notifier::with_events`).
+    /// A receiver more than EVENT_BUFFER events behind gets `RecvError::Lagged` and skips the oldest ones
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// Monitor until `shutdown` is cancelled, the run time limit or an error
+    pub async fn run(&self, cloud: &openstack::Cloud, config_watch: Option<ConfigWatch>, shutdown: CancellationToken) -> Result<()> {
+        let watches = &self.watches;
+        if watches.len() > 1 {
+            let single: Vec<&str> = SINGLE_SERVER_KEYS
+                .into_iter()
+                .filter(|key| config::var(key).is_ok_and(|v| !v.trim().is_empty()))
+                .collect();
+            if !single.is_empty() {
+                anyhow::bail!("{} can't be used with several SERVERS - run one unshelved per server for them", single.join(", "));
+            }
+            println!("Monitoring {} servers: {}", watches.len(), watches.iter().map(Watch::describe).collect::<Vec<_>>().join(", "));
+        }
+        let names: Vec<String> = watches.iter().map(Watch::server_name).collect::<Result<_>>()?;
+
+        // The receiver outlives the monitors and stops with them
+        let mut tasks = TaskGroup::new(shutdown.child_token());
+        let result = match Daemon::start(&names, self.use_dgram_socket, self.events.clone(), &mut tasks).await {
+            Ok(daemon) => run_all(cloud, &daemon, watches, &names, self.run_limit, config_watch, shutdown).await,
+            Err(e) => Err(e),
+        };
+        tasks.shutdown(SHUTDOWN_GRACE).await;
+        result
+    }
+}
+
+async fn run_all(cloud: &openstack::Cloud, daemon: &Daemon, watches: &[Watch], names: &[String], run_limit: Option<Duration>,
+                 config_watch: Option<ConfigWatch>, shutdown: CancellationToken) -> Result<()> {
+    if let ([watch], [name]) = (watches, names) {
+        return start_one(cloud, daemon, watch, name, run_limit, config_watch, shutdown).await;
+    }
+    let stop = shutdown.child_token();
+    // The first monitor watches etcd config, its exit on changes restarts all of them
+    let mut config_watch = config_watch;
+    let monitors = watches.iter().zip(names).map(|(watch, name)| {
+        let stop = stop.clone();
+        let config_watch = config_watch.take();
+        async move {
+            let result = start_one(cloud, daemon, watch, name, run_limit, config_watch, stop.child_token())
+                .await
+                .context(format!("Monitoring of '{}' failed", watch.describe()));
+            match &result {
+                Err(e) if e.downcast_ref::<Restart>().is_some() => stop.cancel(),
+                Err(e) => daemon.notifier.event(Event::new(Severity::Critical, "monitoring_failed", name,
+                                                           format!("🚨 {:#}. Other servers are still monitored, restart the daemon to monitor '{}' again",
+                                                                   e, name))).await,
+                Ok(()) => {},
+            }
+            result
+        }
+    });
+    let mut failed = vec![];
+    for result in futures::future::join_all(monitors).await {
+        match result {
+            Err(e) if e.downcast_ref::<Restart>().is_some() => return Err(e),
+            Err(e) => failed.push(e),
+            Ok(()) => {},
+        }
+    }
+    // Failures were announced as they happened, the daemon fails when none of its monitors is left
+    if failed.len() == watches.len() {
+        return Err(failed.remove(0));
+    }
+    Ok(())
+}
+
+async fn run_monitor(cloud: &openstack::Cloud, daemon: &Daemon, watch: &Watch, server_name: &str, run_limit: Option<Duration>,
+                     config_watch: Option<ConfigWatch>, tasks: &mut TaskGroup) -> Result<()> {
+    let server_name = server_name.to_string();
+    labels::set(&server_name, &watch.labels)?;
+    // HA pair: the standby waits here until the active instance is gone
+    let lock = etcd::Lock::acquire(&server_name, tasks).await?;
+    if tasks.token().is_cancelled() {
+        return Ok(());
+    }
+    let startup = STARTUP.lock().await;
+
+    let check_mode = CheckMode::from_env()?;
+
+    let ping_interval_minutes: u64 = config::var("PING_INTERVAL_MINUTES")
+        .unwrap_or_else(|_| "5".to_string())
+        .parse()
+        .context("PING_INTERVAL_MINUTES must be a number")?;
+
+    let ping_timeout_secs: u64 = config::var("PING_TIMEOUT_SECONDS")
+        .unwrap_or_else(|_| "3".to_string())
+        .parse()
+        .context("PING_TIMEOUT_SECONDS must be a number")?;
+
+    println!("Starting monitoring for server '{}'", server_name);
+
+    let ping_ip: Option<IpAddr> = match check_mode {
+        CheckMode::Ping => {
+            let ip: IpAddr = match (watch.ping_ip, &watch.server) {
+                (Some(ip), _) => ip,
+                (None, Some(server)) => anyhow::bail!("No IP address for '{}' in SERVERS (NAME=IP) - required with CHECK_MODE=ping", server),
+                (None, None) => config::var("PING_IP")
+                    .context("PING_IP not set in environment")?
+                    .parse()
+                    .context("PING_IP must be an IP address")?,
+            };
+            Some(ip)
+        },
+        CheckMode::StatusOnly => None,
+    };
+
+    let notifier = daemon.notifier.clone();
+
+    // Several servers with the same name - refuse to guess which one to unshelve
+    let servers = cloud
+        .list_servers()
+        .await
+        .context("Failed to fetch server list")?;
+    let duplicates: Vec<String> = servers
+        .iter()
+        .filter(|s| s.name() == &server_name)
+        .map(|s| s.id().clone())
+        .collect();
+    if duplicates.len() > 1 {
+        anyhow::bail!("{} servers are named '{}' ({}). Set SERVER_NAME to the UUID of the server to monitor",
+                      duplicates.len(), server_name, duplicates.join(", "));
+    }
+
+    // Pin UUID of the server monitored by name to detect recreated servers
+    let server_id = match cloud.get_server(&server_name).await {
+        Ok(server) => {
+            if !daemon.pins.verify(&server_name, server.id(), true)? {
+                anyhow::bail!("Server '{}' was recreated. Set ALLOW_SERVER_RECREATE=true or remove its pin to monitor the new instance", server_name);
+            }
+            Some(server.id().clone())
+        },
+        Err(e) => {
+            println!("⚠️ Failed to resolve server UUID on startup: {}", e);
+            None
+        },
+    };
+
+    // A PING_IP that always answers would hide every shelve
+    if let Some(ip) = ping_ip {
+        target::validate(cloud, ip, server_id.as_deref()).await?;
+    }
+
+    // Read-only credentials would fail the first unshelve - find out now and only observe
+    let credential_scope = match &server_id {
+        Some(id) => match scope::check(cloud, &server_name, id).await {
+            Ok(scope) => scope,
+            Err(e) => format!("unknown ({:#})", e),
+        },
+        None => "not checked (server not found)".to_string(),
+    };
+    if let Some(reason) = killswitch::read_only(&server_name) {
+        notifier.event(Event::new(Severity::Warning, "credentials_read_only", &server_name,
+                                  format!("⚠️ Monitoring of '{}' runs observe-only: the {}. Fix the role or application credential and restart",
+                                          server_name, reason))).await;
+    }
+
+    // Several instances share one database - each watches its own server
+    let shared = daemon.shared.clone();
+    if let Some(shared) = &shared {
+        let Some(id) = &server_id else {
+            anyhow::bail!("Server UUID is required to claim the server in shared state (STATE_URL)");
+        };
+        shared.claim(id, &server_name)?;
+        println!("✓ Server claimed by instance '{}'", shared.instance());
+    }
+
+    // Unshelve submitted before a restart (or by another instance) - wait for it instead of submitting again
+    let in_flight = match server_id.as_deref() {
+        Some(id) => daemon.actions.in_flight(id, "unshelve")?,
+        None => None,
+    };
+    let incident_id = in_flight
+        .map(|record| {
+            println!("Resuming incident {}: unshelve submitted at {}", record.incident, record.submitted.format("%Y-%m-%d %H:%M:%S"));
+            record.incident
+        });
+    notifier.set_incident(&server_name, incident_id.clone());
+
+    let ping = match ping_ip {
+        Some(ip) => {
+            let use_dgram_socket = watch.use_dgram_socket.unwrap_or(daemon.use_dgram_socket);
+            let prober = Prober::self_test(use_dgram_socket, ip.is_ipv6(), Duration::from_secs(ping_timeout_secs)).await?;
+            Some((prober, ip))
+        },
+        None => None,
+    };
+    // Before privileges are dropped - entering TCP_CHECK_NETNS needs them
+    let tcp_check = match ping_ip {
+        Some(ip) => TcpCheck::from_env(ip, Duration::from_secs(ping_timeout_secs))?,
+        None => None,
+    };
+    privileges::drop_privileges()?;
+
+    let rtt_history_size: usize = config::var("RTT_HISTORY_SIZE")
+        .unwrap_or_else(|_| "30".to_string())
+        .parse()
+        .context("RTT_HISTORY_SIZE must be a number")?;
+
+    let remote_writer = RemoteWriter::from_env()?;
+
+    // AODH alarms / Nova notifications wake the monitor up before the next check
+    let signal = daemon.signal(&server_name);
+    let webhook_enabled = daemon.control_status.is_some();
+    #[cfg(feature = "amqp")]
+    let amqp_enabled = amqp::start(&server_name, signal.clone(), tasks).await?;
+    #[cfg(not(feature = "amqp"))]
+    let amqp_enabled = false;
+    let external_signal = if webhook_enabled || amqp_enabled { Some(signal) } else { None };
+
+    let scoring = Scoring::from_env()?;
+
+    let bundle_dir = bundle::bundle_dir();
+    let drift = DriftWatch::from_env()?;
+    let maintenance = MaintenanceCalendar::from_env()?;
+    let recent_checks_size: usize = config::var("INCIDENT_BUNDLE_CHECKS")
+        .unwrap_or_else(|_| "20".to_string())
+        .parse()
+        .context("INCIDENT_BUNDLE_CHECKS must be a number")?;
+
+    let backoff = UnshelveBackoff::from_env()?;
+    let boot_grace_minutes: u64 = config::var("BOOT_GRACE_MINUTES")
+        .unwrap_or_else(|_| "3".to_string())
+        .parse()
+        .context("BOOT_GRACE_MINUTES must be a number")?;
+    let pipeline = Pipeline::from_env()?;
+    // Boot-from-volume: the root volume has to be reattached on unshelve
+    let root_volume = match &server_id {
+        Some(id) => match volume::root_volume(cloud, id).await {
+            Ok(root) => root.map(|v| v.id().clone()),
+            Err(e) => {
+                println!("⚠️ Failed to detect boot volume: {:#}", e);
+                None
+            },
+        },
+        None => None,
+    };
+    // Zone and host before a shelve, to see where unshelve puts the server
+    let placement = match &server_id {
+        Some(id) => match nova::server(cloud, id).await {
+            Ok(details) => Some(details.placement()),
+            Err(e) => {
+                println!("⚠️ Failed to get server placement: {:#}", e);
+                None
+            },
+        },
+        None => None,
+    };
+    let consul = Consul::register(&server_name, ping_ip, Duration::from_secs(ping_interval_minutes * 60)).await?;
+
+    // What was actually loaded, to compare with what was intended
+    let disabled = || "disabled".to_string();
+    let channel_names = notifier.channel_names();
+    let summary: Vec<(&str, String)> = vec![
+        ("Server", server_name.clone()),
+        ("Labels", Some(labels::describe(&labels::get(&server_name))).filter(|l| !l.is_empty()).unwrap_or_else(disabled)),
+        ("Check mode", match ping_ip {
+            Some(ip) => format!("ping {} (timeout {}s, routing {})", ip, ping_timeout_secs,
+                                ping.as_ref().map(|(prober, _)| prober.routing().describe()).unwrap_or_default()),
+            None => "status-only (OpenStack API polling, no ping)".to_string(),
+        }),
+        ("Check interval", format!("{} minutes", ping_interval_minutes)),
+        ("TCP check", tcp_check.as_ref().map(|t| format!("{} (routing {})", t.addr(), t.routing().describe())).unwrap_or_else(disabled)),
+        ("Signal weights", scoring.as_ref().map(|s| s.describe()).unwrap_or_else(disabled)),
+        ("External signals", match (webhook_enabled, amqp_enabled) {
+            (true, true) => "webhook, amqp".to_string(),
+            (true, false) => "webhook".to_string(),
+            (false, true) => "amqp".to_string(),
+            (false, false) => disabled(),
+        }),
+        ("Unshelve backoff", backoff.schedule_string()),
+        ("Automatic actions", killswitch::describe(&server_name)),
+        ("Credential scope", credential_scope),
+        ("Recovery pipeline", pipeline.describe()),
+        ("Boot volume", root_volume.clone().unwrap_or_else(|| "none (boots from image)".to_string())),
+        ("Placement", placement.as_ref().map(nova::Placement::describe).unwrap_or_else(|| "unknown".to_string())),
+        ("Precondition", precondition::describe().unwrap_or_else(|| "none".to_string())),
+        ("Warmup", warmup::describe().unwrap_or_else(|| "none".to_string())),
+        ("Notifications", if channel_names.is_empty() { disabled() } else { channel_names.join(", ") }),
+        ("Event history", notifier.history().unwrap_or_else(disabled)),
+        ("Redis output", notifier.redis().unwrap_or_else(disabled)),
+        ("SNMP traps", notifier.snmp().unwrap_or_else(disabled)),
+        ("Alertmanager", notifier.alertmanager().unwrap_or_else(disabled)),
+        ("Consul service", consul.as_ref().map(|c| c.service_id().to_string()).unwrap_or_else(disabled)),
+        ("etcd config", config_watch.as_ref().map(|w| w.prefix().to_string()).unwrap_or_else(disabled)),
+        ("etcd lock", lock.as_ref().map(|l| l.key().to_string()).unwrap_or_else(disabled)),
+        ("Remote-write", remote_writer.as_ref().map(|w| w.url().to_string()).unwrap_or_else(disabled)),
+        ("Incident bundles", bundle_dir.clone().unwrap_or_else(disabled)),
+        ("Shared state", shared.as_ref().map(|s| s.describe()).unwrap_or_else(disabled)),
+        ("Maintenance", maintenance.as_ref().map(|m| m.url().to_string()).unwrap_or_else(disabled)),
+        ("Drift watch", drift.as_ref().map(|d| d.selector().to_string()).unwrap_or_else(disabled)),
+        ("Snapshot file", daemon.snapshot_file.clone().unwrap_or_else(disabled)),
+        ("Run time", match run_limit {
+            Some(limit) => format!("stop at {}", (chrono::Local::now() + limit).format("%Y-%m-%d %H:%M")),
+            None => "unlimited".to_string(),
+        }),
+    ];
+    let summary: Vec<String> = summary
+        .into_iter()
+        .map(|(key, value)| format!("{:<18}: {}", key, redact::redact(&value)))
+        .collect();
+    println!("{}", "=".repeat(80));
+    summary.iter().for_each(|line| println!("{}", line));
+    println!("{}", "=".repeat(80));
+    drop(startup);
+    notifier.event(Event::new(Severity::Debug, "config_loaded", &server_name,
+                              format!("Effective configuration:\n{}", summary.join("\n")))).await;
+
+    let mut monitor = ServerMonitor {
+        cloud,
+        daemon,
+        server_name,
+        server_id,
+        ping,
+        interval: Duration::from_secs(ping_interval_minutes * 60),
+        notifier,
+        backoff,
+        rtt_history: RttHistory::new(rtt_history_size),
+        remote_writer,
+        external_signal,
+        external_signal_received: false,
+        recreated_notified: false,
+        check_error: None,
+        tcp_check,
+        scoring,
+        last_verdict: None,
+        recent_checks: VecDeque::with_capacity(recent_checks_size),
+        recent_checks_size,
+        bundle_dir,
+        incident_bundle: None,
+        deadline: run_limit.map(|limit| Instant::now() + limit),
+        shared,
+        incident_id,
+        rate_limit: RateLimit::default(),
+        healthy: None,
+        last_status: None,
+        last_check: None,
+        drift,
+        maintenance,
+        maintenance_notified: None,
+        placement,
+        placement_before_unshelve: None,
+        boot_grace: Duration::from_secs(boot_grace_minutes * 60),
+        grace_until: None,
+        gone: false,
+        pipeline,
+        actions_disabled_notified: false,
+        precondition_failure: None,
+        soft_deleted_notified: false,
+        root_volume,
+        root_volume_alerted: false,
+        anomaly_notified: None,
+        consul,
+        config_watch,
+        lock,
+        shutdown: tasks.token(),
+    };
+    monitor.run().await
+}
+
+impl ServerMonitor<'_> {
+    async fn run(&mut self) -> Result<()> {
+        let shutdown = self.shutdown.clone();
+        let reason = loop {
+            if let Some(expires) = federation::expires() {
+                if chrono::Local::now() + TOKEN_RENEW_MARGIN >= expires {
+                    self.notifier.event(Event::new(Severity::Info, "token_expiring", &self.server_name,
+                                                   format!("Federated OpenStack token of '{}' expires at {} - restarting to log in again",
+                                                           self.server_name, expires.format("%H:%M")))).await;
+                    // The identity provider is only asked at startup, the service manager restarts the daemon
+                    return Err(Restart(format!("Federated token expires at {} - exiting to be restarted with a new one",
+                                               expires.format("%Y-%m-%d %H:%M:%S"))).into());
+                }
+            }
+            if let Some(lock) = &self.lock {
+                if !lock.is_held() {
+                    anyhow::bail!("Lost etcd lock {} - the standby instance may act on the server now, stopping", lock.key());
+                }
+            }
+            // A check cancelled mid-flight is safe: a submitted unshelve is recorded
+            // in the action store and resumed after restart
+            let mut interval = tokio::select! {
+                result = self.check() => match result {
+                    Ok(interval) => {
+                        self.check_error = None;
+                        interval
+                    },
+                    Err(e) => {
+                        self.check_failed(e).await;
+                        self.interval
+                    },
+                },
+                _ = shutdown.cancelled() => break "shutdown requested",
+            };
+            self.last_check = Some(chrono::Local::now());
+            self.write_snapshot(interval).await;
+            self.report_consul().await;
+            if let Some(watch) = &self.config_watch {
+                let changed = watch.changed().await;
+                if !changed.is_empty() {
+                    self.notifier.event(Event::new(Severity::Info, "config_changed", &self.server_name,
+                                                   format!("Config of '{}' changed in etcd ({}) - restarting to apply it", self.server_name, changed.join(", ")))).await;
+                    // Config is read once at startup, the service manager restarts the daemon with the new one
+                    return Err(Restart("Config in etcd changed - exiting to be restarted with it".to_string()).into());
+                }
+            }
+            if self.gone {
+                println!("Checks stopped - update SERVER_NAME and restart to monitor again");
+                let deadline = async {
+                    match self.deadline {
+                        Some(deadline) => tokio::time::sleep_until(deadline).await,
+                        None => std::future::pending::<()>().await,
+                    }
+                };
+                tokio::select! {
+                    _ = deadline => break "run time limit reached",
+                    _ = shutdown.cancelled() => break "shutdown requested",
+                }
+            }
+            self.check_drift().await;
+            // println!("Next check in {} minutes...", ping_interval_minutes);
+            if let Some(deadline) = self.deadline {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    break "run time limit reached";
+                }
+                interval = interval.min(remaining);
+            }
+            if !self.wait(interval).await {
+                break "shutdown requested";
+            }
+            if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                break "run time limit reached";
+            }
+        };
+
+        self.notifier.event(Event::new(Severity::Info, "monitoring_stopped", &self.server_name,
+                                       format!("Monitoring of '{}' stopped - {}", self.server_name, reason))).await;
+        Ok(())
+    }
+
+    /// A check that failed before a verdict is announced once per distinct error, the next one retries
+    async fn check_failed(&mut self, error: anyhow::Error) {
+        let error = format!("{:#}", error);
+        if self.check_error.as_deref() == Some(error.as_str()) {
+            println!("✗ Check of '{}' failed again: {}", self.server_name, redact::redact(&error));
+            return;
+        }
+        self.notifier.event(Event::new(Severity::Warning, "check_failed", &self.server_name,
+                                       format!("⚠️ Check of '{}' failed: {} - retrying in {} minutes",
+                                               self.server_name, error, self.interval.as_secs() / 60))).await;
+        self.check_error = Some(error);
+    }
+
+    /// Sleep until the next check or an external down signal, false on shutdown
+    async fn wait(&mut self, interval: Duration) -> bool {
+        let shutdown = self.shutdown.clone();
+        match &self.external_signal {
+            Some(signal) => tokio::select! {
+                _ = sleep(interval) => {},
+                _ = signal.notified() => self.external_signal_received = true,
+                _ = shutdown.cancelled() => return false,
+            },
+            None => tokio::select! {
+                _ = sleep(interval) => {},
+                _ = shutdown.cancelled() => return false,
+            },
+        }
+        true
+    }
+
+    /// One monitoring cycle, returns delay before the next one
+    async fn check(&mut self) -> Result<Duration> {
+        let external_signal = std::mem::take(&mut self.external_signal_received);
+        let mut signals: Vec<Signal> = vec![];
+
+        if let Some((prober, ip)) = &mut self.ping {
+            let ip = *ip;
+            let ping_result = ping_server(prober, ip).await?;
+            self.rtt_history.push(ping_result.as_ref().ok().copied());
+            println!("RTT: {}", self.rtt_history.summary());
+            self.push_metrics(&ping_result).await;
+
+            match &ping_result {
+                Ok(rtt) => {
+                    self.notifier.event(Event::new(Severity::Debug, "ping_ok", &self.server_name,
+                                                   format!("{} ping successful {:?}", ip, rtt))).await;
+                    signals.push(Signal::new("icmp", true, format!("{:?}", rtt)));
+                },
+                Err(cause) => {
+                    self.notifier.event(Event::new(Severity::Debug, "ping_failed", &self.server_name,
+                                                   format!("{} ping failed ({}): {}", ip, cause.kind(), cause))).await;
+                    signals.push(Signal::new("icmp", false, cause.to_string()));
+                },
+            }
+        }
+        if let Some(tcp) = &self.tcp_check {
+            signals.push(tcp.check().await);
+        }
+        if external_signal {
+            signals.push(Signal::new("external", false, "down signal received".to_string()));
+        }
+        if !signals.is_empty() {
+            let results: Vec<String> = signals
+                .iter()
+                .map(|s| format!("{} {} ({})", s.name, if s.healthy { "ok" } else { "failed" }, s.detail))
+                .collect();
+            self.record_check(results.join(", "));
+        }
+
+        // Services are still starting after unshelve - failed checks don't count yet
+        if let Some(until) = self.grace_until {
+            let remaining = until.saturating_duration_since(Instant::now());
+            if !remaining.is_zero() && !signals.iter().all(|s| s.healthy) {
+                println!("Boot grace period ({}s left) - failed checks ignored", remaining.as_secs());
+                return Ok(self.interval.min(Duration::from_secs(60)).min(remaining));
+            }
+        }
+
+        if self.scoring.is_none() {
+            // Any failed signal means the OpenStack status has to be checked
+            if self.ping.is_some() {
+                if signals.iter().all(|s| s.healthy) {
+                    self.mark_reachable().await;
+                    return Ok(self.interval);
+                }
+                if external_signal {
+                    println!("External signal received - checking OpenStack status");
+                }
+                println!("checking OpenStack status...");
+            }
+            return self.check_status().await;
+        }
+
+        // Combined verdict - API status is one of the weighted signals
+        let server = self.get_server().await;
+        match &server {
+            Ok(server) => {
+                let healthy = ServerState::of(server) == ServerState::Active;
+                self.last_status = Some(server.status().to_string());
+                signals.push(Signal::new("api", healthy, format!("{} ({:?})", server.status(), server.power_state())));
+            },
+            Err(e) => println!("✗ Failed to get server info: {}", e),
+        }
+        let verdict = match &self.scoring {
+            Some(scoring) => scoring.verdict(&signals),
+            None => return Ok(self.interval),
+        };
+        println!("Health: {}", verdict.breakdown);
+
+        if !verdict.down {
+            self.mark_reachable().await;
+            return Ok(self.interval);
+        }
+        self.last_verdict = Some(verdict.breakdown);
+        match server {
+            Ok(server) => self.handle_status(server).await,
+            Err(e) => Ok(self.api_error(&e).await),
+        }
+    }
+
+    /// Current state to SNAPSHOT_FILE and the control API (next to the other servers of the daemon),
+    /// shared state and the Redis hash
+    async fn write_snapshot(&self, next_check: Duration) {
+        if !self.daemon.keeps_snapshot() && self.shared.is_none() && !self.notifier.keeps_state() {
+            return;
+        }
+        let state = serde_json::json!({
+            "name": self.server_name,
+            "id": self.server_id,
+            "labels": labels::get(&self.server_name),
+            "healthy": self.healthy,
+            "status": self.last_status,
+            "last_check": self.last_check.map(|t| t.to_rfc3339()),
+            "next_check": (chrono::Local::now() + next_check).to_rfc3339(),
+            "rtt": self.rtt_history.summary(),
+            "unshelve_attempts": self.backoff.attempts(),
+            "incident": self.incident_id,
+            "incident_bundle": self.incident_bundle.as_ref().map(|p| p.display().to_string()),
+            "rate_limited": self.rate_limit.is_active(),
+        });
+        self.daemon.update_snapshot(&self.server_name, &state);
+        if let (Some(shared), Some(server_id)) = (&self.shared, &self.server_id) {
+            let snapshot = serde_json::json!({ "updated": chrono::Local::now().to_rfc3339(), "servers": [state] });
+            if let Err(e) = shared.heartbeat(server_id, &snapshot) {
+                println!("✗ Failed to update shared state: {}", redact::redact(&format!("{:#}", e)));
+            }
+        }
+        self.notifier.publish_state(&self.server_name, &state).await;
+    }
+
+    /// Pass or fail the Consul TTL check with the result of the last check
+    async fn report_consul(&self) {
+        let Some(consul) = &self.consul else {
+            return;
+        };
+        let status = self.last_status.as_deref().unwrap_or("not checked");
+        let note = match self.healthy {
+            Some(true) => format!("reachable, RTT {}", self.rtt_history.summary()),
+            _ if self.backoff.attempts() > 0 => format!("{} - unshelve attempt #{}", status, self.backoff.attempts()),
+            _ => format!("unreachable, OpenStack status {}", status),
+        };
+        if let Err(e) = consul.report(self.healthy, self.backoff.attempts() > 0, &note).await {
+            println!("✗ {}", redact::redact(&format!("{:#}", e)));
+        }
+    }
+
+    /// Alert when servers matching DRIFT_SELECTOR were deleted or created
+    async fn check_drift(&mut self) {
+        if self.rate_limit.is_active() {
+            return;
+        }
+        let Some(drift) = &mut self.drift else {
+            return;
+        };
+        let servers: Vec<(String, String)> = match self.cloud.list_servers().await {
+            Ok(servers) => servers.iter().map(|s| (s.id().clone(), s.name().clone())).collect(),
+            Err(e) => {
+                println!("✗ Failed to fetch server list for drift check: {}", e);
+                return;
+            },
+        };
+        match drift.check(&servers) {
+            Ok(Some(changes)) => {
+                let message = changes.message(drift.selector());
+                self.notifier.event(Event::new(Severity::Warning, "server_drift", drift.selector(), message)).await;
+            },
+            Ok(None) => {},
+            Err(e) => println!("✗ {:#}", e),
+        }
+    }
+
+    async fn get_server(&mut self) -> openstack::Result<openstack::compute::Server> {
+        if let Some(e) = chaos::api_error() {
+            return Err(e);
+        }
+        let server = self.cloud.get_server(&self.server_name).await;
+        if server.is_ok() && self.rate_limit.is_active() {
+            if let Some(lasted) = self.rate_limit.clear() {
+                println!("✓ OpenStack API is not rate limited anymore (after {} min)", lasted.as_secs() / 60);
+            }
+            self.push_rate_limited(false).await;
+        }
+        server
+    }
+
+    /// Delay before the next check after a failed API call: widened while rate limited (429)
+    async fn api_error(&mut self, e: &openstack::Error) -> Duration {
+        if e.kind() == openstack::ErrorKind::ResourceNotFound {
+            self.server_gone().await;
+            return self.interval;
+        }
+        if !ratelimit::is_rate_limited(e) {
+            return self.interval;
+        }
+        let delay = self.rate_limit.hit(ratelimit::retry_after(e), self.interval);
+        self.notifier.event(Event::new(Severity::Warning, "rate_limited", &self.server_name,
+                                       format!("⚠️ OpenStack API rate limited - next check in {} min", delay.as_secs().div_ceil(60)))).await;
+        self.push_rate_limited(true).await;
+        delay
+    }
+
+    /// Server no longer exists under the monitored name - one alert, then checks stop
+    async fn server_gone(&mut self) {
+        if self.gone {
+            return;
+        }
+        self.gone = true;
+        self.healthy = None;
+        self.last_status = Some("GONE".to_string());
+
+        // A renamed server is still found by its UUID
+        let renamed = match &self.server_id {
+            Some(id) if *id != self.server_name => self.cloud.get_server(id).await.ok().map(|s| s.name().clone()),
+            _ => None,
+        };
+        let message = match renamed {
+            Some(name) => format!("✗ Server '{}' was renamed to '{}' - monitoring stopped until SERVER_NAME is updated", self.server_name, name),
+            None => format!("✗ Server '{}' was deleted - monitoring stopped", self.server_name),
+        };
+        self.notifier.event(Event::new(Severity::Critical, "server_gone", &self.server_name, message)).await;
+        if let Some(server_id) = &self.server_id {
+            if let Err(e) = self.daemon.actions.complete(server_id) {
+                println!("✗ {:#}", e);
+            }
+        }
+    }
+
+    /// Announce a soft-deleted server once per deletion
+    async fn server_soft_deleted(&mut self, server: &openstack::compute::Server) {
+        if self.soft_deleted_notified {
+            return;
+        }
+        self.soft_deleted_notified = true;
+        self.notifier.event(Event::new(Severity::Critical, "server_soft_deleted", &self.server_name,
+                                       format!("✗ Server '{}' was deleted ({}) - run `unshelve restore {}` to bring it back",
+                                               self.server_name, restore::describe(server), self.server_name))).await;
+    }
+
+    async fn push_rate_limited(&self, limited: bool) {
+        let Some(writer) = &self.remote_writer else {
+            return;
+        };
+        let series = vec![TimeSeries::now("unshelve_api_rate_limited", &self.server_name, if limited { 1.0 } else { 0.0 })];
+        if let Err(e) = writer.push(series).await {
+            println!("✗ {}", redact::redact(&format!("{:#}", e)));
+        }
+    }
+
+    /// Notification text with incident id, signal breakdown of the last verdict and the incident bundle
+    fn with_breakdown(&self, message: String) -> String {
+        let mut message = match &self.incident_id {
+            Some(incident) => format!("{}\nIncident: {}", message, incident),
+            None => message,
+        };
+        if let Some(breakdown) = &self.last_verdict {
+            message = format!("{}\nSignals: {}", message, breakdown);
+        }
+        if let Some(path) = &self.incident_bundle {
+            message = format!("{}\nDiagnostics: {}", message, path.display());
+        }
+        message
+    }
+
+    /// Keep check result for the incident bundle
+    fn record_check(&mut self, result: String) {
+        if self.recent_checks_size == 0 {
+            return;
+        }
+        if self.recent_checks.len() == self.recent_checks_size {
+            self.recent_checks.pop_front();
+        }
+        self.recent_checks.push_back(format!("[{}] {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), result));
+    }
+
+    /// Collect diagnostic bundle once per incident
+    async fn collect_bundle(&mut self, server: &openstack::compute::Server) {
+        let Some(dir) = &self.bundle_dir else {
+            return;
+        };
+        if self.incident_bundle.is_some() {
+            return;
+        }
+        let checks: Vec<String> = self.recent_checks.iter().cloned().collect();
+        let ping_ip = self.ping.as_ref().map(|(_, ip)| *ip);
+        match bundle::collect(dir, server, &checks, ping_ip).await {
+            Ok(path) => {
+                println!("Incident bundle: {}", path.display());
+                self.incident_bundle = Some(path);
+            },
+            Err(e) => println!("✗ Failed to collect incident bundle: {:#}", e),
+        }
+    }
+
+    async fn push_metrics(&self, ping_result: &Result<Duration, ProbeError>) {
+        let Some(writer) = &self.remote_writer else {
+            return;
+        };
+        let mut series = vec![
+            TimeSeries::now("unshelve_ping_up", &self.server_name, if ping_result.is_ok() { 1.0 } else { 0.0 }),
+            TimeSeries::now("unshelve_unshelve_attempts", &self.server_name, self.backoff.attempts() as f64),
+        ];
+        if let Ok(rtt) = ping_result {
+            series.push(TimeSeries::now("unshelve_ping_rtt_seconds", &self.server_name, rtt.as_secs_f64()));
+        }
+        if let Err(e) = writer.push(series).await {
+            println!("✗ {}", redact::redact(&format!("{:#}", e)));
+        }
+    }
+
+    /// Server is up again - report recovery after unshelve attempts
+    async fn mark_reachable(&mut self) {
+        self.healthy = Some(true);
+        let placement = self.check_placement().await;
+        if self.backoff.attempts() > 0 {
+            let mut message = format!("✅ Server '{}' is reachable again", self.server_name);
+            if let Some(placement) = placement {
+                message = format!("{}\n{}", message, placement);
+            }
+            if let Some(root_volume) = self.check_root_volume().await {
+                message = format!("{}\n{}", message, root_volume);
+            }
+            // Prime caches before users hit the freshly unshelved server, unless the pipeline did
+            if !self.pipeline.has_warmup() {
+                let server_id = self.server_id.clone().unwrap_or_else(|| self.server_name.clone());
+                match warmup::run(&self.server_name, &server_id).await {
+                    Ok(Some(warmup)) => message = format!("{}\n{}", message, warmup),
+                    Ok(None) => {},
+                    Err(e) => println!("✗ Warmup skipped: {:#}", e),
+                }
+            }
+            self.notifier.event(Event::new(Severity::Info, "recovered", &self.server_name, message)).await;
+            self.check_shelve_frequency().await;
+        }
+        self.backoff.reset();
+        self.precondition_failure = None;
+        self.grace_until = None;
+        self.last_verdict = None;
+        self.incident_bundle = None;
+        self.incident_id = None;
+        self.notifier.set_incident(&self.server_name, None);
+        self.root_volume_alerted = false;
+        if let Some(server_id) = &self.server_id {
+            if let Err(e) = self.daemon.actions.complete(server_id) {
+                println!("✗ {:#}", e);
+            }
+        }
+    }
+
+    /// Low-priority notice if the server gets shelved unusually often, judged by the event history.
+    /// Auto-unshelve hides the cause, so someone should look at it
+    async fn check_shelve_frequency(&mut self) {
+        if self.anomaly_notified.is_some_and(|t| t.elapsed() < ANOMALY_NOTIFY_INTERVAL) {
+            return;
+        }
+        let Some(store) = &self.daemon.history else {
+            return;
+        };
+        match anomaly::detect(store.as_ref()) {
+            Ok(anomalies) => {
+                if let Some(anomaly) = anomalies.into_iter().find(|a| a.server == self.server_name) {
+                    self.anomaly_notified = Some(Instant::now());
+                    self.notifier.event(Event::new(Severity::Info, "shelve_frequency_anomaly", &self.server_name,
+                                                   format!("⚠️ Server '{}' is shelved unusually often: {}. Auto-unshelve keeps it up - find out what shelves it",
+                                                           self.server_name, anomaly.reason))).await;
+                }
+            },
+            Err(e) => println!("✗ Shelve frequency check failed: {:#}", e),
+        }
+    }
+
+    /// Compare availability zone and host after unshelve with the ones before, alert if the server moved.
+    /// Returns placement line for the recovery message
+    async fn check_placement(&mut self) -> Option<String> {
+        let before = self.placement_before_unshelve.take()?;
+        let details = match self.get_server().await {
+            Ok(server) => nova::server(self.cloud, server.id()).await,
+            Err(e) => Err(e.into()),
+        };
+        let after = match details {
+            Ok(details) => details.placement(),
+            Err(e) => {
+                println!("✗ Failed to get server placement after unshelve: {:#}", e);
+                return None;
+            },
+        };
+        self.placement = Some(after.clone());
+        let changes = after.changes_from(&before);
+        if !changes.is_empty() {
+            self.notifier.event(Event::new(Severity::Warning, "placement_changed", &self.server_name,
+                                           format!("⚠️ Server '{}' was unshelved elsewhere: {}. Now: {}",
+                                                   self.server_name, changes.join(", "), after.describe()))).await;
+        }
+        Some(format!("Placement: {} (before unshelve: {})", after.describe(), before.describe()))
+    }
+
+    /// Root volume of a boot-from-volume server after unshelve, alerts once per incident
+    /// if it's not attached or not in-use. Returns a line for the recovery message
+    async fn check_root_volume(&mut self) -> Option<String> {
+        let volume_id = self.root_volume.clone()?;
+        let server_id = self.server_id.clone()?;
+        let volume = match self.cloud.get_volume(&volume_id).await {
+            Ok(volume) => volume,
+            Err(e) => {
+                println!("✗ Failed to get root volume {}: {}", volume_id, e);
+                return None;
+            },
+        };
+        let details = volume::describe(&volume, &server_id);
+        if !volume::is_healthy(&volume, &server_id) && !self.root_volume_alerted {
+            self.root_volume_alerted = true;
+            self.notifier.event(Event::new(Severity::Critical, "root_volume_failed", &self.server_name,
+                                           self.with_breakdown(format!("✗ Server '{}' is ACTIVE but its root volume is not healthy after unshelve: {}",
+                                                                       self.server_name, details)))).await;
+        }
+        Some(format!("Root volume: {}", details))
+    }
+
+    /// Get server status from OpenStack and unshelve it if needed
+    async fn check_status(&mut self) -> Result<Duration> {
+        match self.get_server().await {
+            Ok(server) => self.handle_status(server).await,
+            Err(e) => {
+                println!("✗ Failed to get server info: {}", e);
+                Ok(self.api_error(&e).await)
+            }
+        }
+    }
+
+    async fn handle_status(&mut self, mut server: openstack::compute::Server) -> Result<Duration> {
+        self.server_id = Some(server.id().clone());
+        self.healthy = Some(false);
+        self.last_status = Some(server.status().to_string());
+        let status = server.status();
+        let power_state = server.power_state();
+        println!("Server status in OpenStack: {} (power state {:?})", status, power_state);
+        self.record_check(format!("status {} (power state {:?})", status, power_state));
+
+        if !self.daemon.pins.verify(&self.server_name, server.id(), false)? {
+            if self.recreated_notified {
+                println!("✗ Server '{}' points to a different instance than pinned - no action taken", self.server_name);
+            } else {
+                self.notifier.event(Event::new(Severity::Critical, "server_recreated", &self.server_name,
+                                               format!("⚠️ Server '{}' was recreated (new UUID {}) - auto-unshelve disabled until confirmed", self.server_name, server.id()))).await;
+                self.recreated_notified = true;
+            }
+            return Ok(self.interval);
+        }
+
+        let state = ServerState::of(&server);
+        if state != ServerState::SoftDeleted {
+            self.soft_deleted_notified = false;
+        }
+        // The status isn't settled while a task runs: ACTIVE and powering-off, SHELVED and unshelving.
+        // The OpenStack client doesn't expose task_state, it comes from the server details
+        let task_state = match nova::server(self.cloud, server.id()).await {
+            Ok(details) => details.task_state().map(String::from),
+            Err(e) => {
+                println!("⚠️ {:#} - task state unknown", e);
+                None
+            },
+        };
+        if let Some(task) = task_state.filter(|_| state != ServerState::SoftDeleted && state != ServerState::Deleted) {
+            println!("Server is {} with task {} in progress - waiting for it to finish", status, task);
+            self.record_check(format!("task {} in progress", task));
+            return Ok(self.interval.min(Duration::from_secs(60)));
+        }
+        // Power state tells the rest: ACTIVE but not running is powering on or off
+        match state {
+            // SHELVED still has its disk on the hypervisor, unshelve works the same way
+            ServerState::ShelvedOffloaded | ServerState::Shelved => self.unshelve(&mut server).await,
+            ServerState::PowerTransition => {
+                println!("Server is ACTIVE but power state is {:?} - transition in progress, waiting", power_state);
+                Ok(self.interval)
+            },
+            // Without ping ACTIVE status is the only sign of recovery
+            ServerState::Active if self.ping.is_none() => {
+                self.mark_reachable().await;
+                Ok(self.interval)
+            },
+            ServerState::Active => {
+                // ACTIVE but unreachable after unshelve - a root volume in error explains it
+                if self.backoff.attempts() > 0 {
+                    self.check_root_volume().await;
+                }
+                if self.backoff.attempts() > 0 && self.grace_until.is_none() && !self.boot_grace.is_zero() {
+                    self.grace_until = Some(Instant::now() + self.boot_grace);
+                    println!("Server is ACTIVE after unshelve - boot grace period {} min, failed checks don't count",
+                             self.boot_grace.as_secs() / 60);
+                    return Ok(self.interval.min(Duration::from_secs(60)));
+                }
+                println!("Server is ACTIVE in OpenStack - unreachable for another reason, unshelve not needed");
+                Ok(self.interval)
+            },
+            // Transitions finish by themselves, the next check sees the result
+            ServerState::Transition => {
+                println!("Server is in transition ({}) - waiting", status);
+                Ok(self.interval)
+            },
+            // Checks go on - the server is monitored again once restored
+            ServerState::SoftDeleted => {
+                self.server_soft_deleted(&server).await;
+                Ok(self.interval)
+            },
+            ServerState::Deleted => {
+                self.server_gone().await;
+                Ok(self.interval)
+            },
+            ServerState::Shutoff | ServerState::Rescue | ServerState::Stopped | ServerState::Error | ServerState::Unknown => {
+                println!("Server status is '{}' - not handled automatically, manual action may be required", status);
+                Ok(self.interval)
+            },
+        }
+    }
+
+    /// Unshelve server respecting backoff and precondition hooks
+    async fn unshelve(&mut self, server: &mut openstack::compute::Server) -> Result<Duration> {
+        let status = server.status().to_string().to_lowercase();
+        let server_id = server.id().clone();
+
+        // Submitted before a restart (or by another instance) - wait for it
+        match self.daemon.actions.in_flight(&server_id, "unshelve") {
+            Ok(Some(record)) => {
+                println!("Server is {} - unshelve already submitted at {} (incident {}), waiting",
+                         status, record.submitted.format("%Y-%m-%d %H:%M:%S"), record.incident);
+                return Ok(self.interval.min(Duration::from_secs(60)));
+            },
+            Ok(None) => {},
+            Err(e) => {
+                println!("✗ Failed to check in-flight actions: {} - unshelve not submitted", redact::redact(&format!("{:#}", e)));
+                return Ok(self.interval);
+            },
+        }
+
+        // Humans have taken over - observe and alert only
+        if let Some(reason) = killswitch::disabled(&self.server_name) {
+            println!("Server is {} - automatic actions disabled ({}), unshelve skipped", status, reason);
+            if !self.actions_disabled_notified {
+                self.actions_disabled_notified = true;
+                let message = self.with_breakdown(format!("⚠️ Server '{}' is {} - auto-unshelve skipped, automatic actions are disabled: {}",
+                                                          self.server_name, status, reason));
+                self.notifier.event(Event::new(Severity::Warning, "actions_disabled", &self.server_name, message)).await;
+            }
+            return Ok(self.interval);
+        }
+        self.actions_disabled_notified = false;
+
+        if let Some(maintenance) = &mut self.maintenance {
+            if let Some(window) = maintenance.active().await {
+                println!("Server is {} - maintenance '{}' until {}, unshelve suppressed",
+                         status, window.summary, window.end.format("%Y-%m-%d %H:%M"));
+                if self.maintenance_notified != Some(window.start) {
+                    self.maintenance_notified = Some(window.start);
+                    self.notifier.event(Event::new(Severity::Info, "maintenance_suppressed", &self.server_name,
+                                                   format!("Server '{}' is {} - auto-unshelve suppressed by maintenance '{}' until {}",
+                                                           self.server_name, status, window.summary, window.end.format("%Y-%m-%d %H:%M")))).await;
+                }
+                return Ok(self.interval);
+            }
+        }
+
+        if let Some(wait) = self.backoff.remaining() {
+            println!("Server is {} - unshelve attempt #{} in {} min (backoff: {})",
+                     status, self.backoff.attempts() + 1, wait.as_secs().div_ceil(60), self.backoff.schedule_string());
+            return Ok(self.interval.min(wait));
+        }
+
+        println!("Server is {} - attempting to unshelve...", status);
+        self.collect_bundle(server).await;
+
+        // Ask the precondition hook (billing/credit check) before unshelving. A failure counts as an
+        // attempt for the backoff and is announced once per distinct reason
+        match precondition::check(&self.server_name, &server_id).await {
+            Ok(Precondition::Passed) => self.precondition_failure = None,
+            Ok(Precondition::Failed(reason)) => {
+                let delay = self.backoff.record_attempt();
+                println!("⚠️ Unshelve blocked by precondition: {} - next check not before {} min (backoff: {})",
+                         reason, delay.as_secs() / 60, self.backoff.schedule_string());
+                if self.precondition_failure.as_deref() != Some(reason.as_str()) {
+                    let message = self.with_breakdown(format!("✗ Server '{}' is shelved, unshelve blocked by precondition: {}\n\
+                                                               Manual intervention required", self.server_name, reason));
+                    self.notifier.event(Event::new(Severity::Critical, "precondition_failed", &self.server_name, message)).await;
+                    self.precondition_failure = Some(reason);
+                }
+                return Ok(self.interval.min(delay));
+            },
+            Err(e) => {
+                println!("✗ Precondition check failed: {:#} - unshelve skipped", e);
+                return Ok(self.interval);
+            },
+        }
+
+        // Persist the action first, so a crash right after submission doesn't submit it twice
+        let incident = self.incident_id
+            .get_or_insert_with(|| {
+                let short_id: String = server_id.chars().take(8).collect();
+                format!("{}-{}", chrono::Local::now().format("%Y%m%d-%H%M%S"), short_id)
+            })
+            .clone();
+        self.notifier.set_incident(&self.server_name, Some(incident.clone()));
+        if let Err(e) = self.daemon.actions.record(&server_id, "unshelve", &incident) {
+            println!("✗ {:#} - unshelve not submitted", e);
+            return Ok(self.interval);
+        }
+
+        let mut interval = self.interval;
+        let delay = self.backoff.record_attempt();
+        let result = match chaos::api_error() {
+            Some(e) => Err(e),
+            None => server.action(openstack::compute::ServerAction::Unshelve).await,
+        };
+        match result {
+            Ok(_) => {
+                // Shelved offloaded servers have no host anymore - the last one seen while it was up counts
+                let before = self.placement.clone().unwrap_or_else(|| nova::Placement {
+                    zone: server.availability_zone().clone(),
+                    host: None,
+                    host_id: None,
+                });
+                let message = self.with_breakdown(format!("✓ Server '{}' was shelved, unshelve command sent (last placement: {})",
+                                                          self.server_name, before.describe()));
+                self.placement_before_unshelve = Some(before);
+                self.notifier.event(Event::new(Severity::Warning, "unshelve_sent", &self.server_name, message)).await;
+
+                if self.pipeline.after_unshelve().is_empty() {
+                    // Wait for server to become active
+                    println!("Waiting for server to become ACTIVE...");
+                    interval = Duration::from_secs(60);
+                } else {
+                    interval = self.run_pipeline(&server_id).await;
+                }
+            }
+            Err(e) if ratelimit::is_rate_limited(&e) => {
+                if let Err(e) = self.daemon.actions.complete(&server_id) {
+                    println!("✗ {:#}", e);
+                }
+                println!("✗ Unshelve rejected: {}", e);
+                interval = self.api_error(&e).await;
+            }
+            // Nova refuses actions while a task runs (task_state unshelving, shelving, ...) - one started
+            // after the check, someone else is already unshelving or the shelve isn't finished
+            Err(e) if e.kind() == openstack::ErrorKind::Conflict => {
+                if let Err(e) = self.daemon.actions.complete(&server_id) {
+                    println!("✗ {:#}", e);
+                }
+                println!("Server is {} but a task is in progress ({}) - waiting for it to finish", status, e);
+                interval = self.interval.min(Duration::from_secs(60));
+            }
+            Err(e) => {
+                if let Err(e) = self.daemon.actions.complete(&server_id) {
+                    println!("✗ {:#}", e);
+                }
+                let mut message = format!("✗ Failed to unshelve server '{}' (attempt #{}): {}", self.server_name, self.backoff.attempts(), e);
+                if let Some(hint) = scheduling_hint(&e.to_string()) {
+                    message = format!("{}\n{}", message, hint);
+                    match nova::server_groups(self.cloud, &server_id).await {
+                        Ok(groups) if groups.is_empty() => message = format!("{}. The server is in no server group", message),
+                        Ok(groups) => message = format!("{}. Server groups: {}", message, nova::describe(&groups)),
+                        Err(e) => println!("✗ {:#}", e),
+                    }
+                }
+                let message = self.with_breakdown(message);
+                self.notifier.event(Event::new(Severity::Critical, "unshelve_failed", &self.server_name, message)).await;
+            }
+        }
+        println!("Attempt #{} - next unshelve attempt not before {} min (backoff: {})",
+                 self.backoff.attempts(), delay.as_secs() / 60, self.backoff.schedule_string());
+        Ok(interval)
+    }
+
+    /// Run RECOVERY_PIPELINE steps after unshelve was accepted. A failed step stops the pipeline
+    /// unless it's optional, the next unshelve attempt follows the backoff as usual
+    async fn run_pipeline(&mut self, server_id: &str) -> Duration {
+        let stages = self.pipeline.after_unshelve().to_vec();
+        let mut results: Vec<String> = vec![];
+        for stage in &stages {
+            if stage.step == Step::Notify {
+                let message = format!("Recovery pipeline for '{}':\n{}", self.server_name, results.join("\n"));
+                self.notifier.event(Event::new(Severity::Info, "pipeline_progress", &self.server_name, message)).await;
+                continue;
+            }
+            println!("Pipeline step '{}' (timeout {}s)...", stage.step, stage.timeout.as_secs());
+            let mut target = Target {
+                cloud: self.cloud,
+                server_id,
+                server_name: &self.server_name,
+                ping: self.ping.as_mut(),
+            };
+            match stage.run(&mut target).await {
+                Ok(result) => {
+                    println!("✓ {}: {}", stage.step, result);
+                    results.push(format!("✓ {}: {}", stage.step, result));
+                },
+                Err(e) if stage.optional => {
+                    println!("✗ {}: {:#} - optional, continuing", stage.step, e);
+                    results.push(format!("✗ {}: {:#} (optional)", stage.step, e));
+                },
+                Err(e) => {
+                    results.push(format!("✗ {}: {:#}", stage.step, e));
+                    let message = self.with_breakdown(format!("✗ Recovery pipeline for '{}' stopped at step '{}':\n{}",
+                                                              self.server_name, stage.step, results.join("\n")));
+                    self.notifier.event(Event::new(Severity::Critical, "pipeline_failed", &self.server_name, message)).await;
+                    return self.interval;
+                },
+            }
+        }
+        // Check right away, a reachable server is reported as recovered
+        Duration::from_secs(5)
+    }
+}
+
+/// Scheduler errors that usually mean the server group policy can't be satisfied,
+/// the notification adds the groups of the server with their policy
+fn scheduling_hint(error: &str) -> Option<&'static str> {
+    let error = error.to_lowercase();
+    if error.contains("affinity") {
+        Some("Hint: server group affinity/anti-affinity policy can't be satisfied - check the hosts of the other group members")
+    } else if error.contains("no valid host") {
+        Some("Hint: no hypervisor can take the server - with an anti-affinity server group every host may already run a group member")
+    } else {
+        None
+    }
+}
+
+/// Ping server once. Permission errors abort monitoring - they mean misconfiguration, not a down server
+async fn ping_server(prober: &mut Prober, ip: IpAddr) -> Result<Result<Duration, ProbeError>> {
+    let result = if chaos::ping_fail() {
+        Err(ProbeError::Other("injected failure".to_string()))
+    } else {
+        prober.probe(ip).await
+    };
+    match &result {
+        Ok(rtt) => {
+            println!("[{}] {} Ping successful {:?}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), ip, rtt);
+        },
+        Err(ProbeError::PermissionDenied(e)) => {
+            anyhow::bail!("Ping to {} not permitted: {}. Check socket type and net.ipv4.ping_group_range", ip, e);
+        },
+        Err(e) => {
+            println!("[{}] {} Ping failed: {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), ip, e);
+        },
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn servers_list() {
+        let watches = Watch::parse_list("web1=10.0.0.11, db=10.0.0.20/raw,web2=fd00::2/dgram,status").unwrap();
+        let parsed: Vec<(Option<&str>, Option<IpAddr>, Option<bool>)> = watches
+            .iter()
+            .map(|w| (w.server.as_deref(), w.ping_ip, w.use_dgram_socket))
+            .collect();
+        assert_eq!(parsed, vec![
+            (Some("web1"), Some("10.0.0.11".parse().unwrap()), None),
+            (Some("db"), Some("10.0.0.20".parse().unwrap()), Some(false)),
+            (Some("web2"), Some("fd00::2".parse().unwrap()), Some(true)),
+            (Some("status"), None, None),
+        ]);
+    }
+
+    #[test]
+    fn invalid_servers_list() {
+        assert!(Watch::parse_list("").is_err());
+        assert!(Watch::parse_list("web1=10.0.0.11,web1=10.0.0.12").is_err());
+        assert!(Watch::parse_list("web1=not-an-ip").is_err());
+        assert!(Watch::parse_list("web1=10.0.0.11/icmp").is_err());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("45s").unwrap(), Duration::from_secs(45));
+        assert_eq!(parse_duration(" 8h ").unwrap(), Duration::from_secs(8 * 3600));
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse_duration("1d2h").unwrap(), Duration::from_secs(93600));
+        for invalid in ["", "90", "0m", "m", "5w", "1h 30m", "-5m"] {
+            assert!(parse_duration(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn run_limits() {
+        assert_eq!(run_limit(None, None).unwrap(), None);
+        assert_eq!(run_limit(Some("2h"), Some("18:00")).unwrap(), Some(Duration::from_secs(7200)), "--for wins");
+        // --until is today or, once the time has passed, tomorrow
+        let now = chrono::Local::now();
+        for until in [now + chrono::Duration::hours(1), now - chrono::Duration::hours(1)] {
+            let limit = run_limit(None, Some(&until.format("%H:%M").to_string())).unwrap().unwrap();
+            assert!(limit > Duration::ZERO && limit <= Duration::from_secs(86400), "{:?}", limit);
+        }
+        assert!(run_limit(None, Some("25:00")).is_err());
+        assert!(run_limit(Some("soon"), None).is_err());
+    }
+}
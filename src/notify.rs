@@ -0,0 +1,592 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex, PoisonError};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde_json::json;
+
+use crate::alertmanager::Alertmanager;
+use crate::config;
+use crate::history::{self, EventStore};
+use crate::labels;
+use crate::monitor;
+use crate::pubsub::RedisOutput;
+use crate::redact;
+use crate::silence::SilenceCache;
+use crate::snmp::TrapSender;
+use tokio::sync::broadcast;
+use tokio::time::{Duration, Instant};
+
+/// Event severity
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Debug,
+    Info,
+    Warning,
+    Critical,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Severity::Debug => "DEBUG",
+            Severity::Info => "INFO",
+            Severity::Warning => "WARNING",
+            Severity::Critical => "CRITICAL",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Severity {
+    /// Minimum severity from env var, default if not set
+    fn from_env(key: &str, default: Severity) -> Result<Severity> {
+        match env_non_empty(key) {
+            Some(value) => Severity::from_str(&value, true)
+                .map_err(|_| anyhow::anyhow!("{} must be one of: debug, info, warning, critical", key)),
+            None => Ok(default),
+        }
+    }
+}
+
+/// Something that happened to a monitored server
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub time: chrono::DateTime<chrono::Local>,
+    pub severity: Severity,
+    /// Machine-readable event type, e.g. ping_failed, unshelve_sent
+    pub kind: &'static str,
+    pub server: String,
+    pub message: String,
+    /// Labels of the server (SERVER_LABELS and its own)
+    pub labels: BTreeMap<String, String>,
+    /// Incident the event belongs to, stamped by the notifier (see `Notifier::set_incident`)
+    pub incident: Option<String>,
+}
+
+impl Event {
+    pub fn new(severity: Severity, kind: &'static str, server: &str, message: String) -> Self {
+        Event { time: chrono::Local::now(), severity, kind, server: server.to_string(), message, labels: labels::get(server), incident: None }
+    }
+}
+
+/// At most `max` messages per `window` to a channel, e.g. NOTIFY_RATE_LIMIT='10/1h', counted for all servers
+/// of the daemon. Suppressed events are counted by kind and summarized in the next message that goes out
+struct ChannelLimit {
+    max: usize,
+    window: Duration,
+    spec: String,
+    sent: VecDeque<Instant>,
+    suppressed: BTreeMap<&'static str, usize>,
+}
+
+impl ChannelLimit {
+    /// Limit from env var as N/DURATION (10/1h, 30/15m, 10/h), None if not set
+    fn from_env(key: &str, default: Option<&str>) -> Result<Option<Self>> {
+        let Some(spec) = env_non_empty(key).or(default.map(String::from)) else {
+            return Ok(None);
+        };
+        let (max, window) = spec
+            .split_once('/')
+            .context(format!("{} must be N/DURATION, e.g. 10/1h", key))?;
+        let max: usize = max.trim().parse().context(format!("{} must be N/DURATION, e.g. 10/1h", key))?;
+        let window = window.trim();
+        let window = if window.starts_with(|c: char| c.is_ascii_digit()) {
+            monitor::parse_duration(window)?
+        } else {
+            monitor::parse_duration(&format!("1{}", window))?
+        };
+        Ok(Some(ChannelLimit { max, window, spec, sent: VecDeque::new(), suppressed: BTreeMap::new() }))
+    }
+
+    /// Take a slot for the message, false if the limit is reached and the event is suppressed
+    fn admit(&mut self, kind: &'static str) -> bool {
+        while self.sent.front().is_some_and(|t| t.elapsed() >= self.window) {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= self.max {
+            *self.suppressed.entry(kind).or_default() += 1;
+            return false;
+        }
+        self.sent.push_back(Instant::now());
+        true
+    }
+
+    /// "Suppressed N events ..." line for the next message, None if nothing was suppressed
+    fn take_summary(&mut self) -> Option<String> {
+        if self.suppressed.is_empty() {
+            return None;
+        }
+        let total: usize = self.suppressed.values().sum();
+        let kinds: Vec<String> = self.suppressed.iter().map(|(kind, count)| format!("{} {}", count, kind)).collect();
+        self.suppressed.clear();
+        Some(format!("(suppressed {} similar events by rate limit {}: {})", total, self.spec, kinds.join(", ")))
+    }
+}
+
+/// Notification channel configured in env
+pub enum Channel {
+    /// NOTIFY_WEBHOOK_URL - generic JSON POST {"text": "..."}
+    Webhook { url: String },
+    /// NOTIFY_SLACK_WEBHOOK_URL - Slack incoming webhook
+    Slack { url: String },
+    /// NOTIFY_GOOGLE_CHAT_WEBHOOK_URL - Google Chat incoming webhook, sent as a card
+    GoogleChat { url: String },
+    /// NOTIFY_TEAMS_WEBHOOK_URL - Microsoft Teams workflow webhook, sent as an Adaptive Card
+    Teams { url: String },
+    /// NOTIFY_TELEGRAM_BOT_TOKEN + NOTIFY_TELEGRAM_CHAT_ID
+    Telegram { token: String, chat_id: String },
+    /// NOTIFY_SIGNAL_URL (signal-cli REST API gateway) + NOTIFY_SIGNAL_NUMBER (registered sender)
+    /// + NOTIFY_SIGNAL_RECIPIENTS (phone numbers or group ids)
+    Signal { url: String, number: String, recipients: Vec<String> },
+    /// NOTIFY_PUSHOVER_TOKEN + NOTIFY_PUSHOVER_USER. Critical events go with `priority`,
+    /// emergency (2) repeats every `retry` seconds until acknowledged or `expire` seconds pass
+    Pushover { token: String, user: String, priority: i8, retry: u64, expire: u64 },
+}
+
+impl Channel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Channel::Webhook { .. } => "webhook",
+            Channel::Slack { .. } => "slack",
+            Channel::GoogleChat { .. } => "google_chat",
+            Channel::Teams { .. } => "teams",
+            Channel::Telegram { .. } => "telegram",
+            Channel::Signal { .. } => "signal",
+            Channel::Pushover { .. } => "pushover",
+        }
+    }
+
+    async fn send(&self, client: &reqwest::Client, severity: Severity, message: &str) -> Result<()> {
+        let request = match self {
+            Channel::Webhook { url } => client.post(url).json(&json!({ "text": message })),
+            Channel::Slack { url } => client.post(url).json(&json!({ "text": message })),
+            Channel::GoogleChat { url } => client.post(url).json(&json!({
+                "cardsV2": [{
+                    "cardId": "unshelve",
+                    "card": {
+                        "header": { "title": "unshelve", "subtitle": severity.to_string() },
+                        "sections": [{ "widgets": [{ "textParagraph": { "text": message } }] }],
+                    },
+                }],
+            })),
+            Channel::Teams { url } => {
+                let color = match severity {
+                    Severity::Critical => "Attention",
+                    Severity::Warning => "Warning",
+                    Severity::Info | Severity::Debug => "Default",
+                };
+                client.post(url).json(&json!({
+                    "type": "message",
+                    "attachments": [{
+                        "contentType": "application/vnd.microsoft.card.adaptive",
+                        "content": {
+                            "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                            "type": "AdaptiveCard",
+                            "version": "1.4",
+                            "body": [
+                                { "type": "TextBlock", "text": format!("unshelve - {}", severity), "weight": "Bolder", "color": color },
+                                { "type": "TextBlock", "text": message, "wrap": true },
+                            ],
+                        },
+                    }],
+                }))
+            },
+            Channel::Telegram { token, chat_id } => client
+                .post(format!("https://api.telegram.org/bot{}/sendMessage", token))
+                .json(&json!({ "chat_id": chat_id, "text": message })),
+            Channel::Signal { url, number, recipients } => client
+                .post(format!("{}/v2/send", url.trim_end_matches('/')))
+                .json(&json!({ "number": number, "recipients": recipients, "message": message })),
+            Channel::Pushover { token, user, priority, retry, expire } => {
+                let priority = if severity == Severity::Critical { *priority } else { 0 };
+                let mut body = json!({ "token": token, "user": user, "title": "unshelve", "message": message, "priority": priority });
+                if priority == 2 {
+                    body["retry"] = json!(retry);
+                    body["expire"] = json!(expire);
+                }
+                client.post("https://api.pushover.net/1/messages.json").json(&body)
+            },
+        };
+
+        // URL is dropped from errors, it may contain the token
+        request
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| anyhow::anyhow!("{} notification failed: {}", self.name(), e.without_url()))?;
+        Ok(())
+    }
+}
+
+/// Routes events to console, event history and notification channels,
+/// each with its own minimum severity and optional rate limit
+pub struct Notifier {
+    client: reqwest::Client,
+    channels: Vec<(Channel, Severity, Option<Mutex<ChannelLimit>>)>,
+    log_min_severity: Severity,
+    history: Option<(Arc<dyn EventStore>, Severity)>,
+    redis: Option<(RedisOutput, Severity)>,
+    snmp: Option<TrapSender>,
+    alertmanager: Option<Alertmanager>,
+    /// Current incident by server, stamped on the server's events until it's cleared
+    incidents: Mutex<HashMap<String, String>>,
+    silences: Mutex<SilenceCache>,
+    /// Subscribers of the embedding application (see `monitor::Monitor::subscribe`)
+    events: Option<broadcast::Sender<Event>>,
+}
+
+impl Notifier {
+    pub fn from_env() -> Result<Self> {
+        Notifier::with_history(history::from_env()?)
+    }
+
+    /// Notifier recording events in an event store opened by the caller
+    pub fn with_history(history: Option<Arc<dyn EventStore>>) -> Result<Self> {
+        let mut channels: Vec<Channel> = vec![];
+
+        if let Some(url) = env_non_empty("NOTIFY_WEBHOOK_URL") {
+            channels.push(Channel::Webhook { url });
+        }
+        if let Some(url) = env_non_empty("NOTIFY_SLACK_WEBHOOK_URL") {
+            channels.push(Channel::Slack { url });
+        }
+        if let Some(url) = env_non_empty("NOTIFY_GOOGLE_CHAT_WEBHOOK_URL") {
+            channels.push(Channel::GoogleChat { url });
+        }
+        if let Some(url) = env_non_empty("NOTIFY_TEAMS_WEBHOOK_URL") {
+            channels.push(Channel::Teams { url });
+        }
+        match (env_non_empty("NOTIFY_TELEGRAM_BOT_TOKEN"), env_non_empty("NOTIFY_TELEGRAM_CHAT_ID")) {
+            (Some(token), Some(chat_id)) => channels.push(Channel::Telegram { token, chat_id }),
+            (None, None) => {},
+            _ => anyhow::bail!("Both NOTIFY_TELEGRAM_BOT_TOKEN and NOTIFY_TELEGRAM_CHAT_ID must be set for Telegram notifications"),
+        }
+        match (env_non_empty("NOTIFY_SIGNAL_URL"), env_non_empty("NOTIFY_SIGNAL_NUMBER"), env_non_empty("NOTIFY_SIGNAL_RECIPIENTS")) {
+            (Some(url), Some(number), Some(recipients)) => {
+                let recipients = recipients.split(',').map(str::trim).filter(|r| !r.is_empty()).map(String::from).collect();
+                channels.push(Channel::Signal { url, number, recipients });
+            },
+            (None, None, None) => {},
+            _ => anyhow::bail!("NOTIFY_SIGNAL_URL, NOTIFY_SIGNAL_NUMBER and NOTIFY_SIGNAL_RECIPIENTS must all be set for Signal notifications"),
+        }
+        match (env_non_empty("NOTIFY_PUSHOVER_TOKEN"), env_non_empty("NOTIFY_PUSHOVER_USER")) {
+            (Some(token), Some(user)) => {
+                let priority: i8 = config::var("NOTIFY_PUSHOVER_PRIORITY")
+                    .unwrap_or_else(|_| "1".to_string())
+                    .parse()
+                    .context("NOTIFY_PUSHOVER_PRIORITY must be a number")?;
+                if !(-2..=2).contains(&priority) {
+                    anyhow::bail!("NOTIFY_PUSHOVER_PRIORITY must be from -2 to 2");
+                }
+                let retry: u64 = config::var("NOTIFY_PUSHOVER_RETRY_SECONDS")
+                    .unwrap_or_else(|_| "60".to_string())
+                    .parse()
+                    .context("NOTIFY_PUSHOVER_RETRY_SECONDS must be a number")?;
+                // Pushover rejects emergency messages retried more often than every 30 seconds
+                if priority == 2 && retry < 30 {
+                    anyhow::bail!("NOTIFY_PUSHOVER_RETRY_SECONDS must be at least 30");
+                }
+                let expire: u64 = config::var("NOTIFY_PUSHOVER_EXPIRE_SECONDS")
+                    .unwrap_or_else(|_| "3600".to_string())
+                    .parse()
+                    .context("NOTIFY_PUSHOVER_EXPIRE_SECONDS must be a number")?;
+                channels.push(Channel::Pushover { token, user, priority, retry, expire });
+            },
+            (None, None) => {},
+            _ => anyhow::bail!("Both NOTIFY_PUSHOVER_TOKEN and NOTIFY_PUSHOVER_USER must be set for Pushover notifications"),
+        }
+
+        // NOTIFY_MIN_SEVERITY and NOTIFY_RATE_LIMIT for all channels, NOTIFY_<CHANNEL>_... override them
+        let default_min = Severity::from_env("NOTIFY_MIN_SEVERITY", Severity::Info)?;
+        let default_limit = env_non_empty("NOTIFY_RATE_LIMIT");
+        let mut routed: Vec<(Channel, Severity, Option<Mutex<ChannelLimit>>)> = vec![];
+        for channel in channels {
+            let key = format!("NOTIFY_{}_MIN_SEVERITY", channel.name().to_uppercase());
+            let min = Severity::from_env(&key, default_min)?;
+            let key = format!("NOTIFY_{}_RATE_LIMIT", channel.name().to_uppercase());
+            let limit = ChannelLimit::from_env(&key, default_limit.as_deref())?;
+            routed.push((channel, min, limit.map(Mutex::new)));
+        }
+
+        let log_min_severity = Severity::from_env("LOG_MIN_SEVERITY", Severity::Info)?;
+        let history = match history {
+            Some(store) => Some((store, Severity::from_env("EVENTS_MIN_SEVERITY", Severity::Debug)?)),
+            None => None,
+        };
+
+        let redis = match RedisOutput::from_env()? {
+            Some(output) => Some((output, Severity::from_env("REDIS_MIN_SEVERITY", Severity::Debug)?)),
+            None => None,
+        };
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        Ok(Notifier {
+            client,
+            channels: routed,
+            log_min_severity,
+            history,
+            redis,
+            snmp: TrapSender::from_env()?,
+            alertmanager: Alertmanager::from_env()?,
+            incidents: Mutex::new(HashMap::new()),
+            silences: Mutex::new(SilenceCache::default()),
+            events: None,
+        })
+    }
+
+    /// Also publish every event to `events`: redacted, with labels and incident. The stream is independent
+    /// of the built-in channels - subscribers get events of every severity, silenced and rate-limited ones too,
+    /// and a slow subscriber doesn't hold back notifications
+    pub fn with_events(mut self, events: broadcast::Sender<Event>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Events of the server from now on belong to this incident (None - to no incident), for `history show`
+    pub fn set_incident(&self, server: &str, incident: Option<String>) {
+        let mut incidents = self.incidents.lock().unwrap_or_else(PoisonError::into_inner);
+        match incident {
+            Some(incident) => incidents.insert(server.to_string(), incident),
+            None => incidents.remove(server),
+        };
+    }
+
+    /// Configured channels with their minimum severity and rate limit, e.g. "slack (warning+, max 10/1h)"
+    pub fn channel_names(&self) -> Vec<String> {
+        self.channels
+            .iter()
+            .map(|(c, min, limit)| match limit {
+                Some(limit) => {
+                    let limit = limit.lock().unwrap_or_else(PoisonError::into_inner);
+                    format!("{} ({}+, max {})", c.name(), min.to_string().to_lowercase(), limit.spec)
+                },
+                None => format!("{} ({}+)", c.name(), min.to_string().to_lowercase()),
+            })
+            .collect()
+    }
+
+    /// Log event and send it to every destination accepting its severity.
+    /// Secrets are redacted from the message first. Failures are only logged - monitoring must go on
+    pub async fn event(&self, mut event: Event) {
+        event.message = redact::redact(&event.message);
+        if event.incident.is_none() {
+            event.incident = self.incidents.lock().unwrap_or_else(PoisonError::into_inner).get(&event.server).cloned();
+        }
+        if let Some(events) = &self.events {
+            // No subscribers is not an error
+            let _ = events.send(event.clone());
+        }
+        if event.severity >= self.log_min_severity {
+            println!("[{}] {} {}", event.time.format("%Y-%m-%d %H:%M:%S"), event.severity, event.message);
+        }
+
+        if let Some((store, min)) = &self.history {
+            if event.severity >= *min {
+                if let Err(e) = store.append(&event) {
+                    println!("✗ Failed to record event in {}: {}", store.describe(), redact::redact(&format!("{:#}", e)));
+                }
+            }
+        }
+
+        if let Some((output, min)) = &self.redis {
+            if event.severity >= *min {
+                if let Err(e) = output.publish(&event).await {
+                    println!("✗ Failed to publish event: {}", redact::redact(&format!("{:#}", e)));
+                }
+            }
+        }
+
+        if let Some(alertmanager) = &self.alertmanager {
+            if let Err(e) = alertmanager.handle(&self.client, &event).await {
+                println!("✗ {}", redact::redact(&format!("{:#}", e)));
+            }
+        }
+
+        // Silences mute notifications, the event is still logged and recorded above.
+        // The file is read again when it changes, so `unshelve silence` applies to a running daemon
+        if !self.channels.is_empty() || self.snmp.is_some() {
+            let silenced = match self.silences.lock().unwrap_or_else(PoisonError::into_inner).get() {
+                Ok(silences) => silences.matching(&event).map(String::from),
+                Err(e) => {
+                    println!("✗ {:#}", e);
+                    None
+                },
+            };
+            if let Some(id) = silenced {
+                println!("Silenced by {} - {} notification not sent", id, event.kind);
+                return;
+            }
+        }
+
+        // Trap selection is by event kind (UNSHELVE-MIB notifications), not severity
+        if let Some(snmp) = &self.snmp {
+            if let Err(e) = snmp.send(&event).await {
+                println!("✗ {:#}", e);
+            }
+        }
+
+        for (channel, min, limit) in &self.channels {
+            if event.severity < *min {
+                continue;
+            }
+            let mut message = format!("[{}] {}", event.severity, event.message);
+            if !event.labels.is_empty() {
+                message = format!("{}\nLabels: {}", message, labels::describe(&event.labels));
+            }
+            if let Some(limit) = limit {
+                let mut limit = limit.lock().unwrap_or_else(PoisonError::into_inner);
+                if !limit.admit(event.kind) {
+                    println!("Rate limit {} of {} reached - {} event suppressed", limit.spec, channel.name(), event.kind);
+                    continue;
+                }
+                if let Some(summary) = limit.take_summary() {
+                    message = format!("{}\n{}", message, summary);
+                }
+            }
+            if let Err(e) = channel.send(&self.client, event.severity, &message).await {
+                println!("✗ {}", redact::redact(&e.to_string()));
+            }
+        }
+    }
+
+    /// Event history with its minimum severity, e.g. "events.jsonl (debug+)"
+    pub fn history(&self) -> Option<String> {
+        self.history
+            .as_ref()
+            .map(|(store, min)| format!("{} ({}+)", store.describe(), min.to_string().to_lowercase()))
+    }
+
+    /// Redis output with its minimum severity, e.g. "redis:6379 (channel unshelve:events) (debug+)"
+    pub fn redis(&self) -> Option<String> {
+        self.redis
+            .as_ref()
+            .map(|(output, min)| format!("{} ({}+)", output.describe(), min.to_string().to_lowercase()))
+    }
+
+    /// Alertmanager alerts endpoint
+    pub fn alertmanager(&self) -> Option<String> {
+        self.alertmanager.as_ref().map(|a| redact::redact(a.url()))
+    }
+
+    /// SNMP trap receiver, e.g. "nms.example.com:162"
+    pub fn snmp(&self) -> Option<String> {
+        self.snmp.as_ref().map(|s| s.target().to_string())
+    }
+
+    /// Current state is kept in the Redis hash (REDIS_STATE_KEY)
+    pub fn keeps_state(&self) -> bool {
+        self.redis.as_ref().is_some_and(|(output, _)| output.keeps_state())
+    }
+
+    /// Update the server state in the Redis hash, failures are only logged
+    pub async fn publish_state(&self, server: &str, state: &serde_json::Value) {
+        if let Some((output, _)) = &self.redis {
+            if let Err(e) = output.set_state(server, state).await {
+                println!("✗ Failed to update Redis state: {}", redact::redact(&format!("{:#}", e)));
+            }
+        }
+    }
+
+    /// Send test message to all channels or the one with the given name
+    pub async fn test(&self, channel_name: Option<&str>) -> Result<()> {
+        self.deliver(channel_name, Severity::Info, &format!("[unshelve] Test message from {}", hostname())).await
+    }
+
+    /// Send message to all channels accepting its severity, or to the one with the given name regardless of
+    /// its minimum severity. Error if any channel failed
+    pub async fn deliver(&self, channel_name: Option<&str>, severity: Severity, message: &str) -> Result<()> {
+        let channels: Vec<&Channel> = self.channels
+            .iter()
+            .filter(|(c, min, _)| match channel_name {
+                Some(name) => c.name() == name,
+                None => severity >= *min,
+            })
+            .map(|(c, _, _)| c)
+            .collect();
+
+        if channels.is_empty() {
+            match channel_name {
+                Some(name) => anyhow::bail!("Notification channel '{}' is not configured", name),
+                None if self.channels.is_empty() => anyhow::bail!("No notification channels configured"),
+                None => {
+                    println!("No notification channel accepts {} messages", severity);
+                    return Ok(());
+                },
+            }
+        }
+
+        let mut failed = 0;
+        for channel in channels {
+            match channel.send(&self.client, severity, &redact::redact(message)).await {
+                Ok(_) => println!("✓ {} - message sent", channel.name()),
+                Err(e) => {
+                    println!("✗ {}", redact::redact(&e.to_string()));
+                    failed += 1;
+                }
+            }
+        }
+
+        if failed > 0 {
+            anyhow::bail!("{} notification channel(s) failed", failed);
+        }
+        Ok(())
+    }
+}
+
+fn env_non_empty(key: &str) -> Option<String> {
+    config::var(key).ok().filter(|v| !v.trim().is_empty())
+}
+
+pub fn hostname() -> String {
+    std::fs::read_to_string("/etc/hostname")
+        .map(|h| h.trim().to_string())
+        .unwrap_or_else(|_| "unknown host".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn subscribers_get_stamped_events() {
+        let (events, mut first) = broadcast::channel(8);
+        let mut second = events.subscribe();
+        let notifier = Notifier::with_history(None).unwrap().with_events(events);
+        notifier.set_incident("web1", Some("inc-1".to_string()));
+        notifier.event(Event::new(Severity::Warning, "ping_failed", "web1", "web1 doesn't answer".to_string())).await;
+        notifier.event(Event::new(Severity::Debug, "ping_ok", "web2", "web2 answers".to_string())).await;
+
+        for receiver in [&mut first, &mut second] {
+            let event = receiver.try_recv().unwrap();
+            assert_eq!((event.kind, event.server.as_str()), ("ping_failed", "web1"));
+            assert_eq!(event.incident.as_deref(), Some("inc-1"));
+            let event = receiver.try_recv().unwrap();
+            assert_eq!((event.kind, event.incident), ("ping_ok", None));
+        }
+        assert!(first.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn channel_limit_throttles_burst_from_several_servers() {
+        let mut notifier = Notifier::with_history(None).unwrap();
+        let limit = ChannelLimit::from_env("UNSHELVE_TEST_UNSET_RATE_LIMIT", Some("2/1h")).unwrap().unwrap();
+        notifier.channels = vec![(Channel::Webhook { url: "http://127.0.0.1:9/".to_string() }, Severity::Info, Some(Mutex::new(limit)))];
+        let notifier = Arc::new(notifier);
+
+        let burst = ["web1", "web2", "db"].map(|server| {
+            let notifier = notifier.clone();
+            async move {
+                notifier.event(Event::new(Severity::Critical, "server_shelved", server, format!("{} is shelved", server))).await;
+            }
+        });
+        futures::future::join_all(burst).await;
+
+        let mut limit = notifier.channels[0].2.as_ref().unwrap().lock().unwrap();
+        assert_eq!(limit.sent.len(), 2);
+        assert_eq!(limit.take_summary().unwrap(), "(suppressed 1 similar events by rate limit 2/1h: 1 server_shelved)");
+    }
+}
@@ -0,0 +1,177 @@
+use anyhow::{Context, Result};
+use osauth::services::COMPUTE;
+use serde_json::Value;
+
+/// OS-EXT-STS:power_state of a running server
+pub const POWER_RUNNING: u64 = 1;
+
+/// Server details as Nova returns them, with the extended attributes the OpenStack client doesn't expose
+pub struct ServerDetails {
+    raw: Value,
+}
+
+impl ServerDetails {
+    /// The `server` object of GET /servers/{id}
+    pub fn new(raw: Value) -> Self {
+        ServerDetails { raw }
+    }
+
+    pub fn placement(&self) -> Placement {
+        Placement {
+            zone: self.attribute("OS-EXT-AZ:availability_zone").unwrap_or_default().to_string(),
+            host: self.attribute("OS-EXT-SRV-ATTR:host").map(String::from),
+            host_id: self.attribute("hostId").map(String::from),
+        }
+    }
+
+    /// OS-EXT-STS:task_state - unshelving, powering-off, shelving_offloading... None when no task runs
+    pub fn task_state(&self) -> Option<&str> {
+        self.attribute("OS-EXT-STS:task_state")
+    }
+
+    fn attribute(&self, key: &str) -> Option<&str> {
+        self.raw[key].as_str().filter(|v| !v.is_empty())
+    }
+}
+
+/// Availability zone and host of the server, as far as the credentials see them
+#[derive(Clone, Debug, PartialEq)]
+pub struct Placement {
+    pub zone: String,
+    /// OS-EXT-SRV-ATTR:host, visible to admin credentials only
+    pub host: Option<String>,
+    /// hostId - the host hashed with the project, visible to every user. None while the server
+    /// has no host (shelved offloaded)
+    pub host_id: Option<String>,
+}
+
+impl Placement {
+    /// "zone nova, host cmp-12", the host id if the host name isn't visible
+    pub fn describe(&self) -> String {
+        match (&self.host, &self.host_id) {
+            (Some(host), _) => format!("zone {}, host {}", self.zone, host),
+            (None, Some(id)) => format!("zone {}, host id {} (host name is visible to admin credentials only)", self.zone, id),
+            (None, None) => format!("zone {}, no host", self.zone),
+        }
+    }
+
+    /// What changed since `before`, e.g. "host cmp-12 -> cmp-7". Hosts are compared by hostId,
+    /// so it works without admin credentials. Empty if nothing changed or there is nothing to compare
+    pub fn changes_from(&self, before: &Placement) -> Vec<String> {
+        let mut changes = vec![];
+        if !before.zone.is_empty() && before.zone != self.zone {
+            changes.push(format!("availability zone {} -> {}", before.zone, self.zone));
+        }
+        if let (Some(id_before), Some(id_after)) = (&before.host_id, &self.host_id) {
+            if id_before != id_after {
+                changes.push(match (&before.host, &self.host) {
+                    (Some(host_before), Some(host_after)) => format!("host {} -> {}", host_before, host_after),
+                    _ => format!("host id {} -> {}", id_before, id_after),
+                });
+            }
+        }
+        changes
+    }
+}
+
+/// Server group the server is a member of
+#[derive(Debug, PartialEq)]
+pub struct ServerGroup {
+    pub name: String,
+    /// affinity, anti-affinity, soft-affinity or soft-anti-affinity
+    pub policy: String,
+}
+
+/// GET on the compute API with the session of the OpenStack client
+pub async fn get(cloud: &openstack::Cloud, path: &[&str]) -> Result<Value> {
+    cloud
+        .session()
+        .get(COMPUTE, path)
+        .fetch()
+        .await
+        .context(format!("GET /{} failed", path.join("/")))
+}
+
+/// GET /servers/{id}
+pub async fn server(cloud: &openstack::Cloud, server_id: &str) -> Result<ServerDetails> {
+    let body = get(cloud, &["servers", server_id])
+        .await
+        .context(format!("Failed to get details of server {}", server_id))?;
+    Ok(ServerDetails::new(body["server"].clone()))
+}
+
+/// Server groups of the project the server is a member of
+pub async fn server_groups(cloud: &openstack::Cloud, server_id: &str) -> Result<Vec<ServerGroup>> {
+    let body = get(cloud, &["os-server-groups"]).await.context("Failed to list server groups")?;
+    Ok(groups_of(&body, server_id))
+}
+
+/// Groups listing `server_id` as a member. The policy is `policy` since microversion 2.64, `policies` before
+pub fn groups_of(body: &Value, server_id: &str) -> Vec<ServerGroup> {
+    body["server_groups"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|group| group["members"].as_array().is_some_and(|members| members.iter().any(|m| m.as_str() == Some(server_id))))
+        .map(|group| ServerGroup {
+            name: group["name"].as_str().unwrap_or_default().to_string(),
+            policy: group["policy"]
+                .as_str()
+                .or_else(|| group["policies"][0].as_str())
+                .unwrap_or("unknown")
+                .to_string(),
+        })
+        .collect()
+}
+
+/// "web (anti-affinity), db (affinity)" for server-info and notifications
+pub fn describe(groups: &[ServerGroup]) -> String {
+    groups.iter().map(|g| format!("{} ({})", g.name, g.policy)).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_of_server_with_old_and_new_policy_format() {
+        let body = serde_json::json!({ "server_groups": [
+            { "name": "web", "policies": ["anti-affinity"], "members": ["s1", "s2"] },
+            { "name": "db", "policy": "soft-affinity", "members": ["s1"] },
+            { "name": "other", "policy": "affinity", "members": ["s3"] },
+        ]});
+        let groups = groups_of(&body, "s1");
+        assert_eq!(describe(&groups), "web (anti-affinity), db (soft-affinity)");
+        assert!(groups_of(&body, "s4").is_empty());
+    }
+
+    fn placement(zone: &str, host: Option<&str>, host_id: Option<&str>) -> Placement {
+        Placement { zone: zone.to_string(), host: host.map(String::from), host_id: host_id.map(String::from) }
+    }
+
+    #[test]
+    fn task_state_none_without_task() {
+        let details = ServerDetails { raw: serde_json::json!({ "OS-EXT-STS:task_state": "powering-off" }) };
+        assert_eq!(details.task_state(), Some("powering-off"));
+        let details = ServerDetails { raw: serde_json::json!({ "OS-EXT-STS:task_state": null }) };
+        assert_eq!(details.task_state(), None);
+    }
+
+    #[test]
+    fn placement_from_server_details() {
+        let details = ServerDetails { raw: serde_json::json!({ "OS-EXT-AZ:availability_zone": "az1", "hostId": "" }) };
+        assert_eq!(details.placement(), placement("az1", None, None));
+    }
+
+    #[test]
+    fn host_changes_compared_by_host_id() {
+        let before = placement("az1", None, Some("a1"));
+        assert!(placement("az1", None, Some("a1")).changes_from(&before).is_empty());
+        assert_eq!(placement("az2", None, Some("b2")).changes_from(&before), ["availability zone az1 -> az2", "host id a1 -> b2"]);
+        let before = placement("az1", Some("cmp-12"), Some("a1"));
+        assert_eq!(placement("az1", Some("cmp-7"), Some("b2")).changes_from(&before), ["host cmp-12 -> cmp-7"]);
+        // Offloaded: no host before unshelve
+        assert!(placement("az1", None, Some("b2")).changes_from(&placement("", None, None)).is_empty());
+        assert_eq!(placement("az1", None, Some("a1")).describe(), "zone az1, host id a1 (host name is visible to admin credentials only)");
+    }
+}
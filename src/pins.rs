@@ -0,0 +1,125 @@
+use std::fs;
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use anyhow::{Context, Result};
+
+use crate::config;
+
+/// UUIDs pinned for servers monitored by name, stored in PIN_FILE as name=uuid lines.
+/// One store is shared by the monitors of a daemon, so a re-pin doesn't drop the pins of other servers
+pub struct PinStore {
+    path: PathBuf,
+    pins: Mutex<HashMap<String, String>>,
+}
+
+impl PinStore {
+    pub fn load() -> Result<Self> {
+        let path = path();
+        let mut pins: HashMap<String, String> = HashMap::new();
+
+        if path.exists() {
+            let content = fs::read_to_string(&path)
+                .context(format!("Failed to read pin file: {}", path.display()))?;
+            for line in content.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+                if let Some((name, uuid)) = line.split_once('=') {
+                    pins.insert(name.trim().to_string(), uuid.trim().to_string());
+                }
+            }
+        }
+
+        Ok(PinStore { path, pins: Mutex::new(pins) })
+    }
+
+    fn save(&self, pins: &HashMap<String, String>) -> Result<()> {
+        let mut lines: Vec<String> = pins.iter().map(|(n, u)| format!("{}={}", n, u)).collect();
+        lines.sort();
+        fs::write(&self.path, lines.join("\n") + "\n")
+            .context(format!("Failed to write pin file: {}", self.path.display()))
+    }
+
+    /// Check that the name still resolves to the pinned UUID, pinning it on first run.
+    /// Returns false if the server was recreated and acting on it isn't confirmed
+    pub fn verify(&self, name: &str, uuid: &str, interactive: bool) -> Result<bool> {
+        // Identifier is the UUID itself - nothing to pin
+        if name == uuid {
+            return Ok(true);
+        }
+
+        // Held through the prompt - other monitors wait instead of pinning in between
+        let mut pins = self.pins.lock().map_err(|_| anyhow::anyhow!("Pin store lock poisoned"))?;
+        let pinned = match pins.get(name) {
+            Some(pinned) if pinned == uuid => return Ok(true),
+            Some(pinned) => pinned.clone(),
+            None => {
+                pins.insert(name.to_string(), uuid.to_string());
+                self.save(&pins)?;
+                println!("Pinned server '{}' to UUID {}", name, uuid);
+                return Ok(true);
+            }
+        };
+
+        println!("⚠️ Server '{}' now resolves to UUID {} but {} is pinned - server was recreated?", name, uuid, pinned);
+
+        let allow_recreate = config::var("ALLOW_SERVER_RECREATE")
+            .map(|v| v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+
+        let accepted = if allow_recreate {
+            println!("ALLOW_SERVER_RECREATE is set - accepting new instance");
+            true
+        } else if interactive && std::io::stdin().is_terminal() {
+            confirm(&format!("Monitor and act on the new instance {}?", uuid))?
+        } else {
+            false
+        };
+
+        if accepted {
+            pins.insert(name.to_string(), uuid.to_string());
+            self.save(&pins)?;
+            println!("Re-pinned server '{}' to UUID {}", name, uuid);
+        }
+        Ok(accepted)
+    }
+}
+
+/// PIN_FILE
+pub fn path() -> PathBuf {
+    PathBuf::from(config::var("PIN_FILE").unwrap_or_else(|_| ".unshelve-pins".to_string()))
+}
+
+#[cfg(feature = "cli")]
+fn confirm(prompt: &str) -> Result<bool> {
+    dialoguer::Confirm::new()
+        .with_prompt(prompt)
+        .default(false)
+        .interact()
+        .context("Failed to read confirmation")
+}
+
+/// Builds without the CLI have no prompts
+#[cfg(not(feature = "cli"))]
+fn confirm(_prompt: &str) -> Result<bool> {
+    println!("Set ALLOW_SERVER_RECREATE=true to accept the new instance");
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pins_of_every_server_are_saved() {
+        let path = std::env::temp_dir().join(format!("unshelve-test-pins-{}", std::process::id()));
+        let store = PinStore { path: path.clone(), pins: Mutex::new(HashMap::new()) };
+        assert!(store.verify("web", "uuid-web", false).unwrap());
+        assert!(store.verify("db", "uuid-db", false).unwrap());
+        assert!(store.verify("web", "uuid-web", false).unwrap());
+        assert!(store.verify("uuid-cache", "uuid-cache", false).unwrap());
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(content, "db=uuid-db\nweb=uuid-web\n");
+    }
+}
@@ -0,0 +1,276 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::process::Stdio;
+use anyhow::{Context, Result};
+use tokio::process::Command;
+use tokio::time::{sleep, Duration};
+
+use crate::config;
+use crate::monitor::parse_duration;
+use crate::probe::Prober;
+use crate::state::ServerState;
+use crate::warmup;
+
+/// Delay between polls of wait-* steps
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Step timeout when none is given in RECOVERY_PIPELINE
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Step {
+    Unshelve,
+    /// Poll OpenStack until the server is ACTIVE and running
+    WaitActive,
+    /// Associate the floating IP with the first port of the server
+    AttachFloatingIp(IpAddr),
+    /// Ping PING_IP until it answers
+    WaitPing,
+    /// Shell command, UNSHELVE_SERVER_NAME and UNSHELVE_SERVER_ID are passed in env
+    Run(String),
+    /// WARMUP_URLS / WARMUP_COMMAND
+    Warmup,
+    /// Notification with the results of the steps so far
+    Notify,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Unshelve => write!(f, "unshelve"),
+            Step::WaitActive => write!(f, "wait-active"),
+            Step::AttachFloatingIp(ip) => write!(f, "attach-fip={}", ip),
+            Step::WaitPing => write!(f, "wait-ping"),
+            Step::Run(command) => write!(f, "run={}", command),
+            Step::Warmup => write!(f, "warmup"),
+            Step::Notify => write!(f, "notify"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Stage {
+    pub step: Step,
+    pub timeout: Duration,
+    /// Failure is reported and the next steps still run (`?` suffix), otherwise the pipeline stops
+    pub optional: bool,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.step)?;
+        if self.timeout != DEFAULT_TIMEOUT {
+            write!(f, ":{}s", self.timeout.as_secs())?;
+        }
+        if self.optional {
+            write!(f, "?")?;
+        }
+        Ok(())
+    }
+}
+
+/// What the steps work on, borrowed from the monitor
+pub struct Target<'a> {
+    pub cloud: &'a openstack::Cloud,
+    pub server_id: &'a str,
+    pub server_name: &'a str,
+    /// Prober and PING_IP, None in status-only mode
+    pub ping: Option<&'a mut (Prober, IpAddr)>,
+}
+
+/// Ordered recovery steps from RECOVERY_PIPELINE, e.g.
+/// 'unshelve > wait-active:15m > attach-fip=203.0.113.10 > wait-ping:10m > warmup? > notify'.
+/// Each step is NAME[=ARG][:TIMEOUT][?]. The pipeline starts with unshelve, which keeps
+/// its backoff and precondition handling; the other steps run after the command is accepted
+pub struct Pipeline {
+    stages: Vec<Stage>,
+}
+
+impl Pipeline {
+    /// Only unshelve if RECOVERY_PIPELINE is not set
+    pub fn from_env() -> Result<Self> {
+        let raw = config::var("RECOVERY_PIPELINE").ok().filter(|v| !v.trim().is_empty()).unwrap_or_else(|| "unshelve".to_string());
+        Self::parse(&raw).context("Invalid RECOVERY_PIPELINE")
+    }
+
+    pub fn parse(value: &str) -> Result<Self> {
+        let mut stages = vec![];
+        for token in value.split('>').map(str::trim) {
+            if token.is_empty() {
+                anyhow::bail!("Empty step in '{}'", value);
+            }
+            let (token, optional) = match token.strip_suffix('?') {
+                Some(token) => (token.trim_end(), true),
+                None => (token, false),
+            };
+            // Timeout is the part after the last ':' if it parses as a duration, run=... may contain ':'
+            let (spec, timeout) = match token.rsplit_once(':').map(|(s, t)| (s, parse_duration(t))) {
+                Some((spec, Ok(timeout))) => (spec.trim(), timeout),
+                _ => (token, DEFAULT_TIMEOUT),
+            };
+            let (name, arg) = match spec.split_once('=') {
+                Some((name, arg)) => (name.trim(), Some(arg.trim())),
+                None => (spec, None),
+            };
+            let step = match (name.to_lowercase().as_str(), arg) {
+                ("unshelve", None) => Step::Unshelve,
+                ("wait-active", None) => Step::WaitActive,
+                ("attach-fip", Some(ip)) => Step::AttachFloatingIp(ip.parse().context(format!("Invalid floating IP '{}'", ip))?),
+                ("wait-ping", None) => Step::WaitPing,
+                ("run", Some(command)) if !command.is_empty() => Step::Run(command.to_string()),
+                ("warmup", None) => Step::Warmup,
+                ("notify", None) => Step::Notify,
+                ("attach-fip", None) => anyhow::bail!("attach-fip needs an address: attach-fip=203.0.113.10"),
+                ("run", _) => anyhow::bail!("run needs a command: run=/usr/local/bin/script.sh"),
+                _ => anyhow::bail!("Unknown step '{}'. Allowed steps: unshelve, wait-active, attach-fip=IP, wait-ping, run=COMMAND, warmup, notify", spec),
+            };
+            stages.push(Stage { step, timeout, optional });
+        }
+        if stages.first().map(|s| &s.step) != Some(&Step::Unshelve) {
+            anyhow::bail!("The pipeline must start with unshelve");
+        }
+        if stages.iter().skip(1).any(|s| s.step == Step::Unshelve) {
+            anyhow::bail!("unshelve can only be the first step");
+        }
+        Ok(Pipeline { stages })
+    }
+
+    /// Steps after unshelve
+    pub fn after_unshelve(&self) -> &[Stage] {
+        &self.stages[1..]
+    }
+
+    /// Warmup runs as a step instead of on recovery
+    pub fn has_warmup(&self) -> bool {
+        self.stages.iter().any(|s| s.step == Step::Warmup)
+    }
+
+    pub fn describe(&self) -> String {
+        self.stages.iter().map(Stage::to_string).collect::<Vec<_>>().join(" > ")
+    }
+}
+
+impl Stage {
+    /// Run the step within its timeout, returns a result line for notifications.
+    /// Notify is handled by the caller, it has the notifier
+    pub async fn run(&self, target: &mut Target<'_>) -> Result<String> {
+        match tokio::time::timeout(self.timeout, self.execute(target)).await {
+            Ok(result) => result,
+            Err(_) => anyhow::bail!("timed out after {}s", self.timeout.as_secs()),
+        }
+    }
+
+    async fn execute(&self, target: &mut Target<'_>) -> Result<String> {
+        match &self.step {
+            Step::Unshelve | Step::Notify => Ok(String::new()),
+            Step::WaitActive => loop {
+                match target.cloud.get_server(target.server_id).await {
+                    Ok(server) => match ServerState::of(&server) {
+                        ServerState::Active => return Ok("server is ACTIVE".to_string()),
+                        ServerState::Error | ServerState::SoftDeleted | ServerState::Deleted => anyhow::bail!("server went to {} status", server.status()),
+                        _ => println!("Pipeline: waiting for ACTIVE, status {} (power state {:?})", server.status(), server.power_state()),
+                    },
+                    Err(e) => println!("Pipeline: failed to get server info: {}", e),
+                }
+                sleep(POLL_INTERVAL).await;
+            },
+            Step::AttachFloatingIp(ip) => attach_floating_ip(target, *ip).await,
+            Step::WaitPing => {
+                let Some((prober, ip)) = target.ping.as_deref_mut() else {
+                    anyhow::bail!("wait-ping needs PING_IP - not available in status-only mode");
+                };
+                loop {
+                    match prober.probe(*ip).await {
+                        Ok(rtt) => return Ok(format!("{} answers ping ({:?})", ip, rtt)),
+                        Err(e) => println!("Pipeline: {} ping failed: {}", ip, e),
+                    }
+                    sleep(POLL_INTERVAL).await;
+                }
+            },
+            Step::Run(command) => {
+                let output = Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .env("UNSHELVE_SERVER_NAME", target.server_name)
+                    .env("UNSHELVE_SERVER_ID", target.server_id)
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::piped())
+                    .kill_on_drop(true)
+                    .output()
+                    .await
+                    .context("Failed to run command")?;
+                if !output.status.success() {
+                    anyhow::bail!("exited with {}: {}", output.status, String::from_utf8_lossy(&output.stderr).trim());
+                }
+                Ok("command finished".to_string())
+            },
+            Step::Warmup => match warmup::run(target.server_name, target.server_id).await? {
+                Some(outcome) if !outcome.failures.is_empty() => anyhow::bail!("{}", outcome),
+                Some(outcome) => Ok(outcome.to_string()),
+                None => Ok("nothing configured (WARMUP_URLS, WARMUP_COMMAND)".to_string()),
+            },
+        }
+    }
+}
+
+/// Floating IPs are not restored by unshelve in some clouds - associate it with the server port again
+async fn attach_floating_ip(target: &Target<'_>, ip: IpAddr) -> Result<String> {
+    let mut floating_ip = target.cloud
+        .find_floating_ips()
+        .with_floating_ip_address(ip)
+        .one()
+        .await
+        .context(format!("Floating IP {} not found", ip))?;
+    let port = target.cloud
+        .find_ports()
+        .with_device_id(target.server_id)
+        .one()
+        .await
+        .context("Failed to find the server port")?;
+    if floating_ip.port_id().as_deref() == Some(port.id().as_str()) {
+        return Ok(format!("floating IP {} already attached", ip));
+    }
+    floating_ip
+        .associate(port.id().clone(), None)
+        .await
+        .context(format!("Failed to attach floating IP {}", ip))?;
+    Ok(format!("floating IP {} attached to port {}", ip, port.id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_with_timeouts_and_optional() {
+        let pipeline = Pipeline::parse("unshelve > wait-active:15m > attach-fip=203.0.113.10 > wait-ping:10m? > warmup? > notify").unwrap();
+        let stages = pipeline.after_unshelve();
+        assert_eq!(stages[0].step, Step::WaitActive);
+        assert_eq!(stages[0].timeout, Duration::from_secs(900));
+        assert_eq!(stages[1].step, Step::AttachFloatingIp("203.0.113.10".parse().unwrap()));
+        assert_eq!(stages[1].timeout, DEFAULT_TIMEOUT);
+        assert!(stages[2].optional && stages[2].timeout == Duration::from_secs(600));
+        assert!(stages[3].optional);
+        assert!(pipeline.has_warmup());
+        assert_eq!(pipeline.describe(), "unshelve > wait-active:900s > attach-fip=203.0.113.10 > wait-ping:600s? > warmup? > notify");
+    }
+
+    #[test]
+    fn run_command_with_colons() {
+        let pipeline = Pipeline::parse("unshelve > run=cmd:with:colons > run=curl -sf http://10.0.0.11:8080/health:30s?").unwrap();
+        let stages = pipeline.after_unshelve();
+        assert_eq!(stages[0].step, Step::Run("cmd:with:colons".to_string()));
+        assert_eq!(stages[0].timeout, DEFAULT_TIMEOUT);
+        assert_eq!(stages[1].step, Step::Run("curl -sf http://10.0.0.11:8080/health".to_string()));
+        assert_eq!(stages[1].timeout, Duration::from_secs(30));
+        assert!(stages[1].optional);
+    }
+
+    #[test]
+    fn invalid_pipelines() {
+        for invalid in ["wait-active > unshelve", "unshelve > unshelve", "unshelve >", "unshelve > run=", "unshelve > attach-fip",
+                        "unshelve > attach-fip=gateway", "unshelve > reboot"] {
+            assert!(Pipeline::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::process::Stdio;
+use anyhow::{Context, Result};
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+
+use crate::config::Vars;
+
+/// Result of the precondition hooks consulted before an automatic unshelve
+pub enum Precondition {
+    /// No hook configured or every configured hook allowed the action
+    Passed,
+    /// A hook rejected the action (e.g. no credit left), with its reason
+    Failed(String),
+}
+
+/// Run the configured precondition hooks.
+/// PRECONDITION_COMMAND - shell command, exit code 0 allows unshelve
+/// PRECONDITION_URL - HTTP GET, 2xx response allows unshelve
+pub async fn check(server_name: &str, server_id: &str) -> Result<Precondition> {
+    check_in(&Vars::default(), server_name, server_id).await
+}
+
+/// `check` with the hooks from config values read into memory
+pub async fn check_in(vars: &Vars, server_name: &str, server_id: &str) -> Result<Precondition> {
+    let timeout_secs: u64 = vars.get("PRECONDITION_TIMEOUT_SECONDS")
+        .unwrap_or_else(|| "10".to_string())
+        .parse()
+        .context("PRECONDITION_TIMEOUT_SECONDS must be a number")?;
+    let limit = Duration::from_secs(timeout_secs);
+
+    if let Some(command) = vars.get("PRECONDITION_COMMAND") {
+        if let Precondition::Failed(reason) = run_command(&command, server_name, server_id, limit).await {
+            return Ok(Precondition::Failed(reason));
+        }
+    }
+
+    if let Some(url) = vars.get("PRECONDITION_URL") {
+        if let Precondition::Failed(reason) = call_url(&url, server_name, server_id, limit).await {
+            return Ok(Precondition::Failed(reason));
+        }
+    }
+
+    Ok(Precondition::Passed)
+}
+
+/// Configured hooks for the startup summary, None if there are none
+pub fn describe() -> Option<String> {
+    let hooks: Vec<String> = [("command", "PRECONDITION_COMMAND"), ("url", "PRECONDITION_URL")]
+        .iter()
+        .filter_map(|(name, key)| Vars::default().get(key).map(|value| format!("{} {}", name, value)))
+        .collect();
+    if hooks.is_empty() { None } else { Some(hooks.join(", ")) }
+}
+
+async fn run_command(command: &str, server_name: &str, server_id: &str, limit: Duration) -> Precondition {
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("UNSHELVE_SERVER_NAME", server_name)
+        .env("UNSHELVE_SERVER_ID", server_id)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .output();
+
+    match timeout(limit, child).await {
+        Ok(Ok(output)) if output.status.success() => Precondition::Passed,
+        Ok(Ok(output)) => {
+            let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            let details = if stdout.is_empty() { stderr } else { stdout };
+            Precondition::Failed(format!("command exited with {}: {}", output.status, details))
+        },
+        Ok(Err(e)) => Precondition::Failed(format!("failed to run command: {}", e)),
+        Err(_) => Precondition::Failed(format!("command timed out after {} seconds", limit.as_secs())),
+    }
+}
+
+async fn call_url(url: &str, server_name: &str, server_id: &str, limit: Duration) -> Precondition {
+    let client = match reqwest::Client::builder().timeout(limit).build() {
+        Ok(client) => client,
+        Err(e) => return Precondition::Failed(format!("failed to create HTTP client: {}", e)),
+    };
+
+    let response = client
+        .get(url)
+        .query(&[("server_name", server_name), ("server_id", server_id)])
+        .send()
+        .await;
+
+    match response {
+        Ok(r) if r.status().is_success() => Precondition::Passed,
+        Ok(r) => {
+            let status = r.status();
+            let body = r.text().await.unwrap_or_default();
+            Precondition::Failed(format!("{} returned {}: {}", url, status, body.trim()))
+        },
+        Err(e) => Precondition::Failed(format!("request to {} failed: {}", url, e)),
+    }
+}
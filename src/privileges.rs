@@ -0,0 +1,128 @@
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use nix::unistd::{chown, setgid, setgroups, setuid, User};
+
+use crate::actions;
+use crate::config;
+use crate::drift;
+use crate::pins;
+
+/// Drop root privileges to RUN_AS_USER. Called after ICMP sockets are created,
+/// so raw sockets keep working while the rest of the run is unprivileged.
+/// State files written so far by root are handed over to the user first
+pub fn drop_privileges() -> Result<()> {
+    let is_root = is_sudo::check() == is_sudo::RunningAs::Root;
+    let user_name = config::var("RUN_AS_USER").ok().filter(|u| !u.trim().is_empty());
+
+    let Some(user_name) = user_name else {
+        if is_root {
+            println!("⚠️ Running as root. Set RUN_AS_USER to drop privileges after socket setup");
+        }
+        return Ok(());
+    };
+    if !is_root {
+        println!("RUN_AS_USER '{}' ignored - not running as root", user_name);
+        return Ok(());
+    }
+
+    let user = User::from_name(&user_name)
+        .context(format!("Failed to look up user '{}'", user_name))?
+        .ok_or_else(|| anyhow::anyhow!("User '{}' from RUN_AS_USER not found", user_name))?;
+
+    for file in state_files() {
+        file.hand_over(&user)?;
+    }
+
+    // Group first - after setuid there are no rights left to change it
+    setgroups(&[user.gid]).context("Failed to drop supplementary groups")?;
+    setgid(user.gid).context(format!("Failed to switch group to {}", user.gid))?;
+    setuid(user.uid).context(format!("Failed to switch user to {}", user.uid))?;
+
+    println!("Dropped privileges to user '{}' (uid {}, gid {})", user.name, user.uid, user.gid);
+    Ok(())
+}
+
+/// File the daemon keeps writing after privileges are dropped
+struct StateFile {
+    key: &'static str,
+    path: PathBuf,
+    /// Written to a temporary file and renamed over, so the directory must be writable too
+    replaced: bool,
+}
+
+impl StateFile {
+    /// Give an existing file to the user, check that a missing (or replaced) one can be created
+    fn hand_over(&self, user: &User) -> Result<()> {
+        let mut files = vec![self.path.clone()];
+        if self.replaced {
+            files.push(self.path.with_extension("tmp"));
+        }
+        for file in files.iter().filter(|f| f.exists()) {
+            chown(file, Some(user.uid), Some(user.gid))
+                .context(format!("Failed to give {} ({}) to user '{}'", file.display(), self.key, user.name))?;
+        }
+        if self.replaced || !self.path.exists() {
+            let dir = match self.path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            if !writable_by(dir, user)? {
+                anyhow::bail!("User '{}' from RUN_AS_USER can't write {} ({}) in {}. \
+                               Make the directory writable for the user or point {} to a directory it owns",
+                              user.name, self.path.display(), self.key, dir.display(), self.key);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// State files of the current config
+fn state_files() -> Vec<StateFile> {
+    let mut files = vec![StateFile { key: "PIN_FILE", path: pins::path(), replaced: false }];
+    if !is_set("STATE_URL") {
+        files.push(StateFile { key: "ACTION_STATE_FILE", path: actions::path(), replaced: false });
+    }
+    if is_set("DRIFT_SELECTOR") {
+        files.push(StateFile { key: "DRIFT_STATE_FILE", path: drift::path(), replaced: false });
+    }
+    if let Some(path) = value("SNAPSHOT_FILE") {
+        files.push(StateFile { key: "SNAPSHOT_FILE", path: PathBuf::from(path), replaced: true });
+    }
+    match value("HISTORY_URL") {
+        // SQLite creates journal files next to the database
+        Some(url) => {
+            if let Some(path) = url.strip_prefix("sqlite:") {
+                files.push(StateFile { key: "HISTORY_URL", path: PathBuf::from(path), replaced: true });
+            }
+        },
+        None => {
+            if let Some(path) = value("EVENTS_FILE") {
+                files.push(StateFile { key: "EVENTS_FILE", path: PathBuf::from(path), replaced: false });
+            }
+        },
+    }
+    files
+}
+
+/// Write permission of the owner, group or others bits that apply to the user
+fn writable_by(dir: &Path, user: &User) -> Result<bool> {
+    let metadata = fs::metadata(dir).context(format!("Failed to read directory {}", dir.display()))?;
+    let mode = metadata.mode();
+    Ok(if metadata.uid() == user.uid.as_raw() {
+        mode & 0o200 != 0
+    } else if metadata.gid() == user.gid.as_raw() {
+        mode & 0o020 != 0
+    } else {
+        mode & 0o002 != 0
+    })
+}
+
+fn value(key: &str) -> Option<String> {
+    config::var(key).ok().filter(|v| !v.trim().is_empty())
+}
+
+fn is_set(key: &str) -> bool {
+    value(key).is_some()
+}
@@ -0,0 +1,210 @@
+use std::fmt;
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::fd::BorrowedFd;
+use std::os::unix::fs::MetadataExt;
+use anyhow::{Context, Result};
+use socket2::Type;
+use surge_ping::{Client, Config, PingIdentifier, PingSequence, SurgeError, ICMP};
+use tokio::time::Duration;
+
+use crate::config;
+use crate::routing::Routing;
+
+/// Why a probe got no reply
+#[derive(Debug)]
+pub enum ProbeError {
+    Timeout,
+    NetworkUnreachable,
+    HostUnreachable,
+    /// An ICMP error or a malformed packet came back instead of an echo reply
+    Unreachable(String),
+    /// Socket not allowed (e.g. ping_group_range for dgram) - configuration problem, not a down server
+    PermissionDenied(String),
+    Other(String),
+}
+
+impl ProbeError {
+    /// Machine-readable cause for events
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ProbeError::Timeout => "timeout",
+            ProbeError::NetworkUnreachable => "network_unreachable",
+            ProbeError::HostUnreachable => "host_unreachable",
+            ProbeError::Unreachable(_) => "unreachable",
+            ProbeError::PermissionDenied(_) => "permission_denied",
+            ProbeError::Other(_) => "other",
+        }
+    }
+}
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeError::Timeout => write!(f, "timeout"),
+            ProbeError::NetworkUnreachable => write!(f, "network unreachable"),
+            ProbeError::HostUnreachable => write!(f, "host unreachable"),
+            ProbeError::Unreachable(e) => write!(f, "unreachable: {}", e),
+            ProbeError::PermissionDenied(e) => write!(f, "permission denied: {}", e),
+            ProbeError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<SurgeError> for ProbeError {
+    fn from(e: SurgeError) -> Self {
+        match e {
+            SurgeError::Timeout { .. } => ProbeError::Timeout,
+            SurgeError::IOError(io) => match io.kind() {
+                ErrorKind::PermissionDenied => ProbeError::PermissionDenied(io.to_string()),
+                ErrorKind::NetworkUnreachable => ProbeError::NetworkUnreachable,
+                ErrorKind::HostUnreachable => ProbeError::HostUnreachable,
+                _ => ProbeError::Other(io.to_string()),
+            },
+            e @ (SurgeError::IncorrectBufferSize | SurgeError::MalformedPacket(_) | SurgeError::NetworkError) => {
+                ProbeError::Unreachable(e.to_string())
+            },
+            other => ProbeError::Other(other.to_string()),
+        }
+    }
+}
+
+const PAYLOAD: [u8; 32] = [0; 32];
+
+/// Async ICMP echo prober. Each prober has its own identifier and increasing
+/// sequence numbers, so late or duplicate replies to earlier probes are ignored
+pub struct Prober {
+    client: Client,
+    identifier: PingIdentifier,
+    sequence: u16,
+    timeout: Duration,
+    routing: Routing,
+}
+
+impl Prober {
+    /// The socket is created in PING_NETNS and marked/bound with PING_DSCP and PING_VRF if set
+    pub fn new(use_dgram_socket: bool, ipv6: bool, timeout: Duration) -> Result<Self> {
+        Prober::with_routing(Routing::from_env("PING")?, use_dgram_socket, ipv6, timeout)
+    }
+
+    pub fn with_routing(routing: Routing, use_dgram_socket: bool, ipv6: bool, timeout: Duration) -> Result<Self> {
+        // The client registers with the runtime, also when created on the namespace thread
+        let runtime = tokio::runtime::Handle::try_current().ok();
+        let client = routing.in_namespace(move || {
+            let _runtime = runtime.as_ref().map(|r| r.enter());
+            let config = Config::builder()
+                .kind(if ipv6 { ICMP::V6 } else { ICMP::V4 })
+                .sock_type_hint(if use_dgram_socket { Type::DGRAM } else { Type::RAW })
+                .build();
+            Client::new(&config)
+        })?.context("Failed to create ICMP socket")?;
+        // The socket is owned by the client and lives as long as it
+        let fd = unsafe { BorrowedFd::borrow_raw(client.get_socket().get_native_sock()) };
+        routing.apply(fd, ipv6)?;
+
+        Ok(Prober { client, identifier: PingIdentifier(rand::random()), sequence: 0, timeout, routing })
+    }
+
+    pub fn routing(&self) -> &Routing {
+        &self.routing
+    }
+
+    /// Create prober and ping loopback with it, so socket misconfiguration
+    /// fails fast with remediation instead of looking like a down server
+    pub async fn self_test(use_dgram_socket: bool, ipv6: bool, timeout: Duration) -> Result<Self> {
+        Prober::self_test_with(Routing::from_env("PING")?, use_dgram_socket, ipv6, timeout).await
+    }
+
+    pub async fn self_test_with(routing: Routing, use_dgram_socket: bool, ipv6: bool, timeout: Duration) -> Result<Self> {
+        let socket = if use_dgram_socket { "dgram" } else { "raw" };
+        let mut prober = match Prober::with_routing(routing, use_dgram_socket, ipv6, timeout) {
+            Ok(prober) => prober,
+            Err(e) => anyhow::bail!("{:#}\n{}", e, remediation(use_dgram_socket)),
+        };
+
+        // Loopback is not reachable through a VRF device
+        if let Some(vrf) = prober.routing.vrf() {
+            println!("Socket self-test: {} socket created, loopback ping skipped (bound to {})", socket, vrf);
+            return Ok(prober);
+        }
+        let loopback = if ipv6 { IpAddr::V6(Ipv6Addr::LOCALHOST) } else { IpAddr::V4(Ipv4Addr::LOCALHOST) };
+        match prober.probe(loopback).await {
+            Ok(rtt) => println!("Socket self-test: {} ping to {} OK ({:?})", socket, loopback, rtt),
+            Err(e @ ProbeError::PermissionDenied(_)) => {
+                anyhow::bail!("Socket self-test failed: {} ping to {}: {}\n{}", socket, loopback, e, remediation(use_dgram_socket));
+            },
+            Err(e) => {
+                anyhow::bail!("Socket self-test failed: {} ping to {}: {}. Check local firewall rules for ICMP", socket, loopback, e);
+            },
+        }
+        Ok(prober)
+    }
+
+    /// Send one echo request, RTT is measured when the matching reply is received
+    pub async fn probe(&mut self, ip: IpAddr) -> Result<Duration, ProbeError> {
+        self.sequence = self.sequence.wrapping_add(1);
+        let mut pinger = self.client.pinger(ip, self.identifier).await;
+        pinger.timeout(self.timeout);
+        let (_packet, rtt) = pinger.ping(PingSequence(self.sequence), &PAYLOAD).await?;
+        Ok(rtt)
+    }
+}
+
+/// Socket type setting: true for dgram, false for raw (case insensitive)
+pub fn parse_socket_type(value: &str) -> Result<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "dgram" => Ok(true),
+        "raw" => Ok(false),
+        _ => anyhow::bail!("Invalid socket type: '{}'. Allowed values: 'raw', 'dgram' (Case insensitive)", value.trim()),
+    }
+}
+
+/// PING_SOCKET_TYPE, dgram if not set
+pub fn socket_type_from_env() -> Result<bool> {
+    match config::var("PING_SOCKET_TYPE").ok().filter(|t| !t.trim().is_empty()) {
+        Some(value) => parse_socket_type(&value).context("PING_SOCKET_TYPE"),
+        None => Ok(true),
+    }
+}
+
+/// How to allow ICMP sockets of the chosen type
+fn remediation(use_dgram_socket: bool) -> String {
+    if use_dgram_socket {
+        let range = std::fs::read_to_string("/proc/sys/net/ipv4/ping_group_range")
+            .map(|r| r.split_whitespace().collect::<Vec<_>>().join(" "))
+            .unwrap_or_else(|_| "unknown".to_string());
+        // /proc/self is owned by the effective uid/gid of the process
+        let gid = std::fs::metadata("/proc/self").map(|m| m.gid().to_string()).unwrap_or_else(|_| "<gid>".to_string());
+        format!("Unprivileged (dgram) ICMP sockets are not allowed for group {} (net.ipv4.ping_group_range = \"{}\").\n\
+                 Allow them with: sudo sysctl -w net.ipv4.ping_group_range=\"0 {}\"\n\
+                 or use 'raw' socket type", gid, range, gid)
+    } else {
+        "Raw ICMP sockets need root or CAP_NET_RAW.\n\
+         Run with sudo, grant the capability with: sudo setcap cap_net_raw+ep <path to unshelve>\n\
+         or use 'dgram' socket type".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn socket_types() {
+        assert!(parse_socket_type("dgram").unwrap());
+        assert!(parse_socket_type(" DGRAM ").unwrap());
+        assert!(!parse_socket_type("Raw").unwrap());
+        assert!(parse_socket_type("icmp").is_err());
+    }
+
+    #[test]
+    fn surge_errors_by_cause() {
+        assert_eq!(ProbeError::from(SurgeError::Timeout { seq: PingSequence(1) }).kind(), "timeout");
+        assert_eq!(ProbeError::from(SurgeError::IncorrectBufferSize).kind(), "unreachable");
+        assert_eq!(ProbeError::from(SurgeError::NetworkError).kind(), "unreachable");
+        let io = |kind| SurgeError::IOError(std::io::Error::from(kind));
+        assert_eq!(ProbeError::from(io(ErrorKind::HostUnreachable)).kind(), "host_unreachable");
+        assert_eq!(ProbeError::from(io(ErrorKind::PermissionDenied)).kind(), "permission_denied");
+        assert_eq!(ProbeError::from(io(ErrorKind::ConnectionReset)).kind(), "other");
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::env;
+use anyhow::Result;
+
+/// Apply named profile to config values: variables <PROFILE>__<KEY> override <KEY>,
+/// e.g. with --profile staging STAGING__SERVER_NAME overrides SERVER_NAME.
+/// Profile can belong to a group (<PROFILE>__GROUP=prod), then group defaults
+/// <GROUP>__<KEY> are applied first and the profile overrides them.
+/// Profile variables may also come from the process environment, which wins over the config values anyway.
+/// Returns keys set by the profile and its group, and the group
+pub fn overlay(profile: &str, values: &mut HashMap<String, String>) -> Result<(Vec<String>, Option<String>)> {
+    let mut all = values.clone();
+    all.extend(env::vars());
+    let (vars, group) = resolve(profile, &all)?;
+    let keys = vars.iter().map(|(key, _)| key.clone()).collect();
+    values.extend(vars);
+    Ok((keys, group))
+}
+
+/// Variables of the group (if any) and then of the profile, with the group name
+fn resolve(profile: &str, all: &HashMap<String, String>) -> Result<(Vec<(String, String)>, Option<String>)> {
+    let vars = profile_vars(profile, all);
+    if vars.is_empty() {
+        anyhow::bail!("Profile '{}' not found: no {}* variables in config", profile, prefix(profile));
+    }
+
+    let group = vars.iter().find(|(key, _)| key == "GROUP").map(|(_, value)| value.clone());
+    let mut resolved = vec![];
+    if let Some(group) = &group {
+        let group_vars = profile_vars(group, all);
+        if group_vars.is_empty() {
+            anyhow::bail!("Group '{}' of profile '{}' not found: no {}* variables in config", group, profile, prefix(group));
+        }
+        resolved.extend(group_vars);
+    }
+    resolved.extend(vars);
+    resolved.retain(|(key, _)| key != "GROUP");
+    Ok((resolved, group))
+}
+
+fn prefix(profile: &str) -> String {
+    format!("{}__", profile.to_uppercase().replace('-', "_"))
+}
+
+fn profile_vars(profile: &str, all: &HashMap<String, String>) -> Vec<(String, String)> {
+    let prefix = prefix(profile);
+    let mut vars: Vec<(String, String)> = all
+        .iter()
+        .filter_map(|(key, value)| key.strip_prefix(&prefix).map(|k| (k.to_string(), value.clone())))
+        .filter(|(key, _)| !key.is_empty())
+        .collect();
+    vars.sort();
+    vars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn profile_over_group() {
+        let all = values(&[
+            ("UNSHELVE_TEST_STAGING__GROUP", "unshelve-test-nonprod"),
+            ("UNSHELVE_TEST_STAGING__SERVER_NAME", "staging01"),
+            ("UNSHELVE_TEST_NONPROD__SERVER_NAME", "default"),
+            ("UNSHELVE_TEST_NONPROD__PING_INTERVAL_MINUTES", "15"),
+            ("SERVER_NAME", "prod01"),
+        ]);
+        let (vars, group) = resolve("unshelve_test_staging", &all).unwrap();
+        assert_eq!(group.as_deref(), Some("unshelve-test-nonprod"));
+        // Group first, the profile overrides it when applied in order
+        assert_eq!(vars, vec![
+            ("PING_INTERVAL_MINUTES".to_string(), "15".to_string()),
+            ("SERVER_NAME".to_string(), "default".to_string()),
+            ("SERVER_NAME".to_string(), "staging01".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn overlay_replaces_config_values() {
+        let mut config = values(&[("SERVER_NAME", "prod01"), ("UNSHELVE_TEST_DEV__SERVER_NAME", "dev01")]);
+        let (keys, group) = overlay("unshelve-test-dev", &mut config).unwrap();
+        assert_eq!(config["SERVER_NAME"], "dev01");
+        assert_eq!((keys, group), (vec!["SERVER_NAME".to_string()], None));
+    }
+
+    #[test]
+    fn missing_profile_or_group() {
+        assert!(resolve("unshelve_test_missing", &HashMap::new()).is_err());
+        let all = values(&[("UNSHELVE_TEST_ORPHAN__GROUP", "unshelve_test_nowhere")]);
+        assert!(resolve("unshelve_test_orphan", &all).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use crate::config;
+use crate::notify::Event;
+
+/// Events published to a Redis channel (REDIS_URL, REDIS_CHANNEL) and current state of every
+/// server in a Redis hash (REDIS_STATE_KEY), so web apps can subscribe to live status
+/// without the daemon serving HTTP. The connection is reopened after a failure
+pub struct RedisOutput {
+    url: String,
+    channel: String,
+    state_key: Option<String>,
+    #[cfg(feature = "redis")]
+    client: redis::Client,
+    #[cfg(feature = "redis")]
+    connection: tokio::sync::Mutex<Option<redis::aio::MultiplexedConnection>>,
+}
+
+impl RedisOutput {
+    /// None if REDIS_URL is not set
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(url) = env_non_empty("REDIS_URL") else {
+            return Ok(None);
+        };
+        let channel = env_non_empty("REDIS_CHANNEL").unwrap_or_else(|| "unshelve:events".to_string());
+        let state_key = env_non_empty("REDIS_STATE_KEY");
+        open(url, channel, state_key).map(Some)
+    }
+
+    /// PUBLISH the event as JSON, same fields as in EVENTS_FILE
+    pub async fn publish(&self, event: &Event) -> Result<()> {
+        let payload = json!({
+            "time": event.time.to_rfc3339(),
+            "severity": event.severity.to_string().to_lowercase(),
+            "kind": event.kind,
+            "server": event.server,
+            "message": event.message,
+            "labels": event.labels,
+        });
+        self.send(&["PUBLISH", &self.channel, &payload.to_string()]).await
+    }
+
+    /// HSET the server state from the snapshot, nothing without REDIS_STATE_KEY
+    pub async fn set_state(&self, server: &str, state: &Value) -> Result<()> {
+        let Some(key) = &self.state_key else {
+            return Ok(());
+        };
+        self.send(&["HSET", key, server, &state.to_string()]).await
+    }
+
+    pub fn keeps_state(&self) -> bool {
+        self.state_key.is_some()
+    }
+
+    /// Redis host, channel and state key for logs, credentials are not shown
+    pub fn describe(&self) -> String {
+        let host = self.url.rsplit('@').next().unwrap_or(&self.url).trim_start_matches("redis://").trim_start_matches("rediss://");
+        match &self.state_key {
+            Some(key) => format!("{} (channel {}, state {})", host, self.channel, key),
+            None => format!("{} (channel {})", host, self.channel),
+        }
+    }
+
+    #[cfg(feature = "redis")]
+    async fn send(&self, args: &[&str]) -> Result<()> {
+        use anyhow::Context;
+
+        let mut connection = self.connection.lock().await;
+        if connection.is_none() {
+            *connection = Some(self.client.get_multiplexed_async_connection().await.context("Failed to connect to REDIS_URL")?);
+        }
+        let Some(active) = connection.as_mut() else {
+            return Ok(());
+        };
+        let mut command = redis::cmd(args[0]);
+        command.arg(&args[1..]);
+        if let Err(e) = command.query_async::<()>(active).await {
+            // Reconnect on the next event
+            *connection = None;
+            return Err(e).context(format!("Redis {} failed", args[0]));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "redis"))]
+    async fn send(&self, _args: &[&str]) -> Result<()> {
+        anyhow::bail!("Redis output needs a build with --features redis")
+    }
+}
+
+#[cfg(feature = "redis")]
+fn open(url: String, channel: String, state_key: Option<String>) -> Result<RedisOutput> {
+    use anyhow::Context;
+
+    let client = redis::Client::open(url.as_str()).context("Invalid REDIS_URL")?;
+    Ok(RedisOutput { url, channel, state_key, client, connection: tokio::sync::Mutex::new(None) })
+}
+
+#[cfg(not(feature = "redis"))]
+fn open(_url: String, _channel: String, _state_key: Option<String>) -> Result<RedisOutput> {
+    anyhow::bail!("Redis output needs a build with --features redis")
+}
+
+fn env_non_empty(key: &str) -> Option<String> {
+    config::var(key).ok().filter(|v| !v.trim().is_empty())
+}
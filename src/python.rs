@@ -0,0 +1,152 @@
+//! Python bindings (PyO3), built with maturin (pyproject.toml):
+//!
+//! ```python
+//! import unshelve
+//! monitor = unshelve.Monitor(".env", profile="prod")
+//! if not monitor.check() and monitor.unshelve():
+//!     print("unshelve requested", monitor.status())
+//! cloud = unshelve.Cloud(".env")
+//! print([s["name"] for s in cloud.servers() if s["shelved"]])
+//! ```
+//!
+//! Calls block until the OpenStack API answers, the GIL is released meanwhile
+
+use anyhow::Context;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use tokio::runtime::Runtime;
+
+use crate::aliases;
+use crate::config::Vars;
+use crate::engine::Engine;
+use crate::state::ServerState;
+
+/// Check-and-unshelve engine for SERVER_NAME of the config, as the daemon runs it
+#[pyclass(name = "Monitor")]
+struct PyMonitor {
+    engine: Engine,
+}
+
+#[pymethods]
+impl PyMonitor {
+    #[new]
+    #[pyo3(signature = (config = ".env", profile = None))]
+    fn new(py: Python<'_>, config: &str, profile: Option<&str>) -> PyResult<Self> {
+        let engine = py.allow_threads(|| Engine::new(config, profile)).map_err(error)?;
+        Ok(PyMonitor { engine })
+    }
+
+    /// One check cycle (ping if configured and OpenStack status), True if the server is healthy
+    fn check(&mut self, py: Python<'_>) -> PyResult<bool> {
+        py.allow_threads(|| self.engine.check()).map_err(error)
+    }
+
+    /// Result of the last check as a dict
+    fn status<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        json(py, &self.engine.status().to_string())
+    }
+
+    /// Unshelve if shelved, honouring the kill switch and precondition hooks. False if not shelved
+    fn unshelve(&mut self, py: Python<'_>) -> PyResult<bool> {
+        py.allow_threads(|| self.engine.unshelve()).map_err(error)
+    }
+}
+
+/// OpenStack connection from OS_* variables, of the config file if given. The config is read
+/// into memory, the environment of the Python process is left as it is
+#[pyclass(name = "Cloud")]
+struct PyCloud {
+    runtime: Runtime,
+    vars: Vars,
+    cloud: openstack::Cloud,
+}
+
+#[pymethods]
+impl PyCloud {
+    #[new]
+    #[pyo3(signature = (config = None, profile = None))]
+    fn new(py: Python<'_>, config: Option<&str>, profile: Option<&str>) -> PyResult<Self> {
+        py.allow_threads(|| {
+            let runtime = Runtime::new().context("Failed to start async runtime")?;
+            let vars = match config {
+                Some(config) => crate::read_config(config, profile)?,
+                None => Vars::default(),
+            };
+            let cloud = runtime.block_on(crate::connect_with(&vars))?;
+            anyhow::Ok(PyCloud { runtime, vars, cloud })
+        })
+        .map_err(error)
+    }
+
+    /// Servers of the project: id, name, status, shelved
+    fn servers<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let servers = py
+            .allow_threads(|| {
+                self.runtime.block_on(async {
+                    self.cloud
+                        .find_servers()
+                        .detailed()
+                        .all()
+                        .await
+                        .context("Failed to fetch server list")
+                })
+            })
+            .map_err(error)?;
+        servers
+            .iter()
+            .map(|server| {
+                let dict = PyDict::new_bound(py);
+                dict.set_item("id", server.id())?;
+                dict.set_item("name", server.name())?;
+                dict.set_item("status", server.status().to_string())?;
+                dict.set_item("shelved", ServerState::of(server).is_shelved())?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    /// OpenStack status of a server by name, UUID or alias
+    fn status(&self, py: Python<'_>, server: &str) -> PyResult<String> {
+        py.allow_threads(|| {
+            let name = aliases::resolve_in(server, &self.vars)?;
+            let server = self.runtime.block_on(self.cloud.get_server(&name)).context(format!("Failed to get server '{}'", name))?;
+            anyhow::Ok(server.status().to_string())
+        })
+        .map_err(error)
+    }
+
+    /// Unshelve a server by name, UUID or alias. False if it's not shelved
+    fn unshelve(&self, py: Python<'_>, server: &str) -> PyResult<bool> {
+        py.allow_threads(|| {
+            self.runtime.block_on(async {
+                let name = aliases::resolve_in(server, &self.vars)?;
+                let mut server = self.cloud.get_server(&name).await.context(format!("Failed to get server '{}'", name))?;
+                if !ServerState::of(&server).is_shelved() {
+                    return Ok(false);
+                }
+                server
+                    .action(openstack::compute::ServerAction::Unshelve)
+                    .await
+                    .context(format!("Failed to unshelve server '{}'", name))?;
+                Ok(true)
+            })
+        })
+        .map_err(error)
+    }
+}
+
+#[pymodule]
+fn unshelve(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyMonitor>()?;
+    module.add_class::<PyCloud>()?;
+    Ok(())
+}
+
+fn error(e: anyhow::Error) -> PyErr {
+    PyRuntimeError::new_err(format!("{:#}", e))
+}
+
+fn json<'py>(py: Python<'py>, value: &str) -> PyResult<Bound<'py, PyAny>> {
+    py.import_bound("json")?.call_method1("loads", (value,))
+}
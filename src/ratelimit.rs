@@ -0,0 +1,105 @@
+use std::time::Instant;
+use tokio::time::Duration;
+
+/// Longest delay between API calls while rate limited
+const MAX_DELAY: Duration = Duration::from_secs(30 * 60);
+
+/// OpenStack API answered 429 Too Many Requests (Nova or Keystone).
+/// The client gives no status code or headers - only the error text
+pub fn is_rate_limited(error: &openstack::Error) -> bool {
+    let text = error.to_string().to_lowercase();
+    text.contains("too many requests")
+        || text.contains("rate limit")
+        || text.split(|c: char| !c.is_ascii_alphanumeric()).any(|word| word == "429")
+}
+
+/// Retry-After seconds if the error text carries them
+pub fn retry_after(error: &openstack::Error) -> Option<Duration> {
+    let text = error.to_string().to_lowercase();
+    let rest = &text[text.find("retry-after")? + "retry-after".len()..];
+    let digits: String = rest
+        .trim_start_matches(|c: char| c == ':' || c == '=' || c.is_whitespace())
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse().ok().map(Duration::from_secs)
+}
+
+/// Widened polling while the API is rate limiting us
+#[derive(Default)]
+pub struct RateLimit {
+    delay: Option<Duration>,
+    since: Option<Instant>,
+}
+
+impl RateLimit {
+    /// Register a 429 and return the delay before the next API call:
+    /// Retry-After if given, otherwise the interval doubled on every hit
+    pub fn hit(&mut self, retry_after: Option<Duration>, interval: Duration) -> Duration {
+        let doubled = self.delay.unwrap_or(interval) * 2;
+        let delay = retry_after.unwrap_or(doubled).min(MAX_DELAY);
+        self.delay = Some(delay);
+        self.since.get_or_insert_with(Instant::now);
+        delay
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.delay.is_some()
+    }
+
+    /// API call succeeded, returns how long the rate limiting lasted
+    pub fn clear(&mut self) -> Option<Duration> {
+        self.delay = None;
+        self.since.take().map(|since| since.elapsed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> openstack::Error {
+        openstack::Error::new(openstack::ErrorKind::InternalServerError, text)
+    }
+
+    #[test]
+    fn rate_limit_errors() {
+        assert!(is_rate_limited(&error("429 Too Many Requests")));
+        assert!(is_rate_limited(&error("Rate limit exceeded for compute")));
+        assert!(is_rate_limited(&error("HTTP status 429")));
+        assert!(!is_rate_limited(&error("server 4290abc not found")));
+        assert!(!is_rate_limited(&error("No valid host was found")));
+    }
+
+    #[test]
+    fn retry_after_from_error_text() {
+        assert_eq!(retry_after(&error("429 Too Many Requests, Retry-After: 120")), Some(Duration::from_secs(120)));
+        assert_eq!(retry_after(&error("retry-after=7s")), Some(Duration::from_secs(7)));
+        assert_eq!(retry_after(&error("429 Too Many Requests")), None);
+        assert_eq!(retry_after(&error("Retry-After: soon")), None);
+    }
+
+    #[test]
+    fn delay_doubles_until_cap() {
+        let mut limit = RateLimit::default();
+        assert!(!limit.is_active());
+        assert_eq!(limit.clear(), None);
+        let interval = Duration::from_secs(5 * 60);
+        assert_eq!(limit.hit(None, interval), Duration::from_secs(10 * 60));
+        assert_eq!(limit.hit(None, interval), Duration::from_secs(20 * 60));
+        assert_eq!(limit.hit(None, interval), MAX_DELAY);
+        assert_eq!(limit.hit(None, interval), MAX_DELAY);
+        assert!(limit.is_active());
+    }
+
+    #[test]
+    fn retry_after_wins_and_clear_resets() {
+        let mut limit = RateLimit::default();
+        assert_eq!(limit.hit(Some(Duration::from_secs(90)), Duration::from_secs(60)), Duration::from_secs(90));
+        assert_eq!(limit.hit(Some(Duration::from_secs(3600)), Duration::from_secs(60)), MAX_DELAY);
+        assert!(limit.clear().is_some());
+        assert!(!limit.is_active());
+        // A new burst starts from the interval again
+        assert_eq!(limit.hit(None, Duration::from_secs(60)), Duration::from_secs(120));
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::{HashMap, VecDeque};
+use anyhow::{Context, Result};
+use chrono::Local;
+use tokio::time::{sleep, Duration, Instant};
+
+use crate::aliases;
+use crate::config;
+use crate::history;
+use crate::state::ServerState;
+
+/// Delay between status polls of servers being unshelved
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Unshelving server not ACTIVE after this time frees its in-flight slot and counts as failed
+const ACTIVE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// Upper bound of the stagger stretched after scheduler failures
+const MAX_STAGGER: Duration = Duration::from_secs(10 * 60);
+
+/// Recovery order class from SERVER_PRIORITIES, servers without one are normal
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Critical,
+    High,
+    Normal,
+    /// Best-effort servers, e.g. dev boxes
+    Low,
+}
+
+impl std::fmt::Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Priority::Critical => "critical",
+            Priority::High => "high",
+            Priority::Normal => "normal",
+            Priority::Low => "low",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Parse SERVER_PRIORITIES, e.g. SERVER_PRIORITIES='db=critical,web1=high,dev-box=low'.
+/// Keys are server names, UUIDs or aliases
+fn load_priorities() -> Result<HashMap<String, Priority>> {
+    let aliases = aliases::load()?;
+    let mut priorities: HashMap<String, Priority> = HashMap::new();
+    let raw = config::var("SERVER_PRIORITIES").unwrap_or_default();
+
+    for pair in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let Some((server, priority)) = pair.split_once('=').map(|(s, p)| (s.trim(), p.trim())) else {
+            anyhow::bail!("Invalid SERVER_PRIORITIES entry: '{}'. Expected NAME_OR_UUID=PRIORITY", pair);
+        };
+        let priority = match priority.to_lowercase().as_str() {
+            "critical" => Priority::Critical,
+            "high" => Priority::High,
+            "normal" => Priority::Normal,
+            "low" => Priority::Low,
+            other => anyhow::bail!("Invalid priority '{}' for '{}' in SERVER_PRIORITIES. Allowed values: critical, high, normal, low", other, server),
+        };
+        let server = aliases.get(server).map(String::as_str).unwrap_or(server);
+        priorities.insert(server.to_string(), priority);
+    }
+    Ok(priorities)
+}
+
+/// Unshelve failures per server name in the event history over the last UNSHELVE_FAILURE_LOOKBACK_HOURS.
+/// Empty without history or with a zero lookback
+fn recent_failures() -> Result<HashMap<String, usize>> {
+    let hours = env_number("UNSHELVE_FAILURE_LOOKBACK_HOURS", 24)?;
+    let mut failures: HashMap<String, usize> = HashMap::new();
+    if hours == 0 {
+        return Ok(failures);
+    }
+    let Some(store) = history::from_env()? else {
+        return Ok(failures);
+    };
+    let since = Local::now() - chrono::Duration::hours(hours as i64);
+    for event in store.query(&["unshelve_failed"], since)? {
+        *failures.entry(event.server).or_default() += 1;
+    }
+    Ok(failures)
+}
+
+/// Request pace of a mass unshelve. Hypervisor capacity is admin-only and not exposed by the
+/// OpenStack client, so the pace follows scheduler failures instead: each server going to ERROR
+/// (usually "No valid host") halves the in-flight limit and doubles the stagger, each server
+/// becoming ACTIVE wins one slot back and halves the stagger down to the configured one
+struct Pacing {
+    stagger: Duration,
+    base_stagger: Duration,
+    limit: usize,
+    max_in_flight: usize,
+}
+
+impl Pacing {
+    fn new(base_stagger: Duration, max_in_flight: usize, cautious: bool) -> Self {
+        // Recent failures mean the cloud was short of capacity lately - start at half speed
+        let limit = if cautious { max_in_flight.div_ceil(2) } else { max_in_flight };
+        Pacing { stagger: base_stagger, base_stagger, limit, max_in_flight }
+    }
+
+    fn on_scheduling_failure(&mut self) {
+        self.limit = (self.limit / 2).max(1);
+        self.stagger = (self.stagger * 2).max(POLL_INTERVAL).min(MAX_STAGGER);
+        println!("⚠️ Slowing down: max {} in flight, stagger {}s", self.limit, self.stagger.as_secs());
+    }
+
+    fn on_active(&mut self) {
+        if self.limit < self.max_in_flight || self.stagger > self.base_stagger {
+            self.limit = (self.limit + 1).min(self.max_in_flight);
+            self.stagger = (self.stagger / 2).max(self.base_stagger);
+            println!("Speeding up: max {} in flight, stagger {}s", self.limit, self.stagger.as_secs());
+        }
+    }
+}
+
+struct InFlight {
+    id: String,
+    name: String,
+    priority: Priority,
+    since: Instant,
+}
+
+/// Unshelve several servers without a thundering herd on the cloud scheduler:
+/// UNSHELVE_STAGGER_SECONDS between commands, at most UNSHELVE_MAX_IN_FLIGHT servers not yet ACTIVE,
+/// both adjusted to scheduler failures (see `Pacing`). Servers go in SERVER_PRIORITIES order, critical
+/// first, within a priority servers that failed to unshelve recently go last
+pub async fn unshelve_many(cloud: &openstack::Cloud, identifiers: &[String]) -> Result<()> {
+    let stagger = Duration::from_secs(env_number("UNSHELVE_STAGGER_SECONDS", 30)?);
+    let max_in_flight = env_number("UNSHELVE_MAX_IN_FLIGHT", 3)?.max(1) as usize;
+    let priorities = load_priorities()?;
+    let failures = match recent_failures() {
+        Ok(failures) => failures,
+        Err(e) => {
+            println!("⚠️ Failure history not used: {:#}", e);
+            HashMap::new()
+        },
+    };
+
+    let mut shelved: Vec<(Priority, String, String)> = vec![];
+    for identifier in identifiers {
+        match cloud.get_server(identifier).await {
+            Ok(server) if ServerState::of(&server).is_shelved() => {
+                let priority = priorities
+                    .get(server.name())
+                    .or_else(|| priorities.get(server.id()))
+                    .copied()
+                    .unwrap_or(Priority::Normal);
+                shelved.push((priority, server.id().clone(), server.name().clone()));
+            },
+            Ok(server) => println!("- {} is {} - skipped", server.name(), server.status()),
+            Err(e) => println!("✗ Failed to get server '{}': {}", identifier, e),
+        }
+    }
+    if shelved.is_empty() {
+        println!("No shelved servers to unshelve");
+        return Ok(());
+    }
+    // Stable sort keeps the given order within a priority class
+    shelved.sort_by_key(|(priority, _, name)| (*priority, failures.get(name).copied().unwrap_or(0)));
+    let cautious = shelved.iter().any(|(_, _, name)| failures.contains_key(name));
+    let mut pacing = Pacing::new(stagger, max_in_flight, cautious);
+    let mut queue: VecDeque<(Priority, String, String)> = shelved.into();
+
+    let total = queue.len();
+    println!("Unshelving {} server(s): stagger {}s, max {} in flight{}", total, stagger.as_secs(), pacing.limit,
+             if cautious { " (recent unshelve failures - starting at half speed)" } else { "" });
+    for (i, (priority, _, name)) in queue.iter().enumerate() {
+        match failures.get(name) {
+            Some(count) => println!("{:>4}. {:<40} {} ({} recent failure(s))", i + 1, name, priority, count),
+            None => println!("{:>4}. {:<40} {}", i + 1, name, priority),
+        }
+    }
+    println!("{}", "=".repeat(80));
+
+    let mut in_flight: Vec<InFlight> = vec![];
+    let (mut sent, mut active, mut failed) = (0, 0, 0);
+    while !queue.is_empty() || !in_flight.is_empty() {
+        if queue.is_empty() || in_flight.len() >= pacing.limit {
+            sleep(POLL_INTERVAL).await;
+            let (done, errors) = poll_in_flight(cloud, &mut in_flight, &mut pacing).await;
+            active += done;
+            failed += errors;
+            continue;
+        }
+
+        let Some((priority, id, name)) = queue.pop_front() else { break };
+        sent += 1;
+        let mut server = match cloud.get_server(&id).await {
+            Ok(server) => server,
+            Err(e) => {
+                println!("[{}/{}] ✗ {} ({}) - failed to get server: {}", sent, total, name, priority, e);
+                failed += 1;
+                continue;
+            },
+        };
+        match server.action(openstack::compute::ServerAction::Unshelve).await {
+            Ok(_) => {
+                println!("[{}/{}] ✓ {} ({}) - unshelve command sent", sent, total, name, priority);
+                in_flight.push(InFlight { id, name, priority, since: Instant::now() });
+            },
+            Err(e) => {
+                println!("[{}/{}] ✗ {} ({}) - failed to unshelve: {}", sent, total, name, priority, e);
+                if e.to_string().to_lowercase().contains("no valid host") {
+                    pacing.on_scheduling_failure();
+                }
+                failed += 1;
+            },
+        }
+        if !queue.is_empty() {
+            sleep(pacing.stagger).await;
+        }
+    }
+
+    println!("{}", "=".repeat(80));
+    println!("Recovery finished: {} active, {} failed of {}", active, failed, total);
+    if failed > 0 {
+        anyhow::bail!("{} server(s) were not recovered", failed);
+    }
+    Ok(())
+}
+
+/// Drop servers that became ACTIVE, went to ERROR or timed out, return (active, failed) counts
+async fn poll_in_flight(cloud: &openstack::Cloud, in_flight: &mut Vec<InFlight>, pacing: &mut Pacing) -> (usize, usize) {
+    let (mut active, mut failed) = (0, 0);
+    let mut pending = vec![];
+    for server in in_flight.drain(..) {
+        let (status, state) = match cloud.get_server(&server.id).await {
+            Ok(s) => (s.status().to_string(), ServerState::of(&s)),
+            Err(e) => (format!("unknown ({})", e), ServerState::Unknown),
+        };
+        if state == ServerState::Active {
+            println!("✅ {} ({}) is ACTIVE ({}s)", server.name, server.priority, server.since.elapsed().as_secs());
+            active += 1;
+            pacing.on_active();
+        } else if state == ServerState::Error {
+            println!("✗ {} ({}) went to ERROR", server.name, server.priority);
+            pacing.on_scheduling_failure();
+            failed += 1;
+        } else if server.since.elapsed() >= ACTIVE_TIMEOUT {
+            println!("✗ {} ({}) is not ACTIVE after {} min (status {})", server.name, server.priority, ACTIVE_TIMEOUT.as_secs() / 60, status);
+            failed += 1;
+        } else {
+            pending.push(server);
+        }
+    }
+    *in_flight = pending;
+    (active, failed)
+}
+
+/// Identifiers of all servers in SHELVED_OFFLOADED or SHELVED status
+pub async fn shelved_servers(cloud: &openstack::Cloud) -> Result<Vec<String>> {
+    let servers = cloud
+        .list_servers()
+        .await
+        .context("Failed to fetch server list")?;
+    let mut shelved = vec![];
+    for server in servers {
+        let details = server.details().await?;
+        if ServerState::of(&details).is_shelved() {
+            shelved.push(details.id().clone());
+        }
+    }
+    Ok(shelved)
+}
+
+fn env_number(key: &str, default: u64) -> Result<u64> {
+    config::var(key)
+        .unwrap_or_else(|_| default.to_string())
+        .parse()
+        .context(format!("{} must be a number", key))
+}
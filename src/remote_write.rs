@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+use anyhow::{Context, Result};
+use prost::Message;
+use tokio::time::Duration;
+
+use crate::config;
+use crate::history;
+use crate::labels;
+
+// Prometheus remote-write 1.0 protobuf messages (prompb)
+#[derive(Clone, PartialEq, Message)]
+pub struct WriteRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub timeseries: Vec<TimeSeries>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TimeSeries {
+    #[prost(message, repeated, tag = "1")]
+    pub labels: Vec<Label>,
+    #[prost(message, repeated, tag = "2")]
+    pub samples: Vec<Sample>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Label {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub value: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Sample {
+    #[prost(double, tag = "1")]
+    pub value: f64,
+    #[prost(int64, tag = "2")]
+    pub timestamp: i64,
+}
+
+impl TimeSeries {
+    pub fn new(metric: &str, server: &str, server_labels: &BTreeMap<String, String>, samples: Vec<Sample>) -> Self {
+        // Labels must be sorted by name, __name__ sorts first
+        let mut sorted = server_labels.clone();
+        sorted.insert("server".to_string(), server.to_string());
+        let mut labels = vec![Label { name: "__name__".to_string(), value: metric.to_string() }];
+        labels.extend(sorted.into_iter().map(|(name, value)| Label { name, value }));
+        TimeSeries { labels, samples }
+    }
+
+    /// Series of the monitored server with one sample at the current time
+    pub fn now(metric: &str, server: &str, value: f64) -> Self {
+        let timestamp = chrono::Utc::now().timestamp_millis();
+        TimeSeries::new(metric, server, &labels::get(server), vec![Sample { value, timestamp }])
+    }
+}
+
+/// Pushes series to REMOTE_WRITE_URL (Prometheus, Mimir, VictoriaMetrics)
+pub struct RemoteWriter {
+    client: reqwest::Client,
+    url: String,
+    auth: Option<(String, String)>,
+}
+
+impl RemoteWriter {
+    /// None if REMOTE_WRITE_URL is not set
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(url) = config::var("REMOTE_WRITE_URL").ok().filter(|u| !u.trim().is_empty()) else {
+            return Ok(None);
+        };
+        let auth = match (config::var("REMOTE_WRITE_USERNAME"), config::var("REMOTE_WRITE_PASSWORD")) {
+            (Ok(user), Ok(password)) => Some((user, password)),
+            _ => None,
+        };
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        Ok(Some(RemoteWriter { client, url, auth }))
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub async fn push(&self, timeseries: Vec<TimeSeries>) -> Result<()> {
+        let body = WriteRequest { timeseries }.encode_to_vec();
+        let compressed = snap::raw::Encoder::new()
+            .compress_vec(&body)
+            .context("Failed to compress remote-write request")?;
+
+        let mut request = self.client
+            .post(&self.url)
+            .header("Content-Encoding", "snappy")
+            .header("Content-Type", "application/x-protobuf")
+            .header("X-Prometheus-Remote-Write-Version", "0.1.0")
+            .body(compressed);
+        if let Some((user, password)) = &self.auth {
+            request = request.basic_auth(user, Some(password));
+        }
+
+        let response = request.send().await.context("Remote-write request failed")?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            anyhow::bail!("Remote-write returned {}: {}", status, text.trim());
+        }
+        Ok(())
+    }
+}
+
+/// Backfill unshelve_ping_up from ping results recorded in the event history
+pub async fn export_history() -> Result<()> {
+    let writer = RemoteWriter::from_env()?.context("REMOTE_WRITE_URL not set")?;
+    let store = history::require()?;
+
+    // Labels recorded with the events, a series per server and label set
+    let mut servers: BTreeMap<(String, BTreeMap<String, String>), Vec<Sample>> = BTreeMap::new();
+    let since = chrono::DateTime::<chrono::Local>::from(std::time::UNIX_EPOCH);
+    for event in store.query(&["ping_ok", "ping_failed"], since)? {
+        let value = if event.kind == "ping_ok" { 1.0 } else { 0.0 };
+        servers
+            .entry((event.server, event.labels))
+            .or_default()
+            .push(Sample { value, timestamp: event.time.timestamp_millis() });
+    }
+
+    for ((server, server_labels), mut samples) in servers {
+        samples.sort_by_key(|s| s.timestamp);
+        let count = samples.len();
+        // Keep requests reasonably small
+        for chunk in samples.chunks(5000) {
+            writer.push(vec![TimeSeries::new("unshelve_ping_up", &server, &server_labels, chunk.to_vec())]).await?;
+        }
+        println!("✓ {} - {} samples sent to {}", server, count, writer.url());
+    }
+    Ok(())
+}
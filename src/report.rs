@@ -0,0 +1,291 @@
+use std::collections::BTreeMap;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Local, Timelike, Weekday};
+use clap::ValueEnum;
+
+use crate::anomaly;
+use crate::config;
+use crate::history;
+use crate::state::ServerState;
+
+/// How far back the inventory looks for the last incident
+const INCIDENT_LOOKBACK_DAYS: i64 = 90;
+
+/// Output format of `report inventory`
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Markdown table for wikis and ops notes
+    Md,
+}
+
+/// Availability target and the hours it applies to
+struct SlaTarget {
+    percent: f64,
+    /// Local hours range [start, end), e.g. 9-18
+    hours: Option<(u32, u32)>,
+    weekdays_only: bool,
+}
+
+impl SlaTarget {
+    fn from_env() -> Result<Self> {
+        let percent: f64 = config::var("SLA_TARGET_PERCENT")
+            .unwrap_or_else(|_| "99.5".to_string())
+            .parse()
+            .context("SLA_TARGET_PERCENT must be a number")?;
+        if !(0.0..=100.0).contains(&percent) {
+            anyhow::bail!("SLA_TARGET_PERCENT must be between 0 and 100");
+        }
+
+        let hours = match config::var("SLA_BUSINESS_HOURS").ok().filter(|h| !h.trim().is_empty()) {
+            Some(range) => {
+                let (start, end) = range
+                    .split_once('-')
+                    .and_then(|(s, e)| Some((s.trim().parse::<u32>().ok()?, e.trim().parse::<u32>().ok()?)))
+                    .filter(|(s, e)| s < e && *e <= 24)
+                    .ok_or_else(|| anyhow::anyhow!("SLA_BUSINESS_HOURS must be a range of hours, e.g. 9-18"))?;
+                Some((start, end))
+            },
+            None => None,
+        };
+
+        let weekdays_only = config::var("SLA_WEEKDAYS_ONLY")
+            .map(|v| v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+
+        Ok(SlaTarget { percent, hours, weekdays_only })
+    }
+
+    fn applies(&self, time: &DateTime<Local>) -> bool {
+        if self.weekdays_only && matches!(time.weekday(), Weekday::Sat | Weekday::Sun) {
+            return false;
+        }
+        match self.hours {
+            Some((start, end)) => (start..end).contains(&time.hour()),
+            None => true,
+        }
+    }
+
+    fn describe(&self) -> String {
+        let mut scope = vec![];
+        if let Some((start, end)) = self.hours {
+            scope.push(format!("{:02}:00-{:02}:00", start, end));
+        }
+        if self.weekdays_only {
+            scope.push("weekdays".to_string());
+        }
+        if scope.is_empty() {
+            format!("{}%", self.percent)
+        } else {
+            format!("{}% ({})", self.percent, scope.join(", "))
+        }
+    }
+}
+
+#[derive(Default)]
+struct Checks {
+    total: u64,
+    failed: u64,
+}
+
+impl Checks {
+    /// Share of successful checks, percent
+    fn availability(&self) -> f64 {
+        100.0 * (self.total - self.failed) as f64 / self.total as f64
+    }
+
+    /// Failed checks as a share of those the target allows, percent. 0 for a 100% target
+    fn budget_used(&self, target: f64) -> f64 {
+        let budget = (100.0 - target) / 100.0 * self.total as f64;
+        if budget > 0.0 { 100.0 * self.failed as f64 / budget } else { 0.0 }
+    }
+}
+
+/// Availability and error budget for this month, from ping results in the event history,
+/// per server or per value of a server label
+pub fn sla_report(by: Option<&str>) -> Result<()> {
+    let store = history::require()?;
+    let target = SlaTarget::from_env()?;
+    let now = Local::now();
+    let month_start: DateTime<Local> = now
+        .date_naive()
+        .with_day(1)
+        .and_then(|day| day.and_hms_opt(0, 0, 0))
+        .and_then(|time| time.and_local_timezone(Local).earliest())
+        .context("Failed to get start of the month")?;
+
+    let mut servers: BTreeMap<String, Checks> = BTreeMap::new();
+    for event in store.query(&["ping_ok", "ping_failed"], month_start)? {
+        if !target.applies(&event.time) {
+            continue;
+        }
+        let group = match by {
+            // Events recorded without the label are counted under "-"
+            Some(label) => event.labels.get(label).cloned().unwrap_or_else(|| "-".to_string()),
+            None => event.server,
+        };
+        let checks = servers.entry(group).or_default();
+        checks.total += 1;
+        if event.kind == "ping_failed" {
+            checks.failed += 1;
+        }
+    }
+
+    println!("SLA report for {} (target {})", now.format("%B %Y"), target.describe());
+    println!("{}", "-".repeat(90));
+    println!("{:<20} | {:>8} | {:>8} | {:>12} | {:>15} | {:<10}",
+             by.unwrap_or("server").to_uppercase(), "CHECKS", "FAILED", "AVAILABILITY", "BUDGET USED", "STATUS");
+    println!("{}", "=".repeat(90));
+
+    if servers.is_empty() {
+        println!("No check results recorded this month");
+    }
+
+    for (server, checks) in &servers {
+        let availability = checks.availability();
+        let used = checks.budget_used(target.percent);
+        let status = if availability >= target.percent { "✅ OK" } else { "⚠️ BREACHED" };
+
+        println!("{:<20} | {:>8} | {:>8} | {:>11.3}% | {:>14.1}% | {:<10}",
+                 server, checks.total, checks.failed, availability, used, status);
+    }
+    anomaly::print_report(store.as_ref())
+}
+
+/// Server inventory as a document: status, addresses, flavor, zone and the last incident
+/// from the event history (if recorded), e.g. for wikis and weekly ops notes
+pub async fn inventory(cloud: &openstack::Cloud, format: OutputFormat) -> Result<()> {
+    let mut servers = cloud
+        .find_servers()
+        .detailed()
+        .all()
+        .await
+        .context("Failed to fetch server list")?;
+    servers.sort_by(|a, b| a.name().cmp(b.name()));
+    let incidents = last_incidents()?;
+
+    match format {
+        OutputFormat::Md => {
+            let now = Local::now();
+            println!("# Server inventory");
+            println!();
+            println!("Generated {} by unshelve, {} server(s).", now.format("%Y-%m-%d %H:%M"), servers.len());
+            println!();
+            println!("| Server | Status | Addresses | Flavor | Zone | Last incident |");
+            println!("|---|---|---|---|---|---|");
+            let mut by_status: BTreeMap<String, usize> = BTreeMap::new();
+            for server in &servers {
+                *by_status.entry(server.status().to_string()).or_default() += 1;
+                let mut addresses: Vec<String> = vec![];
+                let mut networks: Vec<_> = server.addresses().iter().collect();
+                networks.sort_by(|a, b| a.0.cmp(b.0));
+                for (network, ips) in networks {
+                    for ip in ips {
+                        match &ip.addr_type {
+                            Some(kind) => addresses.push(format!("{}: {} ({})", network, ip.addr, kind)),
+                            None => addresses.push(format!("{}: {}", network, ip.addr)),
+                        }
+                    }
+                }
+                let status = match ServerState::of(server) {
+                    ServerState::Active => format!("✅ {}", server.status()),
+                    state if state.is_shelved() => format!("💤 {}", server.status()),
+                    ServerState::Error => format!("❌ {}", server.status()),
+                    _ => server.status().to_string(),
+                };
+                let incident = incidents
+                    .get(server.name())
+                    .or_else(|| incidents.get(server.id()))
+                    .map(String::as_str)
+                    .unwrap_or("-");
+                println!("| {} | {} | {} | {} | {} | {} |",
+                         md_cell(server.name()),
+                         status,
+                         md_cell(&if addresses.is_empty() { "-".to_string() } else { addresses.join("<br>") }),
+                         md_cell(&server.flavor().original_name),
+                         md_cell(if server.availability_zone().is_empty() { "-" } else { server.availability_zone().as_str() }),
+                         md_cell(incident));
+            }
+            println!();
+            let summary: Vec<String> = by_status.iter().map(|(status, count)| format!("{} {}", count, status)).collect();
+            println!("Status summary: {}.", if summary.is_empty() { "no servers".to_string() } else { summary.join(", ") });
+        },
+    }
+    Ok(())
+}
+
+/// Last incident per server name from the event history: start time and outcome.
+/// Empty if the history is not recorded
+fn last_incidents() -> Result<BTreeMap<String, String>> {
+    let Some(store) = history::from_env()? else {
+        return Ok(BTreeMap::new());
+    };
+    let since = Local::now() - chrono::Duration::days(INCIDENT_LOOKBACK_DAYS);
+    let mut incidents: BTreeMap<String, (DateTime<Local>, &str)> = BTreeMap::new();
+    for event in store.query(&["unshelve_sent", "unshelve_failed", "recovered"], since)? {
+        match event.kind.as_str() {
+            // Retries belong to the current incident until it's recovered
+            "unshelve_sent" => match incidents.get_mut(&event.server) {
+                Some(incident) if incident.1 != "recovered" => incident.1 = "ongoing",
+                _ => {
+                    incidents.insert(event.server, (event.time, "ongoing"));
+                },
+            },
+            "unshelve_failed" => {
+                if let Some(incident) = incidents.get_mut(&event.server) {
+                    incident.1 = "unshelve failed";
+                }
+            },
+            _ => {
+                if let Some(incident) = incidents.get_mut(&event.server) {
+                    incident.1 = "recovered";
+                }
+            },
+        }
+    }
+    Ok(incidents
+        .into_iter()
+        .map(|(server, (time, outcome))| (server, format!("{}, {}", time.format("%Y-%m-%d %H:%M"), outcome)))
+        .collect())
+}
+
+/// Text safe for a Markdown table cell
+fn md_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(day: u32, hour: u32) -> DateTime<Local> {
+        // 2026-10-12 is a Monday
+        Local.with_ymd_and_hms(2026, 10, day, hour, 30, 0).unwrap()
+    }
+
+    #[test]
+    fn business_hours_and_weekdays() {
+        let target = SlaTarget { percent: 99.5, hours: Some((9, 18)), weekdays_only: true };
+        assert!(target.applies(&at(12, 9)));
+        assert!(target.applies(&at(16, 17)));
+        assert!(!target.applies(&at(12, 8)));
+        assert!(!target.applies(&at(12, 18)), "end hour is excluded");
+        assert!(!target.applies(&at(17, 12)), "Saturday");
+        assert!(!target.applies(&at(18, 12)), "Sunday");
+        assert_eq!(target.describe(), "99.5% (09:00-18:00, weekdays)");
+
+        let always = SlaTarget { percent: 99.9, hours: None, weekdays_only: false };
+        assert!(always.applies(&at(18, 3)));
+        assert_eq!(always.describe(), "99.9%");
+    }
+
+    #[test]
+    fn availability_and_error_budget() {
+        let checks = Checks { total: 1000, failed: 3 };
+        assert!((checks.availability() - 99.7).abs() < 1e-9);
+        // 99.5% of 1000 checks allows 5 failures, 3 of them are used
+        assert!((checks.budget_used(99.5) - 60.0).abs() < 1e-9);
+        assert_eq!(checks.budget_used(100.0), 0.0);
+        assert_eq!(Checks { total: 10, failed: 10 }.availability(), 0.0);
+    }
+}
@@ -0,0 +1,70 @@
+use anyhow::{Context, Result};
+use openstack::compute::{Server, ServerAction};
+use tokio::time::{sleep, Duration, Instant};
+
+use crate::state::ServerState;
+
+/// Delay between status polls
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Boot the server from a rescue image (the cloud default or `image`, name or UUID) with its own
+/// disk attached, and wait for RESCUE status. For servers that came back broken after unshelve
+pub async fn rescue(cloud: &openstack::Cloud, server: &mut Server, image: Option<&str>, limit: Duration) -> Result<()> {
+    match ServerState::of(server) {
+        ServerState::Active | ServerState::PowerTransition | ServerState::Shutoff | ServerState::Error => {},
+        _ => anyhow::bail!("Server '{}' is {} - rescue needs ACTIVE, SHUTOFF or ERROR", server.name(), server.status()),
+    }
+    let from_error = ServerState::of(server) == ServerState::Error;
+    let image_ref = match image {
+        Some(image) => Some(cloud.get_image(image).await.context(format!("Rescue image '{}' not found", image))?.id().clone()),
+        None => None,
+    };
+    server
+        .action(ServerAction::Rescue { image_ref })
+        .await
+        .context(format!("Failed to rescue server '{}'", server.name()))?;
+    println!("✓ Rescue of '{}' sent, waiting for RESCUE status", server.name());
+    wait_for_state(cloud, server.id(), ServerState::Rescue, from_error, limit).await?;
+    println!("✓ Server '{}' is in RESCUE - its disk is attached to the rescue system, `unshelve ssh` works as usual", server.name());
+    Ok(())
+}
+
+/// Boot the server from its own disk again and wait for ACTIVE
+pub async fn unrescue(cloud: &openstack::Cloud, server: &mut Server, limit: Duration) -> Result<()> {
+    if ServerState::of(server) != ServerState::Rescue {
+        anyhow::bail!("Server '{}' is {} - not in RESCUE", server.name(), server.status());
+    }
+    server
+        .action(ServerAction::Unrescue)
+        .await
+        .context(format!("Failed to unrescue server '{}'", server.name()))?;
+    println!("✓ Unrescue of '{}' sent, waiting for ACTIVE status", server.name());
+    wait_for_state(cloud, server.id(), ServerState::Active, false, limit).await?;
+    println!("✓ Server '{}' is ACTIVE", server.name());
+    Ok(())
+}
+
+/// Poll until the server is in the `expected` state. ERROR fails the wait, unless the server was in ERROR before
+async fn wait_for_state(cloud: &openstack::Cloud, server_id: &str, expected: ServerState, from_error: bool, limit: Duration) -> Result<()> {
+    let deadline = Instant::now() + limit;
+    loop {
+        sleep(POLL_INTERVAL).await;
+        match cloud.get_server(server_id).await {
+            Ok(server) => {
+                let state = ServerState::of(&server);
+                if state == expected {
+                    return Ok(());
+                }
+                if !from_error && state == ServerState::Error {
+                    anyhow::bail!("Server went to ERROR status instead of {:?}", expected);
+                }
+                println!("Status {}, waiting for {:?}", server.status(), expected);
+            },
+            // API hiccups are retried until the deadline
+            Err(e) => println!("✗ Failed to get server info: {}", e),
+        }
+        if Instant::now() >= deadline {
+            anyhow::bail!("Server is not {:?} after {}s", expected, limit.as_secs());
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use openstack::compute::{Server, ServerAction};
+
+use crate::config;
+use crate::state::ServerState;
+
+/// End of the reclaim window of a soft-deleted server: RECLAIM_WINDOW_HOURS (the cloud's
+/// reclaim_instance_interval, not exposed by the API) after its last update, the deletion.
+/// None if the window is not configured
+pub fn reclaim_deadline(server: &Server) -> Result<Option<DateTime<Local>>> {
+    let Some(hours) = config::var("RECLAIM_WINDOW_HOURS").ok().filter(|h| !h.trim().is_empty()) else {
+        return Ok(None);
+    };
+    let hours: i64 = hours.trim().parse().context("RECLAIM_WINDOW_HOURS must be a number")?;
+    Ok(Some(server.updated_at().with_timezone(&Local) + chrono::Duration::hours(hours)))
+}
+
+/// "restorable until 2026-10-16 12:00" for listings, the deadline is omitted if unknown
+pub fn describe(server: &Server) -> String {
+    match reclaim_deadline(server) {
+        Ok(Some(deadline)) => format!("soft-deleted, restorable until {}", deadline.format("%Y-%m-%d %H:%M")),
+        _ => "soft-deleted, restorable until the cloud reclaims it".to_string(),
+    }
+}
+
+/// Restore a soft-deleted server, refused for other states and after the reclaim window
+pub async fn restore(server: &mut Server) -> Result<()> {
+    let state = ServerState::of(server);
+    if state != ServerState::SoftDeleted {
+        anyhow::bail!("Server '{}' is {} - only SOFT_DELETED servers can be restored", server.name(), server.status());
+    }
+    if let Some(deadline) = reclaim_deadline(server)? {
+        if Local::now() >= deadline {
+            anyhow::bail!("Reclaim window of '{}' ended at {} - the cloud deletes it for good",
+                          server.name(), deadline.format("%Y-%m-%d %H:%M"));
+        }
+    }
+    server
+        .action(ServerAction::Restore)
+        .await
+        .context(format!("Failed to restore server '{}'", server.name()))?;
+    println!("✓ Restore of '{}' sent - the server comes back ACTIVE", server.name());
+    Ok(())
+}
@@ -0,0 +1,159 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::net::SocketAddr;
+use std::os::fd::{AsFd, BorrowedFd};
+use std::sync::mpsc;
+use std::thread;
+use anyhow::{Context, Result};
+use nix::sched::{setns, CloneFlags};
+use nix::sys::socket::{setsockopt, sockopt};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::{TcpSocket, TcpStream};
+
+use crate::config::Vars;
+
+/// Named network namespaces of `ip netns add`
+const NETNS_DIR: &str = "/var/run/netns";
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Where the packets of one check go, from <PREFIX>_DSCP (0-63, set in the ToS / traffic class byte),
+/// <PREFIX>_VRF (VRF or other device the socket is bound to) and <PREFIX>_NETNS (name in /var/run/netns or path)
+pub struct Routing {
+    dscp: Option<u8>,
+    vrf: Option<String>,
+    netns: Option<Namespace>,
+}
+
+/// Thread that entered the namespace while the process still had CAP_SYS_ADMIN, i.e. before privileges are dropped.
+/// Sockets stay in the namespace they were created in, so this thread creates them for the whole run
+struct Namespace {
+    name: String,
+    jobs: mpsc::Sender<Job>,
+}
+
+impl Routing {
+    pub fn from_env(prefix: &str) -> Result<Self> {
+        Routing::from_vars(prefix, &Vars::default())
+    }
+
+    pub fn from_vars(prefix: &str, vars: &Vars) -> Result<Self> {
+        let value = |key: &str| vars.get(&format!("{}_{}", prefix, key)).map(|v| v.trim().to_string());
+        let dscp = match value("DSCP") {
+            Some(dscp) => Some(dscp.parse::<u8>().ok().filter(|d| *d < 64).context(format!("{}_DSCP must be a number 0-63", prefix))?),
+            None => None,
+        };
+        let netns = match value("NETNS") {
+            Some(name) => Some(Namespace::enter(&name).context(format!("Failed to enter network namespace '{}' ({}_NETNS)", name, prefix))?),
+            None => None,
+        };
+        Ok(Routing { dscp, vrf: value("VRF"), netns })
+    }
+
+    pub fn vrf(&self) -> Option<&str> {
+        self.vrf.as_deref()
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = vec![];
+        if let Some(netns) = &self.netns {
+            parts.push(format!("netns {}", netns.name));
+        }
+        if let Some(vrf) = &self.vrf {
+            parts.push(format!("vrf {}", vrf));
+        }
+        if let Some(dscp) = self.dscp {
+            parts.push(format!("dscp {}", dscp));
+        }
+        if parts.is_empty() { "default".to_string() } else { parts.join(", ") }
+    }
+
+    /// Run `f` in the network namespace, or right here without one
+    pub fn in_namespace<T: Send + 'static>(&self, f: impl FnOnce() -> T + Send + 'static) -> Result<T> {
+        match &self.netns {
+            Some(netns) => netns.run(f),
+            None => Ok(f()),
+        }
+    }
+
+    /// Mark packets of the socket with the DSCP and bind it to the VRF device.
+    /// Binding to a device needs CAP_NET_RAW on kernels before 5.7
+    pub fn apply(&self, fd: BorrowedFd<'_>, ipv6: bool) -> Result<()> {
+        if let Some(dscp) = self.dscp {
+            let tos = (dscp as i32) << 2;
+            let result = if ipv6 { setsockopt(&fd, sockopt::Ipv6TClass, &tos) } else { setsockopt(&fd, sockopt::IpTos, &tos) };
+            result.context(format!("Failed to set DSCP {}", dscp))?;
+        }
+        if let Some(vrf) = &self.vrf {
+            setsockopt(&fd, sockopt::BindToDevice, &OsString::from(vrf)).context(format!("Failed to bind socket to device '{}'", vrf))?;
+        }
+        Ok(())
+    }
+
+    /// TCP connection from a socket created in the namespace, marked and bound
+    pub async fn connect(&self, addr: SocketAddr) -> Result<TcpStream> {
+        let domain = Domain::for_address(addr);
+        let socket = self
+            .in_namespace(move || Socket::new(domain, Type::STREAM, Some(Protocol::TCP)))?
+            .context("Failed to create TCP socket")?;
+        self.apply(socket.as_fd(), addr.is_ipv6())?;
+        socket.set_nonblocking(true)?;
+        let socket = TcpSocket::from_std_stream(socket.into());
+        Ok(socket.connect(addr).await?)
+    }
+}
+
+impl Namespace {
+    fn enter(name: &str) -> Result<Self> {
+        let path = if name.starts_with('/') { name.to_string() } else { format!("{}/{}", NETNS_DIR, name) };
+        let file = File::open(&path).context(format!("No network namespace {}", path))?;
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let (entered, result) = mpsc::channel();
+        thread::Builder::new()
+            .name(format!("netns-{}", name))
+            .spawn(move || {
+                if let Err(e) = setns(file.as_fd(), CloneFlags::CLONE_NEWNET) {
+                    let _ = entered.send(Err(e));
+                    return;
+                }
+                let _ = entered.send(Ok(()));
+                for job in queue {
+                    job();
+                }
+            })
+            .context("Failed to start network namespace thread")?;
+        result
+            .recv()
+            .context("Network namespace thread exited")?
+            .context("setns failed - entering a network namespace needs root or CAP_SYS_ADMIN")?;
+        Ok(Namespace { name: name.to_string(), jobs })
+    }
+
+    fn run<T: Send + 'static>(&self, f: impl FnOnce() -> T + Send + 'static) -> Result<T> {
+        let (done, result) = mpsc::sync_channel(1);
+        self.jobs
+            .send(Box::new(move || {
+                let _ = done.send(f());
+            }))
+            .ok()
+            .context("Network namespace thread is gone")?;
+        result.recv().context("Network namespace thread is gone")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vars {
+        Vars::new(pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect())
+    }
+
+    #[test]
+    fn routing_from_config_values() {
+        let routing = Routing::from_vars("UNSHELVE_TEST", &vars(&[("UNSHELVE_TEST_DSCP", "46"), ("UNSHELVE_TEST_VRF", " mgmt ")])).unwrap();
+        assert_eq!(routing.describe(), "vrf mgmt, dscp 46");
+        assert_eq!(Routing::from_vars("UNSHELVE_TEST", &Vars::default()).unwrap().describe(), "default");
+        assert!(Routing::from_vars("UNSHELVE_TEST", &vars(&[("UNSHELVE_TEST_DSCP", "64")])).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::VecDeque;
+use tokio::time::Duration;
+
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Rolling window of ping results, None for lost probes
+pub struct RttHistory {
+    size: usize,
+    samples: VecDeque<Option<Duration>>,
+}
+
+impl RttHistory {
+    pub fn new(size: usize) -> Self {
+        RttHistory { size: size.max(1), samples: VecDeque::new() }
+    }
+
+    pub fn push(&mut self, rtt: Option<Duration>) {
+        if self.samples.len() == self.size {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(rtt);
+    }
+
+    fn received(&self) -> Vec<Duration> {
+        self.samples.iter().flatten().copied().collect()
+    }
+
+    /// Terminal sparkline, lost probes are shown as '✗'
+    pub fn sparkline(&self) -> String {
+        let received = self.received();
+        let (Some(min), Some(max)) = (received.iter().min(), received.iter().max()) else {
+            return self.samples.iter().map(|_| '✗').collect();
+        };
+        let span = (*max - *min).as_secs_f64();
+
+        self.samples
+            .iter()
+            .map(|sample| match sample {
+                Some(rtt) if span > 0.0 => {
+                    let level = ((*rtt - *min).as_secs_f64() / span * (BARS.len() - 1) as f64).round() as usize;
+                    BARS[level.min(BARS.len() - 1)]
+                },
+                Some(_) => BARS[0],
+                None => '✗',
+            })
+            .collect()
+    }
+
+    /// e.g. "last 12.1ms min 10.0ms avg 11.2ms max 15.3ms loss 1/60 ▁▂▃▅▂✗"
+    pub fn summary(&self) -> String {
+        let received = self.received();
+        let lost = self.samples.len() - received.len();
+        let last = match self.samples.back() {
+            Some(Some(rtt)) => format_ms(*rtt),
+            _ => "-".to_string(),
+        };
+
+        let stats = match (received.iter().min(), received.iter().max()) {
+            (Some(min), Some(max)) => {
+                let avg = received.iter().sum::<Duration>() / received.len() as u32;
+                format!("min {} avg {} max {}", format_ms(*min), format_ms(avg), format_ms(*max))
+            },
+            _ => "min - avg - max -".to_string(),
+        };
+
+        format!("last {} {} loss {}/{} {}", last, stats, lost, self.samples.len(), self.sparkline())
+    }
+}
+
+fn format_ms(d: Duration) -> String {
+    format!("{:.1}ms", d.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(value: u64) -> Option<Duration> {
+        Some(Duration::from_millis(value))
+    }
+
+    #[test]
+    fn window_keeps_last_samples() {
+        let mut history = RttHistory::new(3);
+        for sample in [ms(50), ms(10), None, ms(20)] {
+            history.push(sample);
+        }
+        assert_eq!(history.summary(), "last 20.0ms min 10.0ms avg 15.0ms max 20.0ms loss 1/3 ▁✗█");
+    }
+
+    #[test]
+    fn sparkline_scaled_between_min_and_max() {
+        let mut history = RttHistory::new(8);
+        for value in [10, 11, 12, 13, 14, 15, 16, 17] {
+            history.push(ms(value));
+        }
+        assert_eq!(history.sparkline(), "▁▂▃▄▅▆▇█");
+        let mut flat = RttHistory::new(3);
+        flat.push(ms(5));
+        flat.push(ms(5));
+        assert_eq!(flat.sparkline(), "▁▁");
+    }
+
+    #[test]
+    fn all_probes_lost() {
+        let mut history = RttHistory::new(0);
+        history.push(None);
+        history.push(None);
+        assert_eq!(history.summary(), "last - min - avg - max - loss 1/1 ✗");
+        assert_eq!(RttHistory::new(5).summary(), "last - min - avg - max - loss 0/0 ");
+    }
+}
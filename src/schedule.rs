@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveTime};
+use openstack::compute::ServerAction;
+use serde_json::Value;
+use tokio::time::sleep;
+
+use crate::config;
+use crate::nova;
+use crate::recovery;
+use crate::state::ServerState;
+
+/// Action of a scheduled job
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Shelve,
+    Unshelve,
+}
+
+/// Daily action on the servers whose OpenStack metadata matches a selector, e.g. "shelve env=dev at 20:00".
+/// Servers are selected when the job runs, so new servers with the metadata get the schedule too
+#[derive(Debug, PartialEq)]
+pub struct Job {
+    pub action: Action,
+    /// Metadata every selected server has, KEY=VALUE pairs
+    pub selector: BTreeMap<String, String>,
+    pub at: NaiveTime,
+}
+
+impl Job {
+    /// ACTION KEY=VALUE[,KEY=VALUE] at HH:MM
+    fn parse(entry: &str) -> Result<Self> {
+        let invalid = || format!("Invalid SCHEDULE entry '{}': expected e.g. 'shelve env=dev at 20:00'", entry);
+        let words: Vec<&str> = entry.split_whitespace().collect();
+        let [action, selector, "at", at] = words[..] else {
+            anyhow::bail!(invalid());
+        };
+        let action = match action.to_lowercase().as_str() {
+            "shelve" => Action::Shelve,
+            "unshelve" => Action::Unshelve,
+            other => anyhow::bail!("Unknown action '{}' in SCHEDULE entry '{}': shelve or unshelve", other, entry),
+        };
+        let selector = selector
+            .split(',')
+            .map(|pair| pair.split_once('=').map(|(k, v)| (k.trim().to_string(), v.trim().to_string())))
+            .collect::<Option<BTreeMap<_, _>>>()
+            .filter(|s| s.keys().all(|k| !k.is_empty()))
+            .with_context(invalid)?;
+        let at = NaiveTime::parse_from_str(at, "%H:%M").with_context(invalid)?;
+        Ok(Job { action, selector, at })
+    }
+
+    pub fn matches(&self, metadata: &Value) -> bool {
+        self.selector.iter().all(|(key, value)| metadata[key].as_str() == Some(value.as_str()))
+    }
+
+    /// Next run after `now`, today or tomorrow
+    fn next_run(&self, now: DateTime<Local>) -> DateTime<Local> {
+        let today = now.date_naive().and_time(self.at);
+        let next = if today > now.naive_local() { today } else { today + chrono::Duration::days(1) };
+        // A time skipped by a DST change runs an hour later
+        next.and_local_timezone(Local)
+            .earliest()
+            .or_else(|| (next + chrono::Duration::hours(1)).and_local_timezone(Local).earliest())
+            .unwrap_or(now + chrono::Duration::days(1))
+    }
+
+    fn describe(&self) -> String {
+        let selector: Vec<String> = self.selector.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        let action = match self.action {
+            Action::Shelve => "shelve",
+            Action::Unshelve => "unshelve",
+        };
+        format!("{} {} at {}", action, selector.join(","), self.at.format("%H:%M"))
+    }
+}
+
+/// Jobs from SCHEDULE, entries separated by ';'
+pub fn from_env() -> Result<Vec<Job>> {
+    let spec = config::var("SCHEDULE").unwrap_or_default();
+    let jobs = spec
+        .split(';')
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .map(Job::parse)
+        .collect::<Result<Vec<Job>>>()?;
+    if jobs.is_empty() {
+        anyhow::bail!("SCHEDULE is not set, e.g. SCHEDULE='shelve env=dev at 20:00; unshelve env=dev at 08:00'");
+    }
+    Ok(jobs)
+}
+
+/// Jobs with the servers they select now
+pub async fn list(cloud: &openstack::Cloud, jobs: &[Job]) -> Result<()> {
+    let servers = servers(cloud).await?;
+    for job in jobs {
+        let selected: Vec<&str> = servers
+            .iter()
+            .filter(|s| job.matches(&s["metadata"]))
+            .map(|s| s["name"].as_str().unwrap_or_default())
+            .collect();
+        let next = job.next_run(Local::now()).format("%Y-%m-%d %H:%M");
+        if selected.is_empty() {
+            println!("{:<35} next {} - no servers match", job.describe(), next);
+        } else {
+            println!("{:<35} next {} - {}", job.describe(), next, selected.join(", "));
+        }
+    }
+    Ok(())
+}
+
+/// Run the jobs at their time until Ctrl+C. A failed job is reported and runs again the next day
+pub async fn run(cloud: &openstack::Cloud, jobs: &[Job]) -> Result<()> {
+    loop {
+        let now = Local::now();
+        let (job, at) = jobs
+            .iter()
+            .map(|job| (job, job.next_run(now)))
+            .min_by_key(|(_, at)| *at)
+            .context("No scheduled jobs")?;
+        println!("Next job: {} ({})", job.describe(), at.format("%Y-%m-%d %H:%M"));
+        tokio::select! {
+            _ = sleep((at - now).to_std().unwrap_or_default()) => {},
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+        if let Err(e) = execute(cloud, job).await {
+            println!("✗ Job '{}' failed: {:#}", job.describe(), e);
+        }
+    }
+}
+
+async fn execute(cloud: &openstack::Cloud, job: &Job) -> Result<()> {
+    let servers = servers(cloud).await?;
+    let selected: Vec<(&str, ServerState)> = servers
+        .iter()
+        .filter(|s| job.matches(&s["metadata"]))
+        .map(|s| {
+            let running = s["OS-EXT-STS:power_state"].as_u64() == Some(nova::POWER_RUNNING);
+            (s["id"].as_str().unwrap_or_default(), ServerState::from_status(s["status"].as_str().unwrap_or_default(), running))
+        })
+        .collect();
+    println!("{}: {} server(s) match", job.describe(), selected.len());
+    match job.action {
+        Action::Unshelve => {
+            let shelved: Vec<String> = selected.iter().filter(|(_, state)| state.is_shelved()).map(|(id, _)| id.to_string()).collect();
+            if shelved.is_empty() {
+                println!("No shelved servers to unshelve");
+                return Ok(());
+            }
+            recovery::unshelve_many(cloud, &shelved).await
+        },
+        Action::Shelve => {
+            let mut failed = 0;
+            for (id, _) in selected.iter().filter(|(_, state)| matches!(state, ServerState::Active | ServerState::Shutoff)) {
+                let result = match cloud.get_server(*id).await {
+                    Ok(mut server) => server.action(ServerAction::Shelve).await,
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(_) => println!("✓ Shelve sent to {}", id),
+                    Err(e) => {
+                        println!("✗ Failed to shelve {}: {}", id, e);
+                        failed += 1;
+                    },
+                }
+            }
+            if failed > 0 {
+                anyhow::bail!("{} server(s) not shelved", failed);
+            }
+            Ok(())
+        },
+    }
+}
+
+/// Servers of the project with their metadata (GET /servers/detail)
+async fn servers(cloud: &openstack::Cloud) -> Result<Vec<Value>> {
+    let body = nova::get(cloud, &["servers", "detail"]).await.context("Failed to fetch server list")?;
+    Ok(body["servers"].as_array().cloned().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn entries_with_label_selectors() {
+        let job = Job::parse("shelve env=dev,team=web at 20:00").unwrap();
+        assert_eq!(job.action, Action::Shelve);
+        assert_eq!(job.at, NaiveTime::from_hms_opt(20, 0, 0).unwrap());
+        assert_eq!(job.describe(), "shelve env=dev,team=web at 20:00");
+        assert!(job.matches(&serde_json::json!({ "env": "dev", "team": "web", "owner": "x" })));
+        assert!(!job.matches(&serde_json::json!({ "env": "dev" })));
+        assert!(!job.matches(&serde_json::json!({ "env": "prod", "team": "web" })));
+
+        assert!(Job::parse("shelve env=dev 20:00").is_err());
+        assert!(Job::parse("reboot env=dev at 20:00").is_err());
+        assert!(Job::parse("shelve env at 20:00").is_err());
+        assert!(Job::parse("shelve env=dev at 25:00").is_err());
+    }
+
+    #[test]
+    fn next_run_today_or_tomorrow() {
+        let job = Job::parse("unshelve env=dev at 08:00").unwrap();
+        let morning = Local.with_ymd_and_hms(2026, 3, 10, 7, 30, 0).unwrap();
+        assert_eq!(job.next_run(morning), Local.with_ymd_and_hms(2026, 3, 10, 8, 0, 0).unwrap());
+        let at = Local.with_ymd_and_hms(2026, 3, 10, 8, 0, 0).unwrap();
+        assert_eq!(job.next_run(at), Local.with_ymd_and_hms(2026, 3, 11, 8, 0, 0).unwrap());
+    }
+}
@@ -0,0 +1,40 @@
+use anyhow::{Context, Result};
+use openstack::compute::ServerAction;
+
+use crate::config;
+use crate::killswitch;
+use crate::state::ServerState;
+
+/// Whether the credentials may act on the server, checked once at startup. Nova checks the policy
+/// before the server state, so unshelve of an ACTIVE server is harmless: 409 Conflict if allowed,
+/// 403 Forbidden if the credentials are read-only (reader role, restricted application credential).
+/// On 403 automatic actions on this server are switched off instead of failing during the first incident,
+/// other servers of the daemon may have other policies.
+/// The probe is a real unshelve request that policy hooks and audit logs will see, so it only runs
+/// with CREDENTIAL_SCOPE_CHECK=true. Returns the result for the startup summary
+pub async fn check(cloud: &openstack::Cloud, server_name: &str, server_id: &str) -> Result<String> {
+    let enabled = config::var("CREDENTIAL_SCOPE_CHECK")
+        .unwrap_or_else(|_| "false".to_string())
+        .parse::<bool>()
+        .context("CREDENTIAL_SCOPE_CHECK must be true or false")?;
+    if !enabled {
+        return Ok("not checked (CREDENTIAL_SCOPE_CHECK is off)".to_string());
+    }
+    let mut server = cloud.get_server(server_id).await.context("Failed to get server for the credential check")?;
+    if ServerState::of(&server) != ServerState::Active {
+        // Unshelve would really run - the first action shows whether it's allowed
+        return Ok(format!("not checked (server is {})", server.status()));
+    }
+    match server.action(ServerAction::Unshelve).await {
+        Err(e) if e.kind() == openstack::ErrorKind::AccessDenied => {
+            let reason = "credentials can't perform server actions (403 on unshelve)".to_string();
+            println!("⚠️ The {} - running observe-only, unshelve will have to be done by someone else", reason);
+            killswitch::set_read_only(server_name, &reason);
+            Ok(format!("read-only: {}", e))
+        },
+        Err(e) if e.kind() == openstack::ErrorKind::Conflict => Ok("server actions allowed".to_string()),
+        // Accepted for an ACTIVE server - nothing to do for Nova, but clearly allowed
+        Ok(_) => Ok("server actions allowed".to_string()),
+        Err(e) => Ok(format!("unknown ({})", e)),
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::env;
+use std::net::IpAddr;
+use anyhow::{Context, Result};
+use openstack::compute::{Server, ServerAction};
+use openstack::waiter::Waiter;
+use tokio::time::{sleep, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::address;
+use crate::config::Vars;
+use crate::monitor;
+use crate::probe::{self, Prober};
+use crate::state::ServerState;
+
+/// Delay between status polls and pings
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Monitoring keeps running this long after the server answers, so it reports the recovery itself
+const RECOVERY_REPORT_TIME: Duration = Duration::from_secs(90);
+
+/// Boot a disposable server, shelve it and let the monitor bring it back - proves credentials,
+/// sockets, unshelve permissions and notification channels in this environment.
+/// The server is deleted afterwards unless `keep` is set
+pub async fn selftest(cloud: &openstack::Cloud, flavor: &str, image: &str, network: &str, limit: Duration, keep: bool) -> Result<()> {
+    let name = format!("unshelve-selftest-{}", chrono::Local::now().format("%Y%m%d%H%M%S"));
+    let started = Instant::now();
+    println!("Booting test server '{}' ({}, {}, network {})", name, flavor, image, network);
+    let server = cloud
+        .new_server(&name, flavor)
+        .with_image(image)
+        .with_network(network)
+        .create()
+        .await
+        .context("Failed to create test server")?
+        .wait()
+        .await
+        .context("Test server failed to boot")?;
+    println!("✓ Test server {} is ACTIVE ({}s)", server.id(), started.elapsed().as_secs());
+
+    let result = run(cloud, server.id(), &name, limit).await;
+
+    if keep {
+        println!("Test server '{}' ({}) kept, delete it when done", name, server.id());
+    } else if let Err(e) = delete(cloud, server.id()).await {
+        println!("✗ Failed to delete test server '{}' ({}): {:#} - delete it manually", name, server.id(), e);
+    }
+
+    println!("{}", "=".repeat(80));
+    match &result {
+        Ok(()) => println!("✅ Self-test passed in {}s", started.elapsed().as_secs()),
+        Err(e) => println!("✗ Self-test failed: {:#}", e),
+    }
+    result
+}
+
+async fn run(cloud: &openstack::Cloud, server_id: &str, name: &str, limit: Duration) -> Result<()> {
+    let mut server = cloud.get_server(server_id).await.context("Failed to get test server")?;
+    let ip = address::select(&server, None, None)?;
+    let use_dgram_socket = probe::socket_type_from_env()?;
+    let mut prober = Prober::self_test(use_dgram_socket, ip.is_ipv6(), Duration::from_secs(3)).await?;
+
+    wait_reachable(&mut prober, ip, limit).await
+        .context("Test server doesn't answer pings - check the security group allows ICMP from this host")?;
+    println!("✓ Test server answers pings at {}", ip);
+
+    server.action(ServerAction::Shelve).await.context("Failed to shelve test server")?;
+    wait_for(cloud, server_id, "shelved", limit, |server| ServerState::of(server).is_shelved()).await?;
+    println!("✓ Test server is {}", cloud.get_server(server_id).await?.status());
+
+    // The monitor runs as configured, only pointed at the test server. Pins and shared state
+    // are left alone, the test server shouldn't stay in them
+    let pin_file = env::temp_dir().join(format!(".{}-pins", name));
+    let vars = Vars::new(HashMap::from([
+        ("SERVER_NAME".to_string(), name.to_string()),
+        ("PING_IP".to_string(), ip.to_string()),
+        ("CHECK_MODE".to_string(), "ping".to_string()),
+        ("PING_INTERVAL_MINUTES".to_string(), "1".to_string()),
+        ("PIN_FILE".to_string(), pin_file.display().to_string()),
+        ("STATE_URL".to_string(), String::new()),
+    ]));
+    let watch = monitor::Watch { server: Some(name.to_string()), ping_ip: Some(ip), ..Default::default() };
+    println!("{}", "=".repeat(80));
+    println!("Starting monitoring of the test server, it should be unshelved");
+
+    let shutdown = CancellationToken::new();
+    let monitoring = async {
+        let result = vars
+            .scope(monitor::start_monitoring(cloud, &watch, use_dgram_socket, Some(limit), None, shutdown.clone()))
+            .await;
+        shutdown.cancel();
+        result
+    };
+    let verify = async {
+        let result = tokio::select! {
+            result = wait_reachable(&mut prober, ip, limit) => result,
+            _ = shutdown.cancelled() => Err(anyhow::anyhow!("Monitoring stopped before the server answered")),
+        };
+        if result.is_ok() {
+            sleep(RECOVERY_REPORT_TIME).await;
+        }
+        shutdown.cancel();
+        result
+    };
+    let (monitored, verified) = tokio::join!(monitoring, verify);
+    let _ = std::fs::remove_file(&pin_file);
+    monitored.context("Monitoring failed")?;
+    verified.context("Test server was not recovered by the monitor")?;
+    println!("✓ Test server was unshelved by the monitor and answers pings");
+    Ok(())
+}
+
+/// Ping until the server answers or `limit` runs out
+async fn wait_reachable(prober: &mut Prober, ip: IpAddr, limit: Duration) -> Result<()> {
+    let deadline = Instant::now() + limit;
+    loop {
+        if prober.probe(ip).await.is_ok() {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            anyhow::bail!("{} not reachable after {}s", ip, limit.as_secs());
+        }
+        sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Poll until `done` holds for the server, ERROR fails the wait
+async fn wait_for(cloud: &openstack::Cloud, server_id: &str, expected: &str, limit: Duration, done: impl Fn(&Server) -> bool) -> Result<()> {
+    let deadline = Instant::now() + limit;
+    loop {
+        sleep(POLL_INTERVAL).await;
+        match cloud.get_server(server_id).await {
+            Ok(server) if done(&server) => return Ok(()),
+            Ok(server) if ServerState::of(&server) == ServerState::Error => {
+                anyhow::bail!("Test server went to ERROR status instead of {}", expected);
+            },
+            Ok(server) => println!("Status {}, waiting for {}", server.status(), expected),
+            Err(e) => println!("✗ Failed to get server info: {}", e),
+        }
+        if Instant::now() >= deadline {
+            anyhow::bail!("Test server is not {} after {}s", expected, limit.as_secs());
+        }
+    }
+}
+
+async fn delete(cloud: &openstack::Cloud, server_id: &str) -> Result<()> {
+    let server = cloud.get_server(server_id).await?;
+    server.delete().await?.wait().await?;
+    println!("✓ Test server deleted");
+    Ok(())
+}
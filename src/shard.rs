@@ -0,0 +1,77 @@
+use anyhow::{Context, Result};
+
+/// Part of the fleet handled by one instance, `--shard 2/5` is the second of five.
+/// Servers are assigned by a stable hash of the UUID, so every instance computes the same split
+#[derive(Clone, Copy, Debug)]
+pub struct Shard {
+    /// 1-based
+    index: u64,
+    count: u64,
+}
+
+impl Shard {
+    pub fn parse(value: &str) -> Result<Self> {
+        let (index, count) = value
+            .split_once('/')
+            .context(format!("Invalid shard '{}'. Expected K/N, e.g. 2/5", value))?;
+        let index: u64 = index.trim().parse().context(format!("Invalid shard number in '{}'", value))?;
+        let count: u64 = count.trim().parse().context(format!("Invalid shard count in '{}'", value))?;
+        if count == 0 || index == 0 || index > count {
+            anyhow::bail!("Invalid shard '{}': K must be from 1 to N", value);
+        }
+        Ok(Shard { index, count })
+    }
+
+    /// Whether the server with this UUID belongs to the shard
+    pub fn contains(&self, server_id: &str) -> bool {
+        fnv1a(server_id) % self.count == self.index - 1
+    }
+}
+
+impl std::fmt::Display for Shard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+/// FNV-1a: unlike std's hasher, guaranteed the same across builds and Rust versions
+fn fnv1a(value: &str) -> u64 {
+    value.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_is_stable() {
+        // Reference values of FNV-1a 64 - the split must not change between releases
+        assert_eq!(fnv1a(""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a("a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn every_server_in_exactly_one_shard() {
+        let shards: Vec<Shard> = (1..=3).map(|k| Shard::parse(&format!("{}/3", k)).unwrap()).collect();
+        let servers: Vec<String> = (0..100).map(|i| format!("6f1c2a3e-0000-4000-8000-{:012}", i)).collect();
+        let mut sizes = [0; 3];
+        for server in &servers {
+            let owners: Vec<usize> = (0..3).filter(|&i| shards[i].contains(server)).collect();
+            assert_eq!(owners.len(), 1, "{}", server);
+            sizes[owners[0]] += 1;
+            // Same answer every time
+            assert!(shards[owners[0]].contains(server));
+        }
+        assert!(sizes.iter().all(|&n| n > 0), "{:?}", sizes);
+        assert!(Shard::parse("1/1").unwrap().contains(&servers[0]));
+    }
+
+    #[test]
+    fn invalid_shards() {
+        assert_eq!(Shard::parse(" 2 / 5 ").unwrap().to_string(), "2/5");
+        assert!(Shard::parse("0/5").is_err());
+        assert!(Shard::parse("6/5").is_err());
+        assert!(Shard::parse("1/0").is_err());
+        assert!(Shard::parse("2").is_err());
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use anyhow::{Context, Result};
+use tokio::time::{timeout, Duration};
+
+use crate::chaos;
+use crate::config;
+use crate::routing::Routing;
+
+/// Names of signals that can be weighted in SIGNAL_WEIGHTS
+const SIGNALS: [&str; 4] = ["icmp", "tcp", "api", "external"];
+
+/// Result of one detection signal
+pub struct Signal {
+    pub name: &'static str,
+    pub healthy: bool,
+    pub detail: String,
+}
+
+impl Signal {
+    pub fn new(name: &'static str, healthy: bool, detail: String) -> Self {
+        Signal { name, healthy, detail }
+    }
+}
+
+/// Weights of detection signals, e.g. SIGNAL_WEIGHTS='icmp:1,tcp:1,api:2,external:1'.
+/// Server is down when weighted share of failed signals reaches DOWN_SCORE_THRESHOLD
+pub struct Scoring {
+    weights: HashMap<String, f64>,
+    threshold: f64,
+}
+
+/// Combined health verdict with per-signal breakdown
+pub struct Verdict {
+    pub down: bool,
+    pub score: f64,
+    pub breakdown: String,
+}
+
+impl Scoring {
+    /// None if SIGNAL_WEIGHTS is not set - plain "ping failed, check API" detection is used
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(raw) = config::var("SIGNAL_WEIGHTS").ok().filter(|w| !w.trim().is_empty()) else {
+            return Ok(None);
+        };
+
+        let mut weights: HashMap<String, f64> = HashMap::new();
+        for pair in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (name, weight) = pair
+                .split_once(':')
+                .ok_or_else(|| anyhow::anyhow!("Invalid SIGNAL_WEIGHTS entry: '{}'. Expected signal:weight", pair))?;
+            let name = name.trim().to_lowercase();
+            if !SIGNALS.contains(&name.as_str()) {
+                anyhow::bail!("Unknown signal '{}' in SIGNAL_WEIGHTS. Allowed: {}", name, SIGNALS.join(", "));
+            }
+            let weight: f64 = weight.trim().parse().context(format!("Weight of '{}' must be a number", name))?;
+            weights.insert(name, weight);
+        }
+
+        let threshold: f64 = config::var("DOWN_SCORE_THRESHOLD")
+            .unwrap_or_else(|_| "0.5".to_string())
+            .parse()
+            .context("DOWN_SCORE_THRESHOLD must be a number")?;
+
+        Ok(Some(Scoring { weights, threshold }))
+    }
+
+    pub fn weight(&self, name: &str) -> f64 {
+        self.weights.get(name).copied().unwrap_or(0.0)
+    }
+
+    pub fn describe(&self) -> String {
+        let mut weights: Vec<String> = SIGNALS
+            .iter()
+            .filter(|s| self.weight(s) > 0.0)
+            .map(|s| format!("{}:{}", s, self.weight(s)))
+            .collect();
+        weights.sort();
+        format!("{} (down at {:.2})", weights.join(", "), self.threshold)
+    }
+
+    pub fn verdict(&self, signals: &[Signal]) -> Verdict {
+        let total: f64 = signals.iter().map(|s| self.weight(s.name)).sum();
+        let failed: f64 = signals.iter().filter(|s| !s.healthy).map(|s| self.weight(s.name)).sum();
+        let score = if total > 0.0 { failed / total } else { 0.0 };
+
+        let breakdown: Vec<String> = signals
+            .iter()
+            .map(|s| format!("{} {} {} (w {})", if s.healthy { "✓" } else { "✗" }, s.name, s.detail, self.weight(s.name)))
+            .collect();
+
+        Verdict {
+            down: score >= self.threshold && failed > 0.0,
+            score,
+            breakdown: format!("{} => score {:.2}", breakdown.join(", "), score),
+        }
+    }
+}
+
+/// TCP connect check, configured with TCP_CHECK_PORT on the ping target.
+/// TCP_CHECK_DSCP, TCP_CHECK_VRF and TCP_CHECK_NETNS set its routing
+pub struct TcpCheck {
+    addr: SocketAddr,
+    timeout: Duration,
+    routing: Routing,
+}
+
+impl TcpCheck {
+    pub fn from_env(ip: IpAddr, timeout: Duration) -> Result<Option<Self>> {
+        match config::var("TCP_CHECK_PORT").ok().filter(|p| !p.trim().is_empty()) {
+            Some(port) => {
+                let port: u16 = port.trim().parse().context("TCP_CHECK_PORT must be a port number")?;
+                Ok(Some(TcpCheck { addr: SocketAddr::new(ip, port), timeout, routing: Routing::from_env("TCP_CHECK")? }))
+            },
+            None => Ok(None),
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn routing(&self) -> &Routing {
+        &self.routing
+    }
+
+    pub async fn check(&self) -> Signal {
+        if chaos::tcp_fail() {
+            return Signal::new("tcp", false, format!("{} injected failure", self.addr));
+        }
+        match timeout(self.timeout, self.routing.connect(self.addr)).await {
+            Ok(Ok(_)) => Signal::new("tcp", true, format!("{} open", self.addr)),
+            Ok(Err(e)) => Signal::new("tcp", false, format!("{} {:#}", self.addr, e)),
+            Err(_) => Signal::new("tcp", false, format!("{} timeout", self.addr)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scoring(weights: &[(&str, f64)], threshold: f64) -> Scoring {
+        Scoring { weights: weights.iter().map(|(n, w)| (n.to_string(), *w)).collect(), threshold }
+    }
+
+    fn signal(name: &'static str, healthy: bool) -> Signal {
+        Signal::new(name, healthy, String::new())
+    }
+
+    #[test]
+    fn failed_signals_weighted() {
+        let scoring = scoring(&[("icmp", 1.0), ("tcp", 1.0), ("api", 2.0)], 0.5);
+        let verdict = scoring.verdict(&[signal("icmp", false), signal("tcp", true), signal("api", true)]);
+        assert_eq!(verdict.score, 0.25);
+        assert!(!verdict.down);
+        let verdict = scoring.verdict(&[signal("icmp", true), signal("tcp", true), signal("api", false)]);
+        assert_eq!(verdict.score, 0.5);
+        assert!(verdict.down);
+        assert!(verdict.breakdown.ends_with("✗ api  (w 2) => score 0.50"), "{}", verdict.breakdown);
+    }
+
+    #[test]
+    fn missing_signals_dont_count() {
+        let scoring = scoring(&[("icmp", 1.0), ("tcp", 1.0), ("external", 3.0)], 0.5);
+        // No external signal this round - the score is over the signals present
+        let verdict = scoring.verdict(&[signal("icmp", false), signal("tcp", true)]);
+        assert_eq!(verdict.score, 0.5);
+        assert!(verdict.down);
+        // Unweighted signals and no signals at all never make a server down
+        assert!(!scoring.verdict(&[signal("api", false)]).down);
+        assert_eq!(scoring.verdict(&[]).score, 0.0);
+        assert_eq!(scoring.describe(), "external:3, icmp:1, tcp:1 (down at 0.50)");
+    }
+
+    #[test]
+    fn threshold_boundary() {
+        let signals = [signal("icmp", false), signal("tcp", true), signal("api", true), signal("external", true)];
+        assert!(scoring(&[("icmp", 1.0), ("tcp", 1.0), ("api", 1.0), ("external", 1.0)], 0.25).verdict(&signals).down);
+        assert!(!scoring(&[("icmp", 1.0), ("tcp", 1.0), ("api", 1.0), ("external", 1.0)], 0.26).verdict(&signals).down);
+        // Threshold 0 still needs a failed signal
+        assert!(!scoring(&[("icmp", 1.0)], 0.0).verdict(&[signal("icmp", true)]).down);
+    }
+}
@@ -0,0 +1,266 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDateTime};
+
+use crate::config;
+use crate::drift;
+use crate::notify::Event;
+
+/// Labels an event can be matched on
+const LABELS: [&str; 3] = ["server", "kind", "severity"];
+
+/// Notifications of matching events are not sent between `starts` and `ends`,
+/// the events are still logged and recorded in the history
+pub struct Silence {
+    pub id: String,
+    pub starts: DateTime<Local>,
+    pub ends: DateTime<Local>,
+    /// label=pattern, * stands for any characters
+    pub matchers: Vec<(String, String)>,
+    pub comment: String,
+}
+
+impl Silence {
+    fn matches(&self, event: &Event) -> bool {
+        let now = Local::now();
+        if now < self.starts || now >= self.ends {
+            return false;
+        }
+        let severity = event.severity.to_string().to_lowercase();
+        self.matchers.iter().all(|(label, pattern)| {
+            let value = match label.as_str() {
+                "server" => event.server.as_str(),
+                "kind" => event.kind,
+                _ => severity.as_str(),
+            };
+            drift::matches(pattern, value)
+        })
+    }
+
+    fn matchers_string(&self) -> String {
+        self.matchers.iter().map(|(l, p)| format!("{}={}", l, p)).collect::<Vec<_>>().join(",")
+    }
+}
+
+/// Silences in SILENCE_FILE as `id starts ends matchers comment` lines, managed with `unshelve silence`.
+/// Independent of maintenance windows, which also suppress actions
+pub struct SilenceStore {
+    path: PathBuf,
+    silences: Vec<Silence>,
+}
+
+impl SilenceStore {
+    pub fn load() -> Result<Self> {
+        Self::load_from(Self::path())
+    }
+
+    fn path() -> PathBuf {
+        PathBuf::from(config::var("SILENCE_FILE").unwrap_or_else(|_| ".unshelve-silences".to_string()))
+    }
+
+    fn load_from(path: PathBuf) -> Result<Self> {
+        let mut silences = vec![];
+
+        if path.exists() {
+            let content = fs::read_to_string(&path)
+                .context(format!("Failed to read silence file: {}", path.display()))?;
+            for line in content.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+                let mut fields = line.splitn(5, ' ');
+                let (Some(id), Some(starts), Some(ends), Some(matchers)) = (fields.next(), fields.next(), fields.next(), fields.next()) else {
+                    anyhow::bail!("Invalid line in {}: '{}'", path.display(), line);
+                };
+                let time = |value: &str| {
+                    DateTime::parse_from_rfc3339(value)
+                        .map(|t| t.with_timezone(&Local))
+                        .context(format!("Invalid time in {}: '{}'", path.display(), line))
+                };
+                silences.push(Silence {
+                    id: id.to_string(),
+                    starts: time(starts)?,
+                    ends: time(ends)?,
+                    matchers: parse_matchers(&matchers.split(',').map(String::from).collect::<Vec<_>>())?,
+                    comment: fields.next().unwrap_or_default().to_string(),
+                });
+            }
+        }
+        Ok(SilenceStore { path, silences })
+    }
+
+    fn save(&self) -> Result<()> {
+        let lines: Vec<String> = self.silences
+            .iter()
+            .map(|s| format!("{} {} {} {} {}", s.id, s.starts.to_rfc3339(), s.ends.to_rfc3339(), s.matchers_string(), s.comment).trim_end().to_string())
+            .collect();
+        let content = if lines.is_empty() { String::new() } else { lines.join("\n") + "\n" };
+        fs::write(&self.path, content)
+            .context(format!("Failed to write silence file: {}", self.path.display()))
+    }
+
+    /// Id of the active silence matching the event, None if notifications go out
+    pub fn matching(&self, event: &Event) -> Option<&str> {
+        self.silences.iter().find(|s| s.matches(event)).map(|s| s.id.as_str())
+    }
+
+    /// Add a silence, expired ones are dropped from the file
+    pub fn add(&mut self, matchers: &[String], start: Option<&str>, duration: &str, comment: Option<&str>) -> Result<()> {
+        let starts = match start {
+            Some(value) => NaiveDateTime::parse_from_str(value.trim(), "%Y-%m-%d %H:%M")
+                .ok()
+                .and_then(|t| t.and_local_timezone(Local).earliest())
+                .context(format!("--start must be a local time YYYY-MM-DD HH:MM, got '{}'", value))?,
+            None => Local::now(),
+        };
+        let duration = crate::monitor::parse_duration(duration)?;
+        let ends = starts + chrono::Duration::from_std(duration).context("Silence duration is too long")?;
+        let silence = Silence {
+            id: format!("{:08x}", rand::random::<u32>()),
+            starts,
+            ends,
+            matchers: parse_matchers(matchers)?,
+            comment: comment.unwrap_or_default().replace('\n', " "),
+        };
+        println!("✓ Silence {} ({}) from {} until {}", silence.id, silence.matchers_string(),
+                 starts.format("%Y-%m-%d %H:%M"), ends.format("%Y-%m-%d %H:%M"));
+
+        let now = Local::now();
+        self.silences.retain(|s| s.ends > now);
+        self.silences.push(silence);
+        self.save()
+    }
+
+    pub fn remove(&mut self, id: &str) -> Result<()> {
+        let before = self.silences.len();
+        self.silences.retain(|s| s.id != id);
+        if self.silences.len() == before {
+            anyhow::bail!("Silence '{}' not found", id);
+        }
+        self.save()?;
+        println!("✓ Silence {} removed", id);
+        Ok(())
+    }
+
+    /// Print active and pending silences, with `all` also expired ones
+    pub fn list(&self, all: bool) {
+        let now = Local::now();
+        let shown: Vec<&Silence> = self.silences.iter().filter(|s| all || s.ends > now).collect();
+        if shown.is_empty() {
+            println!("No silences");
+            return;
+        }
+        for silence in shown {
+            let state = if silence.ends <= now { "expired" } else if silence.starts > now { "pending" } else { "active" };
+            println!("{}  {:<8} {} - {}  {}  {}", silence.id, state, silence.starts.format("%Y-%m-%d %H:%M"),
+                     silence.ends.format("%Y-%m-%d %H:%M"), silence.matchers_string(), silence.comment);
+        }
+    }
+}
+
+/// SILENCE_FILE for the notifier, read again only when its modification time changes,
+/// so `unshelve silence` applies to a running daemon without reading the file for every event
+#[derive(Default)]
+pub struct SilenceCache {
+    /// Path, modification time (None if there is no file) and silences read from it
+    cached: Option<(PathBuf, Option<SystemTime>, SilenceStore)>,
+}
+
+impl SilenceCache {
+    pub fn get(&mut self) -> Result<&SilenceStore> {
+        let path = SilenceStore::path();
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let store = match self.cached.take() {
+            Some((cached_path, cached_modified, store)) if cached_path == path && cached_modified == modified => store,
+            _ => SilenceStore::load_from(path.clone())?,
+        };
+        Ok(&self.cached.insert((path, modified, store)).2)
+    }
+}
+
+/// label=pattern matchers, labels: server, kind, severity
+fn parse_matchers(matchers: &[String]) -> Result<Vec<(String, String)>> {
+    let mut parsed = vec![];
+    for matcher in matchers.iter().map(|m| m.trim()).filter(|m| !m.is_empty()) {
+        let Some((label, pattern)) = matcher.split_once('=') else {
+            anyhow::bail!("Invalid matcher '{}'. Expected LABEL=PATTERN, e.g. server=web*", matcher);
+        };
+        let label = label.trim().to_lowercase();
+        if !LABELS.contains(&label.as_str()) {
+            anyhow::bail!("Unknown label '{}' in matcher '{}'. Allowed labels: {}", label, matcher, LABELS.join(", "));
+        }
+        let pattern = pattern.trim();
+        if pattern.is_empty() || pattern.contains([' ', ',']) {
+            anyhow::bail!("Pattern in matcher '{}' can't be empty or contain spaces or commas", matcher);
+        }
+        parsed.push((label, pattern.to_string()));
+    }
+    if parsed.is_empty() {
+        anyhow::bail!("A silence needs at least one matcher, e.g. --match server=web1");
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::notify::Severity;
+
+    fn silence(starts_in_minutes: i64, ends_in_minutes: i64, matchers: &[&str]) -> Silence {
+        let now = Local::now();
+        Silence {
+            id: "s1".to_string(),
+            starts: now + chrono::Duration::minutes(starts_in_minutes),
+            ends: now + chrono::Duration::minutes(ends_in_minutes),
+            matchers: parse_matchers(&matchers.iter().map(|m| m.to_string()).collect::<Vec<_>>()).unwrap(),
+            comment: String::new(),
+        }
+    }
+
+    fn event(severity: Severity, kind: &'static str, server: &str) -> Event {
+        Event::new(severity, kind, server, String::new())
+    }
+
+    #[test]
+    fn matching_by_every_label() {
+        let silence = silence(-5, 60, &["server=web*", "kind=ping_failed", "severity=warning"]);
+        assert!(silence.matches(&event(Severity::Warning, "ping_failed", "web1")));
+        assert!(!silence.matches(&event(Severity::Warning, "ping_failed", "db1")));
+        assert!(!silence.matches(&event(Severity::Warning, "unshelve_sent", "web1")));
+        assert!(!silence.matches(&event(Severity::Critical, "ping_failed", "web1")));
+        assert_eq!(silence.matchers_string(), "server=web*,kind=ping_failed,severity=warning");
+    }
+
+    #[test]
+    fn only_active_silences_match() {
+        let event = event(Severity::Info, "recovered", "web1");
+        assert!(!silence(-60, -5, &["server=web1"]).matches(&event), "expired");
+        assert!(!silence(5, 60, &["server=web1"]).matches(&event), "pending");
+        assert!(silence(0, 60, &["server=web1"]).matches(&event));
+        let store = SilenceStore { path: PathBuf::new(), silences: vec![silence(-60, -5, &["server=*"]), silence(-5, 60, &["kind=*"])] };
+        assert_eq!(store.matching(&event), Some("s1"));
+    }
+
+    #[test]
+    fn invalid_matchers() {
+        assert!(parse_matchers(&["host=web1".to_string()]).is_err());
+        assert!(parse_matchers(&["server=".to_string()]).is_err());
+        assert!(parse_matchers(&["server".to_string()]).is_err());
+        assert!(parse_matchers(&[]).is_err());
+    }
+
+    #[tokio::test]
+    async fn cache_reads_file_again_once_it_changes() {
+        let path = std::env::temp_dir().join(format!("unshelve-test-silences-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let vars = config::Vars::new(HashMap::from([("SILENCE_FILE".to_string(), path.display().to_string())]));
+        vars.scope(async {
+            let mut cache = SilenceCache::default();
+            let event = event(Severity::Warning, "ping_failed", "web1");
+            assert_eq!(cache.get().unwrap().matching(&event), None);
+            SilenceStore::load().unwrap().add(&["server=web1".to_string()], None, "1h", None).unwrap();
+            assert!(cache.get().unwrap().matching(&event).is_some());
+        }).await;
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,14 @@
+use std::fs;
+use std::path::Path;
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+/// Write JSON to a temporary file and rename it over the target,
+/// so readers never see a partially written snapshot
+pub fn write_atomic(path: &str, snapshot: &Value) -> Result<()> {
+    let target = Path::new(path);
+    let tmp = target.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(snapshot)? + "\n")
+        .context(format!("Failed to write snapshot: {}", tmp.display()))?;
+    fs::rename(&tmp, target).context(format!("Failed to replace snapshot: {}", path))
+}
@@ -0,0 +1,133 @@
+use anyhow::{Context, Result};
+use tokio::net::UdpSocket;
+use tokio::time::Instant;
+
+use crate::config;
+use crate::notify::Event;
+
+/// UNSHELVE-MIB root (docs/UNSHELVE-MIB.txt), under NET-SNMP's netSnmpPlaypen - there is no
+/// registered enterprise number, sites with their own can rebase the MIB
+const MIB_ROOT: [u32; 9] = [1, 3, 6, 1, 4, 1, 8072, 9999, 9999];
+const SYS_UPTIME: [u32; 9] = [1, 3, 6, 1, 2, 1, 1, 3, 0];
+const SNMP_TRAP_OID: [u32; 11] = [1, 3, 6, 1, 6, 3, 1, 1, 4, 1, 0];
+
+/// Notification number under unshelveNotifications (MIB_ROOT.0) for the event kind,
+/// None if the event is not sent as a trap
+fn notification(kind: &str) -> Option<u32> {
+    match kind {
+        // Server found shelved
+        "unshelve_sent" | "actions_disabled" | "maintenance_suppressed" => Some(1),
+        "recovered" => Some(2),
+        "unshelve_failed" | "precondition_failed" | "pipeline_failed" | "root_volume_failed" => Some(3),
+        _ => None,
+    }
+}
+
+/// SNMPv2c traps to a classic NMS (SNMP_TRAP_TARGET, SNMP_COMMUNITY) on server down,
+/// recovered and unshelve failures. Varbinds: server name, event kind, severity, message
+pub struct TrapSender {
+    target: String,
+    community: String,
+    started: Instant,
+}
+
+impl TrapSender {
+    /// None if SNMP_TRAP_TARGET is not set
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(mut target) = config::var("SNMP_TRAP_TARGET").ok().filter(|t| !t.trim().is_empty()) else {
+            return Ok(None);
+        };
+        // Default trap port, an IPv6 address needs brackets: [2001:db8::1]:162
+        if target.ends_with(']') || target.rsplit_once(':').is_none_or(|(_, port)| port.parse::<u16>().is_err()) {
+            target = format!("{}:162", target);
+        }
+        let community = config::var("SNMP_COMMUNITY").ok().filter(|c| !c.is_empty()).unwrap_or_else(|| "public".to_string());
+        Ok(Some(TrapSender { target, community, started: Instant::now() }))
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Send a trap if the event kind has a notification in the MIB
+    pub async fn send(&self, event: &Event) -> Result<()> {
+        let Some(number) = notification(event.kind) else {
+            return Ok(());
+        };
+        let socket = UdpSocket::bind(if self.target.starts_with('[') { "[::]:0" } else { "0.0.0.0:0" })
+            .await
+            .context("Failed to open UDP socket for SNMP trap")?;
+        socket
+            .send_to(&self.trap(number, event), &self.target)
+            .await
+            .context(format!("Failed to send SNMP trap to {}", self.target))?;
+        Ok(())
+    }
+
+    /// SNMPv2-Trap-PDU in an SNMPv2c message
+    fn trap(&self, number: u32, event: &Event) -> Vec<u8> {
+        let object = |n: u32| [&MIB_ROOT[..], &[1, n]].concat();
+        let uptime = (self.started.elapsed().as_millis() / 10) as u64 & 0xFFFF_FFFF;
+        let varbinds = [
+            varbind(&SYS_UPTIME, integer(0x43, uptime)),
+            varbind(&SNMP_TRAP_OID, oid(&[&MIB_ROOT[..], &[0, number]].concat())),
+            varbind(&object(1), octet_string(&event.server)),
+            varbind(&object(2), octet_string(event.kind)),
+            varbind(&object(3), octet_string(&event.severity.to_string().to_lowercase())),
+            varbind(&object(4), octet_string(&event.message)),
+        ]
+        .concat();
+        let request_id = event.time.timestamp_subsec_micros() as u64;
+        let pdu = tlv(0xA7, &[integer(0x02, request_id), integer(0x02, 0), integer(0x02, 0), tlv(0x30, &varbinds)].concat());
+        // version 1 is SNMPv2c
+        tlv(0x30, &[integer(0x02, 1), octet_string(&self.community), pdu].concat())
+    }
+}
+
+fn varbind(name: &[u32], value: Vec<u8>) -> Vec<u8> {
+    tlv(0x30, &[oid(name), value].concat())
+}
+
+/// BER type-length-value, long form length above 127 bytes
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len.to_be_bytes().into_iter().skip_while(|b| *b == 0).collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend(bytes);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+/// Non-negative INTEGER or an application type (TimeTicks 0x43) in the fewest bytes,
+/// with a leading zero when the high bit would make it negative
+fn integer(tag: u8, value: u64) -> Vec<u8> {
+    let mut bytes: Vec<u8> = value.to_be_bytes().into_iter().skip_while(|b| *b == 0).collect();
+    if bytes.first().is_none_or(|b| b & 0x80 != 0) {
+        bytes.insert(0, 0);
+    }
+    tlv(tag, &bytes)
+}
+
+fn octet_string(value: &str) -> Vec<u8> {
+    tlv(0x04, value.as_bytes())
+}
+
+/// First two arcs in one byte, the rest base-128 with the continuation bit
+fn oid(arcs: &[u32]) -> Vec<u8> {
+    let mut content = vec![(arcs[0] * 40 + arcs[1]) as u8];
+    for &arc in &arcs[2..] {
+        let mut chunk = vec![(arc & 0x7F) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            chunk.insert(0, (rest & 0x7F) as u8 | 0x80);
+            rest >>= 7;
+        }
+        content.extend(chunk);
+    }
+    tlv(0x06, &content)
+}
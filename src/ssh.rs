@@ -0,0 +1,155 @@
+use std::net::{IpAddr, SocketAddr};
+use std::os::unix::process::CommandExt;
+use anyhow::{Context, Result};
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout, Duration, Instant};
+
+use crate::address::{self, AddressKind};
+use crate::config;
+use crate::guard;
+use crate::state::ServerState;
+
+/// How long to wait for ACTIVE after unshelve
+const ACTIVE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// How long to wait for sshd after the server is ACTIVE - the guest is still booting
+const SSH_READY_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Delay before the tunnel is re-established
+const TUNNEL_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Unshelve the server if needed, wait for ACTIVE and return its address
+pub async fn ensure_up(
+    cloud: &openstack::Cloud,
+    server_identifier: &str,
+    kind: Option<AddressKind>,
+    network: Option<&str>,
+) -> Result<IpAddr> {
+    let mut server = cloud.get_server(server_identifier).await.context("Failed to get server info")?;
+    match ServerState::of(&server) {
+        ServerState::Active => {},
+        state if state.is_shelved() => {
+            println!("Server '{}' is {} - unshelving...", server.name(), server.status());
+            server
+                .action(openstack::compute::ServerAction::Unshelve)
+                .await
+                .context("Failed to unshelve server")?;
+            guard::wait_active(cloud, server_identifier, ACTIVE_TIMEOUT).await?;
+        },
+        ServerState::Transition | ServerState::PowerTransition => {
+            guard::wait_active(cloud, server_identifier, ACTIVE_TIMEOUT).await?;
+        },
+        _ => anyhow::bail!("Server '{}' is {} - start it manually", server.name(), server.status()),
+    }
+    // Addresses may change after unshelve to another host
+    let server = cloud.get_server(server_identifier).await.context("Failed to get server info")?;
+    address::select(&server, kind, network)
+}
+
+/// SSH port from SSH_PORT, default 22
+pub fn ssh_port() -> Result<u16> {
+    config::var("SSH_PORT")
+        .unwrap_or_else(|_| "22".to_string())
+        .parse()
+        .context("SSH_PORT must be a port number")
+}
+
+/// Wait until sshd accepts connections
+async fn wait_ssh(ip: IpAddr, port: u16) -> Result<()> {
+    let addr = SocketAddr::new(ip, port);
+    let deadline = Instant::now() + SSH_READY_TIMEOUT;
+    loop {
+        if let Ok(Ok(_)) = timeout(Duration::from_secs(3), TcpStream::connect(addr)).await {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            anyhow::bail!("SSH on {} is not reachable after {} min", addr, SSH_READY_TIMEOUT.as_secs() / 60);
+        }
+        println!("Waiting for SSH on {}...", addr);
+        sleep(Duration::from_secs(5)).await;
+    }
+}
+
+/// ssh command line: user from SSH_USER, port from SSH_PORT, then extra arguments
+fn ssh_command(ip: IpAddr, port: u16, args: &[String]) -> std::process::Command {
+    let target = match config::var("SSH_USER").ok().filter(|u| !u.trim().is_empty()) {
+        Some(user) => format!("{}@{}", user, ip),
+        None => ip.to_string(),
+    };
+    let mut command = std::process::Command::new("ssh");
+    command.arg("-p").arg(port.to_string()).arg(target).args(args);
+    command
+}
+
+/// Wake up the server and replace this process with ssh to it
+pub async fn ssh(
+    cloud: &openstack::Cloud,
+    server_identifier: &str,
+    kind: Option<AddressKind>,
+    network: Option<&str>,
+    command: &[String],
+) -> Result<()> {
+    let port = ssh_port()?;
+    let ip = ensure_up(cloud, server_identifier, kind, network).await?;
+    wait_ssh(ip, port).await?;
+    // exec returns only on failure
+    let e = ssh_command(ip, port, command).exec();
+    Err(e).context("Failed to run ssh")
+}
+
+/// Keep SSH port forwards up: on every disconnect (e.g. the server was shelved)
+/// the server is unshelved again if needed and the tunnel re-established. Stops on Ctrl+C
+pub async fn tunnel(
+    cloud: &openstack::Cloud,
+    server_identifier: &str,
+    kind: Option<AddressKind>,
+    network: Option<&str>,
+    forwards: &[String],
+) -> Result<()> {
+    let port = ssh_port()?;
+    let mut args: Vec<String> = vec![
+        "-N".to_string(),
+        "-o".to_string(), "ExitOnForwardFailure=yes".to_string(),
+        "-o".to_string(), "ServerAliveInterval=15".to_string(),
+    ];
+    for forward in forwards {
+        args.push("-L".to_string());
+        args.push(forward.clone());
+    }
+
+    loop {
+        let ip = match ensure_up(cloud, server_identifier, kind, network).await {
+            Ok(ip) => ip,
+            Err(e) => {
+                println!("✗ {:#} - retrying in {}s", e, TUNNEL_RETRY_DELAY.as_secs());
+                sleep(TUNNEL_RETRY_DELAY).await;
+                continue;
+            },
+        };
+        if let Err(e) = wait_ssh(ip, port).await {
+            println!("✗ {:#} - retrying in {}s", e, TUNNEL_RETRY_DELAY.as_secs());
+            sleep(TUNNEL_RETRY_DELAY).await;
+            continue;
+        }
+
+        println!("[{}] Tunnel to {} up: {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), ip, forwards.join(", "));
+        let mut child = tokio::process::Command::from(ssh_command(ip, port, &args))
+            .kill_on_drop(true)
+            .spawn()
+            .context("Failed to run ssh")?;
+        // Ctrl+C reaches ssh too - check it first so the exit is not taken for a disconnect
+        tokio::select! {
+            biased;
+            _ = tokio::signal::ctrl_c() => {
+                println!("Tunnel stopped");
+                return Ok(());
+            },
+            status = child.wait() => {
+                let status = status.context("Failed to wait for ssh")?;
+                println!("[{}] ✗ Tunnel closed ({}) - re-establishing in {}s",
+                         chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), status, TUNNEL_RETRY_DELAY.as_secs());
+            },
+        }
+        sleep(TUNNEL_RETRY_DELAY).await;
+    }
+}
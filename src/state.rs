@@ -0,0 +1,146 @@
+use openstack::compute::{Server, ServerPowerState, ServerStatus};
+
+/// Server state from OpenStack status and power state.
+/// Statuses are mapped here only - everything else matches on the enum
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServerState {
+    /// ACTIVE and running
+    Active,
+    /// ACTIVE but not running: powering on or off
+    PowerTransition,
+    /// SHELVED - shut down, disk still on the hypervisor
+    Shelved,
+    /// SHELVED_OFFLOADED - disk moved off the hypervisor
+    ShelvedOffloaded,
+    /// BUILD, REBUILD, REBOOT, HARD_REBOOT, MIGRATING, RESIZE, VERIFY_RESIZE, REVERT_RESIZE, PASSWORD
+    Transition,
+    /// SHUTOFF - powered off on purpose, not handled automatically
+    Shutoff,
+    /// RESCUE - booted from a rescue image with its own disk attached
+    Rescue,
+    /// PAUSED, SUSPENDED - stopped on purpose, not handled automatically
+    Stopped,
+    Error,
+    /// SOFT_DELETED - deleted, but kept until the cloud's reclaim interval ends and can be restored
+    SoftDeleted,
+    /// DELETED
+    Deleted,
+    /// UNKNOWN or a status this program doesn't know
+    Unknown,
+}
+
+impl ServerState {
+    /// State of a server of the OpenStack client. Every status it knows is listed, so a new one
+    /// is a compile error here rather than a silent Unknown
+    pub fn of(server: &Server) -> Self {
+        match server.status() {
+            ServerStatus::Active if server.power_state() == ServerPowerState::Running => ServerState::Active,
+            ServerStatus::Active => ServerState::PowerTransition,
+            ServerStatus::Shelved => ServerState::Shelved,
+            ServerStatus::ShelvedOffloaded => ServerState::ShelvedOffloaded,
+            ServerStatus::Building
+            | ServerStatus::Rebuilding
+            | ServerStatus::Rebooting
+            | ServerStatus::HardRebooting
+            | ServerStatus::Migrating
+            | ServerStatus::Resizing
+            | ServerStatus::VerifyingResize
+            | ServerStatus::RevertingResize
+            | ServerStatus::PasswordReset => ServerState::Transition,
+            ServerStatus::ShutOff => ServerState::Shutoff,
+            ServerStatus::Rescued => ServerState::Rescue,
+            ServerStatus::Paused | ServerStatus::Suspended => ServerState::Stopped,
+            ServerStatus::Error => ServerState::Error,
+            ServerStatus::SoftDeleted => ServerState::SoftDeleted,
+            ServerStatus::Deleted => ServerState::Deleted,
+            ServerStatus::Unknown => ServerState::Unknown,
+        }
+    }
+
+    /// State from the status string of raw Nova JSON (server details, cassettes)
+    pub fn from_status(status: &str, running: bool) -> Self {
+        match status {
+            "ACTIVE" if running => ServerState::Active,
+            "ACTIVE" => ServerState::PowerTransition,
+            "SHELVED" => ServerState::Shelved,
+            "SHELVED_OFFLOADED" => ServerState::ShelvedOffloaded,
+            "BUILD" | "REBUILD" | "REBOOT" | "HARD_REBOOT" | "MIGRATING" | "RESIZE" | "VERIFY_RESIZE"
+            | "REVERT_RESIZE" | "PASSWORD" => ServerState::Transition,
+            "SHUTOFF" => ServerState::Shutoff,
+            "RESCUE" => ServerState::Rescue,
+            "PAUSED" | "SUSPENDED" => ServerState::Stopped,
+            "ERROR" => ServerState::Error,
+            "SOFT_DELETED" => ServerState::SoftDeleted,
+            "DELETED" => ServerState::Deleted,
+            _ => ServerState::Unknown,
+        }
+    }
+
+    /// Can be brought back with unshelve
+    pub fn is_shelved(self) -> bool {
+        match self {
+            ServerState::Shelved | ServerState::ShelvedOffloaded => true,
+            ServerState::Active
+            | ServerState::PowerTransition
+            | ServerState::Transition
+            | ServerState::Shutoff
+            | ServerState::Rescue
+            | ServerState::Stopped
+            | ServerState::Error
+            | ServerState::SoftDeleted
+            | ServerState::Deleted
+            | ServerState::Unknown => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn active_depends_on_power_state() {
+        assert_eq!(ServerState::from_status("ACTIVE", true), ServerState::Active);
+        assert_eq!(ServerState::from_status("ACTIVE", false), ServerState::PowerTransition);
+    }
+
+    #[test]
+    fn shelved_statuses() {
+        assert_eq!(ServerState::from_status("SHELVED", false), ServerState::Shelved);
+        assert_eq!(ServerState::from_status("SHELVED_OFFLOADED", false), ServerState::ShelvedOffloaded);
+        assert!(ServerState::from_status("SHELVED", false).is_shelved());
+        assert!(ServerState::from_status("SHELVED_OFFLOADED", false).is_shelved());
+    }
+
+    #[test]
+    fn every_nova_status() {
+        let cases = [
+            ("BUILD", ServerState::Transition),
+            ("REBUILD", ServerState::Transition),
+            ("REBOOT", ServerState::Transition),
+            ("HARD_REBOOT", ServerState::Transition),
+            ("MIGRATING", ServerState::Transition),
+            ("RESIZE", ServerState::Transition),
+            ("VERIFY_RESIZE", ServerState::Transition),
+            ("REVERT_RESIZE", ServerState::Transition),
+            ("PASSWORD", ServerState::Transition),
+            ("SHUTOFF", ServerState::Shutoff),
+            ("PAUSED", ServerState::Stopped),
+            ("SUSPENDED", ServerState::Stopped),
+            ("RESCUE", ServerState::Rescue),
+            ("ERROR", ServerState::Error),
+            ("SOFT_DELETED", ServerState::SoftDeleted),
+            ("DELETED", ServerState::Deleted),
+            ("UNKNOWN", ServerState::Unknown),
+            ("SOMETHING_NEW", ServerState::Unknown),
+        ];
+        for (status, expected) in cases {
+            // Power state only matters for ACTIVE
+            assert_eq!(ServerState::from_status(status, true), expected, "{}", status);
+            assert_eq!(ServerState::from_status(status, false), expected, "{}", status);
+            assert!(!expected.is_shelved(), "{}", status);
+        }
+        assert!(!ServerState::Active.is_shelved());
+        assert!(!ServerState::PowerTransition.is_shelved());
+    }
+}
@@ -0,0 +1,80 @@
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use anyhow::Result;
+
+use crate::config;
+
+/// Reject PING_IP values whose pings succeed no matter what the server does: an address of this host,
+/// its default gateway or the Keystone endpoint (OS_AUTH_URL). With ALLOW_ANY_PING_IP=true they are
+/// only warned about. PING_IP not among the server's addresses is always just a warning - NAT may explain it
+pub async fn validate(cloud: &openstack::Cloud, ip: IpAddr, server_id: Option<&str>) -> Result<()> {
+    let allow = config::var("ALLOW_ANY_PING_IP").is_ok_and(|v| v.eq_ignore_ascii_case("true"));
+    let mut problems: Vec<String> = vec![];
+
+    if ip.is_loopback() || ip.is_unspecified() {
+        problems.push("it's a loopback or unspecified address".to_string());
+    } else if is_local(ip) {
+        problems.push("it's an address of this host".to_string());
+    }
+    if default_gateway().is_some_and(|gateway| IpAddr::V4(gateway) == ip) {
+        problems.push("it's the default gateway of this host".to_string());
+    }
+    if let Some(host) = keystone_host() {
+        if let Ok(addresses) = tokio::net::lookup_host((host.as_str(), 0)).await {
+            if addresses.into_iter().any(|a| a.ip() == ip) {
+                problems.push(format!("it's the Keystone endpoint {}", host));
+            }
+        }
+    }
+
+    if let Some(server_id) = server_id {
+        if let Ok(server) = cloud.get_server(server_id).await {
+            let own = server.addresses().values().flatten().any(|a| a.addr == ip);
+            if !own && !server.addresses().is_empty() {
+                println!("⚠️ PING_IP {} is not an address of server '{}' - fine behind NAT, otherwise pings don't reflect the server",
+                         ip, server.name());
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+    let message = format!("PING_IP {} can't reflect the monitored server: {}", ip, problems.join(", "));
+    if allow {
+        println!("⚠️ {} (allowed by ALLOW_ANY_PING_IP)", message);
+        return Ok(());
+    }
+    anyhow::bail!("{}. Fix PING_IP or set ALLOW_ANY_PING_IP=true", message)
+}
+
+/// The kernel picks the source address for a route to `ip` - the address itself if it's local
+fn is_local(ip: IpAddr) -> bool {
+    let bind = if ip.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" };
+    UdpSocket::bind(bind)
+        .and_then(|socket| {
+            socket.connect((ip, 9))?;
+            socket.local_addr()
+        })
+        .is_ok_and(|local| local.ip() == ip)
+}
+
+/// IPv4 default gateway from /proc/net/route, None if there is none or it's not Linux
+fn default_gateway() -> Option<Ipv4Addr> {
+    let routes = fs::read_to_string("/proc/net/route").ok()?;
+    routes.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        // Iface Destination Gateway ..., addresses are little-endian hex
+        if fields.get(1) != Some(&"00000000") {
+            return None;
+        }
+        let gateway = u32::from_str_radix(fields.get(2)?, 16).ok()?;
+        (gateway != 0).then(|| Ipv4Addr::from(gateway.swap_bytes()))
+    })
+}
+
+/// Host of OS_AUTH_URL
+fn keystone_host() -> Option<String> {
+    let url = reqwest::Url::parse(&config::var("OS_AUTH_URL").ok()?).ok()?;
+    url.host_str().map(|h| h.trim_matches(['[', ']']).to_string())
+}
@@ -0,0 +1,49 @@
+use std::future::Future;
+use tokio::task::JoinSet;
+use tokio::time::{timeout, Duration};
+use tokio_util::sync::CancellationToken;
+
+use crate::config;
+
+/// Background tasks of the daemon (HTTP endpoints, listeners, lease keepalive) under one
+/// cancellation token. Every task gets a child token and must return soon after it's cancelled,
+/// shutdown waits for all of them
+pub struct TaskGroup {
+    token: CancellationToken,
+    tasks: JoinSet<()>,
+}
+
+impl TaskGroup {
+    pub fn new(token: CancellationToken) -> Self {
+        TaskGroup { token, tasks: JoinSet::new() }
+    }
+
+    /// Token cancelled on shutdown, for waits outside of the tasks
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    pub fn spawn<F, Fut>(&mut self, task: F)
+    where
+        F: FnOnce(CancellationToken) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.tasks.spawn(config::inherit(task(self.token.child_token())));
+    }
+
+    /// Cancel all tasks and wait up to `grace` for them, the rest are aborted
+    pub async fn shutdown(mut self, grace: Duration) {
+        self.token.cancel();
+        let joined = timeout(grace, async {
+            while let Some(result) = self.tasks.join_next().await {
+                if let Err(e) = result {
+                    println!("✗ Background task failed: {}", e);
+                }
+            }
+        }).await;
+        if joined.is_err() {
+            println!("⚠️ {} background task(s) didn't stop in {}s - aborted", self.tasks.len(), grace.as_secs());
+            self.tasks.shutdown().await;
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use anyhow::Result;
+use serde_json::Value;
+
+/// Output template for --format, e.g. '{{ .name }} {{ .status }} {{ .addresses.private.0 }}'.
+/// A field is a dot path into the server JSON, array items are addressed by index.
+/// Missing fields render as empty strings
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+enum Part {
+    Text(String),
+    Field(Vec<String>),
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parts = vec![];
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let Some(end) = rest[start..].find("}}") else {
+                anyhow::bail!("Unclosed '{{{{' in format: {}", template);
+            };
+            let field = rest[start + 2..start + end].trim();
+            let Some(path) = field.strip_prefix('.') else {
+                anyhow::bail!("Invalid field '{}' in format, expected e.g. {{{{ .name }}}}", field);
+            };
+            parts.push(Part::Field(path.split('.').filter(|k| !k.is_empty()).map(String::from).collect()));
+            rest = &rest[start + end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(Template { parts })
+    }
+
+    pub fn render(&self, value: &Value) -> String {
+        let mut output = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => output.push_str(text),
+                Part::Field(path) => {
+                    let field = path.iter().try_fold(value, |value, key| match value {
+                        Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+                        _ => value.get(key),
+                    });
+                    match field {
+                        None | Some(Value::Null) => {},
+                        Some(Value::String(s)) => output.push_str(s),
+                        Some(other) => output.push_str(&other.to_string()),
+                    }
+                },
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(template: &str, value: &Value) -> String {
+        Template::parse(template).unwrap().render(value)
+    }
+
+    #[test]
+    fn placeholders() {
+        let server = json!({ "name": "web1", "status": "ACTIVE", "addresses": { "private": ["10.0.0.11", "fd00::11"] }, "locked": false });
+        assert_eq!(render("{{ .name }} {{.status}}", &server), "web1 ACTIVE");
+        assert_eq!(render("ip={{ .addresses.private.1 }};", &server), "ip=fd00::11;");
+        assert_eq!(render("{{ .locked }}", &server), "false");
+        assert_eq!(render("no fields", &server), "no fields");
+    }
+
+    #[test]
+    fn unknown_keys_render_empty() {
+        let server = json!({ "name": "web1", "image": null, "addresses": { "private": ["10.0.0.11"] } });
+        assert_eq!(render("[{{ .flavor }}][{{ .image }}][{{ .addresses.private.5 }}][{{ .name.first }}]", &server), "[][][][]");
+    }
+
+    #[test]
+    fn values_are_not_escaped() {
+        let server = json!({ "name": "a \"quoted\"\tname", "metadata": { "env": "dev" } });
+        assert_eq!(render("{{ .name }}", &server), "a \"quoted\"\tname");
+        // Objects render as JSON
+        assert_eq!(render("{{ .metadata }}", &server), r#"{"env":"dev"}"#);
+        // Braces outside a field are text
+        assert_eq!(render("}} {{ .metadata.env }}", &server), "}} dev");
+    }
+
+    #[test]
+    fn invalid_templates() {
+        assert!(Template::parse("{{ .name").is_err());
+        assert!(Template::parse("{{ name }}").is_err());
+    }
+}
@@ -0,0 +1,168 @@
+use std::fs;
+use std::io::Read;
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+const BEGIN_MARKER: &str = "# BEGIN unshelve import terraform";
+const END_MARKER: &str = "# END unshelve import terraform";
+
+/// OpenStack instance found in Terraform state
+struct Instance {
+    /// Resource address, e.g. module.app.openstack_compute_instance_v2.web[0]
+    address: String,
+    id: String,
+    name: String,
+    ip: Option<String>,
+}
+
+/// Generate a profile per openstack_compute_instance_v2 from Terraform/OpenTofu state:
+/// <NAME>__SERVER_NAME with the UUID and <NAME>__PING_IP, monitored with --profile <name>.
+/// State is a file, an http(s) URL (http backend) or - for stdin (`terraform state pull`).
+/// With `output` the generated block in that file is replaced, otherwise it is printed
+pub async fn import(state: &str, output: Option<&str>) -> Result<()> {
+    let content = if state == "-" {
+        let mut content = String::new();
+        std::io::stdin().read_to_string(&mut content).context("Failed to read state from stdin")?;
+        content
+    } else if state.starts_with("http://") || state.starts_with("https://") {
+        reqwest::get(state)
+            .await
+            .and_then(|r| r.error_for_status())
+            .context("Failed to fetch Terraform state")?
+            .text()
+            .await?
+    } else {
+        fs::read_to_string(state).context(format!("Failed to read Terraform state: {}", state))?
+    };
+    let state_json: Value = serde_json::from_str(&content).context("Terraform state is not valid JSON")?;
+    let instances = instances(&state_json)?;
+
+    let mut lines = vec![format!("{} ({} instances, {})", BEGIN_MARKER, instances.len(), chrono::Local::now().format("%Y-%m-%d %H:%M:%S"))];
+    for instance in &instances {
+        let profile = profile_name(&instance.name);
+        lines.push(format!("# {} ({}): --profile {}", instance.address, instance.name, profile.to_lowercase()));
+        lines.push(format!("{}__SERVER_NAME='{}'", profile, instance.id));
+        match &instance.ip {
+            Some(ip) => lines.push(format!("{}__PING_IP='{}'", profile, ip)),
+            None => lines.push(format!("{}__CHECK_MODE='status-only'", profile)),
+        }
+    }
+    lines.push(END_MARKER.to_string());
+    let block = lines.join("\n") + "\n";
+
+    let Some(path) = output else {
+        print!("{}", block);
+        return Ok(());
+    };
+    let existing = fs::read_to_string(path).unwrap_or_default();
+    fs::write(path, replace_block(&existing, &block)).context(format!("Failed to write {}", path))?;
+    println!("✓ {} instance(s) imported to {}", instances.len(), path);
+    Ok(())
+}
+
+/// The generated block of `existing` replaced with `block`, appended if there is none
+fn replace_block(existing: &str, block: &str) -> String {
+    match (existing.find(BEGIN_MARKER), existing.find(END_MARKER)) {
+        (Some(begin), Some(end)) if begin < end => {
+            let end = existing[end..].find('\n').map(|i| end + i + 1).unwrap_or(existing.len());
+            format!("{}{}{}", &existing[..begin], block, &existing[end..])
+        },
+        _ if existing.is_empty() || existing.ends_with('\n') => format!("{}{}", existing, block),
+        _ => format!("{}\n{}", existing, block),
+    }
+}
+
+fn instances(state: &Value) -> Result<Vec<Instance>> {
+    let Some(resources) = state["resources"].as_array() else {
+        anyhow::bail!("No resources in Terraform state - state format version 4 expected");
+    };
+    let mut instances = vec![];
+    for resource in resources {
+        if resource["mode"] != "managed" || resource["type"] != "openstack_compute_instance_v2" {
+            continue;
+        }
+        let mut address = format!("openstack_compute_instance_v2.{}", resource["name"].as_str().unwrap_or_default());
+        if let Some(module) = resource["module"].as_str() {
+            address = format!("{}.{}", module, address);
+        }
+        for item in resource["instances"].as_array().into_iter().flatten() {
+            let attributes = &item["attributes"];
+            let Some(id) = attributes["id"].as_str() else {
+                continue;
+            };
+            let address = match &item["index_key"] {
+                Value::Null => address.clone(),
+                Value::String(key) => format!("{}[\"{}\"]", address, key),
+                key => format!("{}[{}]", address, key),
+            };
+            // access_ip_v4 is what Terraform itself uses to connect, fixed IPs are the fallback
+            let ip = [&attributes["access_ip_v4"], &attributes["access_ip_v6"]]
+                .into_iter()
+                .chain(attributes["network"].as_array().into_iter().flatten().map(|n| &n["fixed_ip_v4"]))
+                .filter_map(Value::as_str)
+                .find(|ip| !ip.is_empty())
+                .map(String::from);
+            instances.push(Instance {
+                address,
+                id: id.to_string(),
+                name: attributes["name"].as_str().unwrap_or(id).to_string(),
+                ip,
+            });
+        }
+    }
+    Ok(instances)
+}
+
+/// Profile prefix from server name: web-1.prod -> WEB_1_PROD
+fn profile_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn instances_from_state() {
+        let state = json!({ "version": 4, "resources": [
+            { "mode": "managed", "type": "openstack_compute_instance_v2", "name": "web", "module": "module.app", "instances": [
+                { "index_key": 0, "attributes": { "id": "u1", "name": "web-1.prod", "access_ip_v4": "", "network": [{ "fixed_ip_v4": "10.0.0.11" }] } },
+                { "index_key": "b", "attributes": { "id": "u2", "name": "web-2", "access_ip_v4": "203.0.113.5" } },
+            ]},
+            { "mode": "managed", "type": "openstack_compute_instance_v2", "name": "db", "instances": [
+                { "attributes": { "id": "u3" } },
+            ]},
+            { "mode": "data", "type": "openstack_compute_instance_v2", "name": "other", "instances": [{ "attributes": { "id": "u4" } }] },
+            { "mode": "managed", "type": "openstack_networking_port_v2", "name": "port", "instances": [{ "attributes": { "id": "p1" } }] },
+        ]});
+        let instances = instances(&state).unwrap();
+        let found: Vec<(&str, &str, &str, Option<&str>)> = instances
+            .iter()
+            .map(|i| (i.address.as_str(), i.id.as_str(), i.name.as_str(), i.ip.as_deref()))
+            .collect();
+        assert_eq!(found, [
+            ("module.app.openstack_compute_instance_v2.web[0]", "u1", "web-1.prod", Some("10.0.0.11")),
+            ("module.app.openstack_compute_instance_v2.web[\"b\"]", "u2", "web-2", Some("203.0.113.5")),
+            ("openstack_compute_instance_v2.db", "u3", "u3", None),
+        ]);
+        assert!(super::instances(&json!({ "version": 3 })).is_err());
+    }
+
+    #[test]
+    fn profile_names() {
+        assert_eq!(profile_name("web-1.prod"), "WEB_1_PROD");
+        assert_eq!(profile_name("db"), "DB");
+    }
+
+    #[test]
+    fn generated_block_replaced_in_place() {
+        let block = format!("{}\nNEW=1\n{}\n", BEGIN_MARKER, END_MARKER);
+        let existing = format!("A=1\n{} (1 instances)\nOLD=1\n{}\nB=2\n", BEGIN_MARKER, END_MARKER);
+        assert_eq!(replace_block(&existing, &block), format!("A=1\n{}B=2\n", block));
+        assert_eq!(replace_block("A=1", &block), format!("A=1\n{}", block));
+        assert_eq!(replace_block("", &block), block);
+    }
+}
@@ -0,0 +1,154 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+
+use crate::history::{self, StoredEvent};
+
+/// Failed checks this long before the first event of the incident may belong to its detection
+const DETECTION_LOOKBACK_HOURS: i64 = 24;
+
+/// Events of an incident (INCIDENT_ID from notifications, snapshots and `debug dump`) from the event history.
+/// With `timeline` they are drawn as an aligned timeline with time since the start and the phase of each event,
+/// including the failed checks that led to the incident
+pub fn show(incident: &str, timeline: bool) -> Result<()> {
+    let store = history::require()?;
+    let mut events = store.incident(incident)?;
+    let Some(first) = events.first() else {
+        anyhow::bail!("No events of incident '{}' in {}", incident, store.describe());
+    };
+    let server = first.server.clone();
+    let first_time = first.time;
+
+    if !timeline {
+        for event in &events {
+            println!("{} {:<8} {:<24} {}", event.time.format("%Y-%m-%d %H:%M:%S"), event.severity, event.kind, event.message);
+        }
+        return Ok(());
+    }
+
+    // Checks failing since the last successful one led to the incident, they were recorded before it got an id
+    let since = first_time - chrono::Duration::hours(DETECTION_LOOKBACK_HOURS);
+    let checks: Vec<StoredEvent> = store
+        .query(&["ping_ok", "ping_failed"], since)?
+        .into_iter()
+        .filter(|e| e.server == server && e.time < first_time)
+        .collect();
+    let mut detection = failed_checks(checks);
+    detection.append(&mut events);
+    let events = detection;
+
+    let start = events[0].time;
+    let end = events[events.len() - 1].time;
+    let outcome = outcome(&events);
+    let attempts = events.iter().filter(|e| e.kind == "unshelve_sent").count();
+
+    println!("Incident {} - server '{}'", incident, server);
+    println!("Started {}, {} to the last event, {} unshelve attempt(s), {}",
+             start.format("%Y-%m-%d %H:%M:%S"), elapsed(start, end), attempts, outcome);
+    println!("{}", "=".repeat(100));
+    let mut attempt = 0;
+    for event in &events {
+        let phase = phase(&event.kind, &mut attempt);
+        let marker = match event.severity.as_str() {
+            "critical" => "✗",
+            "warning" => "!",
+            _ => "•",
+        };
+        // Only the first line of multi-line messages fits the timeline
+        let message = event.message.lines().next().unwrap_or("");
+        println!("{}  +{:>8}  {} {:<10} {:<22} │ {}",
+                 event.time.format("%H:%M:%S"), elapsed(start, event.time), marker, phase, event.kind, message);
+    }
+    println!("{}", "=".repeat(100));
+    Ok(())
+}
+
+/// Checks after the last successful one - the failures that led to the incident
+fn failed_checks(checks: Vec<StoredEvent>) -> Vec<StoredEvent> {
+    let last_ok = checks.iter().rposition(|e| e.kind == "ping_ok").map(|i| i + 1).unwrap_or(0);
+    checks.into_iter().skip(last_ok).collect()
+}
+
+/// How the incident ended, by its last recovery or failure event
+fn outcome(events: &[StoredEvent]) -> &'static str {
+    match events.iter().rev().find(|e| matches!(e.kind.as_str(), "recovered" | "unshelve_failed" | "pipeline_failed")) {
+        Some(e) if e.kind == "recovered" => "recovered",
+        Some(_) => "failed",
+        None => "ongoing or unknown",
+    }
+}
+
+/// Phase of an event in the timeline, `attempt` counts the unshelve attempts so far
+fn phase(kind: &str, attempt: &mut usize) -> String {
+    match kind {
+        "ping_failed" | "ping_ok" => "detection".to_string(),
+        "unshelve_sent" => {
+            *attempt += 1;
+            if *attempt == 1 { "api call".to_string() } else { format!("retry #{}", *attempt - 1) }
+        },
+        "pipeline_progress" | "placement_changed" | "root_volume_failed" => "state".to_string(),
+        "recovered" => "recovery".to_string(),
+        kind if kind.ends_with("_failed") => "failure".to_string(),
+        _ => "notice".to_string(),
+    }
+}
+
+/// "1h02m", "14m05s" or "42s"
+fn elapsed(from: DateTime<Local>, to: DateTime<Local>) -> String {
+    let secs = (to - from).num_seconds().max(0);
+    match secs {
+        s if s >= 3600 => format!("{}h{:02}m", s / 3600, s % 3600 / 60),
+        s if s >= 60 => format!("{}m{:02}s", s / 60, s % 60),
+        s => format!("{}s", s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn event(kind: &str, minute: i64) -> StoredEvent {
+        StoredEvent {
+            time: Local::now() + chrono::Duration::minutes(minute),
+            severity: "warning".to_string(),
+            kind: kind.to_string(),
+            server: "web1".to_string(),
+            message: String::new(),
+            labels: BTreeMap::new(),
+            incident: None,
+        }
+    }
+
+    fn kinds(events: &[StoredEvent]) -> Vec<&str> {
+        events.iter().map(|e| e.kind.as_str()).collect()
+    }
+
+    #[test]
+    fn elapsed_time() {
+        let start = Local::now();
+        assert_eq!(elapsed(start, start + chrono::Duration::seconds(42)), "42s");
+        assert_eq!(elapsed(start, start + chrono::Duration::seconds(14 * 60 + 5)), "14m05s");
+        assert_eq!(elapsed(start, start + chrono::Duration::seconds(3720)), "1h02m");
+        assert_eq!(elapsed(start, start - chrono::Duration::seconds(5)), "0s");
+    }
+
+    #[test]
+    fn detection_starts_after_last_successful_check() {
+        let checks = vec![event("ping_failed", 0), event("ping_ok", 1), event("ping_failed", 2), event("ping_failed", 3)];
+        assert_eq!(kinds(&failed_checks(checks)), ["ping_failed", "ping_failed"]);
+        assert_eq!(failed_checks(vec![event("ping_failed", 0)]).len(), 1);
+        assert!(failed_checks(vec![event("ping_ok", 0)]).is_empty());
+    }
+
+    #[test]
+    fn phases_and_outcome() {
+        let events = [event("ping_failed", 0), event("unshelve_sent", 1), event("unshelve_failed", 2), event("unshelve_sent", 3),
+                      event("placement_changed", 4), event("check_failed", 5), event("recovered", 6)];
+        let mut attempt = 0;
+        let phases: Vec<String> = events.iter().map(|e| phase(&e.kind, &mut attempt)).collect();
+        assert_eq!(phases, ["detection", "api call", "failure", "retry #1", "state", "failure", "recovery"]);
+        assert_eq!(outcome(&events), "recovered");
+        assert_eq!(outcome(&events[..4]), "failed");
+        assert_eq!(outcome(&events[..2]), "ongoing or unknown");
+    }
+}
@@ -0,0 +1,364 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::aliases;
+use crate::interpolate;
+use crate::labels;
+use crate::monitor::{CheckMode, Watch};
+
+/// Config in TOML (e.g. unshelve.toml) instead of .env. Typed sections cover the common settings,
+/// `[env]` takes any other variable by its name, including profiles (STAGING__SERVER_NAME).
+/// Everything ends up as environment variables like .env keys, variables already set win.
+/// ${VAR} in values is replaced with the environment or another setting of the file
+///
+/// ```toml
+/// credentials = "openrc.env"   # or cloud = "mycloud" from clouds.yaml
+///
+/// [[servers]]
+/// name = "web1"
+/// ping_ip = "10.0.0.11"
+///
+/// [check]
+/// interval_minutes = 5
+///
+/// [notify]
+/// telegram_bot_token = "..."
+/// telegram_chat_id = "..."
+///
+/// [env]
+/// UNSHELVE_BACKOFF_MINUTES = "1,5,15,60"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// .env file with OS_* credentials, relative to the TOML file
+    pub credentials: Option<PathBuf>,
+    /// Cloud from clouds.yaml (OS_CLOUD) instead of a credentials file
+    pub cloud: Option<String>,
+    /// One server - SERVER_NAME and PING_IP, several - SERVERS
+    #[serde(default)]
+    pub servers: Vec<ServerConfig>,
+    #[serde(default)]
+    pub check: CheckConfig,
+    #[serde(default)]
+    pub notify: NotifyConfig,
+    /// Other settings by variable name: strings, numbers, booleans, arrays (joined with commas)
+    #[serde(default)]
+    pub env: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Name, UUID or alias
+    pub name: String,
+    /// Not needed with check mode status-only
+    pub ping_ip: Option<IpAddr>,
+    /// Instead of socket_type of [check]
+    pub socket_type: Option<SocketType>,
+    /// Labels of this server on top of SERVER_LABELS
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+}
+
+/// CHECK_MODE, PING_* and TCP_CHECK_PORT
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CheckConfig {
+    pub mode: Option<CheckMode>,
+    pub interval_minutes: Option<u64>,
+    pub timeout_seconds: Option<u64>,
+    pub socket_type: Option<SocketType>,
+    pub tcp_port: Option<u16>,
+}
+
+/// ICMP socket type (PING_SOCKET_TYPE)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SocketType {
+    Raw,
+    Dgram,
+}
+
+impl SocketType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SocketType::Raw => "raw",
+            SocketType::Dgram => "dgram",
+        }
+    }
+}
+
+/// NOTIFY_* of the common channels, the others go to [env]
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotifyConfig {
+    pub webhook_url: Option<String>,
+    pub slack_webhook_url: Option<String>,
+    pub google_chat_webhook_url: Option<String>,
+    pub teams_webhook_url: Option<String>,
+    pub telegram_bot_token: Option<String>,
+    pub telegram_chat_id: Option<String>,
+    pub min_severity: Option<String>,
+    pub rate_limit: Option<String>,
+    pub test_on_start: Option<bool>,
+}
+
+impl Config {
+    pub fn load(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path).context(format!("Failed to read config file: {}", path))?;
+        toml::from_str(&content).context(format!("Invalid TOML config {}", path))
+    }
+
+    /// Settings as environment variables
+    pub fn vars(&self) -> Result<Vec<(String, String)>> {
+        let mut vars: Vec<(String, String)> = vec![];
+        let mut set = |key: &str, value: Option<String>| {
+            if let Some(value) = value {
+                vars.push((key.to_string(), value));
+            }
+        };
+        set("OS_CLOUD", self.cloud.clone());
+
+        let check = &self.check;
+        let mut socket_type = check.socket_type;
+        match self.servers.as_slice() {
+            [] => {},
+            [server] => {
+                set("SERVER_NAME", Some(server.name.clone()));
+                set("PING_IP", server.ping_ip.map(|ip| ip.to_string()));
+                set("SERVER_LABELS", Some(labels::to_stored(&server.labels)).filter(|l| !l.is_empty()));
+                socket_type = server.socket_type.or(socket_type);
+            },
+            // For `config show` and the CLI, the daemon takes them from `watches`
+            servers => {
+                let list: Vec<String> = servers
+                    .iter()
+                    .map(|s| match (s.ping_ip, s.socket_type) {
+                        (Some(ip), Some(socket_type)) => Ok(format!("{}={}/{}", s.name, ip, socket_type.as_str())),
+                        (Some(ip), None) => Ok(format!("{}={}", s.name, ip)),
+                        (None, Some(_)) => anyhow::bail!("[[servers]] {}: socket_type needs ping_ip", s.name),
+                        (None, None) => Ok(s.name.clone()),
+                    })
+                    .collect::<Result<_>>()?;
+                set("SERVERS", Some(list.join(",")));
+                for server in servers.iter().filter(|s| !s.labels.is_empty()) {
+                    set(&labels::key(&server.name), Some(labels::to_stored(&server.labels)));
+                }
+            },
+        }
+
+        set("CHECK_MODE", check.mode.map(|mode| mode.as_str().to_string()));
+        set("PING_INTERVAL_MINUTES", check.interval_minutes.map(|v| v.to_string()));
+        set("PING_TIMEOUT_SECONDS", check.timeout_seconds.map(|v| v.to_string()));
+        set("PING_SOCKET_TYPE", socket_type.map(|t| t.as_str().to_string()));
+        set("TCP_CHECK_PORT", check.tcp_port.map(|v| v.to_string()));
+
+        let notify = &self.notify;
+        set("NOTIFY_WEBHOOK_URL", notify.webhook_url.clone());
+        set("NOTIFY_SLACK_WEBHOOK_URL", notify.slack_webhook_url.clone());
+        set("NOTIFY_GOOGLE_CHAT_WEBHOOK_URL", notify.google_chat_webhook_url.clone());
+        set("NOTIFY_TEAMS_WEBHOOK_URL", notify.teams_webhook_url.clone());
+        set("NOTIFY_TELEGRAM_BOT_TOKEN", notify.telegram_bot_token.clone());
+        set("NOTIFY_TELEGRAM_CHAT_ID", notify.telegram_chat_id.clone());
+        set("NOTIFY_MIN_SEVERITY", notify.min_severity.clone());
+        set("NOTIFY_RATE_LIMIT", notify.rate_limit.clone());
+        set("NOTIFY_TEST_ON_START", notify.test_on_start.map(|v| v.to_string()));
+
+        for (key, value) in &self.env {
+            set(key, Some(env_value(key, value)?));
+        }
+        substitute(vars)
+    }
+
+    /// Monitors of several [[servers]], None for one or none - SERVER_NAME and PING_IP cover them
+    pub fn watches(&self) -> Result<Option<Vec<Watch>>> {
+        if self.servers.len() < 2 {
+            return Ok(None);
+        }
+        let mut watches: Vec<Watch> = vec![];
+        for server in &self.servers {
+            let name = aliases::resolve(&server.name)?;
+            if watches.iter().any(|w| w.server.as_deref() == Some(name.as_str())) {
+                anyhow::bail!("Server '{}' is listed twice in [[servers]]", name);
+            }
+            if server.ping_ip.is_none() && server.socket_type.is_some() {
+                anyhow::bail!("[[servers]] {}: socket_type needs ping_ip", server.name);
+            }
+            watches.push(Watch {
+                server: Some(name),
+                ping_ip: server.ping_ip,
+                use_dgram_socket: server.socket_type.map(|t| t == SocketType::Dgram),
+                labels: server.labels.clone(),
+            });
+        }
+        Ok(Some(watches))
+    }
+
+    fn credentials_path(&self, config_path: &str) -> Option<PathBuf> {
+        let credentials = self.credentials.as_ref()?;
+        let base = Path::new(config_path).parent().unwrap_or(Path::new(""));
+        Some(base.join(credentials))
+    }
+}
+
+/// TOML by extension, anything else is a .env file
+pub fn is_toml(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|e| e.eq_ignore_ascii_case("toml"))
+}
+
+/// Variables of the TOML config, then of its credentials file, with the config for settings used as typed values
+pub fn load(path: &str) -> Result<(Config, Vec<(String, String)>)> {
+    let config = Config::load(path)?;
+    let mut vars = config.vars()?;
+    if let Some(credentials) = config.credentials_path(path) {
+        vars.extend(interpolate::read_env_file(&credentials.to_string_lossy())?);
+    }
+    Ok((config, vars))
+}
+
+/// Variables of the TOML config and its credentials file
+pub fn read(path: &str) -> Result<Vec<(String, String)>> {
+    Ok(load(path)?.1)
+}
+
+/// Keys set by the TOML config and its credentials file, for `config show`
+pub fn keys(path: &str) -> Result<HashSet<String>> {
+    let config = Config::load(path)?;
+    let mut keys: HashSet<String> = config.vars()?.into_iter().map(|(key, _)| key).collect();
+    if let Some(credentials) = config.credentials_path(path) {
+        keys.extend(interpolate::read_env_file(&credentials.to_string_lossy())?.into_iter().map(|(key, _)| key));
+    }
+    Ok(keys)
+}
+
+/// ${VAR} from the environment or another setting of the file, each value is substituted once
+fn substitute(vars: Vec<(String, String)>) -> Result<Vec<(String, String)>> {
+    let raw: HashMap<String, String> = vars.iter().cloned().collect();
+    let lookup = |name: &str| env::var(name).ok().or_else(|| raw.get(name).cloned());
+    let mut missing: Vec<String> = vec![];
+    let mut result = vec![];
+    for (key, value) in vars {
+        match interpolate::substitute(&value, lookup) {
+            Ok(value) => result.push((key, value)),
+            Err(names) => missing.extend(names.iter().map(|name| format!("${{{}}} in {}", name, key))),
+        }
+    }
+    if !missing.is_empty() {
+        anyhow::bail!("TOML config references undefined variables:\n  {}", missing.join("\n  "));
+    }
+    Ok(result)
+}
+
+fn env_value(key: &str, value: &toml::Value) -> Result<String> {
+    Ok(match value {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Integer(i) => i.to_string(),
+        toml::Value::Float(f) => f.to_string(),
+        toml::Value::Boolean(b) => b.to_string(),
+        toml::Value::Array(items) => items.iter().map(|item| env_value(key, item)).collect::<Result<Vec<_>>>()?.join(","),
+        other => anyhow::bail!("[env] {} must be a string, number, boolean or array, not {}", key, other.type_str()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(toml: &str) -> Result<BTreeMap<String, String>> {
+        let config: Config = toml::from_str(toml)?;
+        Ok(config.vars()?.into_iter().collect())
+    }
+
+    #[test]
+    fn one_server() {
+        let vars = vars(r#"
+            [[servers]]
+            name = "web1"
+            ping_ip = "10.0.0.11"
+            socket_type = "raw"
+
+            [check]
+            mode = "ping"
+            interval_minutes = 5
+            socket_type = "dgram"
+        "#).unwrap();
+        assert_eq!(vars["SERVER_NAME"], "web1");
+        assert_eq!(vars["PING_IP"], "10.0.0.11");
+        assert_eq!(vars["PING_SOCKET_TYPE"], "raw");
+        assert_eq!(vars["CHECK_MODE"], "ping");
+        assert_eq!(vars["PING_INTERVAL_MINUTES"], "5");
+        assert!(!vars.contains_key("SERVERS"));
+    }
+
+    #[test]
+    fn several_servers() {
+        let vars = vars(r#"
+            [[servers]]
+            name = "web1"
+            ping_ip = "10.0.0.11"
+
+            [[servers]]
+            name = "db"
+            ping_ip = "10.0.0.20"
+            socket_type = "raw"
+
+            [[servers]]
+            name = "batch"
+        "#).unwrap();
+        assert_eq!(vars["SERVERS"], "web1=10.0.0.11,db=10.0.0.20/raw,batch");
+        assert!(!vars.contains_key("SERVER_NAME"));
+    }
+
+    #[test]
+    fn env_section_and_substitution() {
+        let vars = vars(r#"
+            [notify]
+            telegram_chat_id = "${CHAT}"
+
+            [env]
+            CHAT = "-100123"
+            UNSHELVE_BACKOFF_MINUTES = [1, 5, 15]
+            ALLOW_ANY_PING_IP = true
+        "#).unwrap();
+        assert_eq!(vars["NOTIFY_TELEGRAM_CHAT_ID"], "-100123");
+        assert_eq!(vars["UNSHELVE_BACKOFF_MINUTES"], "1,5,15");
+        assert_eq!(vars["ALLOW_ANY_PING_IP"], "true");
+    }
+
+    #[test]
+    fn server_labels() {
+        let one = vars(r#"
+            [[servers]]
+            name = "web1"
+            labels = { team = "web", env = "prod" }
+        "#).unwrap();
+        assert_eq!(one["SERVER_LABELS"], "env=prod,team=web");
+
+        let several = vars(r#"
+            [[servers]]
+            name = "web-1"
+            labels = { team = "web" }
+
+            [[servers]]
+            name = "db"
+        "#).unwrap();
+        assert_eq!(several["SERVER_LABELS_WEB_1"], "team=web");
+        assert!(!several.contains_key("SERVER_LABELS_DB"));
+    }
+
+    #[test]
+    fn invalid_configs() {
+        let undefined = vars("[env]\nA = \"${UNSHELVE_TEST_UNDEFINED}\"\n").unwrap_err();
+        assert!(undefined.to_string().contains("${UNSHELVE_TEST_UNDEFINED} in A"), "{}", undefined);
+        assert!(vars("[check]\nmode = \"tcp\"\n").is_err());
+        assert!(vars("[check]\nsocket_type = \"icmp\"\n").is_err());
+        assert!(vars("[check]\nunknown = 1\n").is_err());
+        assert!(vars("[env]\nTABLE = { a = 1 }\n").is_err());
+    }
+}
@@ -0,0 +1,33 @@
+use anyhow::{Context, Result};
+use openstack::block_storage::Volume;
+
+/// Device names a boot volume is attached as, depending on the hypervisor bus
+const ROOT_DEVICES: [&str; 3] = ["/dev/vda", "/dev/sda", "/dev/xvda"];
+
+/// Root volume of a boot-from-volume server, None if the server boots from an image
+pub async fn root_volume(cloud: &openstack::Cloud, server_id: &str) -> Result<Option<Volume>> {
+    let volumes = cloud.list_volumes().await.context("Failed to list volumes")?;
+    Ok(volumes.into_iter().find(|volume| root_device(volume, server_id).is_some()))
+}
+
+/// Device the volume is attached to the server as, if it's the root device
+fn root_device(volume: &Volume, server_id: &str) -> Option<String> {
+    volume
+        .attachments()
+        .iter()
+        .find(|a| a.server_id == server_id && ROOT_DEVICES.contains(&a.device.as_str()))
+        .map(|a| a.device.clone())
+}
+
+/// Attached to the server as its root device and in-use
+pub fn is_healthy(volume: &Volume, server_id: &str) -> bool {
+    volume.status().to_string() == "in-use" && root_device(volume, server_id).is_some()
+}
+
+/// "root (UUID, 20 GB) in-use as /dev/vda" for notifications
+pub fn describe(volume: &Volume, server_id: &str) -> String {
+    let attached = root_device(volume, server_id)
+        .map(|device| format!("as {}", device))
+        .unwrap_or_else(|| "not attached".to_string());
+    format!("{} ({}, {} GB) {} {}", volume.name(), volume.id(), volume.size(), volume.status(), attached)
+}
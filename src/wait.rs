@@ -0,0 +1,72 @@
+use std::io::Write;
+use std::net::SocketAddr;
+use anyhow::Result;
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout, Duration, Instant};
+
+use crate::address;
+use crate::state::ServerState;
+
+/// Delay between status polls and connection attempts
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Block until the server is ACTIVE and `port` accepts connections, then print a banner and,
+/// with `bell`, ring the terminal bell - "tell me when my VM is back" without a notifier.
+/// Only waits: a shelved server stays shelved unless an unshelve was sent
+pub async fn wait_up(cloud: &openstack::Cloud, server_identifier: &str, limit: Duration, port: u16, bell: bool) -> Result<()> {
+    let started = Instant::now();
+    let deadline = started + limit;
+    let mut last_status = String::new();
+    println!("Waiting for '{}' to accept connections on port {} (up to {} min)...", server_identifier, port, limit.as_secs() / 60);
+
+    loop {
+        match cloud.get_server(server_identifier).await {
+            Ok(server) => {
+                let status = server.status().to_string();
+                if status != last_status {
+                    println!("[{}] Status {}", chrono::Local::now().format("%H:%M:%S"), status);
+                    last_status = status.clone();
+                }
+                match ServerState::of(&server) {
+                    ServerState::Active => {
+                        let addr = SocketAddr::new(address::select(&server, None, None)?, port);
+                        if let Ok(Ok(_)) = timeout(Duration::from_secs(3), TcpStream::connect(addr)).await {
+                            banner(server.name(), &addr, started.elapsed(), bell);
+                            return Ok(());
+                        }
+                    },
+                    ServerState::Error => anyhow::bail!("Server '{}' went to ERROR status", server.name()),
+                    ServerState::Shutoff | ServerState::Rescue | ServerState::Stopped | ServerState::SoftDeleted | ServerState::Deleted => {
+                        anyhow::bail!("Server '{}' is {} - it won't come up by itself", server.name(), status)
+                    },
+                    _ => {},
+                }
+            },
+            // API hiccups are retried until the deadline
+            Err(e) => println!("✗ Failed to get server info: {}", e),
+        }
+
+        if Instant::now() >= deadline {
+            if bell {
+                ring();
+            }
+            anyhow::bail!("Server '{}' is not up after {} min", server_identifier, limit.as_secs() / 60);
+        }
+        sleep(POLL_INTERVAL.min(deadline.saturating_duration_since(Instant::now()))).await;
+    }
+}
+
+fn banner(name: &str, addr: &SocketAddr, elapsed: Duration, bell: bool) {
+    println!("{}", "=".repeat(80));
+    println!("  ✅ Server '{}' is UP - {} accepts connections ({}m {}s)", name, addr, elapsed.as_secs() / 60, elapsed.as_secs() % 60);
+    println!("{}", "=".repeat(80));
+    if bell {
+        ring();
+    }
+}
+
+/// BEL character, the terminal beeps or flashes depending on its settings
+fn ring() {
+    print!("\x07");
+    let _ = std::io::stdout().flush();
+}
@@ -0,0 +1,98 @@
+use std::process::Stdio;
+use anyhow::{Context, Result};
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+
+use crate::config;
+
+/// Result of the warmup, shown in the recovery message
+pub struct Outcome {
+    pub steps: usize,
+    pub failures: Vec<String>,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.failures.is_empty() {
+            write!(f, "Warmup: {} step(s) done", self.steps)
+        } else {
+            write!(f, "Warmup: {} of {} step(s) failed - {}", self.failures.len(), self.steps, self.failures.join("; "))
+        }
+    }
+}
+
+/// Prime caches and JITs once the server is reachable again after unshelve.
+/// WARMUP_URLS - comma-separated URLs requested with GET, WARMUP_COMMAND - shell command.
+/// None if nothing is configured
+pub async fn run(server_name: &str, server_id: &str) -> Result<Option<Outcome>> {
+    let urls: Vec<String> = config::var("WARMUP_URLS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|u| !u.is_empty())
+        .map(String::from)
+        .collect();
+    let command = env_non_empty("WARMUP_COMMAND");
+    if urls.is_empty() && command.is_none() {
+        return Ok(None);
+    }
+    let timeout_secs: u64 = config::var("WARMUP_TIMEOUT_SECONDS")
+        .unwrap_or_else(|_| "60".to_string())
+        .parse()
+        .context("WARMUP_TIMEOUT_SECONDS must be a number")?;
+    let limit = Duration::from_secs(timeout_secs);
+
+    println!("Warming up server '{}'...", server_name);
+    let mut failures: Vec<String> = vec![];
+    if !urls.is_empty() {
+        let client = reqwest::Client::builder()
+            .timeout(limit)
+            .build()
+            .context("Failed to create HTTP client")?;
+        for url in &urls {
+            match client.get(url).send().await {
+                Ok(r) if r.status().is_success() => println!("✓ Warmup {} - {}", url, r.status()),
+                Ok(r) => failures.push(format!("{} returned {}", url, r.status())),
+                Err(e) => failures.push(format!("request to {} failed: {}", url, e)),
+            }
+        }
+    }
+    if let Some(command) = &command {
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("UNSHELVE_SERVER_NAME", server_name)
+            .env("UNSHELVE_SERVER_ID", server_id)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .output();
+        match timeout(limit, child).await {
+            Ok(Ok(output)) if output.status.success() => println!("✓ Warmup command finished"),
+            Ok(Ok(output)) => failures.push(format!("command exited with {}: {}", output.status,
+                                                    String::from_utf8_lossy(&output.stderr).trim())),
+            Ok(Err(e)) => failures.push(format!("failed to run command: {}", e)),
+            Err(_) => failures.push(format!("command timed out after {} seconds", limit.as_secs())),
+        }
+    }
+
+    failures.iter().for_each(|f| println!("✗ Warmup {}", f));
+    Ok(Some(Outcome { steps: urls.len() + usize::from(command.is_some()), failures }))
+}
+
+/// Configured warmup for the startup summary, None if there is none
+pub fn describe() -> Option<String> {
+    let mut steps = vec![];
+    let urls = config::var("WARMUP_URLS").unwrap_or_default().split(',').filter(|u| !u.trim().is_empty()).count();
+    if urls > 0 {
+        steps.push(format!("{} URL(s)", urls));
+    }
+    if let Some(command) = env_non_empty("WARMUP_COMMAND") {
+        steps.push(format!("command {}", command));
+    }
+    if steps.is_empty() { None } else { Some(steps.join(", ")) }
+}
+
+fn env_non_empty(key: &str) -> Option<String> {
+    config::var(key).ok().filter(|v| !v.trim().is_empty())
+}
@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use anyhow::{Context, Result};
+use axum::extract::{Json, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::{get, post};
+use axum::Router;
+use serde_json::Value;
+use tokio::sync::Notify;
+
+use crate::config;
+use crate::killswitch;
+use crate::redact;
+use crate::tasks::TaskGroup;
+
+/// Nova event types (legacy "compute.instance.*" and versioned "instance.*") meaning the server went down
+const DOWN_EVENTS: [&str; 3] = ["instance.shelve.end", "instance.shelve_offload.end", "instance.power_off.end"];
+
+struct Receiver {
+    /// Monitored servers with the signals waking up their monitors
+    servers: Vec<(String, Arc<Notify>)>,
+    token: Option<String>,
+    /// Latest state snapshot of the daemon, served on /status
+    status: Arc<Mutex<Value>>,
+}
+
+impl Receiver {
+    fn authorized(&self, query: &HashMap<String, String>, headers: &HeaderMap) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+        let from_header = headers.get("X-Unshelve-Token").and_then(|v| v.to_str().ok());
+        query.get("token").map(String::as_str) == Some(token.as_str()) || from_header == Some(token.as_str())
+    }
+
+    fn trigger(&self, server: &str, signal: &Notify, reason: &str) {
+        println!("[{}] External signal for '{}': {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), server, reason);
+        signal.notify_one();
+    }
+}
+
+/// Start HTTP receiver on WEBHOOK_LISTEN for AODH alarms and relayed Nova notifications, one for
+/// all servers of the daemon. The signal of a server is notified when it's reported down or shelved,
+/// `status` is served as is on /status for `unshelve fleet status`.
+/// The server runs in `tasks` and finishes open requests on shutdown.
+/// Returns false if the receiver is not configured
+pub async fn start(servers: Vec<(String, Arc<Notify>)>, status: Arc<Mutex<Value>>, tasks: &mut TaskGroup) -> Result<bool> {
+    let Some(listen) = config::var("WEBHOOK_LISTEN").ok().filter(|l| !l.trim().is_empty()) else {
+        return Ok(false);
+    };
+
+    let receiver = Arc::new(Receiver {
+        servers,
+        token: config::var("WEBHOOK_TOKEN").ok().filter(|t| !t.is_empty()),
+        status,
+    });
+
+    let listener = tokio::net::TcpListener::bind(&listen)
+        .await
+        .context(format!("Failed to listen on WEBHOOK_LISTEN {}", listen))?;
+    let address = listener.local_addr().context("Failed to get webhook receiver address")?;
+
+    let mut app = Router::new()
+        .route("/alarm", post(alarm))
+        .route("/notification", post(notification))
+        .route("/status", get(status));
+    if control_allowed(receiver.token.is_some(), address) {
+        app = app
+            .route("/actions/disable", post(disable_actions))
+            .route("/actions/enable", post(enable_actions));
+        println!("Webhook receiver: http://{}/alarm, /notification, /actions/disable, /actions/enable, /status", listen);
+    } else {
+        println!("Webhook receiver: http://{}/alarm, /notification, /status", listen);
+        println!("⚠️ Control API (/actions/disable, /actions/enable) is off: set WEBHOOK_TOKEN or listen on localhost");
+    }
+    let app = app.with_state(receiver);
+
+    tasks.spawn(|token| async move {
+        if let Err(e) = axum::serve(listener, app).with_graceful_shutdown(token.cancelled_owned()).await {
+            println!("✗ Webhook receiver stopped: {}", redact::redact(&e.to_string()));
+        }
+    });
+    Ok(true)
+}
+
+/// Anyone reaching the port could switch auto-unshelve off, so the kill switch routes need
+/// a token unless only local processes can connect
+fn control_allowed(token: bool, address: SocketAddr) -> bool {
+    token || address.ip().is_loopback()
+}
+
+/// AODH alarm webhook. Alarm is configured for a monitored server, so only its state matters.
+/// ?server=NAME selects the server when the daemon watches several, without it all of them are checked
+async fn alarm(
+    State(receiver): State<Arc<Receiver>>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> StatusCode {
+    if !receiver.authorized(&query, &headers) {
+        return StatusCode::UNAUTHORIZED;
+    }
+    if body["current"].as_str() != Some("alarm") {
+        return StatusCode::NO_CONTENT;
+    }
+    let selected: Vec<&(String, Arc<Notify>)> = match query.get("server") {
+        Some(server) => receiver.servers.iter().filter(|(name, _)| name == server).collect(),
+        None => receiver.servers.iter().collect(),
+    };
+    if selected.is_empty() {
+        return StatusCode::NOT_FOUND;
+    }
+    let name = body["alarm_name"].as_str().unwrap_or("unknown");
+    let reason = format!("AODH alarm '{}' {}", name, body["reason"].as_str().unwrap_or(""));
+    for (server, signal) in selected {
+        receiver.trigger(server, signal, &reason);
+    }
+    StatusCode::NO_CONTENT
+}
+
+/// Nova instance notification relayed via HTTP, e.g. {"event_type": "compute.instance.shelve_offload.end",
+/// "payload": {"instance_id": "...", "display_name": "...", "state": "shelved_offloaded"}}
+async fn notification(
+    State(receiver): State<Arc<Receiver>>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> StatusCode {
+    if !receiver.authorized(&query, &headers) {
+        return StatusCode::UNAUTHORIZED;
+    }
+    for (server, signal) in &receiver.servers {
+        if let Some(reason) = down_notification(&body, server) {
+            receiver.trigger(server, signal, &reason);
+        }
+    }
+    StatusCode::NO_CONTENT
+}
+
+/// Control API kill switch: observe and alert only, no automatic actions
+async fn disable_actions(
+    State(receiver): State<Arc<Receiver>>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> StatusCode {
+    if !receiver.authorized(&query, &headers) {
+        return StatusCode::UNAUTHORIZED;
+    }
+    println!("[{}] Automatic actions disabled through the control API", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"));
+    killswitch::set_api_disabled(true);
+    StatusCode::NO_CONTENT
+}
+
+/// Lift the control API kill switch, DISABLE_ACTIONS and the kill switch file still apply
+async fn enable_actions(
+    State(receiver): State<Arc<Receiver>>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> StatusCode {
+    if !receiver.authorized(&query, &headers) {
+        return StatusCode::UNAUTHORIZED;
+    }
+    println!("[{}] Automatic actions enabled through the control API", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"));
+    killswitch::set_api_disabled(false);
+    StatusCode::NO_CONTENT
+}
+
+/// Current state snapshot of the daemon, the same JSON as SNAPSHOT_FILE plus the instance host
+async fn status(
+    State(receiver): State<Arc<Receiver>>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, StatusCode> {
+    if !receiver.authorized(&query, &headers) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let status = receiver.status.lock().map(|s| s.clone()).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(status))
+}
+
+/// Description of a Nova notification if it reports the server going down or shelved
+pub fn down_notification(body: &Value, server_name: &str) -> Option<String> {
+    let payload = &body["payload"];
+    // Versioned notifications wrap instance fields into nova_object.data
+    let instance = if payload["nova_object.data"].is_object() { &payload["nova_object.data"] } else { payload };
+
+    let matches_server = [&instance["instance_id"], &instance["uuid"], &instance["display_name"]]
+        .iter()
+        .any(|v| v.as_str() == Some(server_name));
+    let event_type = body["event_type"].as_str().unwrap_or("");
+    let state = instance["state"].as_str().unwrap_or("");
+    let down_event = DOWN_EVENTS.iter().any(|e| event_type.ends_with(e));
+
+    if matches_server && (down_event || state.starts_with("shelved")) {
+        Some(format!("Nova notification {} (state: {})", event_type, state))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+    use serde_json::json;
+
+    fn receiver() -> (Arc<Receiver>, Arc<Notify>, Arc<Notify>) {
+        let (web, db) = (Arc::new(Notify::new()), Arc::new(Notify::new()));
+        let receiver = Receiver {
+            servers: vec![("web1".to_string(), web.clone()), ("db".to_string(), db.clone())],
+            token: None,
+            status: Arc::new(Mutex::new(Value::Null)),
+        };
+        (Arc::new(receiver), web, db)
+    }
+
+    #[test]
+    fn control_api_needs_token_or_localhost() {
+        assert!(!control_allowed(false, "0.0.0.0:8085".parse().unwrap()));
+        assert!(!control_allowed(false, "10.0.0.5:8085".parse().unwrap()));
+        assert!(control_allowed(false, "127.0.0.1:8085".parse().unwrap()));
+        assert!(control_allowed(false, "[::1]:8085".parse().unwrap()));
+        assert!(control_allowed(true, "0.0.0.0:8085".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn notification_wakes_only_its_server() {
+        let (receiver, web, db) = receiver();
+        let body = json!({ "event_type": "compute.instance.shelve_offload.end", "payload": { "display_name": "db", "state": "shelved_offloaded" } });
+        let status = notification(State(receiver), Query(HashMap::new()), HeaderMap::new(), Json(body)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert!(db.notified().now_or_never().is_some());
+        assert!(web.notified().now_or_never().is_none());
+    }
+
+    #[tokio::test]
+    async fn alarm_selects_server() {
+        let (receiver, web, db) = receiver();
+        let body = json!({ "current": "alarm", "alarm_name": "web1-down" });
+        let query = HashMap::from([("server".to_string(), "web1".to_string())]);
+        assert_eq!(alarm(State(receiver.clone()), Query(query), HeaderMap::new(), Json(body.clone())).await, StatusCode::NO_CONTENT);
+        assert!(web.notified().now_or_never().is_some());
+        assert!(db.notified().now_or_never().is_none());
+
+        let query = HashMap::from([("server".to_string(), "other".to_string())]);
+        assert_eq!(alarm(State(receiver.clone()), Query(query), HeaderMap::new(), Json(body.clone())).await, StatusCode::NOT_FOUND);
+
+        assert_eq!(alarm(State(receiver), Query(HashMap::new()), HeaderMap::new(), Json(body)).await, StatusCode::NO_CONTENT);
+        assert!(web.notified().now_or_never().is_some());
+        assert!(db.notified().now_or_never().is_some());
+    }
+}